@@ -1,5 +1,9 @@
 use crate::commands::{
-    add, clean, clone, discord, info, init, list, login, node, outdated, run, search,
+    access, add, audit, bin, cache, check, clean, clone, completions, compress, config, daemon,
+    decompress, dedupe, deprecate, discord, doctor, graph, info, init, install, licenses, link,
+    links, list, login, migrate, missing, node, outdated, owner, pack, patch, plugin, prune,
+    publish, remove, run, sbom, search, stats, tag, telemetry, token, unused, update, version,
+    watch, why, x,
 }; // remove outdated later
 use async_trait::async_trait;
 use clap::{
@@ -17,36 +21,216 @@ pub trait VoltCommand {
 /// Volt CLI subcommands
 #[derive(Debug, Subcommand)]
 pub enum VoltSubCmd {
+    Access(access::Access),
     Add(add::Add),
+    Audit(audit::Audit),
+    Bin(bin::Bin),
+    Bugs(links::Bugs),
+    Cache(cache::Cache),
+    Check(check::Check),
     Clone(clone::Clone),
+    Completions(completions::Completions),
+    Compress(compress::Compress),
+    Config(config::Config),
+    Daemon(daemon::Daemon),
     Init(init::Init),
+    #[clap(alias = "i")]
+    Install(install::Install),
     Clean(clean::Clean),
+    Dedupe(dedupe::Dedupe),
+    #[clap(alias = "watch")]
+    Dev(watch::Watch),
+    Decompress(decompress::Decompress),
+    Docs(links::Docs),
+    Deprecate(deprecate::Deprecate),
     Discord(discord::Discord),
+    Doctor(doctor::Doctor),
+    DistTag(tag::DistTag),
+    Graph(graph::Graph),
+    Sbom(sbom::Sbom),
     Search(search::Search),
+    Stats(stats::Stats),
     Login(login::Login),
+    Prune(prune::Prune),
+    Publish(publish::Publish),
+    Remove(remove::Remove),
+    Repo(links::Repo),
     Run(run::Run),
+    #[clap(alias = "view")]
     Info(info::Info),
+    Licenses(licenses::Licenses),
+    Link(link::Link),
+    Migrate(migrate::Migrate),
+    Missing(missing::Missing),
     Node(node::Node),
     Outdated(outdated::Outdated), // remove later???
-    List(list::List),             // remove later???
+    Owner(owner::Owner),
+    Pack(pack::Pack),
+    Patch(patch::Patch),
+    PatchCommit(patch::PatchCommit),
+    Plugin(plugin::Plugin),
+    Telemetry(telemetry::Telemetry),
+    Token(token::Token),
+    #[clap(alias = "ls")]
+    List(list::List), // remove later???
+    Unlink(link::Unlink),
+    Unused(unused::Unused),
+    #[clap(alias = "upgrade")]
+    Update(update::Update),
+    Version(version::Version),
+    Why(why::Why),
+    #[clap(alias = "dlx")]
+    X(x::X),
+    /// Runs the `volt-<command>` plugin
+    #[clap(external_subcommand)]
+    External(Vec<String>),
 }
 
 #[async_trait]
 impl VoltCommand for VoltSubCmd {
     async fn exec(self, config: VoltConfig) -> miette::Result<()> {
         match self {
+            Self::Access(x) => x.exec(config).await,
             Self::Add(x) => x.exec(config).await,
+            Self::Audit(x) => x.exec(config).await,
+            Self::Bin(x) => x.exec(config).await,
+            Self::Bugs(x) => x.exec(config).await,
+            Self::Cache(x) => x.exec(config).await,
+            Self::Check(x) => x.exec(config).await,
             Self::Clone(x) => x.exec(config).await,
+            Self::Completions(x) => x.exec(config).await,
+            Self::Compress(x) => x.exec(config).await,
+            Self::Decompress(x) => x.exec(config).await,
+            Self::Config(x) => x.exec(config).await,
+            Self::Daemon(x) => x.exec(config).await,
             Self::Init(x) => x.exec(config).await,
+            Self::Install(x) => x.exec(config).await,
             Self::Clean(x) => x.exec(config).await,
+            Self::Dedupe(x) => x.exec(config).await,
+            Self::Dev(x) => x.exec(config).await,
+            Self::Docs(x) => x.exec(config).await,
+            Self::Deprecate(x) => x.exec(config).await,
             Self::Discord(x) => x.exec(config).await,
+            Self::Doctor(x) => x.exec(config).await,
+            Self::DistTag(x) => x.exec(config).await,
+            Self::Graph(x) => x.exec(config).await,
+            Self::Sbom(x) => x.exec(config).await,
             Self::Search(x) => x.exec(config).await,
+            Self::Stats(x) => x.exec(config).await,
             Self::Login(x) => x.exec(config).await,
+            Self::Prune(x) => x.exec(config).await,
+            Self::Publish(x) => x.exec(config).await,
+            Self::Remove(x) => x.exec(config).await,
+            Self::Repo(x) => x.exec(config).await,
             Self::Run(x) => x.exec(config).await,
             Self::Info(x) => x.exec(config).await,
+            Self::Licenses(x) => x.exec(config).await,
+            Self::Link(x) => x.exec(config).await,
+            Self::Migrate(x) => x.exec(config).await,
+            Self::Missing(x) => x.exec(config).await,
             Self::Node(x) => x.exec(config).await,
             Self::Outdated(x) => x.exec(config).await, // remove later
-            Self::List(x) => x.exec(config).await,     // remove later
+            Self::Owner(x) => x.exec(config).await,
+            Self::Pack(x) => x.exec(config).await,
+            Self::Patch(x) => x.exec(config).await,
+            Self::PatchCommit(x) => x.exec(config).await,
+            Self::Plugin(x) => x.exec(config).await,
+            Self::Telemetry(x) => x.exec(config).await,
+            Self::Token(x) => x.exec(config).await,
+            Self::List(x) => x.exec(config).await, // remove later
+            Self::Unlink(x) => x.exec(config).await,
+            Self::Unused(x) => x.exec(config).await,
+            Self::Update(x) => x.exec(config).await,
+            Self::Version(x) => x.exec(config).await,
+            Self::Why(x) => x.exec(config).await,
+            Self::X(x) => x.exec(config).await,
+            Self::External(args) => plugin::run_external(config, args).await,
+        }
+    }
+}
+
+impl VoltSubCmd {
+    /// Whether the command installs into or runs the project, so it has to be the package
+    /// manager the project's `packageManager` pins
+    pub const fn uses_project(&self) -> bool {
+        matches!(
+            self,
+            Self::Add(_)
+                | Self::Dedupe(_)
+                | Self::Dev(_)
+                | Self::Install(_)
+                | Self::Link(_)
+                | Self::Patch(_)
+                | Self::PatchCommit(_)
+                | Self::Prune(_)
+                | Self::Publish(_)
+                | Self::Remove(_)
+                | Self::Run(_)
+                | Self::Unlink(_)
+                | Self::Update(_)
+                | Self::Version(_)
+        )
+    }
+
+    /// The name of the command, without its arguments
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Access(_) => "access",
+            Self::Add(_) => "add",
+            Self::Audit(_) => "audit",
+            Self::Bin(_) => "bin",
+            Self::Bugs(_) => "bugs",
+            Self::Cache(_) => "cache",
+            Self::Check(_) => "check",
+            Self::Clone(_) => "clone",
+            Self::Completions(_) => "completions",
+            Self::Compress(_) => "compress",
+            Self::Decompress(_) => "decompress",
+            Self::Config(_) => "config",
+            Self::Daemon(_) => "daemon",
+            Self::Init(_) => "init",
+            Self::Install(_) => "install",
+            Self::Clean(_) => "clean",
+            Self::Dedupe(_) => "dedupe",
+            Self::Dev(_) => "dev",
+            Self::Docs(_) => "docs",
+            Self::Deprecate(_) => "deprecate",
+            Self::Discord(_) => "discord",
+            Self::Doctor(_) => "doctor",
+            Self::DistTag(_) => "dist-tag",
+            Self::Graph(_) => "graph",
+            Self::Sbom(_) => "sbom",
+            Self::Search(_) => "search",
+            Self::Stats(_) => "stats",
+            Self::Login(_) => "login",
+            Self::Prune(_) => "prune",
+            Self::Publish(_) => "publish",
+            Self::Remove(_) => "remove",
+            Self::Repo(_) => "repo",
+            Self::Run(_) => "run",
+            Self::Info(_) => "info",
+            Self::Licenses(_) => "licenses",
+            Self::Link(_) => "link",
+            Self::Migrate(_) => "migrate",
+            Self::Missing(_) => "missing",
+            Self::Node(_) => "node",
+            Self::Outdated(_) => "outdated",
+            Self::Owner(_) => "owner",
+            Self::Pack(_) => "pack",
+            Self::Patch(_) => "patch",
+            Self::PatchCommit(_) => "patch-commit",
+            Self::Plugin(_) => "plugin",
+            Self::Telemetry(_) => "telemetry",
+            Self::Token(_) => "token",
+            Self::List(_) => "list",
+            Self::Unlink(_) => "unlink",
+            Self::Unused(_) => "unused",
+            Self::Update(_) => "update",
+            Self::Version(_) => "version",
+            Self::Why(_) => "why",
+            Self::X(_) => "x",
+            // plugin names aren't recorded, they could identify a company
+            Self::External(_) => "external",
         }
     }
 }
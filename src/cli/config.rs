@@ -14,21 +14,100 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use crate::core::utils::errors::VoltError;
+use crate::core::{
+    ci::Ci,
+    config::{Options, Source},
+    events::Events,
+    utils::errors::VoltError,
+};
 
-use clap::{ArgMatches, Parser};
+use clap::{ArgEnum, ArgMatches, Parser};
 use dirs::home_dir;
 use package_spec::{parse_package_spec, PackageSpec};
 use sha1::Digest;
 use sha2::Sha512;
 use ssri::{Algorithm, Integrity};
-use std::{env, path::PathBuf};
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+use tracing::Level;
 
 #[derive(Debug, Clone, Parser)]
 pub struct VoltConfig {
     /// Path to current working directory
     #[clap(short, long)]
     cwd: Option<PathBuf>,
+
+    /// Install packages even if they break the policy in volt.toml
+    #[clap(long, global = true)]
+    no_policy: bool,
+
+    /// Check the attestations of packages that publish them while installing
+    #[clap(long, global = true)]
+    verify_provenance: bool,
+
+    /// Fail instead of warning when packages or the project want a different node or volt
+    /// version in their `engines`
+    #[clap(long, global = true)]
+    engine_strict: bool,
+
+    /// One-time password for registry accounts with two-factor authentication
+    #[clap(long, global = true)]
+    otp: Option<String>,
+
+    /// Set by the commands that take `--dry-run`, so nothing they share with others writes
+    #[clap(skip)]
+    dry_run: bool,
+
+    /// Write a JSON report of the install to a file, with the resolved packages, the time
+    /// each phase took, cache hits and warnings
+    #[clap(long, global = true, value_name = "FILE")]
+    report: Option<PathBuf>,
+
+    /// Stream install events as NDJSON to `-` (stdout), `tcp://host:port` or a Unix socket
+    #[clap(long, global = true, value_name = "TARGET")]
+    emit_events: Option<String>,
+
+    /// Print machine-readable JSON to stdout, with progress and messages on stderr
+    #[clap(long, global = true)]
+    json: bool,
+
+    /// When to print colors and progress bars, `auto` disables them for pipes and `NO_COLOR`
+    #[clap(long, global = true, arg_enum)]
+    color: Option<ColorChoice>,
+
+    /// Log more, `-v` for debug logs and `-vv` for traces
+    #[clap(short, long, global = true, parse(from_occurrences))]
+    verbose: u64,
+
+    /// Only print errors
+    #[clap(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Behave like in CI even when it isn't detected: never prompt and print status lines
+    /// instead of progress bars
+    #[clap(long = "ci", global = true)]
+    force_ci: bool,
+
+    /// Write the debug log of this run to a file instead of `~/.volt/logs`
+    #[clap(long, global = true)]
+    log_file: Option<PathBuf>,
+
+    /// Options from the configuration files and environment, see `load_options`
+    #[clap(skip)]
+    options: Options,
+
+    /// Connected by `connect_events`
+    #[clap(skip)]
+    events: Events,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum ColorChoice {
+    Always,
+    Auto,
+    Never,
 }
 
 impl VoltConfig {
@@ -36,6 +115,193 @@ impl VoltConfig {
     pub const VOLT_HOME: &'static str = ".volt";
     pub const VOLT_LOCK: &'static str = "volt.lock";
 
+    /// Read the configuration files and environment, with the flags that were passed on top
+    pub fn load_options(&mut self) -> miette::Result<()> {
+        let mut options = Options::load(&self.user_config_file()?, &self.cwd()?)?;
+
+        if let Some(color) = self.color {
+            let color = match color {
+                ColorChoice::Always => "always",
+                ColorChoice::Auto => "auto",
+                ColorChoice::Never => "never",
+            };
+
+            options.set_flag("color", color.to_string());
+        }
+
+        if self.verify_provenance {
+            options.set_flag("verify-provenance", String::from("true"));
+        }
+
+        if self.engine_strict {
+            options.set_flag("engine-strict", String::from("true"));
+        }
+
+        if self.force_ci {
+            options.set_flag("ci", String::from("always"));
+        }
+
+        self.options = options;
+
+        Ok(())
+    }
+
+    /// Connect to the target of `--emit-events`, if it was passed
+    pub fn connect_events(&mut self) -> miette::Result<()> {
+        if let Some(target) = &self.emit_events {
+            self.events = Events::connect(target)?;
+        }
+
+        Ok(())
+    }
+
+    /// Where install events are streamed
+    pub fn events(&self) -> &Events {
+        &self.events
+    }
+
+    /// Every configured option, with where its value came from
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// The registry packages are installed from and published to, without a trailing slash
+    pub fn registry(&self) -> &str {
+        self.options.value("registry").trim_end_matches('/')
+    }
+
+    /// How many packages are installed at once
+    pub fn concurrency(&self) -> usize {
+        self.options
+            .value("concurrency")
+            .parse::<usize>()
+            .map_or(1, |concurrency| concurrency.max(1))
+    }
+
+    /// Clone the configuration, pointing it at a different working directory
+    pub fn with_cwd(&self, cwd: PathBuf) -> Self {
+        let mut config = self.clone();
+        config.cwd = Some(cwd);
+        config
+    }
+
+    /// Clone the configuration for a command run with or without `--dry-run`
+    pub fn with_dry_run(&self, dry_run: bool) -> Self {
+        let mut config = self.clone();
+        config.dry_run = dry_run;
+        config
+    }
+
+    /// Whether installs should be checked against the project's policy
+    pub fn policy_enabled(&self) -> bool {
+        !self.no_policy
+    }
+
+    /// Whether installs should verify the provenance attestations of packages
+    pub fn verify_provenance(&self) -> bool {
+        self.options.value("verify-provenance") == "true"
+    }
+
+    /// Whether an unsatisfied `engines` range is an error rather than a warning
+    pub fn engine_strict(&self) -> bool {
+        self.options.value("engine-strict") == "true"
+    }
+
+    /// Whether a project pinning another package manager or volt version is an error
+    pub fn package_manager_strict(&self) -> bool {
+        self.options.value("package-manager-strict") == "true"
+    }
+
+    /// Whether volt runs the version a project pins when it's installed
+    pub fn package_manager_switch(&self) -> bool {
+        self.options.value("package-manager-switch") == "true"
+    }
+
+    /// Whether the user opted into telemetry, which a project can't do for them
+    pub fn telemetry(&self) -> bool {
+        matches!(
+            self.options.get("telemetry"),
+            Some(("true", source)) if source != Source::Project
+        )
+    }
+
+    /// The one-time password passed with `--otp`
+    pub fn otp(&self) -> Option<&str> {
+        self.otp.as_deref()
+    }
+
+    /// Whether stdout is reserved for `--json` or `--emit-events -`, so messages go to stderr
+    pub fn stdout_reserved(&self) -> bool {
+        self.json || self.events.on_stdout()
+    }
+
+    /// The file passed with `--report`
+    pub fn report_file(&self) -> Option<&Path> {
+        self.report.as_deref()
+    }
+
+    /// Whether `--json` was passed
+    pub fn json(&self) -> bool {
+        self.json
+    }
+
+    /// Whether `--dry-run` was passed, so commands only report what they would change
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Whether `-q` was passed, so only errors are printed
+    pub fn quiet(&self) -> bool {
+        self.quiet
+    }
+
+    /// The most detailed level printed to the terminal
+    pub fn log_level(&self) -> Level {
+        match (self.quiet, self.verbose) {
+            (true, _) => Level::ERROR,
+            (_, 0) => Level::INFO,
+            (_, 1) => Level::DEBUG,
+            _ => Level::TRACE,
+        }
+    }
+
+    /// The path passed with `--log-file`
+    pub fn log_file(&self) -> Option<&Path> {
+        self.log_file.as_deref()
+    }
+
+    /// The CI environment volt is running in, if any, as `--ci` and the `ci` option override it
+    pub fn ci(&self) -> Option<Ci> {
+        match self.options.value("ci") {
+            "always" => Some(Ci::detect().unwrap_or(Ci::Other)),
+            "never" => None,
+            _ => Ci::detect(),
+        }
+    }
+
+    /// Whether output goes to a terminal, rather than a pipe, a file or a CI log
+    pub fn interactive(&self) -> bool {
+        atty::is(atty::Stream::Stdout) && self.ci().is_none()
+    }
+
+    /// Whether progress bars should be drawn, which only garble logs and pipes
+    pub fn show_progress(&self) -> bool {
+        self.interactive() && !self.quiet
+    }
+
+    /// Whether output should be colored, following `--color` and the `NO_COLOR` convention
+    pub fn color(&self) -> bool {
+        match self.options.value("color") {
+            "always" => true,
+            "never" => false,
+            // https://no-color.org: set and not empty
+            _ => {
+                env::var_os("NO_COLOR").map_or(true, |value| value.is_empty())
+                    && (self.interactive() || self.ci().map_or(false, Ci::supports_color))
+            }
+        }
+    }
+
     pub fn home(&self) -> miette::Result<PathBuf> {
         Ok(dirs::home_dir().ok_or(VoltError::GetHomeDirError)?)
     }
@@ -65,6 +331,47 @@ impl VoltConfig {
         Ok(self.home()?.join(Self::VOLT_HOME))
     }
 
+    /// Path to the global installation directory (defaults to `~/.volt/global`)
+    pub fn global_dir(&self) -> miette::Result<PathBuf> {
+        Ok(self.volt_home()?.join("global"))
+    }
+
+    /// Path to the directory global executables are linked into (defaults to `~/.volt/bin`)
+    pub fn global_bin(&self) -> miette::Result<PathBuf> {
+        Ok(self.volt_home()?.join("bin"))
+    }
+
+    /// Path to the user configuration (defaults to `~/.volt/config.toml`)
+    pub fn user_config_file(&self) -> miette::Result<PathBuf> {
+        Ok(self.volt_home()?.join("config.toml"))
+    }
+
+    /// Path to the directory plugins are installed into (defaults to `~/.volt/plugins`)
+    pub fn plugins_dir(&self) -> miette::Result<PathBuf> {
+        Ok(self.volt_home()?.join("plugins"))
+    }
+
+    /// Path to the directory node runtimes are installed in (defaults to `~/.volt/node`)
+    pub fn node_dir(&self) -> miette::Result<PathBuf> {
+        Ok(self.volt_home()?.join("node"))
+    }
+
+    /// Path to the directory telemetry is queued in (defaults to `~/.volt/telemetry`)
+    pub fn telemetry_dir(&self) -> miette::Result<PathBuf> {
+        Ok(self.volt_home()?.join("telemetry"))
+    }
+
+    /// Path to the directory debug logs are kept in (defaults to `~/.volt/logs`)
+    pub fn logs_dir(&self) -> miette::Result<PathBuf> {
+        Ok(self.volt_home()?.join("logs"))
+    }
+
+    /// Path to the directory packages registered with `volt link` are linked from
+    /// (defaults to `~/.volt/links`)
+    pub fn links_dir(&self) -> miette::Result<PathBuf> {
+        Ok(self.volt_home()?.join("links"))
+    }
+
     /// Calculate the hash of a tarball
     ///
     /// ## Examples
@@ -0,0 +1,189 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Control who can install and publish a package.
+
+use async_trait::async_trait;
+use clap::{ArgEnum, Parser, Subcommand};
+use colored::Colorize;
+use miette::Result;
+use reqwest::Method;
+use serde_json::json;
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::{
+        auth::{Access as Visibility, RegistryClient},
+        utils::package::PackageJson,
+    },
+};
+
+/// Control who can install and publish a package
+#[derive(Debug, Parser)]
+pub struct Access {
+    #[clap(subcommand)]
+    command: AccessCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum AccessCommand {
+    /// Let anyone install the package
+    Public {
+        /// The package, the current package by default
+        package: Option<String>,
+    },
+    /// Only let members of the package's organization install it
+    Restricted {
+        /// The package, the current package by default
+        package: Option<String>,
+    },
+    /// Give an organization team access to the package
+    Grant {
+        #[clap(arg_enum)]
+        permissions: Permissions,
+        /// The team, as `scope:team`
+        team: String,
+        /// The package, the current package by default
+        package: Option<String>,
+    },
+    /// Remove an organization team's access to the package
+    Revoke {
+        /// The team, as `scope:team`
+        team: String,
+        /// The package, the current package by default
+        package: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ArgEnum)]
+enum Permissions {
+    ReadOnly,
+    ReadWrite,
+}
+
+impl Permissions {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::ReadOnly => "read-only",
+            Self::ReadWrite => "read-write",
+        }
+    }
+}
+
+#[async_trait]
+impl VoltCommand for Access {
+    /// Execute the `volt access` command
+    ///
+    /// Change the visibility of a package, or which organization teams can use it.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Let the `@volt:core` team publish the current package
+    /// // .exec() is an async call so you need to await it
+    /// Access { command: AccessCommand::Grant { permissions: Permissions::ReadWrite, team: "volt:core".into(), package: None } }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let package = match &self.command {
+            AccessCommand::Public { package }
+            | AccessCommand::Restricted { package }
+            | AccessCommand::Grant { package, .. }
+            | AccessCommand::Revoke { package, .. } => package.clone(),
+        };
+
+        let package = match package {
+            Some(package) => package,
+            None => PackageJson::get_from_dir(&config.cwd()?)?.0.name,
+        };
+
+        let mut client = RegistryClient::new(&config)?;
+
+        match self.command {
+            AccessCommand::Public { .. } => {
+                set_visibility(&mut client, &package, Visibility::Public).await?;
+            }
+            AccessCommand::Restricted { .. } => {
+                set_visibility(&mut client, &package, Visibility::Restricted).await?;
+            }
+            AccessCommand::Grant {
+                permissions, team, ..
+            } => {
+                client
+                    .put(
+                        &team_path(&team)?,
+                        &json!({ "package": package, "permissions": permissions.as_str() }),
+                    )
+                    .await?;
+
+                println!(
+                    "{} {} {} access to {}",
+                    "+".bright_green().bold(),
+                    team,
+                    permissions.as_str(),
+                    package
+                );
+            }
+            AccessCommand::Revoke { team, .. } => {
+                client
+                    .request(
+                        Method::DELETE,
+                        &team_path(&team)?,
+                        Some(&json!({ "package": package })),
+                    )
+                    .await?;
+
+                println!("{} {} access to {}", "-".bright_red().bold(), team, package);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+async fn set_visibility(
+    client: &mut RegistryClient,
+    package: &str,
+    visibility: Visibility,
+) -> Result<()> {
+    if !package.starts_with('@') {
+        miette::bail!("only scoped packages can change their access");
+    }
+
+    client
+        .post(
+            &format!("-/package/{}/access", package.replace('/', "%2f")),
+            &json!({ "access": visibility.as_str() }),
+        )
+        .await?;
+
+    println!("{} is now {}", package, visibility.as_str().bright_cyan());
+
+    Ok(())
+}
+
+/// The registry path of a `scope:team`'s packages
+fn team_path(team: &str) -> Result<String> {
+    match team.trim_start_matches('@').split_once(':') {
+        Some((scope, team)) if !scope.is_empty() && !team.is_empty() => Ok(format!(
+            "-/team/{}/{}/package",
+            urlencoding::encode(scope),
+            urlencoding::encode(team)
+        )),
+        _ => miette::bail!("expected a team like `scope:team`, found `{}`", team),
+    }
+}
@@ -16,289 +16,1474 @@ limitations under the License.
 
 //! Add a package to the dependencies for your project.
 
-use std::{collections::HashMap, time::Instant};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::Path,
+    time::Instant,
+};
 
 use crate::{
     cli::{VoltCommand, VoltConfig},
-    core::net::fetch_dep_tree,
-    core::utils::{package::PackageJson, voltapi::VoltPackage},
+    commands::search::thousands,
+    core::ci::{self, annotate, Annotation},
+    core::daemon,
+    core::events::Event,
+    core::hooks::{Hook, Hooks},
+    core::model::{
+        conflict::{find_conflicts, newest, Conflict},
+        engines::{Engines, Mismatch},
+        policy::Policy,
+        provenance::{check_provenance, UNVERIFIED_NOTE},
+        signature::{needs_publish_time, verify_signature, RegistryKey, SignatureMode},
+    },
+    core::net::{
+        fetch_dep_tree, get_publish_times, get_registry_keys, get_registry_packument,
+        get_version_manifests, search_registry, RegistryManifest, SearchObject,
+    },
+    core::output::{print_json, status},
+    core::progress::InstallProgress,
+    core::prompt::prompts::{Confirm, Input, MultiSelect, Select},
+    core::runtime,
+    core::settings::Settings,
+    core::telemetry,
+    core::utils::{
+        errors::VoltError, extensions::PathExtensions, package::PackageJson, voltapi::VoltPackage,
+    },
     core::{
-        model::lock_file::LockFile,
-        utils::{install_package, State},
+        model::{lock_file::LockFile, plan::Plan, report::InstallReport},
+        utils::{install_tree, is_on_path, link_bins, link_package, patch::apply_patch},
     },
 };
 
 use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
 use clap::Parser;
 use colored::Colorize;
-use futures::{stream::FuturesUnordered, StreamExt, TryStreamExt};
-use indicatif::{ProgressBar, ProgressStyle};
 use miette::IntoDiagnostic;
+use node_semver::{Range, Version};
 use package_spec::PackageSpec;
-use reqwest::Client;
+use serde_json::json;
+
+use std::borrow::Cow;
 
 /// Add a package to your project's dependencies
 #[derive(Debug, Parser)]
 pub struct Add {
     /// Packages to add to the dependencies for your project.
     packages: Vec<PackageSpec>,
+
+    /// Install the packages globally and link their executables into the global bin directory
+    #[clap(short, long)]
+    global: bool,
+
+    /// Add the packages to `devDependencies` instead of `dependencies`
+    #[clap(short = 'D', long, conflicts_with = "global")]
+    dev: bool,
+
+    /// Search the registry and pick the packages to add, the default without packages
+    #[clap(short, long)]
+    interactive: bool,
+
+    /// Resolve and print the packages that would change, without installing them
+    #[clap(long)]
+    dry_run: bool,
 }
 
+/// The number of search results to pick from at once
+const SEARCH_RESULTS: usize = 20;
+
 #[async_trait]
 impl VoltCommand for Add {
     async fn exec(self, config: VoltConfig) -> miette::Result<()> {
-        // let global_lock_file = LockFile::load(config.home()?.join(".global.lock"), true).unwrap();
+        let config = config.with_dry_run(self.dry_run);
 
-        // let local_lock_file =
+        // global packages are installed into their own project at `~/.volt/global`
+        let config = if self.global {
+            let global_dir = config.global_dir()?;
 
-        let bar = ProgressBar::new_spinner()
-            .with_style(ProgressStyle::default_spinner().template("{spinner:.cyan} {msg}"));
+            std::fs::create_dir_all(&global_dir).into_diagnostic()?;
 
-        bar.enable_steady_tick(10);
+            config.with_cwd(global_dir)
+        } else {
+            config
+        };
 
-        let resolve_start = Instant::now();
+        // each package is added to `dependencies` or `devDependencies` on its own
+        let selections = if self.interactive || self.packages.is_empty() {
+            pick_packages(self.dev, self.global).await?
+        } else {
+            self.packages
+                .iter()
+                .map(|package| (package.clone(), self.dev))
+                .collect()
+        };
 
-        let mut requested_packages = vec![];
+        if selections.is_empty() {
+            return Ok(());
+        }
 
-        // Fetch pre-flattened dependency trees from the registry
-        let responses = fetch_dep_tree(&self.packages, &bar).await?;
+        let packages = selections
+            .iter()
+            .map(|(package, _)| package.clone())
+            .collect::<Vec<_>>();
 
-        let mut tree: HashMap<String, VoltPackage> = HashMap::new();
+        let dev_packages = selections
+            .iter()
+            .filter(|(_, dev)| *dev)
+            .filter_map(|(package, _)| match package {
+                PackageSpec::Npm { name, .. } => Some(name.clone()),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
 
-        for response in responses {
-            let mut index = 0;
+        let root_packages = install_packages(&config, &packages, self.global).await?;
 
-            for package in &self.packages {
-                if let PackageSpec::Npm {
-                    name,
-                    scope,
-                    requested,
-                } = package
-                {
-                    // recieve the version of a package that has been requested from the response
-                    if *name == response.name {
-                        requested_packages.push(PackageSpec::Npm {
-                            scope: scope.to_owned(),
-                            name: name.to_owned(),
-                            requested: Some(package_spec::VersionSpec::Tag(
-                                response.version.clone(),
-                            )),
-                        });
+        if config.dry_run() {
+            return Ok(());
+        }
+
+        let project_dir = config.cwd()?;
+
+        let project_name = if self.global {
+            String::from("volt-global")
+        } else {
+            project_dir
+                .file_name_as_string()
+                .ok_or(VoltError::GetCurrentDirNameError)?
+        };
+
+        let mut package_file = PackageJson::load_or_new(&project_dir, &project_name)?;
+
+        let requested = packages
+            .iter()
+            .filter_map(|package| match package {
+                PackageSpec::Npm { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+
+        // peers installed to resolve conflicts were already added to package.json
+        for package in root_packages
+            .iter()
+            .filter(|package| requested.contains(package.name.as_str()))
+        {
+            let dependencies = if dev_packages.contains(&package.name) {
+                package_file
+                    .dev_dependencies
+                    .get_or_insert_with(BTreeMap::new)
+            } else {
+                package_file.dependencies.get_or_insert_with(BTreeMap::new)
+            };
+
+            dependencies.insert(package.name.clone(), format!("^{}", package.version));
+        }
+
+        // Save package.json
+        package_file.save_to(&project_dir.join("package.json"))?;
+
+        if self.global {
+            link_global_bins(&config, &root_packages)?;
+        }
+
+        if config.json() {
+            let added = root_packages
+                .iter()
+                .map(|package| {
+                    let kind = if self.global {
+                        "global"
+                    } else if dev_packages.contains(&package.name) {
+                        "dev"
                     } else {
-                        requested_packages.push(PackageSpec::Npm {
-                            name: name.to_string(),
-                            scope: scope.to_owned(),
-                            requested: requested.to_owned(),
-                        });
-                    }
-                }
+                        "prod"
+                    };
+
+                    json!({
+                        "name": package.name,
+                        "version": package.version,
+                        "type": kind,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            print_json(&added)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Resolve and install `packages` into the project at `config.cwd()`
+///
+/// The packages are linked into `node_modules` and recorded in the lockfile,
+/// but not added to package.json. Returns the installed root packages.
+pub async fn install_packages(
+    config: &VoltConfig,
+    packages: &[PackageSpec],
+    global: bool,
+) -> miette::Result<Vec<VoltPackage>> {
+    install(config, packages, None, global).await
+}
+
+/// Install a tree that is already resolved, like one imported from another package manager's
+/// lockfile, with the same checks, patches and links as [`install_packages`]
+pub async fn install_resolved(
+    config: &VoltConfig,
+    roots: Vec<VoltPackage>,
+    tree: HashMap<String, VoltPackage>,
+) -> miette::Result<Vec<VoltPackage>> {
+    let packages = roots
+        .iter()
+        .map(|root| {
+            let spec = format!("{}@{}", root.name, root.version);
+
+            spec.parse::<PackageSpec>()
+                .map_err(|_| VoltError::PackageSpecificationError { spec })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    install(config, &packages, Some((roots, tree)), false).await
+}
+
+/// Check, install and link the packages, resolving them first unless `resolved` already holds
+/// the root packages and their tree
+async fn install(
+    config: &VoltConfig,
+    packages: &[PackageSpec],
+    resolved: Option<(Vec<VoltPackage>, HashMap<String, VoltPackage>)>,
+    global: bool,
+) -> miette::Result<Vec<VoltPackage>> {
+    // errors are returned after the group is closed, so they aren't folded away
+    let _group = ci::group(config, "Installing dependencies");
+
+    let progress = InstallProgress::new(config);
+
+    let mut report = InstallReport::new();
+
+    let resolve_start = Instant::now();
+
+    let settings = Settings::load(&config.cwd()?)?;
+
+    let hooks = Hooks::load(&config.user_config_file()?, &settings.hooks)?;
+
+    // a dry run doesn't change anything, which hooks can't promise
+    let run_hook =
+        |hook: Hook, tree: Option<&HashMap<String, VoltPackage>>| -> miette::Result<()> {
+            if config.dry_run() {
+                return Ok(());
             }
 
-            tree.extend(response.tree);
+            let context = hook_context(config, hook, packages, tree, resolve_start)?;
+
+            progress.suspend(|| hooks.run(config, hook, &context))
+        };
+
+    run_hook(Hook::PreInstall, None)?;
+
+    if !config.dry_run() {
+        resolve_lockfile_conflicts(config, global, &progress).await?;
+    }
+
+    let (root_packages, mut tree) = match resolved {
+        Some(resolved) => resolved,
+        None => {
+            resolve(
+                config,
+                &settings,
+                &progress,
+                &mut report,
+                packages,
+                global,
+                resolve_start,
+            )
+            .await?
         }
+    };
+
+    run_hook(Hook::PostResolve, Some(&tree))?;
+
+    report.warnings.extend(check_engines(
+        config,
+        &config.cwd()?,
+        &root_packages,
+        &tree,
+    )?);
+
+    report.phase("resolve", resolve_start);
+
+    if config.dry_run() {
+        progress.finish();
+
+        let before = LockFile::load(config.lockfile()?, global)?;
+        let mut after = before.clone();
+        after.add(&root_packages, tree);
+
+        let mut plan = Plan::between(&before, &after);
+        plan.estimate_downloads(
+            config,
+            after
+                .dependencies
+                .values()
+                .filter(|package| !before.dependencies.contains_key(&package.key())),
+        )
+        .await?;
+        plan.print(config)?;
+
+        return Ok(root_packages);
+    }
+
+    let verify_start = Instant::now();
+
+    let client = reqwest::Client::new();
+
+    let check_policy = config.policy_enabled() && !settings.policy.is_empty();
+    let check_signatures = settings.registry_signatures != SignatureMode::Off;
+    let check_provenance = config.verify_provenance();
+
+    let manifests = if (check_policy && settings.policy.checks_manifests())
+        || check_signatures
+        || check_provenance
+    {
+        get_version_manifests(&client, &tree, config.concurrency()).await?
+    } else {
+        HashMap::new()
+    };
+
+    let package_json = config.cwd()?.join("package.json");
+
+    // a policy denying deprecated packages reports them as errors instead
+    if !(check_policy && settings.policy.deny_deprecated) {
+        report
+            .warnings
+            .extend(warn_deprecated(&manifests, &package_json));
+    }
+
+    if check_policy {
+        enforce_policy(
+            &settings.policy,
+            &root_packages,
+            &tree,
+            &manifests,
+            &package_json,
+        )?;
+    }
+
+    let keys = if check_signatures || check_provenance {
+        get_registry_keys(&client).await?
+    } else {
+        Some(vec![])
+    };
+
+    let keys = match keys {
+        Some(keys) => keys,
+        None => {
+            let warning = format!(
+                "{} doesn't publish signing keys, so signatures aren't checked",
+                config.registry()
+            );
+
+            progress.suspend(|| eprintln!("{}: {}", "warning".yellow().bold(), warning));
+            report.warnings.push(warning);
+
+            vec![]
+        }
+    };
+
+    if check_signatures && !keys.is_empty() {
+        report.warnings.extend(
+            verify_signatures(
+                config,
+                &client,
+                &keys,
+                settings.registry_signatures,
+                &mut tree,
+                &manifests,
+            )
+            .await?,
+        );
+    }
+
+    if check_provenance {
+        verify_provenance(config, &client, &keys, &tree, &manifests).await?;
+    }
+
+    report.phase("verify", verify_start);
+
+    let install_start = Instant::now();
+
+    let tree = install_tree(config, tree, &progress).await?;
+
+    report.phase("install", install_start);
+
+    let patch_start = Instant::now();
+
+    apply_patches(config, &tree, &progress)?;
+
+    report.phase("patch", patch_start);
 
-        bar.finish_and_clear();
+    progress.finish();
 
-        println!(
-            "{} Resolved {} dependencies",
-            format!("[{:.2}{}]", resolve_start.elapsed().as_secs_f32(), "s")
+    let total = tree.len();
+
+    status(
+        config,
+        format!(
+            "{} Installed {} dependencies",
+            format!("[{:.2}{}]", install_start.elapsed().as_secs_f32(), "s")
                 .truecolor(156, 156, 156)
                 .bold(),
-            tree.len().to_string().truecolor(196, 206, 255).bold()
-        );
+            total.to_string().truecolor(196, 206, 255).bold()
+        ),
+    );
+
+    let link_start = Instant::now();
+
+    for package in &root_packages {
+        // node_modules/react -> node_modules/.volt/react@18.0.0/node_modules/react
+        link_package(config, package)?;
+    }
+
+    report.packages(&tree);
+    report.cache.misses = progress.downloads();
+    report.cache.hits = total.saturating_sub(report.cache.misses);
+    report.cache.downloaded_bytes = progress.downloaded_bytes();
+
+    telemetry::cache_stats(report.cache.hits, report.cache.misses);
+
+    // Save the lockfile
+    let mut lock_file = LockFile::load(config.lockfile()?, global)?;
+
+    // an upgrade leaves the dependencies of the old version behind
+    lock_file.add(&root_packages, tree.clone());
+    lock_file.prune();
+    lock_file.save()?;
+
+    report.phase("link", link_start);
+
+    run_hook(Hook::PostInstall, Some(&tree))?;
+
+    config.events().emit(Event::InstallCompleted {
+        packages: total,
+        duration_ms: resolve_start.elapsed().as_millis(),
+    });
+
+    if let Some(path) = config.report_file() {
+        report.write(path)?;
+    }
+
+    if !global {
+        daemon::touch(&config.cwd()?);
+    }
+
+    Ok(root_packages)
+}
+
+/// Resolve `packages` and their dependencies against the registry, along with the peers picked
+/// for unmet peer dependencies, returning the root packages and the whole tree
+async fn resolve(
+    config: &VoltConfig,
+    settings: &Settings,
+    progress: &InstallProgress,
+    report: &mut InstallReport,
+    packages: &[PackageSpec],
+    global: bool,
+    resolve_start: Instant,
+) -> miette::Result<(Vec<VoltPackage>, HashMap<String, VoltPackage>)> {
+    // the packages that were asked for, as opposed to their dependencies
+    let mut root_packages = vec![];
+
+    let packages = match settings.minimum_release_age {
+        Some(days) => apply_release_age(config, settings, days, packages).await?,
+        None => packages.to_vec(),
+    };
 
-        let install_start = Instant::now();
+    let packages = packages.as_slice();
 
-        let nm_dir = config.node_modules()?;
-        let nm_volt_home = nm_dir.join(VoltConfig::VOLT_HOME);
+    config.events().emit(Event::ResolutionStarted {
+        packages: packages.iter().map(ToString::to_string).collect(),
+    });
 
-        if !nm_dir.exists() {
-            std::fs::create_dir_all(&nm_volt_home).unwrap();
+    // Fetch pre-flattened dependency trees from the registry
+    let responses = fetch_dep_tree(packages, progress.resolving()).await?;
+
+    let mut tree: HashMap<String, VoltPackage> = HashMap::new();
+
+    for response in responses {
+        if let Some(root) = response
+            .tree
+            .get(&format!("{}@{}", response.name, response.version))
+        {
+            root_packages.push(root.clone());
         }
 
-        let client = Client::builder().use_rustls_tls().build().unwrap();
+        tree.extend(response.tree);
+    }
 
-        let mut incompatible_packages = vec![];
+    progress.resolved(tree.len());
 
-        // pnpm linking algorithm
-        for value in tree.values() {
-            // None means it's not platform-specific
-            // We get a list of platforms, and if our current OS isn't on this list - it means that we can skip this package
-            // this is only if the package is optional
+    progress.suspend(|| {
+        status(
+            config,
+            format!(
+                "{} Resolved {} dependencies",
+                format!("[{:.2}{}]", resolve_start.elapsed().as_secs_f32(), "s")
+                    .truecolor(156, 156, 156)
+                    .bold(),
+                tree.len().to_string().truecolor(196, 206, 255).bold()
+            ),
+        )
+    });
 
-            if let Some(os) = &value.os {
-                if !os.contains(&"win32".to_string()) && !os.contains(&format!("!{}", "win32")) {
-                    incompatible_packages.push(format!("{}@{}", value.name, value.version));
-                    continue;
-                }
-            }
+    if !global {
+        let peers = resolve_conflicts(config, progress, &root_packages, &tree, report).await?;
 
-            if let Some(architecture) = &value.cpu {
-                if !architecture.contains(&"x64".to_string()) {
-                    incompatible_packages.push(format!("{}@{}", value.name, value.version));
-                    continue;
+        if !peers.is_empty() {
+            for response in fetch_dep_tree(&peers, progress.resolving()).await? {
+                if let Some(root) = response
+                    .tree
+                    .get(&format!("{}@{}", response.name, response.version))
+                {
+                    root_packages.push(root.clone());
                 }
+
+                tree.extend(response.tree);
             }
+        }
+    }
 
-            let mut name = value.name.clone();
-            let mut scope: Option<String> = None;
-            let mut last: Option<String> = None;
-
-            if value.name.starts_with('@') {
-                // replace @ with +
-                name = name.replace('/', "+");
-
-                scope = Some(
-                    name.split('+')
-                        .map(|v| v.to_string())
-                        .collect::<Vec<String>>()
-                        .first()
-                        .unwrap()
-                        .to_string(),
-                );
+    Ok((root_packages, tree))
+}
+
+/// How the user chose to resolve a peer dependency conflict
+enum Resolution {
+    /// Depend on a version satisfying every range
+    Install(Version),
+    /// Depend on a version satisfying some of the ranges and record it in `overrides`
+    Override(Version),
+    Skip,
+}
+
+/// Warn about the peer dependencies the resolved tree leaves unmet and, when volt can prompt,
+/// let the user pick a resolution for each, which is written to package.json
+///
+/// Returns the peers to install along with the requested packages.
+async fn resolve_conflicts(
+    config: &VoltConfig,
+    progress: &InstallProgress,
+    roots: &[VoltPackage],
+    tree: &HashMap<String, VoltPackage>,
+    report: &mut InstallReport,
+) -> miette::Result<Vec<PackageSpec>> {
+    let package_json = config.cwd()?.join("package.json");
+
+    let mut manifest = if package_json.exists() {
+        Some(PackageJson::read(&package_json)?)
+    } else {
+        None
+    };
+
+    let overrides = manifest
+        .as_ref()
+        .and_then(|manifest| manifest.overrides.clone())
+        .unwrap_or_default();
+
+    let mut after = LockFile::load(config.lockfile()?, false)?;
+    after.add(roots, tree.clone());
+
+    let conflicts = find_conflicts(&after, tree.values(), &overrides);
+
+    if conflicts.is_empty() {
+        return Ok(vec![]);
+    }
+
+    progress.suspend(|| {
+        for conflict in &conflicts {
+            eprintln!("{}: {}", "warning".yellow().bold(), conflict);
+
+            let mut details = vec![];
 
-                last = Some(
-                    name.split('+')
-                        .map(|v| v.to_string())
-                        .collect::<Vec<String>>()
-                        .last()
-                        .unwrap()
-                        .to_string(),
+            for constraint in conflict.unsatisfied() {
+                eprintln!(
+                    "  {} requires {}@{}",
+                    constraint.dependent, conflict.peer, constraint.range
                 );
+                eprintln!(
+                    "    {} {}",
+                    "path:".truecolor(156, 156, 156),
+                    constraint.path.join(" > ")
+                );
+
+                details.push(format!(
+                    "{} requires {}@{} (path: {})",
+                    constraint.dependent,
+                    conflict.peer,
+                    constraint.range,
+                    constraint.path.join(" > ")
+                ));
             }
 
-            std::fs::create_dir(nm_volt_home.join(format!("{}@{}", name, value.version)))
-                .into_diagnostic()?;
+            annotate(
+                Annotation::Warning,
+                &format!("Unmet peer dependency {}", conflict.peer),
+                Some(&package_json),
+                &details.join("\n"),
+            );
 
-            std::fs::create_dir(
-                nm_volt_home
-                    .join(format!("{}@{}", name, value.version))
-                    .join("node_modules/"),
-            )
-            .into_diagnostic()?;
-
-            if scope.is_none() {
-                std::fs::create_dir(
-                    nm_volt_home
-                        .join(format!("{}@{}", name, value.version))
-                        .join("node_modules/")
-                        .join(&name),
-                )
-                .into_diagnostic()?;
-            } else {
-                std::fs::create_dir(
-                    nm_volt_home
-                        .join(format!("{}@{}", name, value.version))
-                        .join("node_modules/")
-                        .join(scope.as_ref().unwrap()),
-                )
-                .into_diagnostic()?;
-
-                std::fs::create_dir(
-                    nm_volt_home
-                        .join(format!("{}@{}", name, value.version))
-                        .join("node_modules/")
-                        .join(scope.as_ref().unwrap())
-                        .join(&last.unwrap()),
+            report.warnings.push(conflict.to_string());
+        }
+    });
+
+    let manifest = match manifest.as_mut() {
+        Some(manifest) if config.interactive() && !config.dry_run() => manifest,
+        _ => return Ok(vec![]),
+    };
+
+    let client = reqwest::Client::new();
+
+    let mut peers = vec![];
+
+    for conflict in &conflicts {
+        let versions = get_registry_packument(&client, &conflict.peer)
+            .await?
+            .versions
+            .keys()
+            .filter_map(|version| version.parse::<Version>().ok())
+            .collect::<Vec<_>>();
+
+        let declared = manifest
+            .dependencies
+            .iter()
+            .chain(manifest.dev_dependencies.iter())
+            .find_map(|dependencies| dependencies.get(&conflict.peer))
+            .cloned();
+
+        let range = match progress.suspend(|| choose_resolution(conflict, &versions, &declared))? {
+            Resolution::Install(version) => format!("^{}", version),
+            Resolution::Override(version) => {
+                manifest
+                    .overrides
+                    .get_or_insert_with(BTreeMap::new)
+                    .insert(conflict.peer.clone(), version.to_string());
+
+                version.to_string()
+            }
+            Resolution::Skip => continue,
+        };
+
+        // keep a peer the project already depends on in the same section
+        let dependencies = match &mut manifest.dev_dependencies {
+            Some(dev) if dev.contains_key(&conflict.peer) => dev,
+            _ => manifest.dependencies.get_or_insert_with(BTreeMap::new),
+        };
+
+        dependencies.insert(conflict.peer.clone(), range.clone());
+
+        let spec = format!("{}@{}", conflict.peer, range);
+
+        peers.push(
+            spec.parse::<PackageSpec>()
+                .map_err(|_| VoltError::PackageSpecificationError { spec })?,
+        );
+    }
+
+    if !peers.is_empty() {
+        manifest.save_to(&package_json)?;
+    }
+
+    Ok(peers)
+}
+
+/// Ask how to resolve a conflict, offering the newest version satisfying every range, versions
+/// satisfying some of them as overrides, and leaving it unresolved
+fn choose_resolution(
+    conflict: &Conflict,
+    versions: &[Version],
+    declared: &Option<String>,
+) -> miette::Result<Resolution> {
+    let mut choices = vec![];
+
+    let newest_satisfying = conflict.newest_satisfying(versions);
+
+    if let Some(version) = &newest_satisfying {
+        let label = match declared {
+            Some(range) => format!(
+                "Change {} from {} to ^{} in package.json",
+                conflict.peer, range, version
+            ),
+            None => format!("Add {}@^{} to dependencies", conflict.peer, version),
+        };
+
+        choices.push((label, Resolution::Install(version.clone())));
+    }
+
+    let mut offered = newest_satisfying.into_iter().collect::<Vec<_>>();
+
+    for constraint in conflict.unsatisfied() {
+        let range = match constraint.range.parse::<Range>() {
+            Ok(range) => range,
+            Err(_) => continue,
+        };
+
+        if let Some(version) = newest(versions, |version| range.satisfies(version)) {
+            if !offered.contains(&version) {
+                choices.push((
+                    format!(
+                        "Override {} to {}, which {} accepts",
+                        conflict.peer, version, constraint.dependent
+                    ),
+                    Resolution::Override(version.clone()),
+                ));
+
+                offered.push(version);
+            }
+        }
+    }
+
+    if let Some(installed) = conflict
+        .installed
+        .as_ref()
+        .and_then(|version| version.parse::<Version>().ok())
+    {
+        choices.push((
+            format!(
+                "Override {} to {}, keeping the installed version",
+                conflict.peer, installed
+            ),
+            Resolution::Override(installed),
+        ));
+    }
+
+    choices.push((String::from("Skip"), Resolution::Skip));
+
+    let message = if conflict.newest_satisfying(versions).is_some() {
+        format!("Resolve {}", conflict.peer.bright_cyan())
+    } else {
+        format!(
+            "No version of {} satisfies every range, resolve it",
+            conflict.peer.bright_cyan()
+        )
+    };
+
+    let selected = Select {
+        message: message.into(),
+        paged: false,
+        selected: Some(1),
+        items: choices
+            .iter()
+            .map(|(label, _)| Cow::Borrowed(label.as_str()))
+            .collect(),
+    }
+    .run()
+    .into_diagnostic()?;
+
+    Ok(choices.swap_remove(selected).1)
+}
+
+/// What a hook gets on stdin: the requested packages, the resolved ones once they're known,
+/// and how long the install has taken
+fn hook_context(
+    config: &VoltConfig,
+    hook: Hook,
+    packages: &[PackageSpec],
+    tree: Option<&HashMap<String, VoltPackage>>,
+    start: Instant,
+) -> miette::Result<serde_json::Value> {
+    let resolved = tree.map(|tree| {
+        let mut resolved = tree
+            .values()
+            .map(|package| {
+                json!({
+                    "name": package.name,
+                    "version": package.version,
+                    "integrity": package.integrity,
+                    "tarball": package.tarball,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        resolved.sort_by_key(|package| package.to_string());
+        resolved
+    });
+
+    Ok(json!({
+        "hook": hook.name(),
+        "cwd": config.cwd()?,
+        "packages": packages.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        "resolved": resolved,
+        "durationMs": start.elapsed().as_millis() as u64,
+    }))
+}
+
+/// Re-apply the project's `patchedDependencies` to the packages that were just installed
+fn apply_patches(
+    config: &VoltConfig,
+    tree: &HashMap<String, VoltPackage>,
+    progress: &InstallProgress,
+) -> miette::Result<()> {
+    let project_dir = config.cwd()?;
+
+    // global installs don't always have a package.json
+    if !project_dir.join("package.json").exists() {
+        return Ok(());
+    }
+
+    let (manifest, _) = PackageJson::get_from_dir(&project_dir)?;
+
+    let patched = match manifest.patched_dependencies {
+        Some(patched) => patched,
+        None => return Ok(()),
+    };
+
+    let node_modules = config.node_modules()?;
+
+    progress.building(
+        tree.keys()
+            .filter(|key| patched.contains_key(key.as_str()))
+            .count(),
+    );
+
+    for package in tree.values() {
+        if let Some(patch) = patched.get(&package.key()) {
+            apply_patch(
+                &package.package_directory(&node_modules),
+                &project_dir.join(patch),
+                &package.key(),
+            )?;
+
+            progress.built(package);
+
+            progress.suspend(|| {
+                status(
+                    config,
+                    format!("{} {}", "Patched".bright_green().bold(), package.key()),
                 )
-                .into_diagnostic()?;
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Warn about the deprecated packages in the resolved tree, using the manifests fetched for the
+/// other checks, returning the warnings
+fn warn_deprecated(
+    manifests: &HashMap<String, RegistryManifest>,
+    package_json: &Path,
+) -> Vec<String> {
+    let mut deprecated = manifests
+        .values()
+        .filter_map(|manifest| Some((manifest, manifest.deprecated.as_ref()?)))
+        .collect::<Vec<_>>();
+
+    deprecated.sort_by(|a, b| (&a.0.name, &a.0.version).cmp(&(&b.0.name, &b.0.version)));
+
+    let mut warnings = vec![];
+
+    for (manifest, message) in deprecated {
+        let key = format!("{}@{}", manifest.name, manifest.version);
+        let warning = format!("{} is deprecated: {}", key, message);
+
+        eprintln!("{}: {}", "warning".yellow().bold(), warning);
+
+        annotate(
+            Annotation::Warning,
+            &format!("{} is deprecated", key),
+            Some(package_json),
+            message,
+        );
+
+        warnings.push(warning);
+    }
+
+    warnings
+}
+
+/// Fail before installing anything if the resolved tree breaks the project's policy
+fn enforce_policy(
+    policy: &Policy,
+    roots: &[VoltPackage],
+    tree: &HashMap<String, VoltPackage>,
+    manifests: &HashMap<String, RegistryManifest>,
+    package_json: &Path,
+) -> miette::Result<()> {
+    let violations = policy.check(roots, tree, manifests);
+
+    if violations.is_empty() {
+        return Ok(());
+    }
+
+    for violation in &violations {
+        eprintln!("{}: {}", "error".bright_red().bold(), violation);
+        eprintln!(
+            "  {} {}",
+            "path:".truecolor(156, 156, 156),
+            violation.path.join(" > ")
+        );
+
+        annotate(
+            Annotation::Error,
+            "Policy violation",
+            Some(package_json),
+            &format!("{}\npath: {}", violation, violation.path.join(" > ")),
+        );
+    }
+
+    eprintln!(
+        "Pass {} to install anyway while debugging",
+        "--no-policy".bright_cyan()
+    );
+
+    Err(VoltError::PolicyViolationError {
+        count: violations.len(),
+    }
+    .into())
+}
+
+/// Settle the direct dependencies a merge conflict left in the lockfile against the merged
+/// package.json, keeping a side's version while it's still in range and re-resolving the rest
+async fn resolve_lockfile_conflicts(
+    config: &VoltConfig,
+    global: bool,
+    progress: &InstallProgress,
+) -> miette::Result<()> {
+    let mut lock_file = LockFile::load(config.lockfile()?, global)?;
+
+    if lock_file.conflicts.is_empty() {
+        return Ok(());
+    }
+
+    let package_file = PackageJson::load_or_new(&config.cwd()?, "volt-global")?;
+
+    let declared = [&package_file.dependencies, &package_file.dev_dependencies]
+        .into_iter()
+        .flatten()
+        .flatten()
+        .collect::<HashMap<_, _>>();
+
+    let conflicts = std::mem::take(&mut lock_file.conflicts);
+
+    let mut specs = vec![];
+
+    // dependencies package.json no longer declares are dropped with their trees
+    for (name, versions) in &conflicts {
+        let range = match declared.get(name) {
+            Some(range) => *range,
+            None => continue,
+        };
+
+        let kept = range.parse::<Range>().ok().and_then(|range| {
+            versions
+                .iter()
+                .filter_map(|version| version.parse::<Version>().ok())
+                .filter(|version| version.satisfies(&range))
+                .max()
+        });
+
+        match kept {
+            Some(version) => {
+                lock_file.direct.insert(name.clone(), version.to_string());
+            }
+            None => {
+                let spec = format!("{}@{}", name, range);
+
+                specs.push(
+                    spec.parse::<PackageSpec>()
+                        .map_err(|_| VoltError::PackageSpecificationError { spec })?,
+                );
             }
         }
+    }
+
+    if !specs.is_empty() {
+        let mut roots = vec![];
+        let mut tree = HashMap::new();
 
-        for item in incompatible_packages {
-            tree.remove(&item);
+        for response in fetch_dep_tree(&specs, progress.resolving()).await? {
+            if let Some(root) = response
+                .tree
+                .get(&format!("{}@{}", response.name, response.version))
+            {
+                roots.push(root.clone());
+            }
+
+            tree.extend(response.tree);
         }
 
-        let total = tree.len();
+        lock_file.add(&roots, tree);
+    }
+
+    lock_file.prune();
+    lock_file.save()?;
+
+    progress.suspend(|| {
+        status(
+            config,
+            format!(
+                "{} {} conflicting entries in {}",
+                "Resolved".bright_green().bold(),
+                conflicts.len(),
+                VoltConfig::VOLT_LOCK
+            ),
+        )
+    });
 
-        let bar = ProgressBar::new(total as u64);
+    Ok(())
+}
 
-        bar.set_style(
-            ProgressStyle::default_bar()
-                .template("[{bar:40.cyan/blue}] {pos:>7}/{len:7} {msg}")
-                .progress_chars("=>-"),
+/// Check the `engines` of the project in `project_dir` and of a resolved tree against the node
+/// and volt versions in use, returning the warnings, or failing with `--engine-strict`
+pub fn check_engines(
+    config: &VoltConfig,
+    project_dir: &Path,
+    roots: &[VoltPackage],
+    tree: &HashMap<String, VoltPackage>,
+) -> miette::Result<Vec<String>> {
+    let package_json = project_dir.join("package.json");
+    let project = PackageJson::read(&package_json).ok();
+
+    let root = project
+        .as_ref()
+        .map(|package| package.name.clone())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| String::from("project"));
+
+    let engines = Engines::new(runtime::node_version(config, project_dir)?);
+
+    let mut mismatches = engines.check(&root, roots, tree);
+
+    if let Some(ranges) = project.and_then(|package| package.engines) {
+        let unsatisfied = engines.unsatisfied(
+            ranges
+                .iter()
+                .map(|(engine, range)| (engine.as_str(), range.as_str())),
         );
 
-        // todo: display progress bar for downloads that are taking time.
-        tree.values()
-            .map(|data| {
-                install_package(
-                    config.clone(),
-                    data.clone(),
-                    State {
-                        http_client: client.clone(),
-                    },
-                )
+        for (engine, wanted, current) in unsatisfied {
+            mismatches.insert(
+                0,
+                Mismatch {
+                    path: vec![root.clone()],
+                    engine,
+                    wanted,
+                    current,
+                },
+            );
+        }
+    }
+
+    let strict = config.engine_strict();
+
+    let (label, annotation) = if strict {
+        ("error".bright_red().bold(), Annotation::Error)
+    } else {
+        ("warning".yellow().bold(), Annotation::Warning)
+    };
+
+    for mismatch in &mismatches {
+        eprintln!("{}: {}", label, mismatch);
+        eprintln!(
+            "  {} {}",
+            "path:".truecolor(156, 156, 156),
+            mismatch.path.join(" > ")
+        );
+
+        annotate(
+            annotation,
+            "Unsupported engine",
+            Some(&package_json),
+            &format!("{}\npath: {}", mismatch, mismatch.path.join(" > ")),
+        );
+    }
+
+    if strict && !mismatches.is_empty() {
+        return Err(VoltError::EngineMismatchError {
+            count: mismatches.len(),
+        }
+        .into());
+    }
+
+    Ok(mismatches.iter().map(ToString::to_string).collect())
+}
+
+/// Check the registry signature of every package in the resolved tree before installing it,
+/// returning the problems that were only warned about
+async fn verify_signatures(
+    config: &VoltConfig,
+    client: &reqwest::Client,
+    keys: &[RegistryKey],
+    mode: SignatureMode,
+    tree: &mut HashMap<String, VoltPackage>,
+    manifests: &HashMap<String, RegistryManifest>,
+) -> miette::Result<Vec<String>> {
+    // older resolutions only carry a sha-1, the tarball is checked against the signed sha512
+    for (key, package) in tree.iter_mut() {
+        let signed = manifests
+            .get(key)
+            .and_then(|manifest| manifest.dist.integrity.as_ref())
+            .filter(|integrity| integrity.starts_with("sha512-"));
+
+        if let Some(integrity) = signed {
+            if !package.integrity.starts_with("sha512-") {
+                package.integrity = integrity.clone();
+            }
+        }
+    }
+
+    let published = get_publish_times(
+        client,
+        tree.iter()
+            .filter(|(key, _)| {
+                manifests
+                    .get(*key)
+                    .map_or(false, |manifest| needs_publish_time(keys, manifest))
             })
-            .collect::<FuturesUnordered<_>>()
-            .inspect(|_| bar.inc(1))
-            .try_collect::<Vec<_>>()
-            .await
-            .unwrap();
-
-        bar.finish_and_clear();
-
-        // for package in requested_packages.iter() {
-        //     if let PackageSpec::Npm {
-        //         name,
-        //         scope,
-        //         requested,
-        //     } = package
-        //     {
-        //         let mut node_modules_directory = config.node_modules().unwrap();
-
-        //         // path to the package directory
-        //         let mut package_directory = node_modules_directory
-        //             .join(".volt")
-        //             .join(format!("{}@{}", &name, requested.as_ref().unwrap()))
-        //             .join("node_modules/")
-        //             .join(&name);
-
-        //         // path to the symlink
-        //         let mut target_directory = node_modules_directory.join(name);
-
-        //         #[cfg(windows)]
-        //         junction::create(&package_directory, &target_directory).unwrap_or_else(|e| {
-        //             eprintln!(
-        //                 "target: {} destination: {}, {}",
-        //                 package_directory.display(),
-        //                 target_directory.display(),
-        //                 e
-        //             );
-        //             std::process::exit(1);
-        //         });
-
-        //         #[cfg(unix)]
-        //         std::os::unix::fs::symlink(package_directory, target_directory).unwrap_or_else(
-        //             |e| {
-        //                 eprintln!("{}", e);
-        //                 std::process::exit(1);
-        //             },
-        //         );
-        //     }
-        // }
-
-        println!(
-            "{} Installed {} dependencies",
-            format!("[{:.2}{}]", install_start.elapsed().as_secs_f32(), "s")
-                .truecolor(156, 156, 156)
-                .bold(),
-            total.to_string().truecolor(196, 206, 255).bold()
+            .map(|(_, package)| package),
+        config.concurrency(),
+    )
+    .await?;
+
+    let mut problems = tree
+        .iter()
+        .filter_map(|(key, package)| {
+            let manifest = manifests.get(key)?;
+
+            verify_signature(
+                keys,
+                package,
+                manifest,
+                published.get(key).map(String::as_str),
+            )
+            .err()
+            .map(|problem| (key, problem))
+        })
+        .collect::<Vec<_>>();
+
+    if problems.is_empty() {
+        return Ok(vec![]);
+    }
+
+    problems.sort_by(|a, b| a.0.cmp(b.0));
+
+    let label = match mode {
+        SignatureMode::Fail => "error".bright_red().bold(),
+        _ => "warning".yellow().bold(),
+    };
+
+    for (key, problem) in &problems {
+        eprintln!("{}: {} {}", label, key, problem);
+    }
+
+    if mode != SignatureMode::Fail {
+        return Ok(problems
+            .iter()
+            .map(|(key, problem)| format!("{} {}", key, problem))
+            .collect());
+    }
+
+    eprintln!(
+        "Set {} in {} to install anyway",
+        "registry-signatures = \"warn\"".bright_cyan(),
+        Settings::FILE_NAME
+    );
+
+    Err(VoltError::SignatureVerificationError {
+        count: problems.len(),
+    }
+    .into())
+}
+
+/// Verify the attestations of every package in the resolved tree that publishes them
+async fn verify_provenance(
+    config: &VoltConfig,
+    client: &reqwest::Client,
+    keys: &[RegistryKey],
+    tree: &HashMap<String, VoltPackage>,
+    manifests: &HashMap<String, RegistryManifest>,
+) -> miette::Result<()> {
+    let packages = tree
+        .iter()
+        .filter_map(|(key, package)| Some((package, manifests.get(key)?)))
+        .collect::<Vec<_>>();
+
+    let checks = check_provenance(client, keys, &packages).await?;
+
+    let provenanced = checks
+        .iter()
+        .filter(|check| matches!(check.outcome, Ok(Some(_))))
+        .count();
+
+    status(
+        config,
+        format!(
+            "{} of {} packages have unverified build provenance",
+            provenanced.to_string().truecolor(196, 206, 255).bold(),
+            tree.len()
+        ),
+    );
+
+    if provenanced > 0 {
+        eprintln!("{}: {}", "warning".yellow().bold(), UNVERIFIED_NOTE);
+    }
+
+    let failures = checks
+        .iter()
+        .filter_map(|check| Some((check.package, check.outcome.as_ref().err()?)))
+        .collect::<Vec<_>>();
+
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    for (package, problem) in &failures {
+        eprintln!(
+            "{}: {} {}",
+            "error".bright_red().bold(),
+            package.key(),
+            problem
         );
+    }
 
-        // let (mut package_file, path) = PackageJson::get()?;
+    Err(VoltError::ProvenanceVerificationError {
+        count: failures.len(),
+    }
+    .into())
+}
 
-        // for package in requested_packages.iter() {
-        //     package_file.add_dependency(package.to_owned());
-        // }
+/// Pin each requested package to the newest matching version published at least `days` ago
+///
+/// Only the requested packages can be pinned, their dependencies arrive already resolved
+/// from the volt registry.
+async fn apply_release_age(
+    config: &VoltConfig,
+    settings: &Settings,
+    days: u32,
+    packages: &[PackageSpec],
+) -> miette::Result<Vec<PackageSpec>> {
+    let client = reqwest::Client::new();
+    let cutoff = Utc::now() - Duration::days(i64::from(days));
 
-        // // Save package.json
-        // package_file.save()?;
+    let mut pinned = vec![];
 
-        // // Save lockfiles
-        // // global_lock_file.save()?;
-        // // lock_file.save()?;
+    for package in packages {
+        let (name, requested) = match package {
+            PackageSpec::Npm {
+                name, requested, ..
+            } if settings.release_age_applies(name) => (name, requested),
+            _ => {
+                pinned.push(package.clone());
+                continue;
+            }
+        };
 
-        Ok(())
+        let metadata = get_registry_packument(&client, name).await?;
+
+        let requested = requested.as_ref().map(ToString::to_string);
+
+        let exact = requested.as_deref().and_then(|r| r.parse::<Version>().ok());
+
+        let range = match exact {
+            Some(_) => None,
+            None => requested.as_deref().and_then(|r| r.parse::<Range>().ok()),
+        };
+
+        // exact versions and tags become upper bounds, so older releases can stand in for them
+        let limit = match (&exact, &range) {
+            (Some(exact), _) => Some(exact.clone()),
+            (None, Some(_)) => None,
+            (None, None) => metadata
+                .dist_tags
+                .get(requested.as_deref().unwrap_or("latest"))
+                .and_then(|version| version.parse::<Version>().ok()),
+        };
+
+        let matches = |version: &Version| match (&range, &limit) {
+            (Some(range), _) => range.satisfies(version),
+            (None, Some(limit)) => version.pre_release.is_empty() && version <= limit,
+            (None, None) => false,
+        };
+
+        let old_enough = |version: &Version| {
+            metadata
+                .time
+                .get(&version.to_string())
+                .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+                .map_or(false, |time| time <= cutoff)
+        };
+
+        let candidates = metadata
+            .versions
+            .keys()
+            .filter_map(|version| version.parse::<Version>().ok())
+            .filter(|version| matches(version))
+            .collect::<Vec<_>>();
+
+        let newest = candidates.iter().max().cloned();
+
+        let version = candidates
+            .into_iter()
+            .filter(|version| old_enough(version))
+            .max()
+            .ok_or_else(|| VoltError::VersionLookupError { name: name.clone() })?;
+
+        if let Some(newest) = newest.filter(|newest| *newest != version) {
+            status(
+                config,
+                format!(
+                    "{} using {}@{} instead of {}, which was published less than {} days ago",
+                    "info".bright_blue().bold(),
+                    name,
+                    version,
+                    newest,
+                    days
+                ),
+            );
+        }
+
+        let spec = format!("{}@{}", name, version);
+
+        pinned.push(
+            spec.parse()
+                .map_err(|_| VoltError::PackageSpecificationError { spec })?,
+        );
+    }
+
+    Ok(pinned)
+}
+
+/// Link the executables of globally installed packages into the global bin directory
+fn link_global_bins(config: &VoltConfig, packages: &[VoltPackage]) -> miette::Result<()> {
+    let bin_dir = config.global_bin()?;
+    let node_modules = config.node_modules()?;
+
+    for package in packages {
+        let commands = link_bins(
+            &bin_dir,
+            package.bins(),
+            &package.package_directory(&node_modules),
+        )?;
+
+        for command in commands {
+            status(
+                config,
+                format!(
+                    "{} {} from {}@{}",
+                    "Linked".bright_green().bold(),
+                    command,
+                    package.name,
+                    package.version
+                ),
+            );
+        }
     }
+
+    if !is_on_path(&bin_dir) {
+        eprintln!(
+            "{}: {} is not on your PATH, add it to run globally installed executables",
+            "warning".yellow().bold(),
+            bin_dir.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Search the registry until the user has picked the packages to add, along with whether each
+/// one is a dev dependency
+async fn pick_packages(dev: bool, global: bool) -> miette::Result<Vec<(PackageSpec, bool)>> {
+    let client = reqwest::Client::new();
+    let mut picked: Vec<(PackageSpec, bool)> = vec![];
+
+    loop {
+        let query = Input {
+            message: "Search the registry (empty to finish)".into(),
+            default: None,
+            allow_empty: true,
+        }
+        .run()
+        .into_diagnostic()?;
+
+        if query.trim().is_empty() {
+            break;
+        }
+
+        let results = search_registry(&client, query.trim(), SEARCH_RESULTS, 0).await?;
+
+        if results.objects.is_empty() {
+            println!("No packages found for {}", query.bright_cyan());
+            continue;
+        }
+
+        let selected = MultiSelect {
+            message: "Choose the packages to add".into(),
+            items: results
+                .objects
+                .iter()
+                .map(|result| (Cow::Owned(preview(result)), false))
+                .collect(),
+        }
+        .run()
+        .into_diagnostic()?;
+
+        for index in selected {
+            let name = &results.objects[index].package.name;
+
+            // global packages don't have dev dependencies
+            let is_dev = !global
+                && Select {
+                    message: format!("Add {} to", name.bright_cyan()).into(),
+                    paged: false,
+                    selected: Some(if dev { 2 } else { 1 }),
+                    items: vec!["dependencies".into(), "devDependencies".into()],
+                }
+                .run()
+                .into_diagnostic()?
+                    == 1;
+
+            let spec = name
+                .parse::<PackageSpec>()
+                .map_err(|_| VoltError::PackageSpecificationError { spec: name.clone() })?;
+
+            picked.push((spec, is_dev));
+        }
+
+        let more = Confirm {
+            message: "Search for more packages?".into(),
+            default: false,
+        }
+        .run()
+        .into_diagnostic()?;
+
+        if !more {
+            break;
+        }
+    }
+
+    Ok(picked)
+}
+
+/// A search result with its latest version, weekly downloads and description on one line
+fn preview(result: &SearchObject) -> String {
+    let mut line = format!(
+        "{} {}",
+        result.package.name.bold(),
+        result.package.version.truecolor(156, 156, 156)
+    );
+
+    if let Some(downloads) = &result.downloads {
+        line.push_str(&format!(
+            " {}",
+            format!("({} weekly)", thousands(downloads.weekly)).truecolor(156, 156, 156)
+        ));
+    }
+
+    if let Some(description) = &result.package.description {
+        let description = if description.chars().count() > 80 {
+            format!("{}...", description.chars().take(77).collect::<String>())
+        } else {
+            description.clone()
+        };
+
+        line.push_str(&format!("  {}", description));
+    }
+
+    line
 }
@@ -14,168 +14,604 @@
     limitations under the License.
 */
 
-//! Handle an unknown command (can be listed in scripts).
+//! Check installed packages for known vulnerabilities.
 
 use async_trait::async_trait;
+use clap::{Parser, Subcommand};
+use colored::Colorize;
 use miette::Result;
-use serde::{Deserialize, Serialize};
-
-use std::{collections::HashMap, sync::Arc};
-
-use crate::cli::{VoltCommand, VoltConfig};
-
-pub struct Audit {}
-
-#[derive(Debug)]
-pub struct AuditObject {
-    name: String,
-    version: String,
-    install: Vec<String>,
-    remove: Vec<String>,
-    metadata: HashMap<String, String>,
-    requires: HashMap<String, String>,
-    dependencies: HashMap<String, AuditDependency>,
+use node_semver::{Range, Version};
+use package_spec::PackageSpec;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    commands::add::install_packages,
+    core::ci::{annotate, Annotation},
+    core::model::{
+        audit::{audit, AuditReport, Severity},
+        lock_file::{DependencyKind, LockFile},
+        provenance::{check_provenance, UNVERIFIED_NOTE},
+        signature::{needs_publish_time, verify_signature, SignatureProblem},
+    },
+    core::net::{
+        get_cached_registry_package, get_publish_times, get_registry_keys, get_version_manifests,
+    },
+    core::output::print_json,
+    core::settings::Settings,
+    core::utils::{errors::VoltError, package::PackageJson},
+};
+
+/// Number of dependency paths printed for each vulnerability
+const MAX_PATHS: usize = 3;
+
+/// Check installed packages for known vulnerabilities
+#[derive(Debug, Parser)]
+pub struct Audit {
+    #[clap(subcommand)]
+    command: Option<AuditCommand>,
+
+    /// The minimum severity that makes the audit fail
+    #[clap(long, arg_enum, default_value = "low")]
+    audit_level: Severity,
+
+    /// Skip devDependencies
+    #[clap(long)]
+    production: bool,
 }
 
-#[derive(Debug)]
-pub struct AuditDependency {
-    version: String,
-    integrity: String,
-    requires: HashMap<String, String>,
-    dependencies: HashMap<String, AuditDependency>,
-    dev: bool,
+#[derive(Debug, Subcommand)]
+enum AuditCommand {
+    /// Update vulnerable dependencies to versions without known vulnerabilities
+    Fix {
+        /// Allow updates outside the ranges in package.json, which may include breaking changes
+        #[clap(long)]
+        force: bool,
+    },
+    /// Verify the registry signatures and provenance attestations of installed packages
+    Signatures,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct AuditResponse {
-    actions: Vec<String>,
-    advisories: HashMap<String, String>,
-    muted: Vec<String>,
-    metadata: AuditMetadata,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Vulnerabilities {
-    info: u128,
-    low: u128,
-    moderate: u128,
-    high: u128,
-    critical: u128,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct AuditMetadata {
-    vulnerabilities: Vulnerabilities,
-    dependencies: u128,
-    dev_dependencies: u128,
-    optional_dependencies: u128,
-    total_dependencies: u128,
-}
-
-// pub fn flatten_dependency_tree(
-//     package: &VoltPackage,
-//     volt_packages: &HashMap<String, VoltPackage>,
-// ) -> Vec<AuditDependency> {
-//     let mut packages: Vec<AuditDependency> = vec![];
-
-//     if package.dependencies.is_some() {
-//         for dep in package.dependencies.as_ref().unwrap().iter() {
-//             let dependency = volt_packages.get(dep).unwrap();
-
-//             let mut requires: HashMap<String, String> = HashMap::new();
-
-//             if dependency.dependencies.is_some() {
-//                 for de in dependency.dependencies.as_ref().unwrap() {
-//                     let pkg = volt_packages.get(de).unwrap();
-//                     requires.insert(de.to_owned(), pkg.version.clone());
-//                 }
-//             }
-
-//             let fdt = &mut flatten_dependency_tree(dependency, volt_packages);
-
-//             packages.push(AuditDependency {
-//                 version: dependency.version,
-//                 integrity: dependency.integrity,
-//                 requires: requires,
-//                 dependencies: HashMap::new()
-//                     .insert(
-//                         dep.to_owned(),
-//                         ,
-//                     )
-//                     .unwrap(),
-//                 dev: false,
-//             });
-//         }
-//     }
-
-//     packages
-// }
-
 #[async_trait]
 impl VoltCommand for Audit {
     /// Execute the `volt audit` command
     ///
-    /// Execute a audit command
+    /// Submit every installed package to the registry's advisory database and report the
+    /// vulnerabilities found, exiting with an error if any reach the audit level.
     /// ## Arguments
-    /// * `error` - Instance of the command (`Arc<App>`)
+    /// * `config` - The global volt configuration (`VoltConfig`)
     /// ## Examples
     /// ```
-    /// //
+    /// // Fail CI only for high and critical vulnerabilities
     /// // .exec() is an async call so you need to await it
-    /// Audit.exec(app).await;
+    /// Audit { command: None, audit_level: Severity::High, production: false }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
     async fn exec(self, config: VoltConfig) -> Result<()> {
-        // let package_json = PackageJson::from("package.json");
+        let project_dir = config.cwd()?;
+
+        let (package_file, package_path) = PackageJson::get_from_dir(&project_dir)?;
+
+        let lock_file = LockFile::load(config.lockfile()?, false)?;
+
+        let mut manifests = vec![package_file.clone()];
+
+        manifests.extend(
+            package_file
+                .workspace_packages(&project_dir)?
+                .into_iter()
+                .map(|(_, manifest)| manifest),
+        );
+
+        let client = reqwest::Client::new();
+
+        if let Some(AuditCommand::Signatures) = self.command {
+            return audit_signatures(&config, &client, &lock_file, &manifests, self.production)
+                .await;
+        }
+
+        let settings = Settings::load(package_path.parent().unwrap_or(&project_dir))?;
+        let today = chrono::Local::now().naive_local().date();
+
+        let mut report = audit(&client, &lock_file, &manifests, self.production).await?;
+
+        for warning in report.apply_exceptions(&settings.audit_exceptions, today) {
+            eprintln!("{}: {}", "warning".yellow().bold(), warning);
+        }
+
+        if let Some(AuditCommand::Fix { force }) = self.command {
+            let fixes = find_fixes(
+                &client,
+                &package_file,
+                &lock_file,
+                &report,
+                force,
+                self.production,
+            )
+            .await?;
+
+            if fixes.is_empty() {
+                println!(
+                    "{}",
+                    "No vulnerabilities can be fixed automatically".bright_yellow()
+                );
+            } else {
+                apply_fixes(&config, package_file, &package_path, &fixes).await?;
+
+                let lock_file = LockFile::load(config.lockfile()?, false)?;
+                let mut remaining = audit(&client, &lock_file, &manifests, self.production).await?;
+
+                remaining.apply_exceptions(&settings.audit_exceptions, today);
+
+                println!(
+                    "{} {} of {} vulnerabilities",
+                    "Fixed".bright_green().bold(),
+                    report
+                        .vulnerabilities
+                        .len()
+                        .saturating_sub(remaining.vulnerabilities.len()),
+                    report.vulnerabilities.len()
+                );
+
+                report = remaining;
+            }
+
+            if !report.vulnerabilities.is_empty() {
+                println!();
+                println!("{}", "Unfixable".bright_red().bold());
+
+                for vulnerability in &report.vulnerabilities {
+                    println!(
+                        "  {}@{}  {} ({})",
+                        vulnerability.name.bold(),
+                        vulnerability.version,
+                        vulnerability.advisory.title,
+                        vulnerability
+                            .advisory
+                            .severity
+                            .colorize(vulnerability.advisory.severity.as_str())
+                    );
+                }
+
+                if !force {
+                    println!(
+                        "\nRun {} to allow updates that may include breaking changes",
+                        "volt audit fix --force".bright_cyan()
+                    );
+                }
+            }
+        } else if config.json() {
+            print_json(&report)?;
+        } else {
+            print_report(&report);
+        }
+
+        for vulnerability in &report.vulnerabilities {
+            let annotation = if vulnerability.advisory.severity >= self.audit_level {
+                Annotation::Error
+            } else {
+                Annotation::Warning
+            };
+
+            annotate(
+                annotation,
+                &format!(
+                    "{} vulnerability in {}@{}",
+                    vulnerability.advisory.severity, vulnerability.name, vulnerability.version
+                ),
+                Some(&package_path),
+                &format!(
+                    "{}\n{}",
+                    vulnerability.advisory.title, vulnerability.advisory.url
+                ),
+            );
+        }
+
+        if report.fails(self.audit_level) {
+            return Err(VoltError::VulnerabilitiesError {
+                count: report
+                    .vulnerabilities
+                    .iter()
+                    .filter(|v| v.advisory.severity >= self.audit_level)
+                    .count(),
+                level: self.audit_level.to_string(),
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+fn print_report(report: &AuditReport) {
+    if report.vulnerabilities.is_empty() {
+        println!(
+            "{} found 0 vulnerabilities in {} packages ({} ignored)",
+            "audit".bright_green().bold(),
+            report.dependencies,
+            report.ignored.len()
+        );
+
+        return;
+    }
 
-        // let mut requires = package_json.dependencies;
-        // requires.extend(package_json.dev_dependencies);
+    for severity in Severity::ALL {
+        let count = report.count(severity);
+
+        if count == 0 {
+            continue;
+        }
+
+        println!(
+            "{} ({})",
+            severity.colorize(&severity.as_str().to_uppercase()),
+            count
+        );
+
+        for vulnerability in report
+            .vulnerabilities
+            .iter()
+            .filter(|v| v.advisory.severity == severity)
+        {
+            println!(
+                "  {}@{}  {}",
+                vulnerability.name.bold(),
+                vulnerability.version,
+                vulnerability.advisory.title
+            );
+
+            println!(
+                "    {} {}  {}",
+                "vulnerable:".truecolor(156, 156, 156),
+                vulnerability.advisory.vulnerable_versions,
+                vulnerability.advisory.url.underline()
+            );
+
+            for path in vulnerability.paths.iter().take(MAX_PATHS) {
+                println!("    {} {}", "path:".truecolor(156, 156, 156), path);
+            }
+
+            if vulnerability.paths.len() > MAX_PATHS {
+                println!(
+                    "    {} and {} more",
+                    "path:".truecolor(156, 156, 156),
+                    vulnerability.paths.len() - MAX_PATHS
+                );
+            }
+        }
+
+        println!();
+    }
 
-        // let responses =
-        //     utils::get_volt_response_multi(requires.keys().cloned().collect::<Vec<String>>()).await;
+    if !report.ignored.is_empty() {
+        println!(
+            "{} vulnerabilities ignored by audit exceptions",
+            report.ignored.len()
+        );
+    }
 
-        // let mut dependencies: HashMap<String, AuditDependency> = HashMap::new();
+    let counts = Severity::ALL
+        .iter()
+        .filter(|severity| report.count(**severity) > 0)
+        .map(|severity| format!("{} {}", report.count(*severity), severity))
+        .collect::<Vec<_>>();
+
+    println!(
+        "{} found {} vulnerabilities ({}) in {} packages",
+        "audit".bright_red().bold(),
+        report.vulnerabilities.len(),
+        counts.join(", "),
+        report.dependencies
+    );
+}
 
-        // let start = Instant::now();
+/// Check every installed package's registry signature and, where published, its attestations
+async fn audit_signatures(
+    config: &VoltConfig,
+    client: &reqwest::Client,
+    lock_file: &LockFile,
+    manifests: &[PackageJson],
+    production: bool,
+) -> Result<()> {
+    let roots = manifests
+        .iter()
+        .flat_map(|manifest| lock_file.roots(manifest))
+        .filter(|(kind, _, _)| !(production && *kind == DependencyKind::Dev))
+        .filter_map(|(_, _, package)| package);
+
+    let tree = lock_file
+        .reachable(roots)
+        .into_iter()
+        .filter_map(|key| {
+            let package = lock_file.dependencies.get(&key)?.clone();
+
+            Some((key, package))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let keys = match get_registry_keys(client).await? {
+        Some(keys) => keys,
+        None => {
+            println!(
+                "{} doesn't publish signing keys, so there are no signatures to audit",
+                config.registry()
+            );
+
+            return Ok(());
+        }
+    };
+
+    let registry_manifests = get_version_manifests(client, &tree, config.concurrency()).await?;
+
+    let published = get_publish_times(
+        client,
+        tree.iter()
+            .filter(|(key, _)| {
+                registry_manifests
+                    .get(*key)
+                    .map_or(false, |manifest| needs_publish_time(&keys, manifest))
+            })
+            .map(|(_, package)| package),
+        config.concurrency(),
+    )
+    .await?;
+
+    let mut packages = tree
+        .iter()
+        .filter_map(|(key, package)| Some((package, registry_manifests.get(key)?)))
+        .collect::<Vec<_>>();
+
+    packages.sort_by(|a, b| a.0.key().cmp(&b.0.key()));
+
+    let mut signed = 0;
+    let mut missing = vec![];
+    let mut invalid = vec![];
+
+    for (package, manifest) in &packages {
+        match verify_signature(
+            &keys,
+            package,
+            manifest,
+            published.get(&package.key()).map(String::as_str),
+        ) {
+            Ok(()) => signed += 1,
+            Err(SignatureProblem::Missing) => missing.push(package.key()),
+            Err(problem) => invalid.push(format!("{} {}", package.key(), problem)),
+        }
+    }
 
-        // for res in responses {
-        //     let version = res.version;
+    let checks = check_provenance(client, &keys, &packages).await?;
+
+    let attested = checks.iter().filter(|check| check.outcome.is_ok()).count();
+
+    let tampered = checks
+        .iter()
+        .filter_map(|check| {
+            let problem = check.outcome.as_ref().err()?;
+
+            Some(format!("{} {}", check.package.key(), problem))
+        })
+        .collect::<Vec<_>>();
+
+    let provenanced = checks
+        .iter()
+        .filter(|check| matches!(check.outcome, Ok(Some(_))))
+        .count();
+
+    println!(
+        "{} audited {} packages",
+        "audit".bright_green().bold(),
+        packages.len()
+    );
+
+    println!("{} packages have verified registry signatures", signed);
+    println!(
+        "{} packages have attestations matching their tarballs",
+        attested
+    );
+
+    if provenanced > 0 {
+        println!(
+            "{} packages have unverified build provenance, {}",
+            provenanced, UNVERIFIED_NOTE
+        );
+    }
 
-        //     let packages = &res.versions.get(&version).unwrap().packages;
+    for check in &checks {
+        if let Ok(Some(provenance)) = &check.outcome {
+            println!(
+                "  {}  {}",
+                check.package.key().bold(),
+                provenance
+                    .source
+                    .as_deref()
+                    .unwrap_or("unknown source")
+                    .truecolor(156, 156, 156)
+            );
+        }
+    }
 
-        //     for package in packages {
-        //         let flattened_dependency_tree: Vec<AuditDependency> =
-        //             flatten_dependency_tree(package.1, packages);
+    let sections = [
+        ("have missing registry signatures", &missing),
+        ("have invalid registry signatures", &invalid),
+        ("have invalid attestations", &tampered),
+    ];
+
+    for (description, entries) in sections {
+        if entries.is_empty() {
+            continue;
+        }
+
+        println!();
+        println!(
+            "{} packages {}",
+            entries.len().to_string().bright_red().bold(),
+            description
+        );
+
+        for entry in entries {
+            println!("  {}", entry);
+        }
+    }
 
-        //         for dp in flattened_dependency_tree {
-        //             dependencies.insert(package.0.to_owned(), dp);
-        //         }
-        //     }
-        // }
+    if !missing.is_empty() || !invalid.is_empty() || !tampered.is_empty() {
+        return Err(VoltError::SignatureAuditError {
+            count: missing.len() + invalid.len() + tampered.len(),
+        }
+        .into());
+    }
 
-        // let audit = AuditObject {
-        //     name: package_json.name,
-        //     version: package_json.version,
-        //     install: vec![],
-        //     remove: vec![],
-        //     metadata: HashMap::new(),
-        //     requires: requires,
-        //     dependencies: dependencies,
-        // };
+    Ok(())
+}
 
-        // let mut response = chttp::post_async(
-        //     "http://registry.npmjs.org/-/npm/v1/security/audits",
-        //     format!("{:?}", audit),
-        // )
-        // .await
-        // .unwrap();
+/// A direct dependency to update in order to remediate vulnerabilities
+struct Fix {
+    name: String,
+    current: String,
+    target: Version,
+    /// Whether `target` is outside the range in package.json
+    breaking: bool,
+}
 
-        // let text = response.text_async().await.unwrap();
+/// Pick a new version for every direct dependency leading to a vulnerability
+///
+/// A vulnerable direct dependency moves to the lowest version none of its advisories affect.
+/// When the vulnerability is further down the tree the direct dependency moves to its highest
+/// allowed version instead, which pulls in the newest versions of its own dependencies.
+async fn find_fixes(
+    client: &reqwest::Client,
+    package_file: &PackageJson,
+    lock_file: &LockFile,
+    report: &AuditReport,
+    force: bool,
+    production: bool,
+) -> Result<Vec<Fix>> {
+    let mut fixes = vec![];
+
+    for (kind, requested, package) in lock_file.roots(package_file) {
+        let package = match package {
+            Some(package) if !(production && kind == DependencyKind::Dev) => package,
+            _ => continue,
+        };
+
+        let reachable = lock_file.reachable([package]);
+
+        let affecting = report
+            .vulnerabilities
+            .iter()
+            .filter(|v| reachable.contains(&format!("{}@{}", v.name, v.version)))
+            .collect::<Vec<_>>();
+
+        if affecting.is_empty() {
+            continue;
+        }
+
+        let range = requested
+            .strip_prefix(&format!("{}@", package.name))
+            .and_then(|range| range.parse::<Range>().ok());
+
+        let current = match package.version.parse::<Version>() {
+            Ok(current) => current,
+            Err(_) => continue,
+        };
+
+        let metadata = get_cached_registry_package(client, &package.name).await?;
+
+        let advisories = affecting
+            .iter()
+            .filter(|v| v.name == package.name)
+            .map(|v| &v.advisory)
+            .collect::<Vec<_>>();
+
+        let mut candidates = metadata
+            .versions
+            .keys()
+            .filter_map(|version| version.parse::<Version>().ok())
+            .filter(|version| *version > current && version.pre_release.is_empty())
+            .filter(|version| !advisories.iter().any(|a| a.affects(&version.to_string())))
+            .collect::<Vec<_>>();
+
+        candidates.sort();
+
+        let within_range =
+            |version: &Version| range.as_ref().map_or(false, |r| r.satisfies(version));
+
+        let allowed = candidates
+            .iter()
+            .filter(|version| force || within_range(version));
+
+        let target = if affecting.iter().all(|v| v.name == package.name) {
+            allowed.min()
+        } else {
+            allowed.max()
+        };
+
+        if let Some(target) = target {
+            fixes.push(Fix {
+                name: package.name.clone(),
+                current: package.version.clone(),
+                breaking: !within_range(target),
+                target: target.clone(),
+            });
+        }
+    }
 
-        // let response: AuditResponse = serde_json::from_str(text.as_str()).unwrap();
+    Ok(fixes)
+}
 
-        Ok(())
+/// Install the fixed versions, rewriting package.json for updates outside their ranges
+async fn apply_fixes(
+    config: &VoltConfig,
+    mut package_file: PackageJson,
+    package_path: &Path,
+    fixes: &[Fix],
+) -> Result<()> {
+    let specs = fixes
+        .iter()
+        .map(|fix| {
+            let spec = format!("{}@{}", fix.name, fix.target);
+
+            spec.parse::<PackageSpec>()
+                .map_err(|_| VoltError::PackageSpecificationError { spec })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let installed = install_packages(config, &specs, false).await?;
+
+    let versions = installed
+        .iter()
+        .map(|package| (package.name.as_str(), package.version.as_str()))
+        .collect::<BTreeMap<_, _>>();
+
+    for fix in fixes.iter().filter(|fix| fix.breaking) {
+        if let Some(version) = versions.get(fix.name.as_str()) {
+            package_file.bump_dependency(&fix.name, version);
+        }
     }
+
+    if fixes.iter().any(|fix| fix.breaking) {
+        package_file.save_to(package_path)?;
+    }
+
+    for fix in fixes {
+        println!(
+            "{} {} {} → {}{}",
+            "Updated".bright_green().bold(),
+            fix.name,
+            fix.current,
+            fix.target,
+            if fix.breaking {
+                " (breaking)".bright_red().to_string()
+            } else {
+                String::new()
+            }
+        );
+    }
+
+    Ok(())
 }
@@ -0,0 +1,71 @@
+/*
+    Copyright 2021, 2022 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Print the directory executables are installed into.
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::utils::is_on_path,
+};
+
+use async_trait::async_trait;
+use clap::Parser;
+use colored::Colorize;
+use miette::Result;
+
+/// Print the directory executables are installed into
+#[derive(Debug, Parser)]
+pub struct Bin {
+    /// Print the directory global executables are linked into
+    #[clap(short, long)]
+    global: bool,
+}
+
+#[async_trait]
+impl VoltCommand for Bin {
+    /// Execute the `volt bin` command
+    ///
+    /// Print `./node_modules/.bin`, or the global bin directory with `--global`.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Print the global bin directory
+    /// // .exec() is an async call so you need to await it
+    /// Bin { global: true }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let bin_dir = if self.global {
+            config.global_bin()?
+        } else {
+            config.node_modules()?.join(".bin")
+        };
+
+        println!("{}", bin_dir.display());
+
+        if self.global && !is_on_path(&bin_dir) {
+            eprintln!(
+                "{}: {} is not on your PATH",
+                "warning".yellow().bold(),
+                bin_dir.display()
+            );
+        }
+
+        Ok(())
+    }
+}
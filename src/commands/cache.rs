@@ -1,113 +1,323 @@
 /*
-Copyright 2021 Volt Contributors
-Licensed under the Apache License, Version 2.0 (the "License");
-you may not use this file except in compliance with the License.
-You may obtain a copy of the License at
-    http://www.apache.org/licenses/LICENSE-2.0
-Unless required by applicable law or agreed to in writing, software
-distributed under the License is distributed on an "AS IS" BASIS,
-WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
-See the License for the specific language governing permissions and
-limitations under the License.
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
 */
 
-//! Clean cached download files.
+//! Manage the content-addressable store packages are cached in.
 
 use crate::{
-    App,
-    Command,
-    coreutils::constants::PROGRESS_CHARS,
+    cli::{VoltCommand, VoltConfig},
+    core::{output::progress, utils::directory_size},
 };
-use volt_core::VERSION;
 
 use async_trait::async_trait;
+use clap::{Parser, Subcommand};
 use colored::Colorize;
-use indicatif::{ProgressBar, ProgressStyle};
-use miette::Result;
+use indicatif::{HumanBytes, ProgressBar};
+use miette::{IntoDiagnostic, Result};
+use ssri::Integrity;
 
 use std::{
-    env::temp_dir,
-    fs::remove_file,
-    process::exit,
-    sync::Arc,
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
 };
 
-/// Struct implementation for the `Add` command.
-#[derive(Clone)]
-pub struct Cache {}
+/// The directories cacache keeps inside the store, next to volt's global installs
+const CACHE_DIRS: [&str; 3] = ["index-v5", "content-v2", "tmp"];
 
-#[async_trait]
-impl Command for Cache {
-    /// Display a help menu for the `volt cahe` command.
-    fn help() -> String {
-        format!(
-            r#"volt {}
-    
-Handle the volt cache files.
-Usage: {} {} {}
-
-Commands:
-  clean - Clean downloaded cache files and metadata. 
-
-Options: 
-    
-  {} {} Output verbose messages on internal operations.
-  {} {} Disable progress bar."#,
-            VERSION.bright_green().bold(),
-            "volt".bright_green().bold(),
-            "cache".bright_purple(),
-            "[command]".bright_purple(),
-            "--verbose".blue(),
-            "(-v)".yellow(),
-            "--no-progress".blue(),
-            "(-np)".yellow()
-        )
-    }
+/// Manage the package cache
+#[derive(Debug, Parser)]
+pub struct Cache {
+    #[clap(subcommand)]
+    command: CacheCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum CacheCommand {
+    /// Remove every cached package, or only the versions of one (`react` or `react@18.0.0`)
+    Clean { package: Option<String> },
+    /// Re-hash every cached file, dropping the packages with corrupt files
+    Verify,
+    /// Print the location of the cache
+    Dir,
+    /// Show how many packages are cached and how much space they use
+    Stats,
+}
 
+/// A cached package, stored under `pkg::{name}::{version}::{integrity}`
+struct Entry {
+    key: String,
+    name: String,
+    version: String,
+    /// Integrity of the map of file names to file integrities
+    integrity: Integrity,
+}
+
+#[async_trait]
+impl VoltCommand for Cache {
     /// Execute the `volt cache` command
     ///
-    /// Clean your download cache.
+    /// Clean, verify, locate or measure the store volt extracts packages from.
     /// ## Arguments
-    /// * `app` - Instance of the command (`Arc<App>`)
+    /// * `config` - The global volt configuration (`VoltConfig`)
     /// ## Examples
     /// ```
-    /// // Clean your download cache (does not break symlinks)
+    /// // Drop every cached version of react
     /// // .exec() is an async call so you need to await it
-    /// Add.exec(app).await;
+    /// Cache { command: CacheCommand::Clean { package: Some("react".into()) } }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
-    async fn exec(app: Arc<App>) -> Result<()> {
-        // if app.args.len() == 1 {
-        //     println!("{}", Self::help());
-        //     exit(1);
-        // }
-        // if app.args[1].as_str() == "clean" {
-        //     let files: Vec<_> = fs::read_dir(temp_dir().join("volt")).unwrap().collect();
-
-        //     let count = files.len();
-
-        //     let progress_bar = ProgressBar::new(count.to_owned() as u64);
-
-        //     progress_bar.set_style(
-        //         ProgressStyle::default_bar()
-        //             .progress_chars(PROGRESS_CHARS)
-        //             .template(&format!(
-        //                 "{} [{{bar:40.magenta/blue}}] {{msg:.blue}} {{len}} / {{pos}}",
-        //                 "Deleting Cache".bright_blue()
-        //             )),
-        //     );
-
-        //     for file in files {
-        //         let os_str = file.unwrap().file_name();
-        //         let f_name = format!(r"{}volt\{}", temp_dir().display(), os_str.to_str().unwrap());
-
-        //         remove_file(f_name).unwrap();
-        //         progress_bar.inc(1);
-        //     }
-
-        //     progress_bar.finish();
-        // }
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let cache = config.volt_home()?;
+
+        match self.command {
+            CacheCommand::Dir => println!("{}", cache.display()),
+            CacheCommand::Clean { package: None } => {
+                let size = cache_size(&cache);
+
+                // the store also holds global packages, so only remove what cacache owns
+                for dir in CACHE_DIRS {
+                    let dir = cache.join(dir);
+
+                    if dir.exists() {
+                        fs::remove_dir_all(&dir).into_diagnostic()?;
+                    }
+                }
+
+                println!(
+                    "{} {} from the cache",
+                    "Removed".bright_green().bold(),
+                    HumanBytes(size)
+                );
+            }
+            CacheCommand::Clean {
+                package: Some(spec),
+            } => {
+                // the first `@` of a scoped package is part of its name
+                let (name, version) = match spec.rfind('@') {
+                    Some(index) if index > 0 => (&spec[..index], Some(&spec[index + 1..])),
+                    _ => (spec.as_str(), None),
+                };
+
+                let removed = entries(&cache)?
+                    .into_iter()
+                    .filter(|entry| {
+                        entry.name == name && version.map_or(true, |v| entry.version == v)
+                    })
+                    .collect::<Vec<_>>();
+
+                if removed.is_empty() {
+                    println!("{} is not cached", spec);
+                    return Ok(());
+                }
+
+                for entry in &removed {
+                    cacache::remove_sync(&cache, &entry.key).into_diagnostic()?;
+                }
+
+                let freed = collect_garbage(&cache)?;
+
+                for entry in &removed {
+                    println!(
+                        "{} {}@{}",
+                        "-".bright_red().bold(),
+                        entry.name,
+                        entry.version
+                    );
+                }
+
+                println!(
+                    "{} {} from the cache",
+                    "Removed".bright_green().bold(),
+                    HumanBytes(freed)
+                );
+            }
+            CacheCommand::Verify => {
+                let entries = entries(&cache)?;
+
+                let bar = progress(&config, ProgressBar::new(entries.len() as u64));
+
+                let mut corrupt = vec![];
+                let mut files = 0;
+
+                for entry in &entries {
+                    bar.set_message(format!("{}@{}", entry.name, entry.version));
+
+                    match verify_entry(&cache, entry) {
+                        Ok(count) => files += count,
+                        Err(bad) => {
+                            for integrity in bad {
+                                // content that's already missing can't be removed
+                                let _ = cacache::remove_hash_sync(&cache, &integrity);
+                            }
+
+                            cacache::remove_sync(&cache, &entry.key).into_diagnostic()?;
+
+                            corrupt.push(entry);
+                        }
+                    }
+
+                    bar.inc(1);
+                }
+
+                bar.finish_and_clear();
+
+                println!(
+                    "Verified {} files of {} packages",
+                    files.to_string().bright_cyan(),
+                    (entries.len() - corrupt.len()).to_string().bright_cyan()
+                );
+
+                if !corrupt.is_empty() {
+                    println!(
+                        "{} {} corrupt packages, they will be downloaded again on the next install:",
+                        "Removed".bright_yellow().bold(),
+                        corrupt.len()
+                    );
+
+                    for entry in corrupt {
+                        println!("  {}@{}", entry.name, entry.version);
+                    }
+                }
+            }
+            CacheCommand::Stats => {
+                let entries = entries(&cache)?;
+
+                let packages = entries
+                    .iter()
+                    .map(|entry| entry.name.as_str())
+                    .collect::<HashSet<_>>();
+
+                println!("{}: {}", "location".bold(), cache.display());
+                println!(
+                    "{}: {} ({} distinct packages)",
+                    "versions".bold(),
+                    entries.len().to_string().bright_cyan(),
+                    packages.len()
+                );
+                println!(
+                    "{}: {}",
+                    "size".bold(),
+                    HumanBytes(cache_size(&cache)).to_string().bright_cyan()
+                );
+            }
+        }
+
         Ok(())
     }
 }
+
+/// Every package in the cache index
+fn entries(cache: &Path) -> Result<Vec<Entry>> {
+    if !cache.join("index-v5").exists() {
+        return Ok(vec![]);
+    }
+
+    let mut entries = vec![];
+
+    for metadata in cacache::list_sync(cache) {
+        let metadata = metadata.into_diagnostic()?;
+
+        let mut parts = metadata.key.splitn(4, "::");
+
+        if let (Some("pkg"), Some(name), Some(version)) = (parts.next(), parts.next(), parts.next())
+        {
+            entries.push(Entry {
+                name: name.to_string(),
+                version: version.to_string(),
+                key: metadata.key.clone(),
+                integrity: metadata.integrity,
+            });
+        }
+    }
+
+    entries.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+
+    Ok(entries)
+}
+
+/// Re-hash the files of a package, returning how many there are or the integrities that
+/// failed to verify
+fn verify_entry(cache: &Path, entry: &Entry) -> std::result::Result<usize, Vec<Integrity>> {
+    // reading by hash checks the content against it
+    let files = cacache::read_hash_sync(cache, &entry.integrity)
+        .ok()
+        .and_then(|map| serde_json::from_slice::<HashMap<String, Integrity>>(&map).ok())
+        .ok_or_else(|| vec![entry.integrity.clone()])?;
+
+    let bad = files
+        .values()
+        .filter(|integrity| cacache::read_hash_sync(cache, integrity).is_err())
+        .cloned()
+        .collect::<Vec<_>>();
+
+    if bad.is_empty() {
+        Ok(files.len())
+    } else {
+        Err(bad)
+    }
+}
+
+/// Delete content no package in the index refers to anymore, returning the bytes freed
+fn collect_garbage(cache: &Path) -> Result<u64> {
+    let mut referenced = HashSet::new();
+
+    for entry in entries(cache)? {
+        referenced.insert(entry.integrity.to_hex().1);
+
+        if let Ok(map) = cacache::read_hash_sync(cache, &entry.integrity) {
+            if let Ok(files) = serde_json::from_slice::<HashMap<String, Integrity>>(&map) {
+                referenced.extend(files.values().map(|integrity| integrity.to_hex().1));
+            }
+        }
+    }
+
+    let content = cache.join("content-v2");
+    let mut freed = 0;
+
+    for entry in jwalk::WalkDir::new(&content)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+    {
+        let path = entry.path();
+
+        // content-v2/sha512/ab/cd/ef01... holds the content hashing to abcdef01...
+        let hex = path
+            .strip_prefix(&content)
+            .into_diagnostic()?
+            .iter()
+            .skip(1)
+            .map(|part| part.to_string_lossy())
+            .collect::<String>();
+
+        if !referenced.contains(&hex) {
+            freed += fs::metadata(&path).map_or(0, |metadata| metadata.len());
+            fs::remove_file(&path).into_diagnostic()?;
+        }
+    }
+
+    Ok(freed)
+}
+
+fn cache_size(cache: &Path) -> u64 {
+    CACHE_DIRS
+        .iter()
+        .map(|dir| cache.join(dir))
+        .filter(|dir| dir.exists())
+        .map(|dir| directory_size(&dir))
+        .sum()
+}
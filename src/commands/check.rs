@@ -14,32 +14,235 @@
     limitations under the License.
 */
 
-//! Check for errors
+//! Verify that node_modules still matches the lock file.
 
-use crate::cli::{VoltCommand, VoltConfig};
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::{
+        model::lock_file::LockFile,
+        output::print_json,
+        utils::{errors::VoltError, package::PackageJson, voltapi::VoltPackage},
+    },
+};
 
 use async_trait::async_trait;
+use clap::Parser;
 use colored::Colorize;
 use miette::Result;
+use rayon::prelude::*;
+use serde::Serialize;
+use serde_json::Value;
+use ssri::Integrity;
 
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    path::Path,
+};
 
-/// Struct implementation for the `Check` command.
-pub struct Check;
+/// Check that node_modules matches the lock file
+#[derive(Debug, Parser)]
+pub struct Check {
+    /// Also hash every installed file against the integrity recorded when it was installed
+    #[clap(long)]
+    integrity: bool,
+}
+
+/// A difference between node_modules and the lock file
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum Drift {
+    /// A locked package isn't installed
+    Missing { package: String },
+    /// The installed package.json has another version than the lock file
+    Version { package: String, installed: String },
+    /// `node_modules/{name}` doesn't point at the locked version of a direct dependency
+    Unlinked { package: String },
+    /// An installed file was edited or deleted since it was installed
+    Modified { package: String, file: String },
+    /// The package was installed before volt recorded file integrities
+    NoManifest { package: String },
+    /// A package is installed but isn't in the lock file anymore
+    Extraneous { directory: String },
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing { package } => write!(f, "{} is not installed", package),
+            Self::Version { package, installed } => {
+                write!(f, "{} is installed as version {}", package, installed)
+            }
+            Self::Unlinked { package } => {
+                write!(f, "node_modules does not link to {}", package)
+            }
+            Self::Modified { package, file } => write!(f, "{} has a modified {}", package, file),
+            Self::NoManifest { package } => {
+                write!(f, "{} has no recorded file integrities", package)
+            }
+            Self::Extraneous { directory } => {
+                write!(f, "{} is installed but not in the lock file", directory)
+            }
+        }
+    }
+}
 
 #[async_trait]
 impl VoltCommand for Check {
-    /// Execute the `volt Check` command
+    /// Execute the `volt check` command
     ///
-    /// Removes a package from your direct dependencies.
+    /// Compare every package in the lock file against what's installed in node_modules, and
+    /// with `--integrity` every file against the hashes recorded at install time, failing if
+    /// anything drifted.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
     /// ## Examples
     /// ```
+    /// // Make sure nothing in node_modules was edited before a release build
     /// // .exec() is an async call so you need to await it
-    /// Create.exec(app, vec![], vec!["--verbose"]).await;
+    /// Check { integrity: true }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
     async fn exec(self, config: VoltConfig) -> Result<()> {
+        let node_modules = config.node_modules()?;
+
+        let lock_file = LockFile::load(config.lockfile()?, false)?;
+
+        let (package_file, _) = PackageJson::get_from_dir(&config.cwd()?)?;
+
+        // patched files differ from the published ones on purpose
+        let patched = package_file
+            .patched_dependencies
+            .unwrap_or_default()
+            .into_keys()
+            .collect::<HashSet<_>>();
+
+        let mut drift = lock_file
+            .dependencies
+            .par_iter()
+            .flat_map_iter(|(key, package)| {
+                let check_files = self.integrity && !patched.contains(key);
+
+                check_package(&node_modules, package, check_files)
+            })
+            .collect::<Vec<_>>();
+
+        for (name, version) in &lock_file.direct {
+            let package = match lock_file.dependencies.get(&format!("{}@{}", name, version)) {
+                Some(package) => package,
+                None => continue,
+            };
+
+            let linked = fs::canonicalize(node_modules.join(name)).ok();
+            let target = fs::canonicalize(package.package_directory(&node_modules)).ok();
+
+            if target.is_some() && linked != target {
+                drift.push(Drift::Unlinked {
+                    package: package.key(),
+                });
+            }
+        }
+
+        let locked = lock_file
+            .dependencies
+            .values()
+            .map(VoltPackage::directory_name)
+            .collect::<HashSet<_>>();
+
+        for entry in fs::read_dir(node_modules.join(".volt"))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+        {
+            let directory = entry.file_name().to_string_lossy().to_string();
+
+            if !locked.contains(&directory) {
+                drift.push(Drift::Extraneous { directory });
+            }
+        }
+
+        if config.json() {
+            print_json(&drift)?;
+        } else {
+            for item in &drift {
+                eprintln!("{}: {}", "drift".bright_red().bold(), item);
+            }
+        }
+
+        if !drift.is_empty() {
+            return Err(VoltError::NodeModulesDriftError { count: drift.len() }.into());
+        }
+
+        if !config.json() {
+            println!(
+                "{} {} packages match {}",
+                "Checked".bright_green().bold(),
+                lock_file.dependencies.len().to_string().bright_cyan(),
+                VoltConfig::VOLT_LOCK
+            );
+        }
+
         Ok(())
     }
 }
+
+/// Compare an installed package against the lock file, and its files against their integrities
+fn check_package(node_modules: &Path, package: &VoltPackage, check_files: bool) -> Vec<Drift> {
+    let directory = package.package_directory(node_modules);
+
+    let manifest = match fs::read_to_string(directory.join("package.json")) {
+        Ok(manifest) => manifest,
+        // optional packages are skipped on platforms they don't support
+        Err(_) if package.optional => return vec![],
+        Err(_) => {
+            return vec![Drift::Missing {
+                package: package.key(),
+            }]
+        }
+    };
+
+    let installed = serde_json::from_str::<Value>(&manifest)
+        .ok()
+        .and_then(|manifest| manifest["version"].as_str().map(String::from))
+        .unwrap_or_default();
+
+    if installed != package.version {
+        return vec![Drift::Version {
+            package: package.key(),
+            installed,
+        }];
+    }
+
+    if !check_files {
+        return vec![];
+    }
+
+    let files = fs::read(package.files_manifest(node_modules))
+        .ok()
+        .and_then(|files| serde_json::from_slice::<HashMap<String, Integrity>>(&files).ok());
+
+    let files = match files {
+        Some(files) => files,
+        None => {
+            return vec![Drift::NoManifest {
+                package: package.key(),
+            }]
+        }
+    };
+
+    let mut modified = files
+        .into_iter()
+        .filter(|(file, integrity)| {
+            fs::read(directory.join(file))
+                .map_or(true, |contents| integrity.check(contents).is_err())
+        })
+        .map(|(file, _)| Drift::Modified {
+            package: package.key(),
+            file,
+        })
+        .collect::<Vec<_>>();
+
+    modified.sort_by(|a, b| a.to_string().cmp(&b.to_string()));
+    modified
+}
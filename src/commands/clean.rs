@@ -16,7 +16,10 @@
 
 //! Clean `./node_modules` and reduce its size.
 
-use crate::cli::{VoltCommand, VoltConfig};
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::output::progress,
+};
 
 use async_trait::async_trait;
 use clap::Parser;
@@ -56,7 +59,7 @@ impl VoltCommand for Clean {
     /// ```
     /// ## Returns
     /// * `Result<()>`
-    async fn exec(self, _config: VoltConfig) -> Result<()> {
+    async fn exec(self, config: VoltConfig) -> Result<()> {
         let regexes = get_regexes(self.remove_licenses);
 
         let mut matches: Vec<PathBuf> = vec![];
@@ -116,8 +119,8 @@ impl VoltCommand for Clean {
             initial_file_size += initial_size;
         }
 
-        let matches_bar = ProgressBar::new(matches.len() as u64);
-        let minify_bar = ProgressBar::new(minify_files.len() as u64);
+        let matches_bar = progress(&config, ProgressBar::new(matches.len() as u64));
+        let minify_bar = progress(&config, ProgressBar::new(minify_files.len() as u64));
 
         minify_bar.set_style(
             ProgressStyle::default_bar()
@@ -0,0 +1,192 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Generate shell completion scripts.
+
+use crate::{
+    cli::{VoltCli, VoltCommand, VoltConfig},
+    core::utils::package::PackageJson,
+};
+
+use async_trait::async_trait;
+use clap::{ArgEnum, CommandFactory, Parser};
+use clap_complete::Shell;
+use miette::Result;
+
+/// Print a shell completion script
+#[derive(Debug, Parser)]
+pub struct Completions {
+    /// The shell to complete volt in
+    #[clap(arg_enum, required_unless_present = "list")]
+    shell: Option<Shell>,
+
+    /// Print the names the completion scripts complete dynamically, one per line
+    #[clap(long, arg_enum, hide = true)]
+    list: Option<CompletionList>,
+}
+
+#[derive(Debug, Clone, Copy, ArgEnum)]
+enum CompletionList {
+    /// Scripts in package.json, for `volt run`
+    Scripts,
+    /// Dependencies in package.json, for `volt remove`, `volt update`, `volt why` and `volt patch`
+    Packages,
+}
+
+/// Subcommands completing script names
+const SCRIPT_COMMANDS: &str = "run";
+
+/// Subcommands completing installed package names
+const PACKAGE_COMMANDS: &str = "remove update why patch";
+
+#[async_trait]
+impl VoltCommand for Completions {
+    /// Execute the `volt completions` command
+    ///
+    /// Print a completion script for a shell. Bash, zsh and fish also complete the scripts and
+    /// dependencies of the project in the current directory.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Print the bash completions, to be sourced from ~/.bashrc
+    /// // .exec() is an async call so you need to await it
+    /// Completions { shell: Some(Shell::Bash), list: None }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        if let Some(list) = self.list {
+            // completing outside of a project completes nothing
+            let package_file = match PackageJson::get_from_dir(&config.cwd()?) {
+                Ok((package_file, _)) => package_file,
+                Err(_) => return Ok(()),
+            };
+
+            let mut names = match list {
+                CompletionList::Scripts => package_file
+                    .scripts
+                    .unwrap_or_default()
+                    .into_keys()
+                    .collect::<Vec<_>>(),
+                CompletionList::Packages => package_file
+                    .dependencies
+                    .into_iter()
+                    .chain(package_file.dev_dependencies)
+                    .flatten()
+                    .map(|(name, _)| name)
+                    .collect(),
+            };
+
+            names.sort();
+
+            for name in names {
+                println!("{}", name);
+            }
+
+            return Ok(());
+        }
+
+        let shell = match self.shell {
+            Some(shell) => shell,
+            None => return Ok(()),
+        };
+
+        let mut script = vec![];
+
+        clap_complete::generate(shell, &mut VoltCli::command(), "volt", &mut script);
+
+        let mut script = String::from_utf8_lossy(&script).to_string();
+
+        match shell {
+            Shell::Bash => script.push_str(&bash_dynamic()),
+            Shell::Fish => script.push_str(&fish_dynamic()),
+            Shell::Zsh => {
+                // the generated script ends by calling `_volt`, which is when zsh completes
+                if let Some(index) = script.rfind("_volt \"$@\"") {
+                    script.replace_range(index.., &zsh_dynamic());
+                }
+            }
+            _ => {}
+        }
+
+        print!("{}", script);
+
+        Ok(())
+    }
+}
+
+fn bash_dynamic() -> String {
+    format!(
+        r#"
+_volt_dynamic() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+
+    if [[ $COMP_CWORD -eq 2 && $cur != -* ]]; then
+        case " {scripts} " in *" ${{COMP_WORDS[1]}} "*)
+            COMPREPLY=($(compgen -W "$(volt completions --list scripts 2>/dev/null)" -- "$cur"))
+            return
+        esac
+        case " {packages} " in *" ${{COMP_WORDS[1]}} "*)
+            COMPREPLY=($(compgen -W "$(volt completions --list packages 2>/dev/null)" -- "$cur"))
+            return
+        esac
+    fi
+
+    _volt "$@"
+}}
+
+complete -F _volt_dynamic -o bashdefault -o default volt
+"#,
+        scripts = SCRIPT_COMMANDS,
+        packages = PACKAGE_COMMANDS
+    )
+}
+
+fn fish_dynamic() -> String {
+    format!(
+        r#"
+complete -c volt -n "__fish_seen_subcommand_from {scripts}" -f -a "(volt completions --list scripts 2>/dev/null)"
+complete -c volt -n "__fish_seen_subcommand_from {packages}" -f -a "(volt completions --list packages 2>/dev/null)"
+"#,
+        scripts = SCRIPT_COMMANDS,
+        packages = PACKAGE_COMMANDS
+    )
+}
+
+fn zsh_dynamic() -> String {
+    format!(
+        r#"_volt_dynamic() {{
+    if (( CURRENT == 3 )) && [[ $words[CURRENT] != -* ]]; then
+        case " {scripts} " in *" $words[2] "*)
+            compadd -- ${{(f)"$(volt completions --list scripts 2>/dev/null)"}}
+            return
+        esac
+        case " {packages} " in *" $words[2] "*)
+            compadd -- ${{(f)"$(volt completions --list packages 2>/dev/null)"}}
+            return
+        esac
+    fi
+
+    _volt "$@"
+}}
+
+_volt_dynamic "$@"
+"#,
+        scripts = SCRIPT_COMMANDS,
+        packages = PACKAGE_COMMANDS
+    )
+}
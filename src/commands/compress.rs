@@ -0,0 +1,485 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Shrink `./node_modules` by removing the files packages don't need at runtime, and store
+//! what's left in a single `node_modules.pack` archive.
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::{
+        output::{print_json, progress, status},
+        pack::{self, Codec, Compression, Summary, FILE_NAME},
+        settings::Settings,
+        utils::errors::VoltError,
+    },
+};
+
+use async_trait::async_trait;
+use clap::Parser;
+use colored::Colorize;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+use miette::{IntoDiagnostic, Result};
+use rayon::prelude::*;
+use serde::Serialize;
+
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+/// Files and directories removed from every package, matched like `.gitignore` patterns
+/// against paths inside the package, ignoring case
+///
+/// Licenses are kept, add them to `[compress] remove` in volt.toml to remove them too.
+pub const REMOVABLES: &[&str] = &[
+    // documentation
+    "readme*",
+    "changelog*",
+    "changes",
+    "history*",
+    "authors",
+    "contributors",
+    "*.md",
+    "*.markdown",
+    "*.mkd",
+    "/docs",
+    "/doc",
+    "/example",
+    "/examples",
+    "/website",
+    // tests
+    "test",
+    "tests",
+    "__tests__",
+    "__mocks__",
+    "powered-test",
+    "coverage",
+    ".nyc_output",
+    "jest.config.js",
+    "karma.conf.js",
+    "wallaby.js",
+    "wallaby.conf.js",
+    // tooling configuration
+    ".github",
+    ".circleci",
+    ".idea",
+    ".vscode",
+    ".travis.yml",
+    ".gitlab-ci.yml",
+    "appveyor.yml",
+    ".appveyor.yml",
+    "circle.yml",
+    ".coveralls.yml",
+    ".editorconfig",
+    ".eslintrc*",
+    ".eslintignore",
+    ".stylelintrc*",
+    "stylelint.config.js",
+    ".prettierrc*",
+    "prettier.config.js",
+    ".jshintrc",
+    ".flowconfig",
+    ".babelrc",
+    ".npmignore",
+    ".npmrc",
+    ".gitattributes",
+    ".gitignore",
+    ".yarn-metadata.json",
+    ".yarn-integrity",
+    ".yarnclean",
+    "tslint.json",
+    "makefile",
+    "gulpfile.js",
+    "gruntfile.js",
+    ".ds_store",
+    // leftovers
+    "*.tgz",
+    "*.swp",
+];
+
+/// Shrink node_modules by removing documentation, tests and tooling configuration, then pack it
+/// into a single file
+#[derive(Debug, Parser)]
+pub struct Compress {
+    /// Only remove files, without writing node_modules.pack
+    #[clap(long)]
+    no_pack: bool,
+
+    /// Where to write the archive, node_modules.pack in the project by default
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    /// How to compress the archive
+    #[clap(long, arg_enum, default_value = "zstd")]
+    codec: Codec,
+
+    /// Compression level, 1 to 22 for zstd (3 by default) and 1 to 12 for deflate (6 by default)
+    #[clap(short, long)]
+    level: Option<i32>,
+
+    /// Store the files without compressing them, the fastest to write and extract
+    #[clap(long, conflicts_with_all = &["codec", "level"])]
+    store: bool,
+
+    /// Print the files that would be removed, without removing them
+    #[clap(long)]
+    dry_run: bool,
+}
+
+/// What was (or with `--dry-run` would be) removed
+#[derive(Debug, Default, Serialize)]
+pub struct Removal {
+    /// The matched files and directories, relative to the project
+    pub paths: Vec<PathBuf>,
+    /// Files removed, counting the ones inside removed directories
+    pub files: u64,
+    pub bytes: u64,
+    /// The archive written once the files are removed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pack: Option<Summary>,
+}
+
+#[async_trait]
+impl VoltCommand for Compress {
+    /// Execute the `volt compress` command
+    ///
+    /// Remove the files matching `REMOVABLES` and the project's `[compress] remove` patterns,
+    /// except for the ones matching `[compress] keep`, from every package in node_modules, then
+    /// write node_modules.pack unless `--no-pack` is passed. An existing archive is updated
+    /// with the packages that changed, unless it was compressed with another codec.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Shrink and pack node_modules as small as zstd gets it before building a container image
+    /// // .exec() is an async call so you need to await it
+    /// Compress { no_pack: false, output: None, codec: Codec::Zstd, level: Some(19), store: false, dry_run: false }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let config = config.with_dry_run(self.dry_run);
+
+        let project_dir = config.cwd()?;
+        let node_modules = config.node_modules()?;
+
+        if !node_modules.exists() {
+            return Err(VoltError::ReadFileError {
+                source: std::io::ErrorKind::NotFound.into(),
+                name: node_modules.display().to_string(),
+            }
+            .into());
+        }
+
+        let settings = Settings::load(&project_dir)?;
+        let matcher = removables(
+            &node_modules,
+            &settings.compress.remove,
+            &settings.compress.keep,
+        )?;
+
+        let compression = if self.store {
+            Compression::new(Codec::Store, None)?
+        } else {
+            Compression::new(self.codec, self.level)?
+        };
+
+        let mut removal = find_removable(&node_modules, &matcher);
+
+        let output = self.output.unwrap_or_else(|| project_dir.join(FILE_NAME));
+
+        if !config.dry_run() {
+            remove(&config, &node_modules, &removal.paths)?;
+
+            if !self.no_pack {
+                let bar = progress(
+                    &config,
+                    ProgressBar::new(0).with_style(
+                        ProgressStyle::default_bar()
+                            .template(
+                                "{prefix:>11.bold} [{bar:30.cyan/blue}] {bytes}/{total_bytes} {binary_bytes_per_sec}",
+                            )
+                            .progress_chars("=>-"),
+                    ),
+                );
+
+                bar.set_prefix("packing");
+
+                let summary = pack::write_pack(&node_modules, &output, compression, &bar)?;
+                bar.finish_and_clear();
+
+                removal.pack = Some(summary);
+            }
+        }
+
+        if config.json() {
+            return print_json(&Removal {
+                paths: relative(&project_dir, removal.paths),
+                ..removal
+            });
+        }
+
+        if config.dry_run() {
+            for path in relative(&project_dir, removal.paths) {
+                println!("{}", path.display());
+            }
+        }
+
+        let verb = if config.dry_run() {
+            "Would remove"
+        } else {
+            "Removed"
+        };
+
+        status(
+            &config,
+            format!(
+                "{} {} files, saving {}",
+                verb.bright_green().bold(),
+                removal.files,
+                HumanBytes(removal.bytes).to_string().bright_green()
+            ),
+        );
+
+        if let Some(summary) = &removal.pack {
+            status(
+                &config,
+                format!(
+                    "{} {} of {} packages into {} ({} from {})",
+                    "Packed".bright_green().bold(),
+                    summary.written,
+                    summary.packages,
+                    output
+                        .strip_prefix(&project_dir)
+                        .unwrap_or(&output)
+                        .display(),
+                    HumanBytes(summary.packed_size).to_string().bright_green(),
+                    HumanBytes(summary.size)
+                ),
+            );
+
+            // updates leave the data of replaced packages behind
+            if summary.garbage > summary.packed_size / 2 {
+                status(
+                    &config,
+                    format!(
+                        "{} of the archive is unused, run {} to reclaim it",
+                        HumanBytes(summary.garbage),
+                        format!("volt pack compact {}", output.display()).bright_cyan()
+                    ),
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The patterns of removable files, with the project's additions and exclusions
+pub fn removables(node_modules: &Path, remove: &[String], keep: &[String]) -> Result<Gitignore> {
+    let mut builder = GitignoreBuilder::new(node_modules);
+    builder.case_insensitive(true).into_diagnostic()?;
+
+    let lines = REMOVABLES
+        .iter()
+        .map(|pattern| pattern.to_string())
+        .chain(remove.iter().cloned())
+        // later lines win, so a negated pattern keeps what the ones before it matched
+        .chain(keep.iter().map(|pattern| format!("!{}", pattern)));
+
+    for line in lines {
+        builder.add_line(None, &line).into_diagnostic()?;
+    }
+
+    builder.build().into_diagnostic()
+}
+
+/// Find the files and directories `matcher` matches inside the packages in `node_modules`
+pub fn find_removable(node_modules: &Path, matcher: &Gitignore) -> Removal {
+    let mut removal = Removal::default();
+
+    // links into the store aren't followed, so every package is walked once
+    let mut entries = jwalk::WalkDir::new(node_modules)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| (entry.path(), entry.file_type().is_dir()))
+        .collect::<Vec<_>>();
+
+    // directories come right before their contents
+    entries.sort();
+
+    for (path, is_dir) in entries {
+        // the contents of a removed directory are counted with it
+        if removal
+            .paths
+            .last()
+            .map_or(false, |removed| path.starts_with(removed))
+        {
+            continue;
+        }
+
+        let inside = match package_relative(&path) {
+            Some(inside) => inside,
+            None => continue,
+        };
+
+        if !matcher
+            .matched_path_or_any_parents(&inside, is_dir)
+            .is_ignore()
+        {
+            continue;
+        }
+
+        let (files, bytes) = if is_dir {
+            measure(&path)
+        } else {
+            (1, size(&path))
+        };
+
+        removal.files += files;
+        removal.bytes += bytes;
+        removal.paths.push(path);
+    }
+
+    removal
+}
+
+/// The path of a file relative to the package it's in, `None` for the package directories
+/// themselves and the directories holding them
+fn package_relative(path: &Path) -> Option<PathBuf> {
+    let components = path.components().collect::<Vec<_>>();
+
+    let last = components
+        .iter()
+        .rposition(|component| component.as_os_str() == "node_modules")?;
+
+    let mut rest = components[last + 1..].iter();
+
+    let name = rest.next()?.as_os_str().to_string_lossy();
+
+    if name.starts_with('.') {
+        return None;
+    }
+
+    // scoped packages are two directories deep
+    if name.starts_with('@') {
+        rest.next()?;
+    }
+
+    let inside = rest.map(Component::as_os_str).collect::<PathBuf>();
+
+    if inside.as_os_str().is_empty() {
+        None
+    } else {
+        Some(inside)
+    }
+}
+
+fn size(path: &Path) -> u64 {
+    path.symlink_metadata().map_or(0, |metadata| metadata.len())
+}
+
+fn measure(dir: &Path) -> (u64, u64) {
+    jwalk::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !entry.file_type().is_dir())
+        .fold((0, 0), |(files, bytes), entry| {
+            (files + 1, bytes + size(&entry.path()))
+        })
+}
+
+/// Remove `paths` in parallel
+fn remove(config: &VoltConfig, node_modules: &Path, paths: &[PathBuf]) -> Result<()> {
+    let bar = progress(config, ProgressBar::new(paths.len() as u64));
+
+    paths.par_iter().try_for_each(|path| {
+        let removed = if path.symlink_metadata().map_or(false, |m| m.is_dir()) {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        };
+
+        bar.inc(1);
+
+        removed.map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: path
+                .strip_prefix(node_modules)
+                .unwrap_or(path)
+                .display()
+                .to_string(),
+        })
+    })?;
+
+    bar.finish_and_clear();
+
+    Ok(())
+}
+
+fn relative(base: &Path, paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    paths
+        .into_iter()
+        .map(|path| {
+            path.strip_prefix(base)
+                .map_or(path.clone(), Path::to_path_buf)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_inside_packages() {
+        let node_modules = Path::new("/app/node_modules");
+        let matcher = removables(
+            node_modules,
+            &[String::from("*.map")],
+            &[String::from("README.md")],
+        )
+        .unwrap();
+
+        let removable = |path: &str, is_dir: bool| {
+            let path = node_modules.join(path);
+            package_relative(&path).map_or(false, |inside| {
+                matcher
+                    .matched_path_or_any_parents(&inside, is_dir)
+                    .is_ignore()
+            })
+        };
+
+        assert!(removable(
+            ".volt/ms@2.1.3/node_modules/ms/History.md",
+            false
+        ));
+        assert!(removable(
+            ".volt/ms@2.1.3/node_modules/ms/index.js.map",
+            false
+        ));
+        assert!(removable("@babel/core/test", true));
+        assert!(!removable(
+            ".volt/ms@2.1.3/node_modules/ms/README.md",
+            false
+        ));
+        assert!(!removable(".volt/ms@2.1.3/node_modules/ms/index.js", false));
+        assert!(!removable(".volt/ms@2.1.3/node_modules/ms/lib/docs", true));
+        // a package called `test` isn't a test directory
+        assert!(!removable("test", true));
+    }
+}
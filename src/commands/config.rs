@@ -0,0 +1,233 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Read and edit the configuration.
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::{
+        config::{read_file, write_file, Key, Source, KEYS},
+        output::{print_json, status},
+        settings::Settings,
+    },
+};
+
+use async_trait::async_trait;
+use clap::{ArgEnum, Parser, Subcommand};
+use colored::Colorize;
+use comfy_table::{
+    modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL, Attribute, Cell, ContentArrangement, Table,
+};
+use miette::Result;
+use serde::Serialize;
+
+use std::path::PathBuf;
+
+/// Read and edit the configuration
+#[derive(Debug, Parser)]
+pub struct Config {
+    #[clap(subcommand)]
+    command: ConfigCommand,
+
+    /// The file `set` and `delete` edit, `project` is the project's `volt.toml` (or `.voltrc`)
+    #[clap(long, global = true, arg_enum, default_value = "user")]
+    location: Location,
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommand {
+    /// Print the value of an option
+    Get { key: String },
+    /// Set an option in the user or project configuration
+    Set { key: String, value: String },
+    /// Remove an option from the user or project configuration
+    Delete { key: String },
+    /// Show every option, its value and where the value came from
+    List,
+}
+
+#[derive(Debug, Clone, Copy, ArgEnum)]
+enum Location {
+    /// `~/.volt/config.toml`
+    User,
+    /// The project's settings file, which is committed with it
+    Project,
+}
+
+impl Location {
+    fn path(self, config: &VoltConfig) -> Result<PathBuf> {
+        match self {
+            Self::User => config.user_config_file(),
+            Self::Project => Ok(Settings::path(&config.cwd()?)),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Entry {
+    key: &'static str,
+    value: String,
+    source: Source,
+    description: &'static str,
+}
+
+#[async_trait]
+impl VoltCommand for Config {
+    /// Execute the `volt config` command
+    ///
+    /// Get, set, delete or list the options read from the built-in defaults,
+    /// `~/.volt/config.toml`, the project's `volt.toml`, `VOLT_*` environment variables and
+    /// command line flags.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Install from a mirror
+    /// // .exec() is an async call so you need to await it
+    /// Config { command: ConfigCommand::Set { key: "registry".into(), value: "https://registry.npmmirror.com".into() }, location: Location::User }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        match self.command {
+            ConfigCommand::Get { key } => {
+                let key = Key::find(&key)?;
+                let entry = entry(&config, key);
+
+                if config.json() {
+                    print_json(&entry)?;
+                } else {
+                    println!("{}", entry.value);
+                }
+            }
+            ConfigCommand::Set { key, value } => {
+                let key = Key::find(&key)?;
+                let path = self.location.path(&config)?;
+
+                let mut table = read_file(&path)?;
+                table.insert(key.name.to_string(), key.parse(&value)?);
+                write_file(&path, &table)?;
+
+                status(
+                    &config,
+                    format!(
+                        "{} {} = {} in {}",
+                        "Set".bright_green().bold(),
+                        key.name,
+                        value,
+                        path.display()
+                    ),
+                );
+
+                if let (Location::User, Some((overridden, Source::Project))) =
+                    (self.location, config.options().get(key.name))
+                {
+                    eprintln!(
+                        "{}: the project sets {} to {}, which takes precedence",
+                        "warning".bright_yellow().bold(),
+                        key.name,
+                        overridden
+                    );
+                }
+
+                if let Ok(overridden) = std::env::var(key.env()) {
+                    eprintln!(
+                        "{}: {} is set to {}, which takes precedence",
+                        "warning".bright_yellow().bold(),
+                        key.env(),
+                        overridden
+                    );
+                }
+            }
+            ConfigCommand::Delete { key } => {
+                let key = Key::find(&key)?;
+                let path = self.location.path(&config)?;
+
+                let mut table = read_file(&path)?;
+
+                if table.remove(key.name).is_none() {
+                    status(
+                        &config,
+                        format!("{} is not set in {}", key.name, path.display()),
+                    );
+                    return Ok(());
+                }
+
+                write_file(&path, &table)?;
+
+                status(
+                    &config,
+                    format!(
+                        "{} {} from {}",
+                        "Deleted".bright_green().bold(),
+                        key.name,
+                        path.display()
+                    ),
+                );
+            }
+            ConfigCommand::List => {
+                let entries = KEYS
+                    .iter()
+                    .map(|key| entry(&config, key))
+                    .collect::<Vec<_>>();
+
+                if config.json() {
+                    return print_json(&entries);
+                }
+
+                let mut table = Table::new();
+
+                table
+                    .load_preset(UTF8_FULL)
+                    .apply_modifier(UTF8_ROUND_CORNERS)
+                    .set_content_arrangement(ContentArrangement::Dynamic);
+
+                table.set_header(
+                    ["Key", "Value", "Source", "Description"]
+                        .iter()
+                        .map(|header| Cell::new(header).add_attribute(Attribute::Bold)),
+                );
+
+                for entry in &entries {
+                    table.add_row(vec![
+                        Cell::new(entry.key),
+                        Cell::new(&entry.value),
+                        Cell::new(entry.source),
+                        Cell::new(entry.description),
+                    ]);
+                }
+
+                println!("{}", table);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn entry(config: &VoltConfig, key: &'static Key) -> Entry {
+    let (value, source) = config
+        .options()
+        .get(key.name)
+        .unwrap_or((key.default, Source::Default));
+
+    Entry {
+        key: key.name,
+        value: value.to_string(),
+        source,
+        description: key.description,
+    }
+}
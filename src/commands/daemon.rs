@@ -0,0 +1,222 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Run, inspect and stop the daemon keeping the caches of recent projects warm.
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::{
+        daemon::{self, Request, Response},
+        output::{print_json, status},
+        utils::errors::VoltError,
+    },
+};
+
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use clap::{Parser, Subcommand};
+use colored::Colorize;
+use miette::{IntoDiagnostic, Result};
+
+use std::{
+    fs::{self, OpenOptions},
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+/// Keep the caches of recently used projects warm in the background
+#[derive(Debug, Parser)]
+pub struct Daemon {
+    // without one the daemon runs in the foreground
+    #[clap(subcommand)]
+    command: Option<DaemonCommand>,
+}
+
+#[derive(Debug, Subcommand)]
+enum DaemonCommand {
+    /// Start the daemon in the background
+    Start,
+    /// Show whether the daemon is running and the projects it keeps warm
+    Status,
+    /// Stop the running daemon
+    Stop,
+}
+
+#[async_trait]
+impl VoltCommand for Daemon {
+    /// Execute the `volt daemon` command
+    ///
+    /// Run the daemon, which revalidates the registry metadata of the dependencies of the
+    /// projects volt was recently used in and downloads new versions in range into the store,
+    /// or start, inspect or stop it.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Start the daemon in the background
+    /// // .exec() is an async call so you need to await it
+    /// Daemon { command: Some(DaemonCommand::Start) }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        match self.command {
+            None => daemon::serve(&config).await,
+            Some(DaemonCommand::Start) => start(&config),
+            Some(DaemonCommand::Status) => {
+                let current = match daemon::request_to(&config, &Request::Status) {
+                    Ok(Response::Status(current)) => Some(current),
+                    _ => None,
+                };
+
+                if config.json() {
+                    return print_json(&current);
+                }
+
+                let current = match current {
+                    Some(current) => current,
+                    None => {
+                        println!(
+                            "The daemon isn't running, start it with {}",
+                            "volt daemon start".bright_cyan()
+                        );
+
+                        return Ok(());
+                    }
+                };
+
+                let time = |timestamp: u64| {
+                    Utc.timestamp(timestamp as i64, 0)
+                        .format("%Y-%m-%d %H:%M UTC")
+                        .to_string()
+                };
+
+                println!(
+                    "{} volt {} as process {} since {}",
+                    "Running".bright_green().bold(),
+                    current.version,
+                    current.pid,
+                    time(current.started)
+                );
+
+                println!(
+                    "{} packages cached, {} revalidations, {} packages prefetched",
+                    current.packages, current.revalidated, current.prefetched
+                );
+
+                if let Some(refreshed) = current.last_refresh {
+                    println!("Last refreshed {}", time(refreshed));
+                }
+
+                if !current.projects.is_empty() {
+                    println!();
+                }
+
+                for (project, used) in &current.projects {
+                    println!(
+                        "  {} {}",
+                        project.display(),
+                        format!("(used {})", time(*used)).truecolor(156, 156, 156)
+                    );
+                }
+
+                Ok(())
+            }
+            Some(DaemonCommand::Stop) => {
+                match daemon::request_to(&config, &Request::Stop) {
+                    Ok(_) => status(&config, format!("{} the daemon", "Stopped".bright_green())),
+                    Err(_) => status(&config, "The daemon isn't running"),
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Run `volt daemon` detached from the terminal, logging to `~/.volt/daemon/daemon.log`
+fn start(config: &VoltConfig) -> Result<()> {
+    if let Ok(Response::Status(current)) = daemon::request_to(config, &Request::Status) {
+        return Err(VoltError::DaemonRunningError { pid: current.pid }.into());
+    }
+
+    let dir = daemon::dir(config)?;
+    fs::create_dir_all(&dir).map_err(VoltError::CreateDirError)?;
+
+    let log_file = daemon::log_file(&dir);
+
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_file)
+        .map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: log_file.display().to_string(),
+        })?;
+
+    let mut command = Command::new(std::env::current_exe().into_diagnostic()?);
+
+    command
+        .arg("daemon")
+        .stdin(Stdio::null())
+        .stdout(log.try_clone().into_diagnostic()?)
+        .stderr(log);
+
+    // out of the terminal's process group, so closing it doesn't stop the daemon
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        // DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP
+        command.creation_flags(0x0000_0008 | 0x0000_0200);
+    }
+
+    let child = command.spawn().into_diagnostic()?;
+
+    // wait for it to answer, it failing to start is in the log
+    for _ in 0..20 {
+        if daemon::request_to(config, &Request::Status).is_ok() {
+            status(
+                config,
+                format!(
+                    "{} the daemon as process {}",
+                    "Started".bright_green().bold(),
+                    child.id()
+                ),
+            );
+
+            return Ok(());
+        }
+
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    status(
+        config,
+        format!(
+            "The daemon didn't answer yet, see {} if it doesn't start",
+            log_file.display()
+        ),
+    );
+
+    Ok(())
+}
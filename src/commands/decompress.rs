@@ -0,0 +1,257 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Recreate `./node_modules` from a `node_modules.pack` archive.
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::{
+        output::{print_json, progress, status},
+        pack::{PackReader, Summary, FILE_NAME},
+        utils::errors::VoltError,
+    },
+};
+
+use async_trait::async_trait;
+use clap::Parser;
+use colored::Colorize;
+use indicatif::{HumanBytes, ProgressBar};
+use miette::Result;
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Recreate node_modules from a node_modules.pack archive
+#[derive(Debug, Parser)]
+pub struct Decompress {
+    /// The archive to extract, node_modules.pack in the project by default
+    pack: Option<PathBuf>,
+
+    /// Replace an existing node_modules
+    #[clap(long)]
+    clean: bool,
+
+    /// Only extract these packages, by name or name@version, into node_modules as it is
+    #[clap(long, use_value_delimiter = true, conflicts_with = "clean")]
+    only: Vec<String>,
+
+    /// Print what would be extracted, without writing to node_modules
+    #[clap(long)]
+    dry_run: bool,
+}
+
+#[async_trait]
+impl VoltCommand for Decompress {
+    /// Execute the `volt decompress` command
+    ///
+    /// Check the archive, then extract every package in it with its permissions and links.
+    /// The packages are extracted next to node_modules and only moved in place once they all
+    /// are, so a corrupt archive never leaves a half extracted node_modules behind. With
+    /// `--only`, just the data of the requested packages is read and extracted in place.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Replace node_modules with the packages in node_modules.pack
+    /// // .exec() is an async call so you need to await it
+    /// Decompress { pack: None, clean: true, only: vec![], dry_run: false }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let config = config.with_dry_run(self.dry_run);
+
+        let project_dir = config.cwd()?;
+        let node_modules = config.node_modules()?;
+
+        let pack = self.pack.unwrap_or_else(|| project_dir.join(FILE_NAME));
+
+        let mut reader = PackReader::open(&pack)?;
+
+        let summary = if self.only.is_empty() {
+            extract_all(&config, &mut reader, &pack, &node_modules, self.clean)?
+        } else {
+            let mut paths = HashSet::new();
+
+            for spec in &self.only {
+                let found = reader.find(spec);
+
+                if found.is_empty() {
+                    return Err(VoltError::PackPackageNotFoundError {
+                        name: spec.clone(),
+                        pack: pack.display().to_string(),
+                    }
+                    .into());
+                }
+
+                paths.extend(found.into_iter().map(|package| package.path.clone()));
+            }
+
+            if config.dry_run() {
+                let packages = reader
+                    .index()
+                    .packages
+                    .iter()
+                    .filter(|package| paths.contains(&package.path));
+
+                Summary {
+                    packages: packages.clone().count(),
+                    files: packages.clone().map(|p| p.files.len()).sum(),
+                    size: packages.flat_map(|p| &p.files).map(|file| file.size).sum(),
+                    ..Summary::default()
+                }
+            } else {
+                let bar = progress(&config, ProgressBar::new(0));
+                let summary =
+                    reader.extract_packages(&paths, &node_modules, &node_modules, &bar)?;
+                bar.finish_and_clear();
+
+                summary
+            }
+        };
+
+        if config.json() {
+            return print_json(&summary);
+        }
+
+        let verb = if config.dry_run() {
+            "Would extract"
+        } else {
+            "Extracted"
+        };
+
+        status(
+            &config,
+            format!(
+                "{} {} packages ({}) from {}",
+                verb.bright_green().bold(),
+                summary.packages,
+                HumanBytes(summary.size).to_string().bright_green(),
+                pack.strip_prefix(&project_dir).unwrap_or(&pack).display()
+            ),
+        );
+
+        Ok(())
+    }
+}
+
+/// Extract the whole archive next to `node_modules`, then move it in place
+fn extract_all(
+    config: &VoltConfig,
+    reader: &mut PackReader,
+    pack: &Path,
+    node_modules: &Path,
+    clean: bool,
+) -> Result<Summary> {
+    let exists = node_modules.symlink_metadata().is_ok();
+
+    if exists && !clean {
+        return Err(VoltError::NodeModulesExistsError {
+            path: node_modules.display().to_string(),
+        }
+        .into());
+    }
+
+    if config.dry_run() {
+        let files = reader.index().packages.iter().flat_map(|p| &p.files);
+
+        return Ok(Summary {
+            packages: reader
+                .index()
+                .packages
+                .iter()
+                .filter(|p| !p.name.is_empty())
+                .count(),
+            files: files.clone().count(),
+            size: files.map(|file| file.size).sum(),
+            packed_size: fs::metadata(pack).map_or(0, |metadata| metadata.len()),
+            garbage: reader.garbage(),
+            ..Summary::default()
+        });
+    }
+
+    let staging = sibling(node_modules, "partial");
+
+    if staging.symlink_metadata().is_ok() {
+        remove_dir(&staging)?;
+    }
+
+    let bar = progress(config, ProgressBar::new(0));
+
+    let summary = match reader.extract(&staging, node_modules, &bar) {
+        Ok(summary) => summary,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&staging);
+            return Err(e);
+        }
+    };
+
+    bar.finish_and_clear();
+
+    replace(node_modules, &staging, exists)?;
+
+    Ok(summary)
+}
+
+/// A directory next to `node_modules`, on the same filesystem so it can be renamed into place
+fn sibling(node_modules: &Path, suffix: &str) -> PathBuf {
+    let name = node_modules.file_name().map_or_else(
+        || String::from("node_modules"),
+        |name| name.to_string_lossy().to_string(),
+    );
+
+    node_modules.with_file_name(format!(".{}.{}", name, suffix))
+}
+
+/// Move the extracted `staging` to `node_modules`, putting the old one back if that fails
+fn replace(node_modules: &Path, staging: &Path, exists: bool) -> Result<()> {
+    let rename_error = |e: std::io::Error, path: &Path| VoltError::WriteFileError {
+        source: e,
+        name: path.display().to_string(),
+    };
+
+    if !exists {
+        fs::rename(staging, node_modules).map_err(|e| rename_error(e, node_modules))?;
+        return Ok(());
+    }
+
+    let old = sibling(node_modules, "old");
+
+    if old.symlink_metadata().is_ok() {
+        remove_dir(&old)?;
+    }
+
+    fs::rename(node_modules, &old).map_err(|e| rename_error(e, node_modules))?;
+
+    if let Err(e) = fs::rename(staging, node_modules) {
+        let _ = fs::rename(&old, node_modules);
+        return Err(rename_error(e, node_modules).into());
+    }
+
+    remove_dir(&old)
+}
+
+fn remove_dir(path: &Path) -> Result<()> {
+    fs::remove_dir_all(path).map_err(|e| VoltError::WriteFileError {
+        source: e,
+        name: path.display().to_string(),
+    })?;
+
+    Ok(())
+}
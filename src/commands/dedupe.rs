@@ -0,0 +1,360 @@
+/*
+    Copyright 2021, 2022 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Collapse duplicate versions of packages in the dependency graph.
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::model::lock_file::LockFile,
+    core::utils::{
+        create_link, directory_size, errors::VoltError, link_package, package::PackageJson,
+        voltapi::VoltPackage,
+    },
+};
+
+use async_trait::async_trait;
+use clap::Parser;
+use colored::Colorize;
+use indicatif::HumanBytes;
+use miette::{IntoDiagnostic, Result};
+use node_semver::{Range, Version};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs::read_to_string,
+    path::Path,
+};
+
+/// Remove duplicate versions of packages where a single version satisfies every dependent
+#[derive(Debug, Parser)]
+pub struct Dedupe {
+    /// Only report the duplicates, exiting with an error if any could be removed
+    #[clap(long)]
+    check: bool,
+}
+
+/// A dependency on a package along with the range it was requested with
+struct Edge {
+    /// `name@version` of the dependent package, `None` for the project and its workspaces
+    dependent: Option<String>,
+    name: String,
+    range: String,
+    /// The version currently locked for the dependency
+    locked: Option<String>,
+}
+
+/// Versions of a package that can all be replaced by `target`
+struct Duplicate {
+    name: String,
+    removed: Vec<String>,
+    target: String,
+}
+
+#[async_trait]
+impl VoltCommand for Dedupe {
+    /// Execute the `volt dedupe` command
+    ///
+    /// Replace duplicate versions of a package with the highest version that satisfies every
+    /// package depending on it, then remove the versions that are no longer needed.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Report duplicates without changing anything
+    /// // .exec() is an async call so you need to await it
+    /// Dedupe { check: true }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let project_dir = config.cwd()?;
+        let node_modules = config.node_modules()?;
+
+        let (package_file, _) = PackageJson::get_from_dir(&project_dir)?;
+
+        let lock_file = LockFile::load(config.lockfile()?, false)?;
+
+        let mut manifests = vec![package_file.clone()];
+
+        manifests.extend(
+            package_file
+                .workspace_packages(&project_dir)?
+                .into_iter()
+                .map(|(_, manifest)| manifest),
+        );
+
+        let edges = collect_edges(&lock_file, &manifests, &node_modules);
+        let duplicates = find_duplicates(&lock_file, &edges);
+
+        if duplicates.is_empty() {
+            println!("{}", "No duplicate packages found!".bright_green());
+            return Ok(());
+        }
+
+        let mut deduped = lock_file.clone();
+
+        // (dependent directory, dependency name, new version) for every link to rewrite
+        let mut relinks = vec![];
+
+        for duplicate in &duplicates {
+            for edge in edges.iter().filter(|e| e.name == duplicate.name) {
+                if edge.locked.as_deref() == Some(duplicate.target.as_str()) {
+                    continue;
+                }
+
+                match &edge.dependent {
+                    Some(key) => {
+                        if let Some(package) = deduped.dependencies.get_mut(key) {
+                            for dependencies in [
+                                package.dependencies.as_mut(),
+                                package.optional_dependencies.as_mut(),
+                            ]
+                            .into_iter()
+                            .flatten()
+                            {
+                                if let Some(version) = dependencies.get_mut(&edge.name) {
+                                    *version = duplicate.target.clone();
+                                }
+                            }
+
+                            relinks.push((
+                                Some(package.directory_name()),
+                                edge.name.clone(),
+                                duplicate.target.clone(),
+                            ));
+                        }
+                    }
+                    None => {
+                        if let Some(version) = deduped.direct.get_mut(&edge.name) {
+                            *version = duplicate.target.clone();
+                        }
+
+                        relinks.push((None, edge.name.clone(), duplicate.target.clone()));
+                    }
+                }
+            }
+        }
+
+        // the duplicates themselves, and anything only they depended on
+        let before = lock_file.reachable(root_packages(&lock_file, &manifests));
+        let after = deduped.reachable(root_packages(&deduped, &manifests));
+
+        let mut removed = before.difference(&after).cloned().collect::<BTreeSet<_>>();
+
+        for duplicate in &duplicates {
+            for version in &duplicate.removed {
+                removed.insert(format!("{}@{}", duplicate.name, version));
+            }
+        }
+
+        deduped.dependencies.retain(|key, _| !removed.contains(key));
+
+        let mut saved = 0;
+
+        for key in &removed {
+            if let Some(package) = lock_file.dependencies.get(key) {
+                saved += directory_size(&node_modules.join(".volt").join(package.directory_name()));
+            }
+        }
+
+        let label = if self.check { "Duplicate" } else { "Deduped" };
+
+        for duplicate in &duplicates {
+            println!(
+                "{} {} {} → {}",
+                label.bright_yellow().bold(),
+                duplicate.name,
+                duplicate.removed.join(", ").bright_red(),
+                duplicate.target.bright_green()
+            );
+        }
+
+        if self.check {
+            println!(
+                "{} packages could be removed, saving {}",
+                removed.len().to_string().bright_yellow().bold(),
+                HumanBytes(saved)
+            );
+
+            return Err(VoltError::DuplicatePackagesError {
+                count: removed.len(),
+            }
+            .into());
+        }
+
+        for (dependent, name, version) in relinks {
+            let target = match deduped.dependencies.get(&format!("{}@{}", name, version)) {
+                Some(target) => target,
+                None => continue,
+            };
+
+            match dependent {
+                // node_modules/.volt/send@0.17.2/node_modules/ms -> node_modules/.volt/ms@2.1.3/node_modules/ms
+                Some(directory) => {
+                    let dependent_dir = node_modules.join(".volt").join(directory);
+
+                    if dependent_dir.exists() {
+                        create_link(
+                            &target.package_directory(&node_modules),
+                            &dependent_dir.join("node_modules").join(&name),
+                        )?;
+                    }
+                }
+                None => link_package(&config, target)?,
+            }
+        }
+
+        for key in &removed {
+            if let Some(package) = lock_file.dependencies.get(key) {
+                let directory = node_modules.join(".volt").join(package.directory_name());
+
+                if directory.exists() {
+                    std::fs::remove_dir_all(&directory).into_diagnostic()?;
+                }
+            }
+        }
+
+        deduped.save()?;
+
+        println!(
+            "{} {} packages, saved {}",
+            "Removed".bright_green().bold(),
+            removed.len(),
+            HumanBytes(saved)
+        );
+
+        Ok(())
+    }
+}
+
+/// The installed direct dependencies of every manifest
+fn root_packages<'a>(lock_file: &'a LockFile, manifests: &[PackageJson]) -> Vec<&'a VoltPackage> {
+    manifests
+        .iter()
+        .flat_map(|manifest| lock_file.roots(manifest))
+        .filter_map(|(_, _, package)| package)
+        .collect()
+}
+
+/// Every dependency in the graph, along with the range it was requested with
+fn collect_edges(
+    lock_file: &LockFile,
+    manifests: &[PackageJson],
+    node_modules: &Path,
+) -> Vec<Edge> {
+    let mut edges = vec![];
+
+    for manifest in manifests {
+        for dependencies in [&manifest.dependencies, &manifest.dev_dependencies] {
+            for (name, range) in dependencies.iter().flatten() {
+                edges.push(Edge {
+                    dependent: None,
+                    name: name.clone(),
+                    range: range.clone(),
+                    locked: lock_file.direct.get(name).cloned(),
+                });
+            }
+        }
+    }
+
+    for (key, package) in &lock_file.dependencies {
+        let declared = declared_ranges(package, node_modules);
+
+        for dependencies in [&package.dependencies, &package.optional_dependencies] {
+            for (name, version) in dependencies.iter().flatten() {
+                edges.push(Edge {
+                    dependent: Some(key.clone()),
+                    name: name.clone(),
+                    // without the installed package.json only the locked version is known to work
+                    range: declared.get(name).unwrap_or(version).clone(),
+                    locked: Some(version.clone()),
+                });
+            }
+        }
+    }
+
+    edges
+}
+
+/// The ranges an installed package declares for its dependencies
+fn declared_ranges(package: &VoltPackage, node_modules: &Path) -> HashMap<String, String> {
+    let manifest = package.package_directory(node_modules).join("package.json");
+
+    let value = match read_to_string(manifest)
+        .ok()
+        .and_then(|data| serde_json::from_str::<Value>(&data).ok())
+    {
+        Some(value) => value,
+        None => return HashMap::new(),
+    };
+
+    ["dependencies", "optionalDependencies"]
+        .iter()
+        .filter_map(|field| value.get(field)?.as_object())
+        .flatten()
+        .filter_map(|(name, range)| Some((name.clone(), range.as_str()?.to_string())))
+        .collect()
+}
+
+/// Packages with several versions installed where the highest version that satisfies every
+/// dependent could replace the others
+fn find_duplicates(lock_file: &LockFile, edges: &[Edge]) -> Vec<Duplicate> {
+    let mut versions: BTreeMap<&str, Vec<Version>> = BTreeMap::new();
+
+    for package in lock_file.dependencies.values() {
+        if let Ok(version) = package.version.parse::<Version>() {
+            versions
+                .entry(package.name.as_str())
+                .or_default()
+                .push(version);
+        }
+    }
+
+    let mut duplicates = vec![];
+
+    for (name, mut versions) in versions {
+        if versions.len() < 2 {
+            continue;
+        }
+
+        versions.sort();
+
+        let incoming = edges.iter().filter(|e| e.name == name).collect::<Vec<_>>();
+
+        let target = versions.iter().rev().find(|version| {
+            incoming
+                .iter()
+                .all(|edge| match edge.range.parse::<Range>() {
+                    Ok(range) => range.satisfies(version),
+                    Err(_) => edge.range == version.to_string(),
+                })
+        });
+
+        if let Some(target) = target {
+            duplicates.push(Duplicate {
+                name: name.to_string(),
+                removed: versions
+                    .iter()
+                    .filter(|version| *version != target)
+                    .map(|version| version.to_string())
+                    .collect(),
+                target: target.to_string(),
+            });
+        }
+    }
+
+    duplicates
+}
@@ -0,0 +1,140 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Deprecate published versions of a package.
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::{auth::RegistryClient, prompt::prompts::Confirm},
+};
+
+use async_trait::async_trait;
+use clap::Parser;
+use colored::Colorize;
+use miette::{IntoDiagnostic, Result};
+use node_semver::{Range, Version};
+use serde_json::{json, Value};
+
+/// Deprecate versions of a package, or undeprecate them with an empty message
+#[derive(Debug, Parser)]
+pub struct Deprecate {
+    /// The package, optionally with a range of versions (`volt@<1.0.0`)
+    spec: String,
+
+    /// Shown to anyone installing the versions, an empty message undeprecates them
+    message: String,
+
+    /// Don't ask for confirmation when more than one version matches
+    #[clap(short, long)]
+    yes: bool,
+}
+
+#[async_trait]
+impl VoltCommand for Deprecate {
+    /// Execute the `volt deprecate` command
+    ///
+    /// Set the deprecation message of every published version matching the range, which
+    /// defaults to all versions.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Deprecate the 0.x releases
+    /// // .exec() is an async call so you need to await it
+    /// Deprecate { spec: "volt@<1".into(), message: "upgrade to 1.x".into(), yes: false }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        // the first `@` of a scoped package is part of its name
+        let (name, range) = match self.spec.rfind('@') {
+            Some(index) if index > 0 => (&self.spec[..index], &self.spec[index + 1..]),
+            _ => (self.spec.as_str(), "*"),
+        };
+
+        let range = range
+            .parse::<Range>()
+            .map_err(|_| miette::miette!("`{}` is not a valid range", range))?;
+
+        let mut client = RegistryClient::new(&config)?;
+
+        let path = name.replace('/', "%2f");
+        let mut packument = client.get(&path).await?;
+
+        let versions = packument
+            .get_mut("versions")
+            .and_then(Value::as_object_mut)
+            .ok_or_else(|| miette::miette!("{} has no published versions", name))?;
+
+        let mut matching = versions
+            .keys()
+            .filter(|version| {
+                version
+                    .parse::<Version>()
+                    .map_or(false, |version| range.satisfies(&version))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if matching.is_empty() {
+            miette::bail!("no published versions of {} match `{}`", name, range);
+        }
+
+        matching.sort_by_key(|version| version.parse::<Version>().ok());
+
+        let action = if self.message.is_empty() {
+            "Undeprecate"
+        } else {
+            "Deprecate"
+        };
+
+        if matching.len() > 1 && !self.yes {
+            let confirmed = Confirm {
+                message: format!(
+                    "{} {} versions of {} ({} to {})?",
+                    action,
+                    matching.len(),
+                    name,
+                    matching[0],
+                    matching[matching.len() - 1]
+                )
+                .into(),
+                default: false,
+            }
+            .run()
+            .into_diagnostic()?;
+
+            if !confirmed {
+                return Ok(());
+            }
+        }
+
+        for version in &matching {
+            versions[version]["deprecated"] = json!(self.message);
+        }
+
+        client.put(&path, &packument).await?;
+
+        println!(
+            "{} {} versions of {}",
+            format!("{}d", action).bright_green().bold(),
+            matching.len(),
+            name
+        );
+
+        Ok(())
+    }
+}
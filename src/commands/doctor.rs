@@ -0,0 +1,409 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Diagnose problems with the environment volt runs in.
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::utils::{errors::VoltError, is_on_path},
+};
+
+use async_trait::async_trait;
+use clap::Parser;
+use colored::Colorize;
+use indicatif::HumanBytes;
+use miette::Result;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, Instant},
+};
+
+/// Warn when the store has less free space than this
+const MIN_FREE_SPACE: u64 = 1024 * 1024 * 1024;
+
+/// Check your environment for common problems
+#[derive(Debug, Parser)]
+pub struct Doctor {}
+
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// The outcome of a single diagnostic, with how to fix it when it didn't pass
+struct Check {
+    name: &'static str,
+    status: Status,
+    detail: String,
+    fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Warn,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl VoltCommand for Doctor {
+    /// Execute the `volt doctor` command
+    ///
+    /// Check the tools, network, store and PATH volt depends on, suggesting a fix for every
+    /// problem found. Exits with an error when any check fails.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Find out why installs are failing
+    /// // .exec() is an async call so you need to await it
+    /// Doctor {}.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let volt_home = config.volt_home()?;
+        let global_bin = config.global_bin()?;
+
+        let mut checks = vec![
+            check_tool(
+                "node",
+                "node",
+                true,
+                "install Node.js from https://nodejs.org or with `volt node install`",
+            ),
+            check_tool(
+                "npm",
+                "npm",
+                false,
+                "npm ships with Node.js, reinstall Node.js to get it back",
+            ),
+            check_tool(
+                "git",
+                "git",
+                false,
+                "install git from https://git-scm.com to use git dependencies and `volt version`",
+            ),
+            check_registry(&config).await,
+            check_store(&volt_home),
+            check_disk_space(&volt_home),
+            check_path(&global_bin),
+        ];
+
+        if cfg!(windows) {
+            checks.push(check_long_paths());
+        }
+
+        checks.push(check_symlinks(&config.node_modules()?));
+
+        let mut failures = 0;
+
+        for check in &checks {
+            let symbol = match check.status {
+                Status::Ok => "✓".bright_green().bold(),
+                Status::Warn => "!".bright_yellow().bold(),
+                Status::Fail => {
+                    failures += 1;
+                    "✗".bright_red().bold()
+                }
+            };
+
+            println!("{} {:<14} {}", symbol, check.name, check.detail);
+
+            if let Some(fix) = &check.fix {
+                println!("  {} {}", "fix:".bright_cyan(), fix);
+            }
+        }
+
+        if failures > 0 {
+            return Err(VoltError::DoctorChecksError { count: failures }.into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Run `<program> --version`, through the shell on windows where npm is a `.cmd` script
+fn tool_version(program: &str) -> Option<String> {
+    let output = if cfg!(windows) {
+        Command::new("cmd")
+            .args(["/C", program, "--version"])
+            .output()
+    } else {
+        Command::new(program).arg("--version").output()
+    }
+    .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn check_tool(name: &'static str, program: &str, required: bool, fix: &str) -> Check {
+    match tool_version(program) {
+        Some(version) => Check::ok(name, version),
+        None if required => Check::fail(name, format!("`{}` is not on your PATH", program), fix),
+        None => Check::warn(name, format!("`{}` is not on your PATH", program), fix),
+    }
+}
+
+async fn check_registry(config: &VoltConfig) -> Check {
+    let registry = config.registry();
+
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+    {
+        Ok(client) => client,
+        Err(error) => return Check::fail("registry", error.to_string(), "report this as a bug"),
+    };
+
+    let start = Instant::now();
+
+    match client.get(format!("{}/-/ping", registry)).send().await {
+        Ok(response) if response.status().is_success() => {
+            let latency = start.elapsed();
+            let detail = format!("{} responded in {}ms", registry, latency.as_millis());
+
+            if latency > Duration::from_secs(1) {
+                Check::warn(
+                    "registry",
+                    detail,
+                    "the registry is slow to respond, check your network or proxy",
+                )
+            } else {
+                Check::ok("registry", detail)
+            }
+        }
+        Ok(response) => Check::fail(
+            "registry",
+            format!("{} responded with {}", registry, response.status()),
+            "check https://status.npmjs.org for outages",
+        ),
+        Err(error) => Check::fail(
+            "registry",
+            format!("could not reach {}: {}", registry, error),
+            "check your network connection, proxy and firewall settings",
+        ),
+    }
+}
+
+/// The store must be writable or every install fails
+fn check_store(volt_home: &Path) -> Check {
+    let writable = fs::create_dir_all(volt_home)
+        .and_then(|_| tempfile::NamedTempFile::new_in(volt_home))
+        .map(|_| ());
+
+    match writable {
+        Ok(_) => Check::ok("store", volt_home.display().to_string()),
+        Err(error) => Check::fail(
+            "store",
+            format!("{} is not writable: {}", volt_home.display(), error),
+            if cfg!(windows) {
+                format!("give your user full control of {}", volt_home.display())
+            } else {
+                format!("run `sudo chown -R $(whoami) {}`", volt_home.display())
+            },
+        ),
+    }
+}
+
+fn check_disk_space(volt_home: &Path) -> Check {
+    match free_space(volt_home) {
+        Some(free) if free < MIN_FREE_SPACE => Check::warn(
+            "disk space",
+            format!("{} free", HumanBytes(free)),
+            "free up some space, or run `volt cache clean` to shrink the store",
+        ),
+        Some(free) => Check::ok("disk space", format!("{} free", HumanBytes(free))),
+        None => Check::warn(
+            "disk space",
+            "could not determine the free space",
+            "make sure the drive holding the store isn't full",
+        ),
+    }
+}
+
+#[cfg(unix)]
+fn free_space(path: &Path) -> Option<u64> {
+    let output = Command::new("df").arg("-Pk").arg(path).output().ok()?;
+
+    // Filesystem 1024-blocks Used Available Capacity Mounted on
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let available = stdout.lines().nth(1)?.split_whitespace().nth(3)?;
+
+    Some(available.parse::<u64>().ok()? * 1024)
+}
+
+#[cfg(windows)]
+fn free_space(path: &Path) -> Option<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use winapi::um::{fileapi::GetDiskFreeSpaceExW, winnt::ULARGE_INTEGER};
+
+    let path = path
+        .as_os_str()
+        .encode_wide()
+        .chain(Some(0))
+        .collect::<Vec<u16>>();
+
+    let mut free: ULARGE_INTEGER = unsafe { std::mem::zeroed() };
+
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            path.as_ptr(),
+            &mut free,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+
+    if ok == 0 {
+        return None;
+    }
+
+    Some(unsafe { *free.QuadPart() })
+}
+
+fn check_path(global_bin: &Path) -> Check {
+    if is_on_path(global_bin) {
+        return Check::ok("global bin", global_bin.display().to_string());
+    }
+
+    Check::warn(
+        "global bin",
+        format!("{} is not on your PATH", global_bin.display()),
+        if cfg!(windows) {
+            format!("run `setx PATH \"%PATH%;{}\"`", global_bin.display())
+        } else {
+            format!(
+                "add `export PATH=\"{}:$PATH\"` to your shell profile",
+                global_bin.display()
+            )
+        },
+    )
+}
+
+/// Deep node_modules trees easily pass the 260 character limit windows has by default
+fn check_long_paths() -> Check {
+    let enabled = Command::new("reg")
+        .args([
+            "query",
+            r"HKLM\SYSTEM\CurrentControlSet\Control\FileSystem",
+            "/v",
+            "LongPathsEnabled",
+        ])
+        .output()
+        .map_or(false, |output| {
+            String::from_utf8_lossy(&output.stdout).contains("0x1")
+        });
+
+    if enabled {
+        Check::ok("long paths", "enabled")
+    } else {
+        Check::warn(
+            "long paths",
+            "disabled, deeply nested packages may fail to install",
+            r#"run `reg add HKLM\SYSTEM\CurrentControlSet\Control\FileSystem /v LongPathsEnabled /t REG_DWORD /d 1 /f` as an administrator"#,
+        )
+    }
+}
+
+fn check_symlinks(node_modules: &Path) -> Check {
+    if !node_modules.exists() {
+        return Check::ok("node_modules", "not installed");
+    }
+
+    let broken = broken_links(node_modules);
+
+    if broken.is_empty() {
+        return Check::ok("node_modules", "no broken links");
+    }
+
+    let names = broken
+        .iter()
+        .filter_map(|path| path.strip_prefix(node_modules).ok())
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>();
+
+    Check::fail(
+        "node_modules",
+        format!("{} broken links: {}", broken.len(), names.join(", ")),
+        "run `volt install` to relink your dependencies",
+    )
+}
+
+/// Links in the top level of node_modules, scopes and `.bin` whose target no longer exists
+fn broken_links(node_modules: &Path) -> Vec<PathBuf> {
+    let mut broken = vec![];
+    let mut dirs = vec![node_modules.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_link = fs::symlink_metadata(&path).map_or(false, |m| m.file_type().is_symlink());
+
+            if is_link {
+                // metadata follows the link, so it fails when the target is gone
+                if fs::metadata(&path).is_err() {
+                    broken.push(path);
+                }
+            } else if dir == node_modules && path.is_dir() {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+
+                if name.starts_with('@') || name == ".bin" {
+                    dirs.push(path);
+                }
+            }
+        }
+    }
+
+    broken.sort();
+    broken
+}
@@ -0,0 +1,327 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Export the resolved dependency graph for visualization.
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::model::lock_file::{DependencyKind, LockFile},
+    core::utils::{errors::VoltError, package::PackageJson, voltapi::VoltPackage},
+};
+
+use async_trait::async_trait;
+use clap::{ArgEnum, Parser};
+use miette::{IntoDiagnostic, Result};
+use serde::Serialize;
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet, VecDeque},
+    fmt::Write,
+    path::PathBuf,
+};
+
+/// Export the dependency graph as DOT, Mermaid or JSON
+#[derive(Debug, Parser)]
+pub struct Graph {
+    /// Format of the graph
+    #[clap(long, arg_enum, default_value = "dot")]
+    format: GraphFormat,
+
+    /// Only follow dependencies this many levels deep
+    #[clap(long)]
+    depth: Option<usize>,
+
+    /// Only show the dependencies of a package, optionally with a version (`react@18.0.0`)
+    #[clap(long)]
+    focus: Option<String>,
+
+    /// Write the graph to a file instead of stdout
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, ArgEnum)]
+enum GraphFormat {
+    /// Graphviz, render with `dot -Tsvg`
+    Dot,
+    /// Mermaid flowchart, rendered by GitHub and GitLab in markdown
+    Mermaid,
+    /// Nodes and edges as JSON
+    Json,
+}
+
+#[derive(Debug, Serialize)]
+struct Node {
+    id: String,
+    name: String,
+    version: String,
+    /// Whether another version of the package is in the graph too
+    duplicate: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+struct Edge {
+    from: String,
+    to: String,
+    #[serde(rename = "type")]
+    kind: DependencyKind,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct DependencyGraph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+}
+
+#[async_trait]
+impl VoltCommand for Graph {
+    /// Execute the `volt graph` command
+    ///
+    /// Walk the dependency graph in the lock file from the project, or from a single package,
+    /// and print it in a format graph tools can render, highlighting packages installed in more
+    /// than one version.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Draw the direct dependencies of react as a Mermaid chart
+    /// // .exec() is an async call so you need to await it
+    /// Graph { format: GraphFormat::Mermaid, depth: Some(1), focus: Some("react".into()), output: None }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let (package_file, _) = PackageJson::get_from_dir(&config.cwd()?)?;
+
+        let lock_file = LockFile::load(config.lockfile()?, false)?;
+
+        let graph = match &self.focus {
+            Some(focus) => {
+                // the first `@` of a scoped package is part of its name
+                let (name, version) = match focus.rfind('@') {
+                    Some(index) if index > 0 => (&focus[..index], Some(&focus[index + 1..])),
+                    _ => (focus.as_str(), None),
+                };
+
+                let roots = lock_file
+                    .dependencies
+                    .values()
+                    .filter(|package| {
+                        package.name == name && version.map_or(true, |v| package.version == v)
+                    })
+                    .collect::<Vec<_>>();
+
+                if roots.is_empty() {
+                    miette::bail!("{} is not installed", focus);
+                }
+
+                build_graph(&lock_file, vec![], roots, self.depth)
+            }
+            None => {
+                let project = format!("{}@{}", package_file.name, package_file.version);
+
+                let roots = lock_file
+                    .roots(&package_file)
+                    .into_iter()
+                    .filter_map(|(kind, _, package)| Some((kind, package?)))
+                    .collect::<Vec<_>>();
+
+                let mut graph = build_graph(
+                    &lock_file,
+                    roots
+                        .iter()
+                        .map(|(kind, package)| Edge {
+                            from: project.clone(),
+                            to: package.key(),
+                            kind: *kind,
+                        })
+                        .collect(),
+                    roots.iter().map(|(_, package)| *package).collect(),
+                    // the project is the first level
+                    self.depth.map(|depth| depth.saturating_sub(1)),
+                );
+
+                graph.nodes.insert(
+                    0,
+                    Node {
+                        id: project,
+                        name: package_file.name.clone(),
+                        version: package_file.version.clone(),
+                        duplicate: false,
+                    },
+                );
+
+                graph
+            }
+        };
+
+        let rendered = match self.format {
+            GraphFormat::Dot => render_dot(&graph),
+            GraphFormat::Mermaid => render_mermaid(&graph),
+            GraphFormat::Json => serde_json::to_string_pretty(&graph).into_diagnostic()?,
+        };
+
+        match self.output {
+            Some(path) => {
+                std::fs::write(&path, rendered).map_err(|e| VoltError::WriteFileError {
+                    source: e,
+                    name: path.display().to_string(),
+                })?;
+            }
+            None => println!("{}", rendered),
+        }
+
+        Ok(())
+    }
+}
+
+/// Collect the packages reachable from `roots` within `depth` levels, along with the edges
+/// between them
+fn build_graph(
+    lock_file: &LockFile,
+    mut edges: Vec<Edge>,
+    roots: Vec<&VoltPackage>,
+    depth: Option<usize>,
+) -> DependencyGraph {
+    let mut packages: BTreeMap<String, &VoltPackage> = BTreeMap::new();
+    let mut queue = roots
+        .into_iter()
+        .map(|package| (package, 0))
+        .collect::<VecDeque<_>>();
+
+    while let Some((package, level)) = queue.pop_front() {
+        if packages.insert(package.key(), package).is_some() {
+            continue;
+        }
+
+        if depth.map_or(false, |depth| level >= depth) {
+            continue;
+        }
+
+        for (kind, child) in lock_file.children(package) {
+            edges.push(Edge {
+                from: package.key(),
+                to: child.key(),
+                kind,
+            });
+
+            queue.push_back((child, level + 1));
+        }
+    }
+
+    // edges to packages past the depth limit would point at nothing
+    edges.retain(|edge| packages.contains_key(&edge.to));
+    edges.sort();
+    edges.dedup();
+
+    let mut seen = HashSet::new();
+
+    let duplicated = packages
+        .values()
+        .filter(|package| !seen.insert(package.name.as_str()))
+        .map(|package| package.name.as_str())
+        .collect::<BTreeSet<_>>();
+
+    let nodes = packages
+        .iter()
+        .map(|(key, package)| Node {
+            id: key.clone(),
+            name: package.name.clone(),
+            version: package.version.clone(),
+            duplicate: duplicated.contains(package.name.as_str()),
+        })
+        .collect();
+
+    DependencyGraph { nodes, edges }
+}
+
+fn render_dot(graph: &DependencyGraph) -> String {
+    let mut dot = String::from("digraph dependencies {\n    rankdir=LR;\n    node [shape=box];\n");
+
+    for node in &graph.nodes {
+        let style = if node.duplicate {
+            ", color=red, fontcolor=red"
+        } else {
+            ""
+        };
+
+        let _ = writeln!(
+            dot,
+            "    \"{}\" [label=\"{}\\n{}\"{}];",
+            node.id, node.name, node.version, style
+        );
+    }
+
+    for edge in &graph.edges {
+        let style = match edge.kind {
+            DependencyKind::Prod => "",
+            DependencyKind::Dev => " [style=dashed, label=\"dev\"]",
+            DependencyKind::Peer => " [style=dotted, label=\"peer\"]",
+            DependencyKind::Optional => " [style=dashed, color=gray, label=\"optional\"]",
+        };
+
+        let _ = writeln!(dot, "    \"{}\" -> \"{}\"{};", edge.from, edge.to, style);
+    }
+
+    dot.push('}');
+    dot
+}
+
+fn render_mermaid(graph: &DependencyGraph) -> String {
+    let mut mermaid = String::from("graph LR\n");
+
+    // package names aren't valid mermaid ids
+    let ids = graph
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| (node.id.as_str(), format!("n{}", index)))
+        .collect::<BTreeMap<_, _>>();
+
+    for node in &graph.nodes {
+        let _ = writeln!(mermaid, "    {}[\"{}\"]", ids[node.id.as_str()], node.id);
+    }
+
+    for edge in &graph.edges {
+        let arrow = match edge.kind {
+            DependencyKind::Prod => "-->".to_string(),
+            kind => format!("-.->|{}|", kind),
+        };
+
+        let _ = writeln!(
+            mermaid,
+            "    {} {} {}",
+            ids[edge.from.as_str()],
+            arrow,
+            ids[edge.to.as_str()]
+        );
+    }
+
+    let duplicates = graph
+        .nodes
+        .iter()
+        .filter(|node| node.duplicate)
+        .map(|node| ids[node.id.as_str()].as_str())
+        .collect::<Vec<_>>();
+
+    if !duplicates.is_empty() {
+        mermaid.push_str("    classDef duplicate fill:#fdd,stroke:#c00\n");
+
+        let _ = writeln!(mermaid, "    class {} duplicate", duplicates.join(","));
+    }
+
+    mermaid
+}
@@ -16,267 +16,263 @@
 
 //! Display info about a package.
 
-use crate::cli::{VoltCommand, VoltConfig};
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::net::get_registry_document,
+    core::output::print_json,
+};
 
 use async_trait::async_trait;
 use clap::Parser;
 use colored::Colorize;
+use indicatif::HumanBytes;
 use miette::Result;
-use std::sync::Arc;
+use node_semver::{Range, Version};
+use serde_json::Value;
 
 /// Display information about a package
 #[derive(Debug, Parser)]
-pub struct Info {}
+pub struct Info {
+    /// The package, optionally with a version, tag or range (`react@17`)
+    spec: String,
+
+    /// Only print this field, using dots for nested fields (`dependencies.loose-envify`)
+    field: Option<String>,
+}
 
 #[async_trait]
 impl VoltCommand for Info {
     /// Execute the `volt info` command
     ///
-    /// Display info about a package
+    /// Display a summary of a published version of a package, or a single field of its
+    /// manifest
     /// ## Arguments
-    /// * `error` - Instance of the command (`Arc<App>`)
+    /// * `config` - The global volt configuration (`VoltConfig`)
     /// ## Examples
     /// ```
-    /// // Display info about a package
+    /// // Print which version of loose-envify react depends on
     /// // .exec() is an async call so you need to await it
-    /// Info.exec(app).await;
+    /// Info { spec: "react".into(), field: Some("dependencies.loose-envify".into()) }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
     async fn exec(self, config: VoltConfig) -> Result<()> {
-        // #[allow(unused_assignments)]
-        // let mut name = String::new();
-
-        // if !std::env::current_dir()
-        //     .unwrap()
-        //     .join("package.json")
-        //     .exists()
-        //     && app.args.len() == 1
-        // {
-        //     println!(
-        //         "{}: {}\n",
-        //         "warning".yellow().bold(),
-        //         "Could not find a package.json file in the current directory"
-        //     );
-        //     name = utils::get_basename(app.current_dir.to_str().unwrap()).to_string();
-        // }
-
-        // let mut field: String = String::new();
-
-        // if app.args.len() > 2 {
-        //     field = String::from(&app.args[2]);
-        //     name = String::from(&app.args[1]);
-        // } else if app.args.len() == 2 {
-        //     name = String::from(&app.args[1]);
-        // }
-
-        // let package: Package = get_package(&name).await.unwrap().unwrap();
-
-        // if field == String::new() {
-        //     let latest_version = package.dist_tags.get("latest").unwrap();
-        //     println!("{}\n", format!("v{}", latest_version).bright_blue());
-
-        //     if package.description != None {
-        //         println!("{}\n", package.description.unwrap());
-        //     }
-        //     if package.keywords != None {
-        //         print!("{}: ", "keywords".bright_blue().bold());
-        //         for keyword in package.keywords.unwrap().iter() {
-        //             print!("{} ", keyword.green())
-        //         }
-        //         print!("\n\n")
-        //     }
-
-        //     let latestpackage: &Version = &package.versions[latest_version];
-        //     println!("distribution:");
-        //     println!(
-        //         "  tarball: {}",
-        //         latestpackage.dist.tarball.bright_blue().underline()
-        //     );
-        //     println!("  shasum: {}", latestpackage.dist.shasum.bright_green());
-        //     if latestpackage.dist.integrity != "" {
-        //         println!(
-        //             "  integrity: {}",
-        //             latestpackage.dist.integrity.bright_blue()
-        //         );
-        //     }
-        //     if latestpackage.dist.unpacked_size != 0 {
-        //         println!(
-        //             "  unpackedSize: {}{}",
-        //             (latestpackage.dist.unpacked_size / 1024)
-        //                 .to_string()
-        //                 .bright_blue()
-        //                 .bold(),
-        //             "kb".bright_blue().bold()
-        //         );
-        //     }
-
-        //     let dependencies = latestpackage
-        //         .dependencies
-        //         .keys()
-        //         .cloned()
-        //         .collect::<Vec<String>>();
-
-        //     if dependencies.len() != 0 {
-        //         println!("\ndependencies:");
-        //         for dep in dependencies.iter() {
-        //             println!("{}{}", "  - ".bright_magenta(), dep);
-        //         }
-        //     }
-
-        //     println!("{}", "\nmaintainers:");
-        //     for maintainer in latestpackage.maintainers.iter() {
-        //         println!(
-        //             "  {} {}<{}>",
-        //             "-".bright_magenta(),
-        //             maintainer.email,
-        //             maintainer.name.yellow().bold()
-        //         )
-        //     }
-        //     print!("\n");
-        // } else {
-        //     match field.as_str() {
-        //         "readme" => {
-        //             let text: String;
-
-        //             if package.readme.is_some() && package.readme.as_ref().unwrap().trim() != "" {
-        //                 text = package.readme.unwrap();
-        //             } else {
-        //                 let latest_version = package.dist_tags.get("latest").unwrap();
-        //                 let current_version = package.versions.get(latest_version).unwrap();
-
-        //                 if current_version.readme.is_some() {
-        //                     text = current_version.readme.as_ref().unwrap().to_string();
-        //                 } else {
-        //                     text = String::new();
-        //                 }
-        //             }
-        //             if text != String::new() {
-        //                 PrettyPrinter::new()
-        //                     .input_from_bytes(text.as_bytes())
-        //                     .theme("Dracula")
-        //                     .language("markdown")
-        //                     .print()
-        //                     .unwrap();
-
-        //                 print!("\n");
-        //             } else {
-        //                 error!("could not find a readme for {}", name);
-        //             }
-        //         }
-        //         "version" => {
-        //             let mut table = Table::new();
-
-        //             let versions: HashMap<String, String> = package.dist_tags;
-
-        //             let mut labels: Vec<Cell> = vec![Cell::new("")];
-
-        //             let mut values: Vec<Cell> =
-        //                 vec![Cell::new(&name.bright_blue().to_string().as_str())];
-
-        //             for (k, v) in versions.iter() {
-        //                 labels.push(Cell::new(k.as_str()));
-        //                 values.push(Cell::new(v.bright_magenta().to_string().as_str()));
-        //             }
-
-        //             table.add_row(Row::new(labels));
-
-        //             table.add_row(Row::new(values));
-
-        //             table.printstd();
-        //             print!("\n");
-        //         }
-        //         "versions" => {
-        //             let versions = package.versions.keys().cloned().collect::<Vec<String>>();
-
-        //             println!("{}{}", "versions".bright_cyan(), ":".bright_magenta());
-        //             for v in versions {
-        //                 println!("  {} {}", "-".bright_magenta(), v.bright_green());
-        //             }
-        //             print!("\n");
-        //         }
-        //         "description" => {
-        //             let description = package.description;
-
-        //             if description.is_some() {
-        //                 println!("{}", description.unwrap());
-        //                 print!("\n");
-        //             } else {
-        //                 error!("could not find a description for {}", name);
-        //             }
-        //         }
-        //         "name" => {
-        //             println!("{}", name);
-        //             print!("\n");
-        //         }
-        //         "maintainers" => {
-        //             for maintainer in package.maintainers.iter() {
-        //                 println!(
-        //                     "{} <{}>",
-        //                     maintainer.name.bright_green().bold(),
-        //                     maintainer.email.bright_magenta(),
-        //                 )
-        //             }
-        //             print!("\n");
-        //         }
-        //         "time" => {
-        //             let times = package.time;
-
-        //             for (version, time) in times {
-        //                 println!(
-        //                     "{} {} {}",
-        //                     version.bright_cyan(),
-        //                     ":".bright_magenta(),
-        //                     time.bright_black()
-        //                 );
-        //             }
-
-        //             print!("\n");
-        //         }
-        //         "repository" => {
-        //             let repo = package.repository;
-
-        //             if repo.is_some() {
-        //                 let data = repo.unwrap();
-
-        //                 println!("{}: {}", "provider".bright_yellow(), data.type_field);
-        //                 println!("{}: {}", "url".bright_purple(), data.url.underline());
-        //                 print!("\n");
-        //             } else {
-        //                 error!("could not find a repository for {}", name);
-        //             }
-        //         }
-        //         "homepage" => {
-        //             let page = package.homepage;
-
-        //             if page.is_some() {
-        //                 let data = page.unwrap();
-
-        //                 println!("{}", data);
-        //                 print!("\n");
-        //             } else {
-        //                 error!("could not find a homepage for {}", name);
-        //             }
-        //         }
-        //         "keywords" => {
-        //             let keywords = package.keywords;
-        //             println!("{}{}", "keywords".bright_cyan(), ":".bright_cyan());
-        //             if keywords.is_some() {
-        //                 let keywords = keywords.unwrap();
-
-        //                 for kw in keywords {
-        //                     println!("{} {}", "-".bright_magenta(), kw);
-        //                 }
-        //                 print!("\n");
-        //             } else {
-        //                 error!("could not find keywords for {}", name);
-        //             }
-        //         }
-        //         "users" => {}
-        //         &_ => {}
-        //     }
-        // }
+        // the first `@` of a scoped package is part of its name
+        let (name, requested) = match self.spec.rfind('@') {
+            Some(index) if index > 0 => (&self.spec[..index], &self.spec[index + 1..]),
+            _ => (self.spec.as_str(), "latest"),
+        };
+
+        let packument = get_registry_document(&reqwest::Client::new(), name).await?;
+
+        let version = resolve_version(&packument, requested).ok_or_else(|| {
+            miette::miette!("no published version of {} matches `{}`", name, requested)
+        })?;
+
+        let manifest = &packument["versions"][&version];
+
+        if let Some(field) = &self.field {
+            // fields like `time` and `dist-tags` only exist on the package, not the version
+            let value = lookup(manifest, field)
+                .or_else(|| lookup(&packument, field))
+                .ok_or_else(|| miette::miette!("{}@{} has no `{}` field", name, version, field))?;
+
+            match value {
+                Value::String(value) if !config.json() => println!("{}", value),
+                value => print_json(value)?,
+            }
+
+            return Ok(());
+        }
+
+        if config.json() {
+            print_json(manifest)?;
+
+            return Ok(());
+        }
+
+        print_summary(&packument, manifest);
 
         Ok(())
     }
 }
+
+/// Resolve a dist-tag, version or range to a published version
+fn resolve_version(packument: &Value, requested: &str) -> Option<String> {
+    if let Some(version) = packument["dist-tags"][requested].as_str() {
+        return Some(version.to_string());
+    }
+
+    let versions = packument["versions"].as_object()?;
+
+    if versions.contains_key(requested) {
+        return Some(requested.to_string());
+    }
+
+    let range: Range = requested.parse().ok()?;
+
+    versions
+        .keys()
+        .filter_map(|version| version.parse::<Version>().ok())
+        .filter(|version| range.satisfies(version))
+        .max()
+        .map(|version| version.to_string())
+}
+
+/// Follow a dotted field path, preferring the longest key at each step so names containing
+/// dots like `dependencies.lodash.merge` still resolve
+fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    if path.is_empty() {
+        return Some(value);
+    }
+
+    let segments = path.split('.').collect::<Vec<_>>();
+
+    (1..=segments.len()).rev().find_map(|length| {
+        let child = value.get(segments[..length].join("."))?;
+
+        lookup(child, &segments[length..].join("."))
+    })
+}
+
+fn print_summary(packument: &Value, manifest: &Value) {
+    let dependencies = manifest["dependencies"]
+        .as_object()
+        .cloned()
+        .unwrap_or_default();
+
+    println!(
+        "{}@{} | {} | deps: {} | versions: {}",
+        manifest["name"].as_str().unwrap_or_default().bold(),
+        manifest["version"]
+            .as_str()
+            .unwrap_or_default()
+            .bright_green(),
+        manifest["license"]
+            .as_str()
+            .unwrap_or("Proprietary")
+            .bright_green(),
+        dependencies.len().to_string().bright_cyan(),
+        packument["versions"]
+            .as_object()
+            .map_or(0, |versions| versions.len())
+            .to_string()
+            .bright_yellow()
+    );
+
+    if let Some(description) = manifest["description"].as_str() {
+        println!("{}", description);
+    }
+
+    if let Some(homepage) = manifest["homepage"].as_str() {
+        println!("{}", homepage.bright_cyan());
+    }
+
+    if let Some(deprecated) = manifest["deprecated"].as_str() {
+        println!("\n{} {}", "DEPRECATED".bright_red().bold(), deprecated);
+    }
+
+    if let Some(keywords) = manifest["keywords"].as_array() {
+        let keywords = keywords
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>();
+
+        if !keywords.is_empty() {
+            println!("\nkeywords: {}", keywords.join(", ").bright_yellow());
+        }
+    }
+
+    let dist = &manifest["dist"];
+
+    println!("\ndist");
+    println!(
+        ".tarball: {}",
+        dist["tarball"].as_str().unwrap_or_default().bright_cyan()
+    );
+    println!(
+        ".shasum: {}",
+        dist["shasum"].as_str().unwrap_or_default().bright_yellow()
+    );
+
+    if let Some(integrity) = dist["integrity"].as_str() {
+        println!(".integrity: {}", integrity.bright_yellow());
+    }
+
+    if let Some(size) = dist["unpackedSize"].as_u64() {
+        println!(
+            ".unpackedSize: {}",
+            HumanBytes(size).to_string().bright_blue()
+        );
+    }
+
+    if !dependencies.is_empty() {
+        println!("\ndependencies:");
+
+        for (dependency, range) in &dependencies {
+            println!(
+                "{}: {}",
+                dependency.bright_cyan(),
+                range.as_str().unwrap_or_default()
+            );
+        }
+    }
+
+    if let Some(maintainers) = packument["maintainers"].as_array() {
+        println!("\nmaintainers:");
+
+        for maintainer in maintainers {
+            println!(
+                "- {} {}",
+                maintainer["name"].as_str().unwrap_or_default(),
+                maintainer["email"]
+                    .as_str()
+                    .map(|email| format!("<{}>", email))
+                    .unwrap_or_default()
+                    .bright_black()
+            );
+        }
+    }
+
+    if let Some(tags) = packument["dist-tags"].as_object() {
+        println!("\ndist-tags:");
+
+        for (tag, version) in tags {
+            println!(
+                "{}: {}",
+                tag.bright_green().bold(),
+                version.as_str().unwrap_or_default()
+            );
+        }
+    }
+
+    if let Some(published) = manifest["version"]
+        .as_str()
+        .and_then(|version| packument["time"][version].as_str())
+    {
+        println!("\npublished {}", published.bright_black());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn lookup_prefers_keys_containing_dots() {
+        let manifest = json!({
+            "dependencies": { "loose-envify": "^1.1.0", "lodash.merge": "^4.6.2" },
+        });
+
+        assert_eq!(
+            lookup(&manifest, "dependencies.loose-envify"),
+            Some(&json!("^1.1.0"))
+        );
+        assert_eq!(
+            lookup(&manifest, "dependencies.lodash.merge"),
+            Some(&json!("^4.6.2"))
+        );
+        assert_eq!(lookup(&manifest, "dependencies.react"), None);
+    }
+}
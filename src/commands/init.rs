@@ -29,8 +29,14 @@ use async_trait::async_trait;
 use clap::Parser;
 use colored::Colorize;
 use miette::{IntoDiagnostic, Result};
+use node_semver::Version;
 use regex::Regex;
-use std::{fs::File, io::Write, time::Instant};
+use serde_json::{Map, Value};
+use std::{
+    fs::{read_to_string, File},
+    io::Write,
+    path::Path,
+};
 
 const PACKAGE_JSON: &str = "package.json";
 
@@ -48,19 +54,17 @@ impl VoltCommand for Init {
     ///
     /// Interactively create or update a package.json file for a project.
     /// ## Arguments
-    /// * `app` - Instance of the command (`Arc<App>`)
-    /// * `packages` - List of packages to add (`Vec<String>`)
-    /// * `flags` - List of flags passed in through the CLI (`Vec<String>`)
+    /// * `config` - The global volt configuration (`VoltConfig`)
     /// ## Examples
     /// ```
     /// // Initialize a new package.json file without any prompts
     /// // .exec() is an async call so you need to await it
-    /// Init.exec(app, vec![], vec!["--yes"]).await;
+    /// Init { yes: true }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
     async fn exec(self, config: VoltConfig) -> Result<()> {
-        let start = Instant::now();
+        let package_json_path = config.cwd()?.join(PACKAGE_JSON);
 
         // get name of cwd
         let cwd_name = config
@@ -68,19 +72,22 @@ impl VoltCommand for Init {
             .file_name_as_string()
             .ok_or(VoltError::GetCurrentDirNameError)?;
 
+        // fields of an existing package.json are kept and used as defaults
+        let existing = read_existing_package_json(&package_json_path)?;
+
         let data = if self.yes {
             // Set name to current directory name
-            automatic_initialization(cwd_name, &config)?
+            automatic_initialization(cwd_name, existing, &config)?
         } else {
-            manual_initialization(cwd_name, &config)?
+            manual_initialization(cwd_name, existing, &config)?
         };
 
-        let mut file = File::create(PACKAGE_JSON).map_err(|e| VoltError::WriteFileError {
+        let mut file = File::create(&package_json_path).map_err(|e| VoltError::WriteFileError {
             source: e,
             name: String::from(PACKAGE_JSON),
         })?;
 
-        file.write(data.into_string().as_bytes())
+        file.write_all(data.into_string().as_bytes())
             .map_err(|e| VoltError::WriteFileError {
                 source: e,
                 name: String::from(PACKAGE_JSON),
@@ -92,25 +99,84 @@ impl VoltCommand for Init {
     }
 }
 
-fn automatic_initialization(name: String, config: &VoltConfig) -> Result<InitData> {
-    let version = "0.1.0".to_string();
+/// Read the fields of an existing package.json, if there is one
+fn read_existing_package_json(path: &Path) -> Result<Map<String, Value>> {
+    if !path.exists() {
+        return Ok(Map::new());
+    }
 
-    let description = None;
+    let data = read_to_string(path).map_err(|e| VoltError::ReadFileError {
+        source: e,
+        name: String::from(PACKAGE_JSON),
+    })?;
 
-    let main = "index.js".to_string();
+    match serde_json::from_str(&data).into_diagnostic()? {
+        Value::Object(map) => Ok(map),
+        _ => miette::bail!("{} is not a JSON object", PACKAGE_JSON),
+    }
+}
 
-    let author = {
-        let git_user_name = utils::get_git_config(config, "user.name")?;
-        let git_email = utils::get_git_config(config, "user.email")?;
+/// Remove a string field from the existing package.json fields
+fn take_string(existing: &mut Map<String, Value>, key: &str) -> Option<String> {
+    match existing.remove(key) {
+        Some(Value::String(value)) if !value.is_empty() => Some(value),
+        _ => None,
+    }
+}
+
+/// Take the license of an existing package.json, defaulting to MIT when there's none
+///
+/// Licenses `volt init` doesn't know, like ISC, are left in `existing` so they're written back
+/// as they were.
+fn take_license(existing: &mut Map<String, Value>) -> Option<License> {
+    let license = match existing.get("license") {
+        Some(Value::Null) | None => Some(License::default()),
+        Some(Value::String(l)) if l.is_empty() => Some(License::default()),
+        Some(value) => value.as_str().and_then(|l| License::try_from(l).ok()),
+    };
+
+    if license.is_some() {
+        existing.remove("license");
+    }
 
-        if let (Some(git_user_name), Some(git_email)) = (git_user_name, git_email) {
+    license
+}
+
+/// Get the author from the `user.name` and `user.email` git configuration
+fn git_author(config: &VoltConfig) -> Result<Option<String>> {
+    let git_user_name = utils::get_git_config(config, "user.name")?;
+    let git_email = utils::get_git_config(config, "user.email")?;
+
+    Ok(match (git_user_name, git_email) {
+        (Some(git_user_name), Some(git_email)) => {
             Some(format!("{} <{}>", git_user_name, git_email))
-        } else {
-            None
         }
+        (Some(git_user_name), None) => Some(git_user_name),
+        _ => None,
+    })
+}
+
+fn automatic_initialization(
+    default_name: String,
+    mut existing: Map<String, Value>,
+    config: &VoltConfig,
+) -> Result<InitData> {
+    let name = take_string(&mut existing, "name").unwrap_or(default_name);
+
+    let version = take_string(&mut existing, "version").unwrap_or_else(|| "1.0.0".to_string());
+
+    let description = take_string(&mut existing, "description");
+
+    let main = take_string(&mut existing, "main").unwrap_or_else(|| "index.js".to_string());
+
+    let author = match take_string(&mut existing, "author") {
+        Some(author) => Some(author),
+        None => git_author(config)?,
     };
 
-    let license = License::default();
+    let license = take_license(&mut existing);
+
+    let private = existing.remove("private").and_then(|p| p.as_bool());
 
     Ok(InitData {
         name,
@@ -119,15 +185,24 @@ fn automatic_initialization(name: String, config: &VoltConfig) -> Result<InitDat
         main,
         author,
         license,
-        private: None,
+        private,
+        rest: existing,
     })
 }
 
-fn manual_initialization(default_name: String, config: &VoltConfig) -> Result<InitData> {
+fn manual_initialization(
+    default_name: String,
+    mut existing: Map<String, Value>,
+    config: &VoltConfig,
+) -> Result<InitData> {
     // Get "name"
     let input = Input {
         message: "name".into(),
-        default: Some(default_name.into()),
+        default: Some(
+            take_string(&mut existing, "name")
+                .unwrap_or(default_name)
+                .into(),
+        ),
         allow_empty: false,
     };
 
@@ -147,16 +222,29 @@ fn manual_initialization(default_name: String, config: &VoltConfig) -> Result<In
     // Get "version"
     let input = Input {
         message: "version".into(),
-        default: Some("1.0.0".into()),
+        default: Some(
+            take_string(&mut existing, "version")
+                .unwrap_or_else(|| "1.0.0".to_string())
+                .into(),
+        ),
         allow_empty: false,
     };
 
-    let version = input.run().into_diagnostic()?;
+    let mut version;
+    loop {
+        version = input.run().into_diagnostic()?;
+
+        if version.parse::<Version>().is_ok() {
+            break;
+        }
+
+        println!("{}", "Version must be a valid semver version".red());
+    }
 
     // Get "description"
     let input = Input {
         message: "description".into(),
-        default: None,
+        default: take_string(&mut existing, "description").map(Into::into),
         allow_empty: true,
     };
 
@@ -164,43 +252,68 @@ fn manual_initialization(default_name: String, config: &VoltConfig) -> Result<In
 
     // Get "main"
     let input = Input {
-        message: "main".into(),
-        default: Some("index.js".into()),
+        message: "entry point".into(),
+        default: Some(
+            take_string(&mut existing, "main")
+                .unwrap_or_else(|| "index.js".to_string())
+                .into(),
+        ),
         allow_empty: false,
     };
 
     let main = input.run().into_diagnostic()?;
 
     // Get "author"
-    let git_user_name = utils::get_git_config(config, "user.name")?;
-
-    let git_email = utils::get_git_config(config, "user.email")?;
+    let default_author = match take_string(&mut existing, "author") {
+        Some(author) => Some(author),
+        None => git_author(config)?,
+    };
 
     let input = Input {
         message: "author".into(),
-        default: git_user_name
-            .zip(git_email)
-            .map(|(git_user_name, git_email)| format!("{} <{}>", git_user_name, git_email).into()),
+        default: default_author.map(Into::into),
         allow_empty: true,
     };
 
     let author = input.run().into_diagnostic()?;
 
     // Get "license"
+    let default_license = take_license(&mut existing);
+
+    let mut items: Vec<_> = License::OPTIONS.iter().map(|&l| l.into()).collect();
+
+    // Offer to keep a license we don't know as the last item, past the end of `License::OPTIONS`
+    if let Some(kept) = existing.get("license") {
+        let kept = kept.as_str().map_or_else(|| kept.to_string(), String::from);
+        items.push(format!("Keep \"{}\"", kept).into());
+    }
+
+    let selected = match &default_license {
+        Some(default_license) => License::OPTIONS
+            .iter()
+            .position(|&l| l == default_license.as_str()),
+        None => Some(items.len() - 1),
+    };
+
     let select = Select {
         message: "License".into(),
         paged: true,
-        selected: Some(1),
-        items: License::OPTIONS.iter().map(|&l| l.into()).collect(),
+        selected: selected.map(|i| i + 1),
+        items,
     };
 
-    select.run().into_diagnostic()?;
+    let license = License::from_index(select.run().into_diagnostic()?);
 
-    let license = License::from_index(select.selected.unwrap()).unwrap();
+    if license.is_some() {
+        existing.remove("license");
+    }
 
     let input = Confirm {
         message: "private".into(),
-        default: false,
+        default: existing
+            .remove("private")
+            .and_then(|p| p.as_bool())
+            .unwrap_or(false),
     };
 
     let private = input.run().into_diagnostic()?;
@@ -208,10 +321,11 @@ fn manual_initialization(default_name: String, config: &VoltConfig) -> Result<In
     Ok(InitData {
         name,
         version,
-        description: Some(description),
+        description: Some(description).filter(|d| !d.is_empty()),
         main,
-        author: Some(author),
+        author: Some(author).filter(|a| !a.is_empty()),
         license,
         private: Some(private),
+        rest: existing,
     })
 }
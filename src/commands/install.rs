@@ -14,38 +14,91 @@
     limitations under the License.
 */
 
-//! Installs dependencies for a project.
+//! Install the dependencies of a project.
 
-use crate::cli::{VoltCommand, VoltConfig};
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    commands::add::install_packages,
+    core::model::lock_file::LockFile,
+    core::utils::{errors::VoltError, package::PackageJson},
+};
 
 use async_trait::async_trait;
+use clap::Parser;
 use colored::Colorize;
 use miette::Result;
+use node_semver::{Range, Version};
+use package_spec::PackageSpec;
 
-use std::sync::Arc;
-
-/// Struct implementation for the `Install` command.
-pub struct Install;
+/// Install the dependencies in package.json, at their locked versions where there are any
+#[derive(Debug, Parser)]
+pub struct Install {
+    /// Resolve and print the packages that would change, without installing them
+    #[clap(long)]
+    dry_run: bool,
+}
 
 #[async_trait]
 impl VoltCommand for Install {
     /// Execute the `volt install` command
     ///
-    /// Install dependencies for a project.
+    /// Install the `dependencies` and `devDependencies` of a project. Dependencies keep the
+    /// version in volt.lock while it's still in their range, the rest are resolved again.
     /// ## Arguments
-    /// * `app` - Instance of the command (`Arc<App>`)
-    /// * `packages` - List of packages to add (`Vec<String>`)
-    /// * `flags` - List of flags passed in through the CLI (`Vec<String>`)
+    /// * `config` - The global volt configuration (`VoltConfig`)
     /// ## Examples
     /// ```
-    /// // Install dependencies for a project with logging level verbose
+    /// // Restore node_modules from package.json and volt.lock
     /// // .exec() is an async call so you need to await it
-    /// Install.exec(app, vec![], vec!["--verbose"]).await;
+    /// Install { dry_run: false }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
     async fn exec(self, config: VoltConfig) -> Result<()> {
-        // let package_file
+        let config = config.with_dry_run(self.dry_run);
+
+        let (package_file, _) = PackageJson::get_from_dir(&config.cwd()?)?;
+
+        let lock_file = LockFile::load(config.lockfile()?, false)?;
+
+        let specs = package_file
+            .dependencies
+            .iter()
+            .chain(package_file.dev_dependencies.iter())
+            .flatten()
+            .map(|(name, range)| {
+                let spec = format!("{}@{}", name, locked_version(&lock_file, name, range));
+
+                spec.parse::<PackageSpec>()
+                    .map_err(|_| VoltError::PackageSpecificationError { spec })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if specs.is_empty() {
+            println!("{}", "There are no dependencies to install".bright_green());
+            return Ok(());
+        }
+
+        install_packages(&config, &specs, false).await?;
+
         Ok(())
     }
 }
+
+/// The version of `name` to install: the locked one if it's still in `range`, otherwise the range
+fn locked_version<'a>(lock_file: &'a LockFile, name: &str, range: &'a str) -> &'a str {
+    // conflicted entries are re-resolved against package.json
+    if lock_file.conflicts.contains_key(name) {
+        return range;
+    }
+
+    let locked = match lock_file.direct.get(name) {
+        Some(locked) => locked,
+        None => return range,
+    };
+
+    match (range.parse::<Range>(), locked.parse::<Version>()) {
+        (Ok(parsed), Ok(version)) if parsed.satisfies(&version) => locked,
+        _ => range,
+    }
+}
@@ -0,0 +1,205 @@
+/*
+    Copyright 2021, 2022 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Report the licenses of installed packages.
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::model::{
+        license::{collect_licenses, LicenseSource, PackageLicense},
+        lock_file::LockFile,
+    },
+    core::output::print_json,
+    core::utils::package::PackageJson,
+};
+
+use async_trait::async_trait;
+use clap::{Args, Parser, Subcommand};
+use colored::Colorize;
+use comfy_table::{
+    modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL, Attribute, Cell, CellAlignment,
+    ContentArrangement, Table,
+};
+use miette::Result;
+use std::collections::BTreeMap;
+
+/// Report the licenses of installed packages
+#[derive(Debug, Parser)]
+pub struct Licenses {
+    #[clap(subcommand)]
+    command: LicensesCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum LicensesCommand {
+    /// List every installed package grouped by license
+    #[clap(alias = "ls")]
+    List(ReportOptions),
+    /// Count the installed packages using each license
+    Summary(ReportOptions),
+}
+
+#[derive(Debug, Args)]
+struct ReportOptions {
+    /// Skip devDependencies
+    #[clap(long)]
+    production: bool,
+
+    /// Print the report as CSV
+    #[clap(long)]
+    csv: bool,
+}
+
+#[async_trait]
+impl VoltCommand for Licenses {
+    /// Execute the `volt licenses` command
+    ///
+    /// Collect the license of every installed package from its package.json, falling back to
+    /// its LICENSE file, and report them grouped by SPDX identifier.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Export the licenses of production dependencies for a compliance review
+    /// // .exec() is an async call so you need to await it
+    /// Licenses { command: LicensesCommand::List(ReportOptions { production: true, csv: true }) }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let options = match &self.command {
+            LicensesCommand::List(options) | LicensesCommand::Summary(options) => options,
+        };
+
+        let project_dir = config.cwd()?;
+
+        let (package_file, _) = PackageJson::get_from_dir(&project_dir)?;
+
+        let lock_file = LockFile::load(config.lockfile()?, false)?;
+
+        let mut manifests = vec![package_file.clone()];
+
+        manifests.extend(
+            package_file
+                .workspace_packages(&project_dir)?
+                .into_iter()
+                .map(|(_, manifest)| manifest),
+        );
+
+        let licenses = collect_licenses(
+            &lock_file,
+            &manifests,
+            &config.node_modules()?,
+            options.production,
+        );
+
+        let mut groups: BTreeMap<&str, Vec<&PackageLicense>> = BTreeMap::new();
+
+        for license in &licenses {
+            groups
+                .entry(license.license.as_str())
+                .or_default()
+                .push(license);
+        }
+
+        match &self.command {
+            LicensesCommand::List(_) if config.json() => {
+                print_json(&licenses)?;
+            }
+            LicensesCommand::List(_) if options.csv => {
+                println!("name,version,license,source");
+
+                for license in &licenses {
+                    println!(
+                        "{},{},{},{}",
+                        csv_field(&license.name),
+                        csv_field(&license.version),
+                        csv_field(&license.license),
+                        license.source.as_str()
+                    );
+                }
+            }
+            LicensesCommand::List(_) => {
+                for (license, packages) in &groups {
+                    println!("{} ({})", license.bold(), packages.len());
+
+                    for package in packages {
+                        let note = if package.source == LicenseSource::File {
+                            " (from license file)".truecolor(156, 156, 156).to_string()
+                        } else {
+                            String::new()
+                        };
+
+                        println!("  {}@{}{}", package.name, package.version, note);
+                    }
+                }
+            }
+            LicensesCommand::Summary(_) => {
+                let mut counts = groups
+                    .iter()
+                    .map(|(license, packages)| (*license, packages.len()))
+                    .collect::<Vec<_>>();
+
+                counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+                if config.json() {
+                    let counts = counts.into_iter().collect::<BTreeMap<_, _>>();
+
+                    print_json(&counts)?;
+                } else if options.csv {
+                    println!("license,packages");
+
+                    for (license, count) in counts {
+                        println!("{},{}", csv_field(license), count);
+                    }
+                } else {
+                    let mut table = Table::new();
+
+                    table
+                        .load_preset(UTF8_FULL)
+                        .apply_modifier(UTF8_ROUND_CORNERS)
+                        .set_content_arrangement(ContentArrangement::Dynamic);
+
+                    table.set_header(
+                        ["License", "Packages"]
+                            .iter()
+                            .map(|header| Cell::new(header).add_attribute(Attribute::Bold)),
+                    );
+
+                    for (license, count) in counts {
+                        table.add_row(vec![
+                            Cell::new(license),
+                            Cell::new(count).set_alignment(CellAlignment::Right),
+                        ]);
+                    }
+
+                    println!("{}", table);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Quote a CSV field if it contains a separator, quote or newline
+fn csv_field(value: &str) -> String {
+    if value.contains(|c: char| matches!(c, ',' | '"' | '\n')) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,238 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Link local packages into other projects while developing them.
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::{
+        model::lock_file::LockFile,
+        utils::{
+            create_link, is_on_path, link_bins, link_package, package::PackageJson, read_bins,
+            remove_link, unlink_bins,
+        },
+    },
+};
+
+use async_trait::async_trait;
+use clap::Parser;
+use colored::Colorize;
+use miette::Result;
+
+/// Register the current package for linking, or link registered packages into this project
+#[derive(Debug, Parser)]
+pub struct Link {
+    /// Registered packages to link into node_modules, registers the current package if empty
+    packages: Vec<String>,
+}
+
+/// Undo `volt link`, restoring the installed versions of linked packages
+#[derive(Debug, Parser)]
+pub struct Unlink {
+    /// Linked packages to remove from node_modules, unregisters the current package if empty
+    packages: Vec<String>,
+}
+
+#[async_trait]
+impl VoltCommand for Link {
+    /// Execute the `volt link` command
+    ///
+    /// Without arguments, register the current package in `~/.volt/links` and link its
+    /// executables globally. With package names, symlink those registered packages into
+    /// `node_modules` so changes to them are picked up without reinstalling.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // In the app, use the local copy of `my-lib` registered with `volt link`
+    /// // .exec() is an async call so you need to await it
+    /// Link { packages: vec!["my-lib".into()] }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let links_dir = config.links_dir()?;
+
+        if self.packages.is_empty() {
+            let project_dir = config.cwd()?;
+            let (manifest, _) = PackageJson::get_from_dir(&project_dir)?;
+
+            let link = links_dir.join(&manifest.name);
+
+            create_link(&project_dir, &link)?;
+
+            println!(
+                "{} {} -> {}",
+                "Registered".bright_green().bold(),
+                manifest.name,
+                project_dir.display()
+            );
+
+            let bin_dir = config.global_bin()?;
+            let commands = link_bins(&bin_dir, read_bins(&link, &manifest.name)?, &link)?;
+
+            for command in &commands {
+                println!("{} {}", "Linked".bright_green().bold(), command);
+            }
+
+            if !commands.is_empty() && !is_on_path(&bin_dir) {
+                println!(
+                    "{}: {} is not on your PATH, add it to run linked executables",
+                    "warning".yellow().bold(),
+                    bin_dir.display()
+                );
+            }
+
+            println!(
+                "Run {} in a project to use it",
+                format!("volt link {}", manifest.name).bright_cyan()
+            );
+
+            return Ok(());
+        }
+
+        let node_modules = config.node_modules()?;
+
+        for package in &self.packages {
+            let registered = links_dir.join(package);
+
+            let target = std::fs::read_link(&registered).map_err(|_| {
+                miette::miette!(
+                    "{} is not registered, run `volt link` in its directory first",
+                    package
+                )
+            })?;
+
+            if !target.join("package.json").exists() {
+                miette::bail!(
+                    "{} was registered from {}, which no longer has a package.json",
+                    package,
+                    target.display()
+                );
+            }
+
+            create_link(&target, &node_modules.join(package))?;
+
+            link_bins(
+                &node_modules.join(".bin"),
+                read_bins(&target, package)?,
+                &target,
+            )?;
+
+            println!(
+                "{} {} -> {}",
+                "Linked".bright_green().bold(),
+                package,
+                target.display()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl VoltCommand for Unlink {
+    /// Execute the `volt unlink` command
+    ///
+    /// Without arguments, unregister the current package and its global executables. With
+    /// package names, remove their links from `node_modules` and relink the versions in the
+    /// lock file.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Go back to the published `my-lib`
+    /// // .exec() is an async call so you need to await it
+    /// Unlink { packages: vec!["my-lib".into()] }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        if self.packages.is_empty() {
+            let (manifest, _) = PackageJson::get_from_dir(&config.cwd()?)?;
+
+            let link = config.links_dir()?.join(&manifest.name);
+
+            if link.symlink_metadata().is_err() {
+                println!("{} is not registered", manifest.name);
+                return Ok(());
+            }
+
+            unlink_bins(
+                &config.global_bin()?,
+                read_bins(&link, &manifest.name)?,
+                &link,
+            )?;
+
+            remove_link(&link)?;
+
+            println!("{} {}", "Unregistered".bright_green().bold(), manifest.name);
+
+            return Ok(());
+        }
+
+        let node_modules = config.node_modules()?;
+        let bin_dir = node_modules.join(".bin");
+
+        let lock_file = LockFile::load(config.lockfile()?, false)?;
+
+        for package in &self.packages {
+            let link = node_modules.join(package);
+
+            if link.symlink_metadata().is_ok() {
+                unlink_bins(&bin_dir, read_bins(&link, package)?, &link)?;
+                remove_link(&link)?;
+            }
+
+            let installed = lock_file
+                .direct
+                .get(package)
+                .and_then(|version| {
+                    lock_file
+                        .dependencies
+                        .get(&format!("{}@{}", package, version))
+                })
+                .filter(|installed| installed.package_directory(&node_modules).exists());
+
+            match installed {
+                Some(installed) => {
+                    link_package(&config, installed)?;
+                    link_bins(
+                        &bin_dir,
+                        installed.bins(),
+                        &installed.package_directory(&node_modules),
+                    )?;
+
+                    println!(
+                        "{} {}@{}",
+                        "Restored".bright_green().bold(),
+                        package,
+                        installed.version
+                    );
+                }
+                None => println!(
+                    "{} {}, run {} to install it from the registry",
+                    "Unlinked".bright_green().bold(),
+                    package,
+                    "volt install".bright_cyan()
+                ),
+            }
+        }
+
+        Ok(())
+    }
+}
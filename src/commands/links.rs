@@ -0,0 +1,294 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Open the repository, documentation or issue tracker of a package.
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::net::get_registry_document,
+};
+
+use async_trait::async_trait;
+use clap::{Args, Parser};
+use colored::Colorize;
+use miette::{IntoDiagnostic, Result};
+use serde_json::Value;
+
+/// Open the source repository of a package in your browser
+#[derive(Debug, Parser)]
+pub struct Repo {
+    #[clap(flatten)]
+    options: LinkOptions,
+}
+
+/// Open the homepage of a package in your browser
+#[derive(Debug, Parser)]
+pub struct Docs {
+    #[clap(flatten)]
+    options: LinkOptions,
+}
+
+/// Open the issue tracker of a package in your browser
+#[derive(Debug, Parser)]
+pub struct Bugs {
+    #[clap(flatten)]
+    options: LinkOptions,
+}
+
+#[derive(Debug, Args)]
+struct LinkOptions {
+    /// The package, the current package by default
+    package: Option<String>,
+
+    /// Print the URL instead of opening it
+    #[clap(long)]
+    print: bool,
+}
+
+#[async_trait]
+impl VoltCommand for Repo {
+    /// Execute the `volt repo` command
+    ///
+    /// Open the `repository` of a package, preferring the installed copy over the registry.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Open the repository of react
+    /// // .exec() is an async call so you need to await it
+    /// Repo { options: LinkOptions { package: Some("react".into()), print: false } }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let manifest = load_manifest(&config, self.options.package.as_deref()).await?;
+
+        let url = repository_url(&manifest)
+            .ok_or_else(|| miette::miette!("{} has no repository", package_name(&manifest)))?;
+
+        open(&url, self.options.print)
+    }
+}
+
+#[async_trait]
+impl VoltCommand for Docs {
+    /// Execute the `volt docs` command
+    ///
+    /// Open the `homepage` of a package, falling back to the readme of its repository.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Print the homepage of the current package
+    /// // .exec() is an async call so you need to await it
+    /// Docs { options: LinkOptions { package: None, print: true } }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let manifest = load_manifest(&config, self.options.package.as_deref()).await?;
+
+        let url = manifest["homepage"]
+            .as_str()
+            .map(String::from)
+            .or_else(|| repository_url(&manifest).map(|url| format!("{}#readme", url)))
+            .ok_or_else(|| miette::miette!("{} has no homepage", package_name(&manifest)))?;
+
+        open(&url, self.options.print)
+    }
+}
+
+#[async_trait]
+impl VoltCommand for Bugs {
+    /// Execute the `volt bugs` command
+    ///
+    /// Open the `bugs` URL of a package, falling back to the issues of its repository.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Report a bug in react
+    /// // .exec() is an async call so you need to await it
+    /// Bugs { options: LinkOptions { package: Some("react".into()), print: false } }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let manifest = load_manifest(&config, self.options.package.as_deref()).await?;
+
+        // `bugs` is either a URL or `{ "url": ..., "email": ... }`
+        let url = manifest["bugs"]
+            .as_str()
+            .or_else(|| manifest["bugs"]["url"].as_str())
+            .map(String::from)
+            .or_else(|| repository_url(&manifest).map(|url| format!("{}/issues", url)))
+            .ok_or_else(|| miette::miette!("{} has no bug tracker", package_name(&manifest)))?;
+
+        open(&url, self.options.print)
+    }
+}
+
+/// Read the package.json of the current package, an installed package, or the latest
+/// published version when it isn't installed
+async fn load_manifest(config: &VoltConfig, package: Option<&str>) -> Result<Value> {
+    let path = match package {
+        Some(package) => config.node_modules()?.join(package).join("package.json"),
+        None => config.cwd()?.join("package.json"),
+    };
+
+    if path.exists() {
+        let contents = std::fs::read_to_string(&path).into_diagnostic()?;
+
+        return serde_json::from_str(&contents).into_diagnostic();
+    }
+
+    let package = match package {
+        Some(package) => package,
+        None => miette::bail!("no package.json found in the current directory"),
+    };
+
+    let mut packument = get_registry_document(&reqwest::Client::new(), package).await?;
+
+    let latest = packument["dist-tags"]["latest"]
+        .as_str()
+        .ok_or_else(|| miette::miette!("{} has no latest version", package))?
+        .to_string();
+
+    Ok(packument["versions"][&latest].take())
+}
+
+fn package_name(manifest: &Value) -> &str {
+    manifest["name"].as_str().unwrap_or("the package")
+}
+
+/// The browsable URL of the `repository` field, which can be a URL, a shorthand like
+/// `github:user/repo` or `{ "type": "git", "url": ..., "directory": ... }`
+fn repository_url(manifest: &Value) -> Option<String> {
+    let repository = &manifest["repository"];
+
+    let url = repository.as_str().or_else(|| repository["url"].as_str())?;
+
+    let mut url = browsable_url(url)?;
+
+    if let Some(directory) = repository["directory"].as_str() {
+        if url.starts_with("https://github.com/") {
+            url = format!("{}/tree/HEAD/{}", url, directory.trim_matches('/'));
+        }
+    }
+
+    Some(url)
+}
+
+fn browsable_url(url: &str) -> Option<String> {
+    let url = url.trim().trim_end_matches('/');
+    let url = url.strip_suffix(".git").unwrap_or(url);
+
+    for (shorthand, host) in [
+        ("github:", "github.com"),
+        ("gitlab:", "gitlab.com"),
+        ("bitbucket:", "bitbucket.org"),
+        ("gist:", "gist.github.com"),
+    ] {
+        if let Some(path) = url.strip_prefix(shorthand) {
+            return Some(format!("https://{}/{}", host, path));
+        }
+    }
+
+    // `git@github.com:user/repo`
+    if let Some(rest) = url.strip_prefix("git@") {
+        let (host, path) = rest.split_once(':')?;
+
+        return Some(format!("https://{}/{}", host, path));
+    }
+
+    if let Some((scheme, rest)) = url.split_once("://") {
+        if !matches!(
+            scheme,
+            "git" | "git+https" | "git+ssh" | "ssh" | "https" | "http"
+        ) {
+            return None;
+        }
+
+        // drop any `user@`, and the `:port` of ssh URLs unless it's really `host:user/repo`
+        let rest = rest.split_once('@').map_or(rest, |(_, rest)| rest);
+        let (host, path) = rest.split_once('/')?;
+
+        return Some(match host.split_once(':') {
+            Some((host, port)) if port.parse::<u16>().is_err() => {
+                format!("https://{}/{}/{}", host, port, path)
+            }
+            Some((host, _)) => format!("https://{}/{}", host, path),
+            None => format!("https://{}/{}", host, path),
+        });
+    }
+
+    // `user/repo` is shorthand for GitHub
+    if url.matches('/').count() == 1 && !url.contains(':') {
+        return Some(format!("https://github.com/{}", url));
+    }
+
+    None
+}
+
+fn open(url: &str, print: bool) -> Result<()> {
+    if print {
+        println!("{}", url);
+        return Ok(());
+    }
+
+    match webbrowser::open(url) {
+        Ok(_) => println!("Opened {}", url.bright_purple().underline()),
+        Err(_) => println!(
+            "Failed to open your default browser, visit {} instead",
+            url.bright_purple().underline()
+        ),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repository_urls_are_browsable() {
+        for (url, expected) in [
+            (
+                "git+https://github.com/facebook/react.git",
+                "https://github.com/facebook/react",
+            ),
+            (
+                "git@github.com:facebook/react.git",
+                "https://github.com/facebook/react",
+            ),
+            (
+                "git+ssh://git@gitlab.com:2222/org/repo.git",
+                "https://gitlab.com/org/repo",
+            ),
+            (
+                "git+ssh://git@github.com:facebook/react.git",
+                "https://github.com/facebook/react",
+            ),
+            ("github:facebook/react", "https://github.com/facebook/react"),
+            ("facebook/react", "https://github.com/facebook/react"),
+        ] {
+            assert_eq!(browsable_url(url).as_deref(), Some(expected));
+        }
+
+        assert_eq!(browsable_url("file:../react"), None);
+    }
+}
@@ -14,26 +14,45 @@
     limitations under the License.
 */
 
-//! Compress node_modules into node_modules.pack.
-use std::sync::Arc;
+//! List the packages installed in a project.
+
+use std::collections::BTreeMap;
 
 use async_trait::async_trait;
-use clap::{ArgMatches, Parser, Subcommand};
+use clap::Parser;
 use colored::Colorize;
-use miette::Result;
-//use node_semver::Version;
-use serde::{Deserialize, Deserializer};
-use serde_json;
+use miette::{IntoDiagnostic, Result};
+use regex::Regex;
+use serde::Serialize;
 
 use crate::{
     cli::{VoltCommand, VoltConfig},
-    core::utils::extensions::PathExtensions,
-    core::utils::package::PackageJson,
+    core::model::lock_file::{DependencyKind, LockFile},
+    core::output::print_json,
+    core::utils::{package::PackageJson, voltapi::VoltPackage},
 };
 
+/// List installed packages as a dependency tree
 #[derive(Debug, Parser)]
 pub struct List {
+    /// Only show packages whose name matches one of these patterns (`react*`)
+    patterns: Vec<String>,
+
+    /// Maximum depth of the tree (defaults to 0, or unlimited when filtering by pattern)
+    #[clap(long)]
     depth: Option<usize>,
+
+    /// Only show packages from `dependencies`
+    #[clap(long, conflicts_with = "dev")]
+    prod: bool,
+
+    /// Only show packages from `devDependencies`
+    #[clap(long)]
+    dev: bool,
+
+    /// List globally installed packages
+    #[clap(short, long)]
+    global: bool,
 }
 
 // CREDIT:
@@ -62,192 +81,242 @@ pub static ASCII_SYMBOLS: Symbols = Symbols {
 };
 // ------------------------------------------------
 
+/// A package in the rendered dependency tree
+#[derive(Debug, Serialize)]
+pub struct TreeNode {
+    pub version: String,
+    #[serde(rename = "type")]
+    pub kind: DependencyKind,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub missing: bool,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub dependencies: BTreeMap<String, TreeNode>,
+}
+
+#[derive(Debug, Serialize)]
+struct ProjectTree<'a> {
+    name: &'a str,
+    version: &'a str,
+    dependencies: &'a BTreeMap<String, TreeNode>,
+}
+
 #[async_trait]
 impl VoltCommand for List {
     /// Execute the `volt list` command
     ///
-    /// List node_modules into node_modules.pack.
+    /// List the packages installed in a project as a tree, built from the lockfile.
     /// ## Arguments
-    /// * `app` - Instance of the command (`Arc<App>`)
+    /// * `config` - The global volt configuration (`VoltConfig`)
     /// ## Examples
     /// ```
-    /// // List node_modules into node_modules.pack
+    /// // List every installed package matching `react*`
     /// // .exec() is an async call so you need to await it
-    /// Add.exec(app).await;
+    /// List { patterns: vec!["react*".into()], ..list }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
     async fn exec(self, config: VoltConfig) -> Result<()> {
         let symbols = &UTF8_SYMBOLS;
 
-        // grab the project's package.json file to get primary dependencies
-        let (pkg_json, pkg_json_path) = match PackageJson::get() {
-            Ok(p) => (Some(p.0), Some(p.1)),
-            Err(_) => (None, None),
+        // global packages are recorded in `~/.volt/global/package.json`
+        let config = if self.global {
+            config.with_cwd(config.global_dir()?)
+        } else {
+            config
+        };
+
+        let project_dir = config.cwd()?;
+
+        let package_file = if self.global {
+            PackageJson::load_or_new(&project_dir, "volt-global")?
+        } else {
+            match PackageJson::get_from_dir(&project_dir) {
+                Ok((package_file, _)) => package_file,
+                Err(_) => {
+                    println!("Missing 'package.json' file!");
+                    return Ok(());
+                }
+            }
+        };
+
+        let lock_file = LockFile::load(config.lockfile()?, self.global)?;
+
+        let patterns = self
+            .patterns
+            .iter()
+            .map(|p| pattern_to_regex(p))
+            .collect::<Result<Vec<_>>>()?;
+
+        let filter = TreeFilter {
+            patterns: &patterns,
+            depth: self
+                .depth
+                .unwrap_or(if patterns.is_empty() { 0 } else { usize::MAX }),
         };
 
-        if let (Some(pkg_json), Some(pkg_json_path)) = (pkg_json, pkg_json_path) {
-            // package.json exists
-            let mut node_modules = pkg_json_path;
-
-            let project_info = format!(
-                "{}@{} {}",
-                &pkg_json.name,
-                &pkg_json.version,
-                &node_modules
-                    .parent()
-                    .unwrap()
-                    .canonicalize()
-                    .unwrap()
-                    .to_string_lossy()
-            );
-            println!("{}", project_info);
-
-            let last = pkg_json.dependencies.clone();
-
-            if let Some(packages) = pkg_json.dependencies {
-                node_modules.pop();
-                node_modules.push("node_modules");
-
-                // unsure if this is necessary?
-                if !packages.is_empty() {
-                    let last = last.unwrap().into_keys().last().unwrap();
-
-                    for package in packages {
-                        if node_modules.join(&package.0).exists() {
-                            let base_pkg =
-                                PackageJson::get_from_dir(&node_modules.join(&package.0))
-                                    .unwrap()
-                                    .0;
-                            // let current: Version = base_pkg.version.parse().unwrap();
-                            let current = base_pkg.version;
-
-                            let output = format!(
-                                "{}@{}",
-                                &base_pkg.name.truecolor(000, 255, 000),
-                                &current.to_string().truecolor(000, 155, 000),
-                            );
-
-                            if last.eq(&package.0) {
-                                println!("{}{} {}\n", symbols.ell, symbols.right, output);
-                            } else {
-                                println!("{}{} {}", symbols.tee, symbols.right, output);
-                            }
-                        } else {
-                            let output = format!(
-                                "{} {}@{}",
-                                "MISSING".to_string().truecolor(255, 000, 000),
-                                &package.0.truecolor(000, 255, 000),
-                                &package.1.truecolor(000, 155, 000)
-                            );
-
-                            if last.eq(&package.0) {
-                                println!("{}{} {}\n", symbols.ell, symbols.right, output);
-                            } else {
-                                println!("{}{} {}", symbols.tee, symbols.right, output);
-                            }
-                        }
+        let mut tree = BTreeMap::new();
+
+        for (kind, requested, package) in lock_file.roots(&package_file) {
+            if (self.prod && kind != DependencyKind::Prod)
+                || (self.dev && kind != DependencyKind::Dev)
+            {
+                continue;
+            }
+
+            match package {
+                Some(package) => {
+                    let mut stack = vec![];
+
+                    if let Some(node) =
+                        build_node(&lock_file, package, kind, filter.depth, &filter, &mut stack)
+                    {
+                        tree.insert(package.name.clone(), node);
+                    }
+                }
+                None => {
+                    let (name, range) = requested
+                        .rsplit_once('@')
+                        .unwrap_or((requested.as_str(), ""));
+
+                    if filter.matches(name) {
+                        tree.insert(
+                            name.to_string(),
+                            TreeNode {
+                                version: range.to_string(),
+                                kind,
+                                missing: true,
+                                dependencies: BTreeMap::new(),
+                            },
+                        );
                     }
                 }
-            } else {
-                // package.json exists without any packages
-                let output = format!("(No dependencies)");
-                println!("{}{} {}\n", symbols.ell, symbols.right, output);
             }
-        } else {
-            // package.json just doesn't exist
-            let output = format!("Missing 'package.json' file!");
-            println!("{}", output);
         }
 
-        // let flags = &app.flags;
-
-        // let mut depth: u64 = 2;
-
-        // // if flags.contains(&"--depth".to_string()) {
-        // //     depth = app.args.iter().find_map(|s| s.parse().ok()).unwrap_or(2);
-        // // }
-
-        // let dirs = WalkDir::new("node_modules");
-
-        // let dependency_paths: Vec<_> = dirs
-        //     .into_iter()
-        //     .filter_map(Result::ok)
-        //     .filter(|entry| entry.file_type().is_dir() || entry.file_type().is_symlink())
-        //     .collect();
-
-        // if dependency_paths.len() == 1 {
-        //     println!("{}", "No Dependencies Found!".bright_cyan());
-        //     return Ok(());
-        // } else if dependency_paths.is_empty() {
-        //     println!(
-        //         "{} {} {}",
-        //         "Failed to find".bright_cyan(),
-        //         "node_modules".bright_yellow().bold(),
-        //         "folder".bright_cyan(),
-        //     );
-        //     return Ok(());
-        // }
-
-        // for dep in dependency_paths {
-        //     let dep_path = dep.path().to_str().unwrap();
-        //     let dep_path_split: Vec<&str> = dep_path.split('\\').collect();
-        //     let dep_name: &str = dep_path_split[dep_path_split.len() - 1];
-        //     if dep_name != "node_modules"
-        //         && dep_name != "scripts"
-        //         && !dep_name.starts_with("node_modules")
-        //     {
-        //         println!("{} {}", "-".bright_cyan(), dep_name.bright_blue().bold());
-        //         let dirs = WalkDir::new(format!("node_modules/{}/node_modules", dep_name))
-        //             .follow_links(true)
-        //             .max_depth((depth - 1) as usize);
-        //         let dependency_paths: Vec<_> = dirs
-        //             .into_iter()
-        //             .filter_map(Result::ok)
-        //             .filter(|entry| entry.file_type().is_dir() || entry.file_type().is_symlink())
-        //             .collect();
-
-        //         for dep in dependency_paths {
-        //             let dep_path = dep.path().to_str().unwrap();
-        //             let dep_path_split: Vec<&str> = dep_path.split('\\').collect();
-        //             let dep_name: &str = dep_path_split[dep_path_split.len() - 1];
-        //             if dep_name != "node_modules"
-        //                 && dep_name != "scripts"
-        //                 && !dep_path.contains("lib")
-        //                 && !dep_path.contains("src")
-        //                 && !dep_path.contains("dist")
-        //                 && !dep_path.contains("test")
-        //                 && !dep_name.starts_with("node_modules")
-        //             {
-        //                 for _ in 0..dep_path_split.len() {
-        //                     print!("  ");
-        //                 }
-        //                 let mut version = "".to_owned();
-        //                 for file in read_dir(std::env::temp_dir().join("volt")).unwrap() {
-        //                     let file_path: PathBuf = file.unwrap().path();
-        //                     let file_name: &str = file_path.to_str().unwrap();
-        //                     let file_split: Vec<&str> = file_name.split('\\').collect();
-        //                     let name: &str = file_split[file_split.len() - 1];
-        //                     if name.starts_with(dep_name) {
-        //                         let file_split: Vec<&str> = name.split('@').collect();
-        //                         let file_end = file_split[1];
-        //                         let file_split: Vec<&str> = file_end.split(".tgz").collect();
-        //                         version = file_split[0].to_owned();
-        //                     }
-        //                 }
-        //                 let padding = 50 - (dep_path_split.len() * 2);
-        //                 print!(
-        //                     "{} {:<width$}",
-        //                     "-".bright_purple(),
-        //                     dep_name,
-        //                     width = padding
-        //                 );
-        //                 println!("{}", version.clone().truecolor(190, 190, 190));
-        //             }
-        //         }
-        //     }
-        // }
+        if config.json() {
+            let project = ProjectTree {
+                name: &package_file.name,
+                version: &package_file.version,
+                dependencies: &tree,
+            };
+
+            print_json(&project)?;
+
+            return Ok(());
+        }
+
+        println!(
+            "{}@{} {}",
+            package_file.name,
+            package_file.version,
+            project_dir.display().to_string().truecolor(156, 156, 156)
+        );
+
+        if tree.is_empty() {
+            println!("{}{} (No dependencies)", symbols.ell, symbols.right);
+        } else {
+            print_tree(&tree, "", symbols);
+        }
 
         Ok(())
     }
 }
+
+/// Which packages of the tree are shown
+struct TreeFilter<'a> {
+    patterns: &'a [Regex],
+    depth: usize,
+}
+
+impl TreeFilter<'_> {
+    fn matches(&self, name: &str) -> bool {
+        self.patterns.is_empty() || self.patterns.iter().any(|p| p.is_match(name))
+    }
+}
+
+/// Convert a glob-like pattern (`react*`) into a regex matching package names
+fn pattern_to_regex(pattern: &str) -> Result<Regex> {
+    Regex::new(&format!(
+        "^{}$",
+        regex::escape(pattern).replace(r"\*", ".*")
+    ))
+    .into_diagnostic()
+}
+
+/// Build the subtree of a package, returning `None` if neither it nor any of its dependencies
+/// match the filter
+fn build_node(
+    lock_file: &LockFile,
+    package: &VoltPackage,
+    kind: DependencyKind,
+    depth: usize,
+    filter: &TreeFilter,
+    stack: &mut Vec<String>,
+) -> Option<TreeNode> {
+    let mut dependencies = BTreeMap::new();
+
+    let key = format!("{}@{}", package.name, package.version);
+
+    // stop at the depth limit and at circular dependencies
+    if depth > 0 && !stack.contains(&key) {
+        stack.push(key);
+
+        for (child_kind, child) in lock_file.children(package) {
+            if let Some(node) = build_node(lock_file, child, child_kind, depth - 1, filter, stack) {
+                dependencies.insert(child.name.clone(), node);
+            }
+        }
+
+        stack.pop();
+    }
+
+    if filter.matches(&package.name) || !dependencies.is_empty() {
+        Some(TreeNode {
+            version: package.version.clone(),
+            kind,
+            missing: false,
+            dependencies,
+        })
+    } else {
+        None
+    }
+}
+
+fn print_tree(nodes: &BTreeMap<String, TreeNode>, prefix: &str, symbols: &Symbols) {
+    for (index, (name, node)) in nodes.iter().enumerate() {
+        let last = index == nodes.len() - 1;
+
+        let label = if node.missing {
+            format!(
+                "{} {}@{}",
+                "MISSING".truecolor(255, 0, 0),
+                name.truecolor(0, 255, 0),
+                node.version.truecolor(0, 155, 0)
+            )
+        } else {
+            format!(
+                "{}@{}",
+                name.truecolor(0, 255, 0),
+                node.version.truecolor(0, 155, 0)
+            )
+        };
+
+        let kind = match node.kind {
+            DependencyKind::Prod => String::new(),
+            kind => format!(" ({})", kind).truecolor(156, 156, 156).to_string(),
+        };
+
+        println!(
+            "{}{}{} {}{}",
+            prefix,
+            if last { symbols.ell } else { symbols.tee },
+            symbols.right,
+            label,
+            kind
+        );
+
+        let child_prefix = format!("{}{}  ", prefix, if last { " " } else { symbols.down });
+
+        print_tree(&node.dependencies, &child_prefix, symbols);
+    }
+}
@@ -14,142 +14,498 @@
     limitations under the License.
 */
 
-//!  Migrates a package from your direct dependencies.
+//! Move a project from npm, yarn or pnpm to volt.
 
-use crate::cli::{VoltCommand, VoltConfig};
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    commands::add::install_resolved,
+    core::{
+        config::{read_file, write_file},
+        model::{
+            import::{import, ImportedLock, PackageManager},
+            lock_file::LockFile,
+        },
+        net::{get_version_manifests, set_registry, NPM_REGISTRY},
+        settings::Settings,
+        utils::{errors::VoltError, voltapi::VoltPackage},
+    },
+};
 
 use async_trait::async_trait;
+use clap::Parser;
 use colored::Colorize;
-use miette::Result;
+use miette::{IntoDiagnostic, Result};
+use serde_json::Value;
+use ssri::Integrity;
 
-use std::sync::Arc;
+use std::{collections::HashMap, fs, path::Path};
 
-/// Struct implementation for the `Migrate` command.
-pub struct Migrate;
+/// Migrate a project from npm, yarn or pnpm, keeping the versions in its lockfile
+#[derive(Debug, Parser)]
+pub struct Migrate {
+    /// The package manager to migrate from, required when lockfiles of several are present
+    #[clap(long, arg_enum)]
+    from: Option<PackageManager>,
+
+    /// Delete the old lockfile once volt.lock is written
+    #[clap(long)]
+    remove_lockfile: bool,
+
+    /// Only write volt.lock and volt.toml, without installing to verify them
+    #[clap(long)]
+    no_install: bool,
+}
 
 #[async_trait]
 impl VoltCommand for Migrate {
     /// Execute the `volt migrate` command
     ///
-    /// Migrates a project from yarn or npm to volt including lockfiles.
+    /// Import the lockfile of the project's current package manager into `volt.lock`, convert
+    /// the settings volt supports from `.npmrc` and `.yarnrc.yml` into `volt.toml`, then install
+    /// from the new lockfile to check that every direct dependency resolves to the same version.
     /// ## Arguments
-    /// * `app` - Instance of the command (`Arc<App>`)
-    /// * `package_manager_name` - The  (`String`)
-    /// * `flags` - List of flags passed in through the CLI (`Vec<String>`)
+    /// * `config` - The global volt configuration (`VoltConfig`)
     /// ## Examples
     /// ```
-    /// // Migrate a package from your direct dependencies with logging level verbose
+    /// // Migrate a yarn project and drop its yarn.lock
     /// // .exec() is an async call so you need to await it
-    /// Migrate.exec(app, vec![], vec!["--verbose"]).await;
+    /// Migrate { from: Some(PackageManager::Yarn), remove_lockfile: true, no_install: false }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
     async fn exec(self, config: VoltConfig) -> Result<()> {
-        // let packagemanagers: Vec<String> = PackageManager::options();
-        // let mut packagemanager: String = String::new();
-        // if app.args.len() == 1 {
-        //     packagemanager = app.args[0].to_string();
-        // } else if app.args.len() == 1 {
-        //     let select = Select {
-        //         message: String::from("Package Manager"),
-        //         paged: true,
-        //         selected: Some(1),
-        //         items: packagemanagers.clone(),
-        //     };
-        //     let selected = select.run().unwrap_or_else(|err| {
-        //         error!("{}", err.to_string());
-        //         process::exit(1);
-        //     });
-
-        //     packagemanager = PackageManager::from_index(selected).unwrap().to_string();
-        // } else {
-        //     error!("{}", "volt migrate only takes 1 argument");
-        // }
-
-        // if packagemanager.eq_ignore_ascii_case("volt") {
-        //     std::fs::remove_dir_all("node_modules").unwrap();
-
-        //     let files = fs::read_dir(env::current_dir().unwrap()).unwrap();
-        //     files
-        //         .filter_map(Result::ok)
-        //         .filter(|d| {
-        //             if let Some(e) = d.path().extension() {
-        //                 String::from(e.to_str().unwrap()).contains("lock")
-        //             } else {
-        //                 false
-        //             }
-        //         })
-        //         .for_each(|f| std::fs::remove_file(f.file_name()).unwrap());
-        //     println!("{}", "$ volt install".truecolor(147, 148, 148));
-        //     install::command::Install::exec(app).await?; // NOTE WILL ONLY WORK IF THE VAR DEPENDENCIES is populated
-        // } else if packagemanager.eq_ignore_ascii_case("yarn") {
-        //     std::fs::remove_dir_all("node_modules").unwrap();
-
-        //     let files = fs::read_dir(env::current_dir().unwrap()).unwrap();
-        //     files
-        //         .filter_map(Result::ok)
-        //         .filter(|d| {
-        //             if let Some(e) = d.path().extension() {
-        //                 e == "lock"
-        //             } else {
-        //                 false
-        //             }
-        //         })
-        //         .for_each(|f| std::fs::remove_file(f.file_name()).unwrap());
-
-        //     println!("{}", "$ yarn".truecolor(147, 148, 148));
-        //     std::process::Command::new("yarn")
-        //         .spawn()
-        //         .expect("failed to execute")
-        //         .wait()
-        //         .unwrap();
-        // } else if packagemanager.eq_ignore_ascii_case("pnpm") {
-        //     std::fs::remove_dir_all("node_modules").unwrap();
-
-        //     let files = fs::read_dir(env::current_dir().unwrap()).unwrap();
-        //     files
-        //         .filter_map(Result::ok)
-        //         .filter(|d| {
-        //             if let Some(e) = d.path().file_name() {
-        //                 String::from(e.to_str().unwrap()).contains("lock")
-        //             } else {
-        //                 false
-        //             }
-        //         })
-        //         .for_each(|f| std::fs::remove_file(f.file_name()).unwrap());
-
-        //     println!("{}", "$ pnpm install".truecolor(147, 148, 148));
-        //     std::process::Command::new("pnpm")
-        //         .arg("install")
-        //         .spawn()
-        //         .expect("failed to execute")
-        //         .wait()
-        //         .unwrap();
-        // } else if packagemanager.eq_ignore_ascii_case("npm") {
-        //     std::fs::remove_dir_all("node_modules").unwrap();
-
-        //     let files = fs::read_dir(env::current_dir().unwrap()).unwrap();
-        //     files
-        //         .filter_map(Result::ok)
-        //         .filter(|d| {
-        //             if let Some(e) = d.path().file_name() {
-        //                 String::from(e.to_str().unwrap()).contains("lock")
-        //             } else {
-        //                 false
-        //             }
-        //         })
-        //         .for_each(|f| std::fs::remove_file(f.file_name()).unwrap());
-
-        //     println!("{}", "$ npm install".truecolor(147, 148, 148));
-        //     std::process::Command::new("npm")
-        //         .arg("install")
-        //         .spawn()
-        //         .expect("failed to execute")
-        //         .wait()
-        //         .unwrap();
-        // } else {
-        //     println!("Volt accepts only volt, yarn, pnpm or npm for volt migrate's args it does not support {}" ,app.args[0].to_string().red());
-        // }
+        let project_dir = config.cwd()?;
+
+        let manager = match (self.from, PackageManager::detect(&project_dir).as_slice()) {
+            (Some(manager), _) => manager,
+            (None, [manager]) => *manager,
+            (None, []) => miette::bail!(
+                "no package-lock.json, npm-shrinkwrap.json, yarn.lock or pnpm-lock.yaml found in {}",
+                project_dir.display()
+            ),
+            (None, managers) => miette::bail!(
+                "found lockfiles of {}, pick one with `--from`",
+                managers
+                    .iter()
+                    .map(PackageManager::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+
+        let lockfile = manager.lockfile(&project_dir).ok_or_else(|| {
+            miette::miette!("no {} lockfile found in {}", manager, project_dir.display())
+        })?;
+
+        let lock_path = config.lockfile()?;
+
+        if lock_path.exists() {
+            miette::bail!(
+                "{} already exists, delete it to migrate again",
+                VoltConfig::VOLT_LOCK
+            );
+        }
+
+        let manifest: Value =
+            serde_json::from_str(&read(&project_dir.join("package.json"))?).into_diagnostic()?;
+
+        let imported = import(manager, &read(&project_dir.join(lockfile))?, &manifest)?;
+
+        println!(
+            "{} {} packages from {}",
+            "Imported".bright_green().bold(),
+            imported.packages.len().to_string().bright_cyan(),
+            lockfile
+        );
+
+        // the packages are completed from the registry the project's settings point at
+        migrate_settings(&project_dir)?;
+
+        let mut config = config;
+        config.load_options()?;
+        set_registry(config.registry());
+
+        let mut lock_file = LockFile::new(&lock_path, false);
+
+        lock_file.direct = imported.direct.clone();
+        lock_file.dependencies = complete_packages(&config, &imported, lockfile)
+            .await?
+            .into_iter()
+            .collect();
+
+        // keep the old lockfile around until the new one is known to install the same versions,
+        // and only leave volt.lock behind once it is
+        if self.no_install {
+            lock_file.save()?;
+        } else if let Err(error) = verify_install(&config, &lock_file, lockfile).await {
+            if lock_path.exists() {
+                fs::remove_file(&lock_path).into_diagnostic()?;
+            }
+
+            return Err(error);
+        }
+
+        println!(
+            "{} {}",
+            "Wrote".bright_green().bold(),
+            VoltConfig::VOLT_LOCK
+        );
+
+        if self.remove_lockfile {
+            fs::remove_file(project_dir.join(lockfile)).into_diagnostic()?;
+
+            println!("{} {}", "Removed".bright_green().bold(), lockfile);
+        }
+
         Ok(())
     }
 }
+
+/// Install from the new lockfile like `volt add` does and check every direct dependency has the
+/// imported version, which writes volt.lock
+async fn verify_install(config: &VoltConfig, lock_file: &LockFile, lockfile: &str) -> Result<()> {
+    let roots = lock_file
+        .direct
+        .iter()
+        .filter_map(|(name, version)| {
+            lock_file
+                .dependencies
+                .get(&format!("{}@{}", name, version))
+                .cloned()
+        })
+        .collect();
+
+    install_resolved(
+        config,
+        roots,
+        lock_file.dependencies.clone().into_iter().collect(),
+    )
+    .await?;
+
+    // packages that don't support this platform are skipped and left out of it
+    let written = LockFile::load(config.lockfile()?, false)?;
+
+    let node_modules = config.node_modules()?;
+    let mut mismatched = vec![];
+
+    for (name, version) in &lock_file.direct {
+        if !written
+            .dependencies
+            .contains_key(&format!("{}@{}", name, version))
+        {
+            continue;
+        }
+
+        let installed = fs::read_to_string(node_modules.join(name).join("package.json"))
+            .ok()
+            .and_then(|data| serde_json::from_str::<Value>(&data).ok())
+            .and_then(|manifest| manifest["version"].as_str().map(String::from));
+
+        if installed.as_deref() != Some(version.as_str()) {
+            mismatched.push((name, version, installed));
+        }
+    }
+
+    if !mismatched.is_empty() {
+        for (name, version, installed) in &mismatched {
+            eprintln!(
+                "{}: {} should be {} but {} is installed",
+                "error".bright_red().bold(),
+                name,
+                version,
+                installed.as_deref().unwrap_or("nothing")
+            );
+        }
+
+        miette::bail!(
+            "{} direct dependencies don't match {}",
+            mismatched.len(),
+            lockfile
+        );
+    }
+
+    println!(
+        "{} {} dependencies match {}",
+        "Verified".bright_green().bold(),
+        lock_file.direct.len().to_string().bright_cyan(),
+        lockfile
+    );
+
+    Ok(())
+}
+
+fn read(path: &Path) -> Result<String> {
+    Ok(
+        fs::read_to_string(path).map_err(|e| VoltError::ReadFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?,
+    )
+}
+
+/// Fill in what volt locks but other lockfiles don't record from the registry, keyed `name@version`
+async fn complete_packages(
+    config: &VoltConfig,
+    imported: &ImportedLock,
+    lockfile: &str,
+) -> Result<HashMap<String, VoltPackage>> {
+    let mut tree = imported
+        .packages
+        .iter()
+        .map(|(key, package)| {
+            let dependencies =
+                (!package.dependencies.is_empty()).then(|| package.dependencies.clone());
+
+            (
+                key.clone(),
+                VoltPackage {
+                    name: package.name.clone(),
+                    version: package.version.clone(),
+                    optional: package.optional,
+                    integrity: package.integrity.clone().unwrap_or_default(),
+                    tarball: String::new(),
+                    bin: None,
+                    scripts: None,
+                    dependencies,
+                    peer_dependencies: None,
+                    peer_dependencies_meta: None,
+                    optional_dependencies: None,
+                    overrides: None,
+                    engines: None,
+                    os: None,
+                    cpu: None,
+                },
+            )
+        })
+        .collect::<HashMap<_, _>>();
+
+    let manifests =
+        get_version_manifests(&reqwest::Client::new(), &tree, config.concurrency()).await?;
+
+    for (key, package) in tree.iter_mut() {
+        let manifest = match manifests.get(key) {
+            Some(manifest) => manifest,
+            None => continue,
+        };
+
+        if let Some(integrity) = &manifest.dist.integrity {
+            if !package.integrity.is_empty() && !same_content(&package.integrity, integrity) {
+                return Err(VoltError::LockfileIntegrityError {
+                    package: key.clone(),
+                    lockfile: lockfile.to_string(),
+                }
+                .into());
+            }
+
+            package.integrity = integrity.clone();
+        }
+
+        package.tarball = manifest.dist.tarball.clone().unwrap_or_default();
+        package.bin = manifest.bin.clone();
+        package.scripts = manifest.scripts.clone();
+        package.engines = manifest.engines.clone();
+        package.os = manifest.os.clone();
+        package.cpu = manifest.cpu.clone();
+    }
+
+    Ok(tree)
+}
+
+/// Whether two integrities could describe the same tarball, which can only be told apart
+/// when they share an algorithm
+fn same_content(lock: &str, registry: &str) -> bool {
+    match (lock.parse::<Integrity>(), registry.parse::<Integrity>()) {
+        (Ok(lock), Ok(registry)) => {
+            let shared = lock.hashes.iter().any(|hash| {
+                registry
+                    .hashes
+                    .iter()
+                    .any(|h| h.algorithm == hash.algorithm)
+            });
+
+            !shared || lock.matches(&registry).is_some()
+        }
+        _ => true,
+    }
+}
+
+/// Convert the settings of `.npmrc`, `.yarnrc.yml` and `.yarnrc` that volt supports into
+/// `volt.toml`, warning about the rest
+fn migrate_settings(project_dir: &Path) -> Result<()> {
+    let mut settings = Settings::load(project_dir)?;
+    let mut registry = None;
+    let mut converted = vec![];
+    let mut warnings = vec![];
+
+    let npmrc = project_dir.join(".npmrc");
+
+    if npmrc.exists() {
+        for line in read(&npmrc)?.lines() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => continue,
+            };
+
+            match key {
+                // pnpm counts the age in minutes
+                "minimum-release-age" => match value.parse::<u64>() {
+                    Ok(minutes) => {
+                        settings.minimum_release_age = Some(minutes_to_days(minutes));
+                        converted.push(key.to_string());
+                    }
+                    Err(_) => warnings.push(format!("{} = {} is not a number", key, value)),
+                },
+                "minimum-release-age-exclude" | "minimum-release-age-exclude[]" => {
+                    settings.minimum_release_age_exclude.extend(
+                        value
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|name| !name.is_empty())
+                            .map(String::from),
+                    );
+                    converted.push(key.to_string());
+                }
+                "registry" if value.trim_end_matches('/') != NPM_REGISTRY => {
+                    registry = Some(value.to_string());
+                    converted.push(key.to_string());
+                }
+                // credentials stay in .npmrc, which volt reads them from
+                "registry" | "email" => {}
+                key if key.starts_with("//") || key.starts_with('@') || key.starts_with('_') => {}
+                key => warnings.push(format!("{} in .npmrc is not supported", key)),
+            }
+        }
+    }
+
+    let yarnrc = project_dir.join(".yarnrc.yml");
+
+    if yarnrc.exists() {
+        let values: serde_yaml::Mapping =
+            serde_yaml::from_str(&read(&yarnrc)?).into_diagnostic()?;
+
+        for (key, value) in values {
+            let key = key.as_str().unwrap_or_default().to_string();
+
+            match key.as_str() {
+                "npmMinimalAgeGate" => match yarn_duration(&value) {
+                    Some(minutes) => {
+                        settings.minimum_release_age = Some(minutes_to_days(minutes));
+                        converted.push(key);
+                    }
+                    None => warnings.push(format!("{} in .yarnrc.yml is not a duration", key)),
+                },
+                "npmPreapprovedPackages" => {
+                    settings.minimum_release_age_exclude.extend(
+                        value
+                            .as_sequence()
+                            .into_iter()
+                            .flatten()
+                            .filter_map(|name| name.as_str().map(String::from)),
+                    );
+                    converted.push(key);
+                }
+                "npmRegistryServer"
+                    if value.as_str().map(|v| v.trim_end_matches('/')) != Some(NPM_REGISTRY)
+                        && value.as_str() != Some("https://registry.yarnpkg.com") =>
+                {
+                    registry = value.as_str().map(String::from);
+                    converted.push(key);
+                }
+                "npmAuthToken" | "npmScopes" | "npmRegistries" => warnings.push(format!(
+                    "{} in .yarnrc.yml is not read by volt, move the credentials to .npmrc",
+                    key
+                )),
+                // only matter to yarn itself
+                "npmRegistryServer" | "yarnPath" | "cacheFolder" | "enableGlobalCache" => {}
+                _ => warnings.push(format!("{} in .yarnrc.yml is not supported", key)),
+            }
+        }
+    }
+
+    // yarn 1 settings are `key value` lines
+    let legacy_yarnrc = project_dir.join(".yarnrc");
+
+    if legacy_yarnrc.exists() {
+        for line in read(&legacy_yarnrc)?.lines() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let key = line.split_whitespace().next().unwrap_or_default();
+
+            if key != "yarn-path" && key != "lastUpdateCheck" {
+                warnings.push(format!("{} in .yarnrc is not supported", key));
+            }
+        }
+    }
+
+    for warning in &warnings {
+        eprintln!("{}: {}", "warning".yellow().bold(), warning);
+    }
+
+    if converted.is_empty() {
+        return Ok(());
+    }
+
+    let path = Settings::path(project_dir);
+
+    // configuration options like `concurrency` live in the same file, so keep them
+    let mut table = read_file(&path)?;
+
+    if let toml::Value::Table(settings) = toml::Value::try_from(&settings).into_diagnostic()? {
+        table.extend(settings);
+    }
+
+    if let Some(registry) = registry {
+        table.insert(String::from("registry"), toml::Value::String(registry));
+    }
+
+    write_file(&path, &table)?;
+
+    println!(
+        "{} {} to {}",
+        "Converted".bright_green().bold(),
+        converted.join(", "),
+        path.display()
+    );
+
+    Ok(())
+}
+
+/// `minimum-release-age` is in whole days, so round up to never allow newer versions
+fn minutes_to_days(minutes: u64) -> u32 {
+    ((minutes + 24 * 60 - 1) / (24 * 60)) as u32
+}
+
+/// Read a yarn duration, either minutes or a number with a unit (`3d`, `12h`), as minutes
+fn yarn_duration(value: &serde_yaml::Value) -> Option<u64> {
+    if let Some(minutes) = value.as_u64() {
+        return Some(minutes);
+    }
+
+    let value = value.as_str()?.trim();
+
+    if let Ok(minutes) = value.parse::<u64>() {
+        return Some(minutes);
+    }
+
+    let split = value.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = value.split_at(split);
+    let amount = amount.parse::<u64>().ok()?;
+
+    match unit.trim() {
+        "m" => Some(amount),
+        "h" => Some(amount * 60),
+        "d" => Some(amount * 60 * 24),
+        "w" => Some(amount * 60 * 24 * 7),
+        _ => None,
+    }
+}
@@ -0,0 +1,206 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Find the packages a project imports without declaring them.
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    commands::add::install_packages,
+    core::{
+        model::{lock_file::LockFile, usage},
+        output::print_json,
+        utils::{errors::VoltError, package::PackageJson},
+    },
+};
+
+use async_trait::async_trait;
+use clap::Parser;
+use colored::Colorize;
+use miette::Result;
+use package_spec::PackageSpec;
+use serde::Serialize;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+};
+
+/// Find packages that are imported but not declared as dependencies
+#[derive(Debug, Parser)]
+pub struct Missing {
+    /// Add the missing packages to `dependencies` with the version installed now
+    #[clap(long)]
+    fix: bool,
+
+    /// With `--fix`, print the packages that would be added without adding them
+    #[clap(long)]
+    dry_run: bool,
+}
+
+/// An imported package that isn't in package.json
+#[derive(Debug, Serialize)]
+struct MissingDependency {
+    name: String,
+    /// The version in the lockfile, `None` when it isn't installed at all
+    installed: Option<String>,
+    files: BTreeSet<PathBuf>,
+}
+
+#[async_trait]
+impl VoltCommand for Missing {
+    /// Execute the `volt missing` command
+    ///
+    /// Scan the project's sources for imports and requires, and report the packages that
+    /// aren't declared in package.json, which only resolve because another dependency brought
+    /// them in. With `--fix` they're added with the version the lockfile resolved them to.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Declare the packages imported through other dependencies
+    /// // .exec() is an async call so you need to await it
+    /// Missing { fix: true, dry_run: false }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let config = config.with_dry_run(self.dry_run);
+
+        let project_dir = config.cwd()?;
+
+        let (mut package_file, package_path) = PackageJson::get_from_dir(&project_dir)?;
+
+        let lock_file = LockFile::load(config.lockfile()?, false)?;
+
+        // the project and its workspaces import each other without being installed
+        let local = package_file
+            .workspace_packages(&project_dir)?
+            .into_iter()
+            .map(|(_, workspace)| workspace.name)
+            .chain(std::iter::once(package_file.name.clone()))
+            .collect::<BTreeSet<_>>();
+
+        let declared = [&package_file.dependencies, &package_file.dev_dependencies]
+            .into_iter()
+            .flatten()
+            .flat_map(BTreeMap::keys)
+            .cloned()
+            .collect::<BTreeSet<_>>();
+
+        let missing = usage::scan(&project_dir)?
+            .into_iter()
+            .filter(|(name, _)| !declared.contains(name) && !local.contains(name))
+            .map(|(name, files)| MissingDependency {
+                installed: lock_file
+                    .find(&name, "*")
+                    .map(|package| package.version.clone()),
+                name,
+                files,
+            })
+            .collect::<Vec<_>>();
+
+        if config.json() && !self.fix {
+            print_json(&missing)?;
+        } else {
+            for dependency in &missing {
+                let file = dependency
+                    .files
+                    .iter()
+                    .next()
+                    .map(|file| file.display().to_string())
+                    .unwrap_or_default();
+
+                let more = match dependency.files.len() {
+                    0 | 1 => String::new(),
+                    count => format!(" and {} more", count - 1),
+                };
+
+                let installed = match &dependency.installed {
+                    Some(version) => format!("{} is installed", version),
+                    None => String::from("it isn't installed"),
+                };
+
+                eprintln!(
+                    "{}: {} is imported by {}{} but not declared, {}",
+                    "missing".bright_red().bold(),
+                    dependency.name,
+                    file,
+                    more,
+                    installed
+                );
+            }
+        }
+
+        if missing.is_empty() {
+            if !config.json() {
+                println!(
+                    "{} every import is declared",
+                    "Checked".bright_green().bold()
+                );
+            }
+
+            return Ok(());
+        }
+
+        if !self.fix {
+            return Err(VoltError::MissingDependenciesError {
+                count: missing.len(),
+            }
+            .into());
+        }
+
+        // packages that aren't installed get the latest version, like `volt add`
+        let specs = missing
+            .iter()
+            .map(|dependency| {
+                let spec = match &dependency.installed {
+                    Some(version) => format!("{}@{}", dependency.name, version),
+                    None => dependency.name.clone(),
+                };
+
+                spec.parse::<PackageSpec>()
+                    .map_err(|_| VoltError::PackageSpecificationError { spec })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let installed = install_packages(&config, &specs, false).await?;
+
+        if config.dry_run() {
+            return Ok(());
+        }
+
+        for package in installed
+            .iter()
+            .filter(|package| missing.iter().any(|m| m.name == package.name))
+        {
+            package_file
+                .dependencies
+                .get_or_insert_with(BTreeMap::new)
+                .insert(package.name.clone(), format!("^{}", package.version));
+
+            println!(
+                "{} {}@{}",
+                "Declared".bright_green().bold(),
+                package.name,
+                package.version
+            );
+        }
+
+        package_file.save_to(&package_path)?;
+
+        Ok(())
+    }
+}
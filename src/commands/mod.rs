@@ -13,32 +13,60 @@
     See the License for the specific language governing permissions and
     limitations under the License.
 */
+pub mod access;
 pub mod add;
 pub mod audit;
+pub mod bin;
+pub mod cache;
 pub mod check;
 pub mod clean;
 pub mod clone;
+pub mod completions;
+pub mod compress;
+pub mod config;
 pub mod create;
+pub mod daemon;
+pub mod decompress;
+pub mod dedupe;
 pub mod deploy;
+pub mod deprecate;
 pub mod discord;
+pub mod doctor;
 pub mod fix;
+pub mod graph;
 pub mod info;
 pub mod init;
 pub mod install;
+pub mod licenses;
+pub mod link;
+pub mod links;
 pub mod list;
 pub mod login;
 pub mod logout;
 pub mod migrate;
+pub mod missing;
 pub mod node;
 pub mod outdated;
 pub mod owner;
+pub mod pack;
+pub mod patch;
+pub mod plugin;
+pub mod prune;
 pub mod publish;
 pub mod remove;
 pub mod run;
+pub mod sbom;
 pub mod search;
 pub mod set;
 pub mod stat;
+pub mod stats;
 pub mod tag;
 pub mod team;
+pub mod telemetry;
+pub mod token;
+pub mod unused;
 pub mod update;
+pub mod version;
 pub mod watch;
+pub mod why;
+pub mod x;
@@ -15,326 +15,234 @@
 */
 
 //! Check for outdated packages.
-use std::collections::HashMap;
-use std::sync::Arc;
+
+use std::collections::BTreeMap;
 
 use async_trait::async_trait;
-use clap::{ArgMatches, Parser, Subcommand};
+use clap::Parser;
 use colored::Colorize;
+use comfy_table::{
+    modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL, Attribute, Cell, Color, ContentArrangement,
+    Table,
+};
+use futures::{stream::FuturesUnordered, StreamExt};
 use miette::Result;
-use node_semver::{Range, Version};
-use serde::{Deserialize, Deserializer};
-use serde_json;
+use node_semver::Version;
+use serde::Serialize;
 
 use crate::{
     cli::{VoltCommand, VoltConfig},
-    core::utils::extensions::PathExtensions,
+    core::model::lock_file::{DependencyKind, LockFile},
+    core::net::{get_cached_registry_package, RegistryPackage},
+    core::output::print_json,
     core::utils::package::PackageJson,
 };
 
-// https://github.com/npm/registry/blob/master/docs/REGISTRY-API.md#version
-// abbreviated version uses "Accept: application/vnd.npm.install-v1+json" in header
-// this struct is specifically for the appreviated package responses from the registry.
-// this method is used to ensure faster responses when doing all dependencies.
-// all response fields must be optional in order to allow for different response
-// types, and the error response, which only contains a single "error" field.
-#[derive(Deserialize, Debug)]
-pub struct PackageResponse {
-    // resource for handling dynamic registry responses with Rust
-    // https://hamatti.org/posts/learning-rust-4-parsing-json-with-strong-types/
-
-    // resource for serde aliasing since the registry response uses "dist-tags"
-    // https://stackoverflow.com/questions/71328963/how-to-read-response-json-as-structs-when-they-contain-hyphens-in-key-names
-    name: Option<String>, // string name of the package
-    #[serde(alias = "dist-tags")]
-    dist_tags: Option<HashMap<String, String>>, // will always contain at least one tag which is "latest", for latest version
-    versions: Option<HashMap<String, PackageVersion>>, // dictionary of versions with key: semver version string, value: PackageVersion struct
-    modified: Option<String>,                          // string date of package last modified date
-    error: Option<String>,                             // registry request string error
-}
-
-#[derive(Deserialize, Debug)]
-struct PackageVersion {
-    name: String,    // package name
-    version: String, // semver version string
-}
-
+/// Check for outdated packages
 #[derive(Debug, Parser)]
 pub struct Outdated {
-    package: Option<String>,
+    /// Only check these packages
+    packages: Vec<String>,
+}
+
+/// A direct dependency with a newer version available
+#[derive(Debug, Serialize)]
+pub struct OutdatedPackage {
+    pub name: String,
+    /// The locked version, `None` if the package isn't installed
+    pub current: Option<String>,
+    /// The highest version satisfying the range in package.json
+    pub wanted: Option<String>,
+    /// The version tagged `latest` on the registry
+    pub latest: String,
+    #[serde(rename = "type")]
+    pub kind: DependencyKind,
+    /// The workspace declaring the dependency (`.` for the project itself)
+    pub location: String,
 }
 
 #[async_trait]
 impl VoltCommand for Outdated {
-    // TODO: figure out how to run with & without arguments
-
-    // TECHNICALLY DONE.
-    // TODO: figure out how to combine with primary dependencies, to check each dependency/version
-    //       on a project.
-
-    // TECHNICALLY DONE
-    // TODO: understand how semver works and can be used to our advantage
-
-    // TODO: validate that the user provided a dependency/package that is actually installed before
-    //       sending a request to the registry.
-
-    // TECHNICALLY DONE, SHOULD ONLY ACCEPT ONE VERSION
-    // TODO: Need to handle version ranges and exact versions separately
-
-    async fn exec(self, _: VoltConfig) -> Result<()> {
-        // realistically the 'node_modules' file should be in the same directory
-        // as the primary package.json file for a project.
-        let base_url = "https://registry.npmjs.org/";
-
-        // TODO:
-        // make this result optional entirely for when 'package.json' file doesn't exist!
-        let primary_pkg = PackageJson::get().unwrap();
-        let mut primary_deps = primary_pkg.0.dependencies;
-        let mut node_modules = primary_pkg.1;
-        node_modules.pop();
-        node_modules.push("node_modules");
-
-        //println!("Node modules are at {:?}", node_modules);
-
-        // add to the modules path for single dependency, so we can have the
-        // path to that dependency's folder within 'node_modules'
-        //modules.push(&self.dependency);
-
-        //println!("Dependency is at {modules:?}");
-
-        // grab the 'package.json' file for this specific dependency in order
-        // to get exact version information.
-        //let pkg_json = PackageJson::get_from_dir(modules).unwrap().0;
-        // convert the semver version string from the PackageJson struct into
-        // a node_semver Version struct which can be used to compare to the
-        // semver version string 'latest' returned in the registry response.
-        //let current: Version = pkg_json.version.parse().unwrap();
-        //println!("Got version {}", pkg_json.version);
-        //let deps = pkgjson.dependencies.unwrap();
-
-        //let pwd = std::env::current_dir().unwrap();
-        //println!("Running from {pwd:?}");
-
-        // in this case there are no packages installed in a project!
-        if let Some(deps) = &primary_deps {
-            //if !primary_deps.is_none() {
-            // test to see if an argument was provided, if not run for all dependencies???
-            if let Some(package_name) = self.package {
-                //&& !self.dependency.contains("!") {
-                if (!deps.contains_key(&package_name)) {
-                    let output = format!(
-                        "{} is not an installed package!",
-                        &package_name.truecolor(255, 000, 000)
-                    );
-                    println!("{}", output);
-                } else {
-                    //println!("Arguments: {}\n", self.dependency);
-
-                    let mut single = node_modules.clone();
-                    single.push(&package_name);
-
-                    //let pkg_json: PackageJson = PackageJson::get_from_dir(&single).unwrap().0;
-                    //let current: Version = pkg_json.version.parse().unwrap();
-
-                    // println!("Dependency is at {single:?}");
-                    //pkg_json = PackageJson::get_from_dir(&single).unwrap().0;
-                    //current = pkg_json.version.parse().unwrap();
-                    //pkg_json = PackageJson::get_from_dir(&single).unwrap().0;
-                    //current = pkg_json.version.parse().unwrap();
-                    //}
-                    // if !deps.contains_key(&self.dependency) {
-                    //     println!("Dependency not installed!");
-                    //     std::process::exit(1);
-                    // }
-                    // let curr = &deps[&self.dependency];
-
-                    // println!("current version is {curr}");
-                    // let current: Range = deps[&self.dependency].parse().unwrap();
-                    // need client to add headers
-                    let client = reqwest::Client::new();
-
-                    // NOTE: biggest help for handling dynamic JSON responses was hamatti.org!!!!
-                    // https://hamatti.org/posts/learning-rust-4-parsing-json-with-strong-types/
-                    // https://stackoverflow.com/questions/47911513/how-do-i-set-the-request-headers-using-reqwest
-                    // https://stackoverflow.com/questions/63872942/how-can-an-arbitrary-json-structure-be-deserialized-with-reqwest-get-in-rust
-                    // https://stackoverflow.com/questions/68357867/dynamically-receive-json-data-in-rust-with-reqwest
-
-                    /* working version without serde_json structure, this format uses assumed structure...
-                    let package_info = client
-                        .get(format!("{}/{}", base_url, &self.dependency))
-                        .header("Accept", "application/vnd.npm.install-v1+json")
-                        .send()
-                        .await
-                        .unwrap()
-                        .json::<serde_json::Value>()
-                        .await
-                        .unwrap();
-                    println!("Testing: {}", package_info);
-                    */
-
-                    // this format assigns the JSON into the appropriate
-                    // fields within the Package struct.
-                    let package_info: PackageResponse = client
-                        .get(format!("{}/{}", base_url, &package_name))
-                        .header("Accept", "application/vnd.npm.install-v1+json")
-                        .send()
-                        .await
-                        .unwrap()
-                        .json()
-                        .await
-                        .unwrap();
-
-                    // check to see if the optional error value was none
-                    // which indicates the request was successful.
-                    if package_info.error.is_none() {
-                        //println!("Package name: {}\n", package_info.name.unwrap());
-                        //println!("Tags: {:?}", package_info.dist_tags.as_ref().unwrap());
-
-                        let latest: Version = package_info
-                            .dist_tags
-                            .as_ref()
-                            .unwrap()
-                            .get("latest")
-                            .unwrap()
-                            .parse()
-                            .unwrap();
-
-                        println!("Package \t\t| Current \t\t| Latest\n---------------------------------------------------------");
-                        if !single.exists() {
-                            let output = format!(
-                                "{} \t\t| {} \t\t| {}",
-                                package_info.name.unwrap().truecolor(255, 000, 000),
-                                "MISSING",
-                                latest.to_string().truecolor(055, 125, 235),
-                            );
-                            println!("{}", output);
-                        } else {
-                            let pkg_json: PackageJson =
-                                PackageJson::get_from_dir(&single).unwrap().0;
-                            let current: Version = pkg_json.version.parse().unwrap();
-                            if current < latest {
-                                let output = format!(
-                                    "{} \t\t| {} \t\t| {}",
-                                    package_info.name.unwrap().truecolor(255, 000, 000),
-                                    current,
-                                    latest.to_string().truecolor(055, 125, 235),
-                                );
-                                println!("{}", output);
-                            } else {
-                                let output = format!(
-                                    "{} \t\t| {} \t\t| {}",
-                                    package_info.name.unwrap().truecolor(000, 255, 000),
-                                    current,
-                                    latest.to_string().truecolor(055, 125, 235),
-                                );
-                                println!("{}", output);
-                            }
-                        }
-                    } else {
-                        let output = format!(
-                            "Error fetching package info for {} due to: \n\t{}",
-                            package_info.name.unwrap(),
-                            package_info.error.unwrap().truecolor(255, 000, 000)
-                        );
-                        println!("{}", output);
-                    }
-                }
+    /// Execute the `volt outdated` command
+    ///
+    /// Compare the locked versions of a project's direct dependencies against the registry.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Check every dependency and print the result as JSON
+    /// // .exec() is an async call so you need to await it
+    /// Outdated { packages: vec![] }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let project_dir = config.cwd()?;
+
+        let (package_file, _) = PackageJson::get_from_dir(&project_dir)?;
+
+        let lock_file = LockFile::load(config.lockfile()?, false)?;
+
+        let outdated = find_outdated(&config, &package_file, &lock_file, &self.packages).await?;
+
+        if config.json() {
+            print_json(&outdated)?;
+
+            return Ok(());
+        }
+
+        if outdated.is_empty() {
+            println!("{}", "All packages are up to date!".bright_green());
+            return Ok(());
+        }
+
+        let mut table = Table::new();
+
+        table
+            .load_preset(UTF8_FULL)
+            .apply_modifier(UTF8_ROUND_CORNERS)
+            .set_content_arrangement(ContentArrangement::Dynamic);
+
+        table.set_header(
+            [
+                "Package",
+                "Current",
+                "Wanted",
+                "Latest",
+                "Type",
+                "Workspace",
+            ]
+            .iter()
+            .map(|header| Cell::new(header).add_attribute(Attribute::Bold)),
+        );
+
+        for package in &outdated {
+            // red when an update within the range is available, yellow when only a new major is
+            let color = if package.current != package.wanted {
+                Color::Red
             } else {
-                //println!("No dependency provided, run for all????");
-
-                let mut multiple = node_modules.clone();
-                //println!("{:?}", &multiple);
-
-                let mut found_outdated = false;
-
-                let dependencies = primary_deps.unwrap();
-
-                for dependency in dependencies {
-                    //multiple.push(dependency.0);
-                    //println!("Dependency is at {multiple:?}");
-                    let dep_name = dependency.0;
-                    //println!("{:?}", &multiple.join(&dep_name));
-
-                    let client = reqwest::Client::new();
-
-                    let package_info: PackageResponse = client
-                        .get(format!("{}/{}", base_url, &dep_name))
-                        .header("Accept", "application/vnd.npm.install-v1+json")
-                        .send()
-                        .await
-                        .unwrap()
-                        .json()
-                        .await
-                        .unwrap();
-
-                    // check to see if the optional error value was none
-                    // which indicates the request was successful.
-                    if package_info.error.is_none() {
-                        //println!("Package name: {}\n", package_info.name.unwrap());
-                        //println!("Tags: {:?}", package_info.dist_tags.as_ref().unwrap());
-
-                        let latest: Version = package_info
-                            .dist_tags
-                            .as_ref()
-                            .unwrap()
-                            .get("latest")
-                            .unwrap()
-                            .parse()
-                            .unwrap();
-
-                        //println!("Latest version: {}", latest); :? is debug print
-                        //println!("Current version: {:?}", current);
-
-                        if !multiple.join(&dep_name).exists() {
-                            if (!found_outdated) {
-                                println!("Package \t\t| Current \t\t| Latest\n---------------------------------------------------------");
-                                found_outdated = true;
-                            }
-
-                            let output = format!(
-                                "{} \t\t| {} \t\t| {}",
-                                package_info.name.unwrap().truecolor(255, 000, 000),
-                                "MISSING",
-                                latest.to_string().truecolor(55, 125, 235),
-                            );
-                            println!("{}", output);
-                        } else {
-                            let pkg_json = PackageJson::get_from_dir(&multiple.join(&dep_name))
-                                .unwrap()
-                                .0;
-                            let current: Version = pkg_json.version.parse().unwrap();
-                            if current < latest {
-                                if (!found_outdated) {
-                                    println!("Package \t\t| Current \t\t| Latest\n---------------------------------------------------------");
-                                    found_outdated = true;
-                                }
-
-                                let output = format!(
-                                    "{} \t\t| {} \t\t| {}",
-                                    package_info.name.unwrap().truecolor(255, 000, 000),
-                                    current,
-                                    latest.to_string().truecolor(55, 125, 235),
-                                );
-                                println!("{}", output);
-                            }
-                        }
-                    } else {
-                        let output = format!(
-                            "Error fetching package info for {} due to: \n\t{}",
-                            package_info.name.unwrap(),
-                            package_info.error.unwrap().truecolor(255, 000, 000)
-                        );
-                        println!("{}", output);
-                    }
-                }
-
-                if (!found_outdated) {
-                    println!("All packages are up to date!")
-                }
-            }
-        } else {
-            println!("No packages are installed in the current project!")
+                Color::Yellow
+            };
+
+            table.add_row(vec![
+                Cell::new(&package.name).fg(color),
+                Cell::new(package.current.as_deref().unwrap_or("MISSING")),
+                Cell::new(package.wanted.as_deref().unwrap_or("-")).fg(Color::Green),
+                Cell::new(&package.latest).fg(Color::Blue),
+                Cell::new(package.kind),
+                Cell::new(&package.location),
+            ]);
         }
+
+        println!("{}", table);
+
         Ok(())
     }
 }
+
+/// Find the direct dependencies of a project and its workspaces that have newer versions
+pub async fn find_outdated(
+    config: &VoltConfig,
+    package_file: &PackageJson,
+    lock_file: &LockFile,
+    only: &[String],
+) -> Result<Vec<OutdatedPackage>> {
+    let project_dir = config.cwd()?;
+
+    let mut manifests = vec![(String::from("."), package_file.clone())];
+
+    for (directory, workspace) in package_file.workspace_packages(&project_dir)? {
+        let location = directory
+            .strip_prefix(&project_dir)
+            .unwrap_or(&directory)
+            .display()
+            .to_string();
+
+        manifests.push((location, workspace));
+    }
+
+    // (location, kind, name, range, locked version)
+    let mut dependencies = vec![];
+
+    for (location, manifest) in &manifests {
+        for (kind, requested, package) in lock_file.roots(manifest) {
+            let (name, range) = requested
+                .rsplit_once('@')
+                .filter(|(name, _)| !name.is_empty())
+                .map(|(name, range)| (name.to_string(), range.to_string()))
+                .unwrap_or((requested.clone(), String::new()));
+
+            if !only.is_empty() && !only.contains(&name) {
+                continue;
+            }
+
+            dependencies.push((
+                location.clone(),
+                kind,
+                name,
+                range,
+                package.map(|p| p.version.clone()),
+            ));
+        }
+    }
+
+    let client = reqwest::Client::new();
+
+    let mut names = dependencies
+        .iter()
+        .map(|(_, _, name, _, _)| name.clone())
+        .collect::<Vec<_>>();
+
+    names.sort();
+    names.dedup();
+
+    let mut metadata: BTreeMap<String, RegistryPackage> = BTreeMap::new();
+
+    let mut requests = names
+        .iter()
+        .map(|name| get_cached_registry_package(&client, name))
+        .collect::<FuturesUnordered<_>>();
+
+    while let Some(response) = requests.next().await {
+        let package = response?;
+        metadata.insert(package.name.clone(), package);
+    }
+
+    let mut outdated = vec![];
+
+    for (location, kind, name, range, current) in dependencies {
+        let package = match metadata.get(&name) {
+            Some(package) => package,
+            None => continue,
+        };
+
+        let latest = match package.latest() {
+            Some(latest) => latest.to_string(),
+            None => continue,
+        };
+
+        let wanted = package.max_satisfying(&range).map(|v| v.to_string());
+
+        let behind = |version: &str| match (current.as_deref(), version.parse::<Version>()) {
+            (Some(current), Ok(version)) => current
+                .parse::<Version>()
+                .map_or(true, |current| current < version),
+            _ => true,
+        };
+
+        if behind(&latest) || wanted.as_deref().map_or(false, behind) {
+            outdated.push(OutdatedPackage {
+                name,
+                current,
+                wanted,
+                latest,
+                kind,
+                location,
+            });
+        }
+    }
+
+    outdated.sort_by(|a, b| (&a.location, &a.name).cmp(&(&b.location, &b.name)));
+
+    Ok(outdated)
+}
@@ -14,33 +14,166 @@
     limitations under the License.
 */
 
-//! Check for outdated packages.
+//! Manage the maintainers of a package.
 
 use async_trait::async_trait;
+use clap::{Parser, Subcommand};
+use colored::Colorize;
 use miette::Result;
+use serde_json::{json, Value};
 
-use std::sync::Arc;
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::{auth::RegistryClient, utils::package::PackageJson},
+};
 
-use crate::cli::{VoltCommand, VoltConfig};
+/// Manage the maintainers of a package
+#[derive(Debug, Parser)]
+pub struct Owner {
+    #[clap(subcommand)]
+    command: OwnerCommand,
+}
 
-pub struct Owner {}
+#[derive(Debug, Subcommand)]
+enum OwnerCommand {
+    /// Add a maintainer to a package
+    Add {
+        /// The user to add
+        user: String,
+        /// The package, the current package by default
+        package: Option<String>,
+    },
+    /// Remove a maintainer from a package
+    Rm {
+        /// The user to remove
+        user: String,
+        /// The package, the current package by default
+        package: Option<String>,
+    },
+    /// List the maintainers of a package
+    Ls {
+        /// The package, the current package by default
+        package: Option<String>,
+    },
+}
 
 #[async_trait]
 impl VoltCommand for Owner {
-    /// Execute the `volt outdated` command
+    /// Execute the `volt owner` command
     ///
-    /// Check for outdated packages
+    /// Add, remove or list the users that can publish a package.
     /// ## Arguments
-    /// * `error` - Instance of the command (`Arc<App>`)
+    /// * `config` - The global volt configuration (`VoltConfig`)
     /// ## Examples
     /// ```
-    /// // Check for outdated packages
+    /// // Let another user publish the current package
     /// // .exec() is an async call so you need to await it
-    /// Owner.exec(app).await;
+    /// Owner { command: OwnerCommand::Add { user: "xfaded".into(), package: None } }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
     async fn exec(self, config: VoltConfig) -> Result<()> {
+        let package = match &self.command {
+            OwnerCommand::Add { package, .. }
+            | OwnerCommand::Rm { package, .. }
+            | OwnerCommand::Ls { package } => package.clone(),
+        };
+
+        let package = match package {
+            Some(package) => package,
+            None => PackageJson::get_from_dir(&config.cwd()?)?.0.name,
+        };
+
+        let mut client = RegistryClient::new(&config)?;
+
+        let path = package.replace('/', "%2f");
+        let packument = client.get(&path).await?;
+
+        let mut maintainers = packument
+            .get("maintainers")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+
+        let change = match &self.command {
+            OwnerCommand::Ls { .. } => {
+                for maintainer in &maintainers {
+                    println!(
+                        "{} {}",
+                        maintainer["name"].as_str().unwrap_or_default().bold(),
+                        maintainer["email"]
+                            .as_str()
+                            .map(|email| format!("<{}>", email))
+                            .unwrap_or_default()
+                    );
+                }
+
+                return Ok(());
+            }
+            OwnerCommand::Add { user, .. } => {
+                if maintainers
+                    .iter()
+                    .any(|maintainer| is_maintainer(maintainer, user))
+                {
+                    println!("{} is already a maintainer of {}", user, package);
+                    return Ok(());
+                }
+
+                let profile = client
+                    .get(&format!(
+                        "-/user/org.couchdb.user:{}",
+                        urlencoding::encode(user)
+                    ))
+                    .await?;
+
+                maintainers.push(json!({
+                    "name": user,
+                    "email": profile.get("email"),
+                }));
+
+                format!("{} {} to {}", "+".bright_green().bold(), user, package)
+            }
+            OwnerCommand::Rm { user, .. } => {
+                if !maintainers
+                    .iter()
+                    .any(|maintainer| is_maintainer(maintainer, user))
+                {
+                    println!("{} is not a maintainer of {}", user, package);
+                    return Ok(());
+                }
+
+                maintainers.retain(|maintainer| !is_maintainer(maintainer, user));
+
+                if maintainers.is_empty() {
+                    miette::bail!("{} would have no maintainers left", package);
+                }
+
+                format!("{} {} from {}", "-".bright_red().bold(), user, package)
+            }
+        };
+
+        let revision = packument
+            .get("_rev")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+
+        client
+            .put(
+                &format!("{}/-rev/{}", path, revision),
+                &json!({
+                    "_id": packument.get("_id"),
+                    "_rev": revision,
+                    "maintainers": maintainers,
+                }),
+            )
+            .await?;
+
+        println!("{}", change);
+
         Ok(())
     }
 }
+
+fn is_maintainer(maintainer: &Value, user: &str) -> bool {
+    maintainer.get("name").and_then(Value::as_str) == Some(user)
+}
@@ -0,0 +1,291 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Create a tarball of the project, exactly as it would be published, or maintain a
+//! `node_modules.pack` archive.
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::{
+        output::{print_json, progress, status},
+        pack::{self as archive, FILE_NAME},
+        utils::{
+            errors::VoltError,
+            package::PackageJson,
+            packlist::{create_tarball, packlist, tarball_name, PackedFile},
+        },
+    },
+};
+
+use async_trait::async_trait;
+use clap::{Parser, Subcommand};
+use colored::Colorize;
+use indicatif::{HumanBytes, ProgressBar};
+use miette::{IntoDiagnostic, Result};
+use serde_json::Value;
+use ssri::{Algorithm, IntegrityOpts};
+
+use std::{
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+/// Create a tarball of the project, exactly as it would be published
+#[derive(Debug, Parser)]
+pub struct Pack {
+    /// Print the contents of the tarball without writing it
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Directory to write the tarball to (defaults to the project directory)
+    #[clap(long)]
+    pack_destination: Option<PathBuf>,
+
+    #[clap(subcommand)]
+    command: Option<PackCommand>,
+}
+
+#[derive(Debug, Subcommand)]
+enum PackCommand {
+    /// Rewrite a node_modules.pack archive without the data left behind by updates
+    Compact {
+        /// The archive, node_modules.pack in the project by default
+        pack: Option<PathBuf>,
+    },
+    /// Check a node_modules.pack archive and every file in it against their hashes
+    Verify {
+        /// The archive, node_modules.pack in the project by default
+        pack: Option<PathBuf>,
+    },
+}
+
+/// A packed project
+pub struct Tarball {
+    pub name: String,
+    pub version: String,
+    pub filename: String,
+    pub files: Vec<PackedFile>,
+    pub data: Vec<u8>,
+    /// sha512 integrity of the tarball
+    pub integrity: String,
+    /// Hex sha1 of the tarball
+    pub shasum: String,
+}
+
+#[async_trait]
+impl VoltCommand for Pack {
+    /// Execute the `volt pack` command
+    ///
+    /// Select the files npm would publish, honouring the `files` field and `.npmignore`, and
+    /// write them to `<name>-<version>.tgz`.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // List what would be published without writing a tarball
+    /// // .exec() is an async call so you need to await it
+    /// Pack { dry_run: true, pack_destination: None, command: None }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        match self.command {
+            Some(PackCommand::Compact { pack }) => return compact(&config, pack),
+            Some(PackCommand::Verify { pack }) => return verify(&config, pack),
+            None => {}
+        }
+
+        let (_, package_path) = PackageJson::get_from_dir(&config.cwd()?)?;
+        let project_dir = package_path.parent().unwrap_or(&package_path);
+
+        let tarball = pack_project(project_dir)?;
+
+        print_tarball(&tarball);
+
+        if !self.dry_run {
+            let destination = self
+                .pack_destination
+                .unwrap_or_else(|| project_dir.to_path_buf());
+
+            std::fs::create_dir_all(&destination).into_diagnostic()?;
+            std::fs::write(destination.join(&tarball.filename), &tarball.data).into_diagnostic()?;
+        }
+
+        println!("{}", tarball.filename);
+
+        Ok(())
+    }
+}
+
+fn archive_path(config: &VoltConfig, pack: Option<PathBuf>) -> Result<PathBuf> {
+    match pack {
+        Some(pack) => Ok(pack),
+        None => Ok(config.cwd()?.join(FILE_NAME)),
+    }
+}
+
+/// Rewrite an archive without the data its index no longer points to
+fn compact(config: &VoltConfig, pack: Option<PathBuf>) -> Result<()> {
+    let path = archive_path(config, pack)?;
+
+    let before = std::fs::metadata(&path).map_or(0, |metadata| metadata.len());
+    let summary = archive::compact(&path)?;
+
+    if config.json() {
+        return print_json(&summary);
+    }
+
+    status(
+        config,
+        format!(
+            "{} {}, saving {}",
+            "Compacted".bright_green().bold(),
+            path.display(),
+            HumanBytes(before.saturating_sub(summary.packed_size))
+                .to_string()
+                .bright_green()
+        ),
+    );
+
+    Ok(())
+}
+
+/// Check an archive before it's extracted, failing if anything doesn't match its hash
+fn verify(config: &VoltConfig, pack: Option<PathBuf>) -> Result<()> {
+    let path = archive_path(config, pack)?;
+
+    let bar = progress(config, ProgressBar::new(0));
+    let verification = archive::verify(&path, &bar);
+    bar.finish_and_clear();
+
+    if config.json() {
+        print_json(&verification)?;
+    } else {
+        for problem in &verification.problems {
+            eprintln!(
+                "{} {}",
+                problem
+                    .file
+                    .clone()
+                    .unwrap_or_else(|| path.display().to_string())
+                    .bright_red()
+                    .bold(),
+                problem.reason
+            );
+        }
+
+        if verification.valid {
+            status(
+                config,
+                format!(
+                    "{} {} packages and {} files in {} ({})",
+                    "Verified".bright_green().bold(),
+                    verification.packages,
+                    verification.files,
+                    path.display(),
+                    verification.archive_hash.as_deref().unwrap_or_default()
+                ),
+            );
+        }
+    }
+
+    if verification.valid {
+        return Ok(());
+    }
+
+    Err(VoltError::PackCorruptError {
+        path: path.display().to_string(),
+        reason: format!("{} problems found", verification.problems.len()),
+    }
+    .into())
+}
+
+/// Pack the project in `dir` into the tarball that would be published
+pub fn pack_project(dir: &Path) -> Result<Tarball> {
+    let path = dir.join("package.json");
+
+    let data = read_to_string(&path).map_err(|e| VoltError::ReadFileError {
+        source: e,
+        name: path.display().to_string(),
+    })?;
+
+    let manifest: Value = serde_json::from_str(&data).into_diagnostic()?;
+
+    let field = |field: &str| {
+        manifest
+            .get(field)
+            .and_then(Value::as_str)
+            .map(String::from)
+            .ok_or_else(|| miette::miette!("package.json is missing the `{}` field", field))
+    };
+
+    let name = field("name")?;
+    let version = field("version")?;
+
+    let files = packlist(dir, &manifest)?;
+    let data = create_tarball(dir, &files)?;
+
+    let integrity = IntegrityOpts::new()
+        .algorithm(Algorithm::Sha512)
+        .chain(&data)
+        .result()
+        .to_string();
+
+    let shasum = IntegrityOpts::new()
+        .algorithm(Algorithm::Sha1)
+        .chain(&data)
+        .result()
+        .to_hex()
+        .1;
+
+    Ok(Tarball {
+        filename: tarball_name(&name, &version),
+        name,
+        version,
+        files,
+        data,
+        integrity,
+        shasum,
+    })
+}
+
+/// Print the contents and details of a tarball like `npm pack` does
+pub fn print_tarball(tarball: &Tarball) {
+    let label = |label: &str| format!("{:<15}", label).truecolor(156, 156, 156);
+
+    println!("📦  {}@{}", tarball.name.bold(), tarball.version);
+    println!("{}", "Tarball Contents".bright_cyan().bold());
+
+    for file in &tarball.files {
+        println!("  {:>10}  {}", HumanBytes(file.size).to_string(), file.path);
+    }
+
+    let unpacked_size = tarball.files.iter().map(|file| file.size).sum::<u64>();
+
+    println!("{}", "Tarball Details".bright_cyan().bold());
+    println!("  {}{}", label("name:"), tarball.name);
+    println!("  {}{}", label("version:"), tarball.version);
+    println!("  {}{}", label("filename:"), tarball.filename);
+    println!(
+        "  {}{}",
+        label("package size:"),
+        HumanBytes(tarball.data.len() as u64)
+    );
+    println!("  {}{}", label("unpacked size:"), HumanBytes(unpacked_size));
+    println!("  {}{}", label("shasum:"), tarball.shasum);
+    println!("  {}{}", label("integrity:"), tarball.integrity);
+    println!("  {}{}", label("total files:"), tarball.files.len());
+}
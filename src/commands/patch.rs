@@ -0,0 +1,236 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Keep local fixes to dependencies across installs.
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::{
+        model::lock_file::LockFile,
+        utils::{
+            errors::VoltError,
+            package::PackageJson,
+            patch::{apply_patch, create_patch, extract_cached, patch_path},
+            voltapi::VoltPackage,
+        },
+    },
+};
+
+use async_trait::async_trait;
+use clap::Parser;
+use colored::Colorize;
+use miette::{IntoDiagnostic, Result};
+use serde_json::Value;
+
+use std::{fs, path::PathBuf};
+
+/// Copy an installed package into a temporary directory to edit it
+#[derive(Debug, Parser)]
+pub struct Patch {
+    /// The installed package, with its version if more than one is installed (`react@18.0.0`)
+    package: String,
+}
+
+/// Save the changes made to a package prepared by `volt patch`
+#[derive(Debug, Parser)]
+pub struct PatchCommit {
+    /// The directory printed by `volt patch`
+    dir: PathBuf,
+}
+
+#[async_trait]
+impl VoltCommand for Patch {
+    /// Execute the `volt patch` command
+    ///
+    /// Copy an installed package into a temporary directory alongside a pristine copy from the
+    /// store, so the changes can be saved with `volt patch-commit`.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Start fixing a bug in the installed react
+    /// // .exec() is an async call so you need to await it
+    /// Patch { package: "react".into() }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let lock_file = LockFile::load(config.lockfile()?, false)?;
+
+        let package = find_installed(&lock_file, &self.package)?;
+        let package_dir = package.package_directory(&config.node_modules()?);
+
+        if !package_dir.exists() {
+            miette::bail!(
+                "{} is not installed, run `volt install` first",
+                package.key()
+            );
+        }
+
+        let workspace = tempfile::Builder::new()
+            .prefix("volt-patch-")
+            .tempdir()
+            .into_diagnostic()?
+            .into_path();
+
+        extract_cached(&config, package, &workspace.join("original"))?;
+
+        // start from the installed files so an existing patch can be extended
+        let edit_dir = workspace.join("package");
+
+        fs::create_dir_all(&edit_dir).map_err(VoltError::CreateDirError)?;
+
+        let mut options = fs_extra::dir::CopyOptions::new();
+        options.content_only = true;
+
+        fs_extra::dir::copy(&package_dir, &edit_dir, &options).into_diagnostic()?;
+
+        println!(
+            "Edit {} in {}",
+            package.key().bright_cyan(),
+            edit_dir.display().to_string().bold()
+        );
+        println!(
+            "Then run {} to save your changes",
+            format!("volt patch-commit {}", edit_dir.display()).bright_cyan()
+        );
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl VoltCommand for PatchCommit {
+    /// Execute the `volt patch-commit` command
+    ///
+    /// Diff the edited package against the published one, store the diff under `patches/`,
+    /// register it in `patchedDependencies` and apply it to `node_modules`.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Save the fix made in the directory `volt patch` printed
+    /// // .exec() is an async call so you need to await it
+    /// PatchCommit { dir: "/tmp/volt-patch-a1b2c3/package".into() }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let edit_dir = self.dir.canonicalize().into_diagnostic()?;
+
+        let workspace = edit_dir
+            .parent()
+            .filter(|workspace| workspace.join("original").exists())
+            .ok_or_else(|| {
+                miette::miette!("{} was not created by `volt patch`", edit_dir.display())
+            })?
+            .to_path_buf();
+
+        let manifest: Value = serde_json::from_str(
+            &fs::read_to_string(edit_dir.join("package.json")).into_diagnostic()?,
+        )
+        .into_diagnostic()?;
+
+        let spec = format!(
+            "{}@{}",
+            manifest["name"].as_str().unwrap_or_default(),
+            manifest["version"].as_str().unwrap_or_default()
+        );
+
+        let lock_file = LockFile::load(config.lockfile()?, false)?;
+
+        let package = find_installed(&lock_file, &spec)?;
+
+        let patch = create_patch(&workspace.join("original"), &edit_dir)?;
+
+        if patch.is_empty() {
+            miette::bail!("{} has no changes to save", package.key());
+        }
+
+        let project_dir = config.cwd()?;
+        let relative_path = patch_path(&package.name, &package.version);
+        let path = project_dir.join(&relative_path);
+
+        fs::create_dir_all(project_dir.join("patches")).map_err(VoltError::CreateDirError)?;
+
+        fs::write(&path, patch).map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: relative_path.clone(),
+        })?;
+
+        let (mut package_file, package_file_path) = PackageJson::get_from_dir(&project_dir)?;
+
+        package_file
+            .patched_dependencies
+            .get_or_insert_with(Default::default)
+            .insert(package.key(), relative_path.clone());
+
+        package_file.save_to(&package_file_path)?;
+
+        // reinstall the published files so the patch is applied exactly like installs will
+        let package_dir = package.package_directory(&config.node_modules()?);
+
+        if package_dir.exists() {
+            fs::remove_dir_all(&package_dir).into_diagnostic()?;
+        }
+
+        extract_cached(&config, package, &package_dir)?;
+        apply_patch(&package_dir, &path, &package.key())?;
+
+        fs::remove_dir_all(&workspace).into_diagnostic()?;
+
+        println!(
+            "{} {} to {}",
+            "Saved".bright_green().bold(),
+            package.key(),
+            relative_path
+        );
+
+        Ok(())
+    }
+}
+
+/// The installed package matching `name` or `name@version`
+fn find_installed<'a>(lock_file: &'a LockFile, spec: &str) -> Result<&'a VoltPackage> {
+    // the first `@` of a scoped package is part of its name
+    let (name, version) = match spec.rfind('@') {
+        Some(index) if index > 0 => (&spec[..index], Some(&spec[index + 1..])),
+        _ => (spec, None),
+    };
+
+    let installed = lock_file
+        .dependencies
+        .values()
+        .filter(|package| package.name == name)
+        .filter(|package| version.map_or(true, |version| package.version == version))
+        .collect::<Vec<_>>();
+
+    match installed.as_slice() {
+        [package] => Ok(package),
+        [] => miette::bail!("{} is not installed", spec),
+        _ => miette::bail!(
+            "{} versions of {} are installed ({}), pick one with `{}@<version>`",
+            installed.len(),
+            name,
+            installed
+                .iter()
+                .map(|package| package.version.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            name
+        ),
+    }
+}
@@ -0,0 +1,326 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Manage plugins, and run them for the commands volt doesn't know.
+//!
+//! `volt deploy --prod` runs the first `volt-deploy` executable found in `~/.volt/plugins` or
+//! on the `PATH` with `--prod`. Plugins get every option as its `VOLT_*` environment variable,
+//! and `VOLT_PLUGIN_CONTEXT` holds the project's paths and the options as JSON:
+//!
+//! ```text
+//! {"voltVersion":"1.0.0","cwd":"/app","lockfile":"/app/volt.lock","nodeModules":"/app/node_modules",...}
+//! ```
+
+use crate::{
+    cli::{VoltCli, VoltCommand, VoltConfig},
+    core::{
+        config::KEYS,
+        output::{print_json, status},
+        utils::errors::VoltError,
+    },
+};
+
+use async_trait::async_trait;
+use clap::{CommandFactory, Parser, Subcommand};
+use colored::Colorize;
+use miette::{IntoDiagnostic, Result};
+use serde::Serialize;
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+const PREFIX: &str = "volt-";
+
+/// Manage the plugins providing extra commands
+#[derive(Debug, Parser)]
+pub struct Plugin {
+    #[clap(subcommand)]
+    command: PluginCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum PluginCommand {
+    /// List the plugins in ~/.volt/plugins and on the PATH
+    List,
+    /// Install an executable as a plugin in ~/.volt/plugins
+    Add {
+        /// Path to the executable, `volt-deploy` provides `volt deploy`
+        path: PathBuf,
+
+        /// The command the plugin provides, instead of the one in its file name
+        #[clap(long)]
+        name: Option<String>,
+    },
+    /// Remove a plugin from ~/.volt/plugins
+    Remove { name: String },
+}
+
+/// A plugin and where it was found
+#[derive(Debug, Serialize)]
+pub struct Installed {
+    pub name: String,
+    pub path: PathBuf,
+    /// `plugins` for `~/.volt/plugins`, `path` for the `PATH`
+    pub source: &'static str,
+}
+
+/// What a plugin gets in `VOLT_PLUGIN_CONTEXT`
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Context<'a> {
+    volt_version: &'static str,
+    cwd: PathBuf,
+    volt_home: PathBuf,
+    /// The resolved dependency graph of the project
+    lockfile: PathBuf,
+    node_modules: PathBuf,
+    json: bool,
+    config: BTreeMap<&'static str, &'a str>,
+}
+
+#[async_trait]
+impl VoltCommand for Plugin {
+    /// Execute the `volt plugin` command
+    ///
+    /// List, install or remove the plugins volt runs for the commands it doesn't know.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Make `volt deploy` run ./bin/volt-deploy
+    /// // .exec() is an async call so you need to await it
+    /// Plugin { command: PluginCommand::Add { path: "bin/volt-deploy".into(), name: None } }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        match self.command {
+            PluginCommand::List => {
+                let plugins = find_plugins(&config)?;
+
+                if config.json() {
+                    return print_json(&plugins);
+                }
+
+                if plugins.is_empty() {
+                    status(&config, "No plugins installed");
+                }
+
+                for plugin in &plugins {
+                    println!(
+                        "{} {}",
+                        plugin.name.bold(),
+                        plugin.path.display().to_string().truecolor(156, 156, 156)
+                    );
+                }
+            }
+            PluginCommand::Add { path, name } => {
+                let name = match name.or_else(|| plugin_name(&path)) {
+                    Some(name) => name,
+                    None => path
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().to_string())
+                        .unwrap_or_default(),
+                };
+
+                if !path.is_file() {
+                    return Err(VoltError::ReadFileError {
+                        source: std::io::ErrorKind::NotFound.into(),
+                        name: path.display().to_string(),
+                    }
+                    .into());
+                }
+
+                let plugins_dir = config.plugins_dir()?;
+                fs::create_dir_all(&plugins_dir).map_err(VoltError::CreateDirError)?;
+
+                let target = plugins_dir.join(file_name(&name));
+
+                fs::copy(&path, &target).map_err(|e| VoltError::WriteFileError {
+                    source: e,
+                    name: target.display().to_string(),
+                })?;
+
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+
+                    fs::set_permissions(&target, fs::Permissions::from_mode(0o755))
+                        .into_diagnostic()?;
+                }
+
+                status(
+                    &config,
+                    format!(
+                        "{} {}, run it with {}",
+                        "Installed".bright_green().bold(),
+                        target.display(),
+                        format!("volt {}", name).bright_cyan()
+                    ),
+                );
+
+                if VoltCli::command().find_subcommand(&name).is_some() {
+                    eprintln!(
+                        "{}: volt has a built-in `{}` command, which runs instead of the plugin",
+                        "warning".bright_yellow().bold(),
+                        name
+                    );
+                }
+            }
+            PluginCommand::Remove { name } => {
+                let target = config.plugins_dir()?.join(file_name(&name));
+
+                if !target.exists() {
+                    return Err(VoltError::PluginNotFoundError { name }.into());
+                }
+
+                fs::remove_file(&target).map_err(|e| VoltError::WriteFileError {
+                    source: e,
+                    name: target.display().to_string(),
+                })?;
+
+                status(
+                    &config,
+                    format!("{} {}", "Removed".bright_green().bold(), target.display()),
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Run the plugin for a command volt doesn't know, `args` starting with the command's name
+pub async fn run_external(config: VoltConfig, args: Vec<String>) -> Result<()> {
+    let (name, args) = args.split_first().ok_or(VoltError::UnknownError)?;
+
+    let plugin = find_plugins(&config)?
+        .into_iter()
+        .find(|plugin| &plugin.name == name)
+        .ok_or_else(|| VoltError::PluginNotFoundError { name: name.clone() })?;
+
+    let options = config.options();
+
+    let context = Context {
+        volt_version: env!("CARGO_PKG_VERSION"),
+        cwd: config.cwd()?,
+        volt_home: config.volt_home()?,
+        lockfile: config.lockfile()?,
+        node_modules: config.node_modules()?,
+        json: config.json(),
+        config: KEYS
+            .iter()
+            .map(|key| (key.name, options.value(key.name)))
+            .collect(),
+    };
+
+    let status = Command::new(&plugin.path)
+        .args(args)
+        .current_dir(&context.cwd)
+        .envs(KEYS.iter().map(|key| (key.env(), options.value(key.name))))
+        .env(
+            "VOLT_PLUGIN_CONTEXT",
+            serde_json::to_string(&context).into_diagnostic()?,
+        )
+        .status()
+        .into_diagnostic()?;
+
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+
+    Ok(())
+}
+
+/// Every plugin, the ones in `~/.volt/plugins` taking precedence over the ones on the `PATH`
+pub fn find_plugins(config: &VoltConfig) -> Result<Vec<Installed>> {
+    let mut directories = vec![(config.plugins_dir()?, "plugins")];
+
+    if let Some(paths) = std::env::var_os("PATH") {
+        directories.extend(std::env::split_paths(&paths).map(|path| (path, "path")));
+    }
+
+    let mut seen = HashSet::new();
+    let mut plugins = vec![];
+
+    for (directory, source) in directories {
+        let mut found = fs::read_dir(&directory)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| is_executable(path))
+            .filter_map(|path| Some((plugin_name(&path)?, path)))
+            .collect::<Vec<_>>();
+
+        found.sort();
+
+        for (name, path) in found {
+            if seen.insert(name.clone()) {
+                plugins.push(Installed { name, path, source });
+            }
+        }
+    }
+
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(plugins)
+}
+
+/// The command a `volt-*` executable provides
+fn plugin_name(path: &Path) -> Option<String> {
+    let file_name = path.file_name()?.to_str()?;
+
+    let file_name = if cfg!(windows) {
+        file_name
+            .strip_suffix(".exe")
+            .or_else(|| file_name.strip_suffix(".cmd"))
+            .unwrap_or(file_name)
+    } else {
+        file_name
+    };
+
+    file_name
+        .strip_prefix(PREFIX)
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+}
+
+fn file_name(name: &str) -> String {
+    if cfg!(windows) {
+        format!("{}{}.exe", PREFIX, name)
+    } else {
+        format!("{}{}", PREFIX, name)
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path).map_or(false, |metadata| {
+        metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+    })
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
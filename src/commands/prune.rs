@@ -0,0 +1,235 @@
+/*
+    Copyright 2021, 2022 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Remove packages that nothing in the project depends on.
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::model::lock_file::{DependencyKind, LockFile},
+    core::utils::{directory_size, package::PackageJson, remove_link},
+};
+
+use async_trait::async_trait;
+use clap::Parser;
+use colored::Colorize;
+use indicatif::HumanBytes;
+use miette::{IntoDiagnostic, Result};
+use std::{
+    collections::BTreeSet,
+    fs::read_dir,
+    path::{Component, Path, PathBuf},
+};
+
+/// Remove extraneous packages from node_modules
+#[derive(Debug, Parser)]
+pub struct Prune {
+    /// Also remove devDependencies, leaving only what is needed to run the project
+    #[clap(long)]
+    production: bool,
+}
+
+#[async_trait]
+impl VoltCommand for Prune {
+    /// Execute the `volt prune` command
+    ///
+    /// Remove every package in node_modules that can't be reached from the dependencies of the
+    /// project and its workspaces.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Remove dev dependencies before deploying
+    /// // .exec() is an async call so you need to await it
+    /// Prune { production: true }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let project_dir = config.cwd()?;
+        let node_modules = config.node_modules()?;
+
+        let (package_file, _) = PackageJson::get_from_dir(&project_dir)?;
+
+        let mut lock_file = LockFile::load(config.lockfile()?, false)?;
+
+        let mut manifests = vec![package_file.clone()];
+
+        manifests.extend(
+            package_file
+                .workspace_packages(&project_dir)?
+                .into_iter()
+                .map(|(_, manifest)| manifest),
+        );
+
+        let mut root_names = BTreeSet::new();
+        let mut roots = vec![];
+
+        for manifest in &manifests {
+            for (kind, _, package) in lock_file.roots(manifest) {
+                if self.production && kind == DependencyKind::Dev {
+                    continue;
+                }
+
+                if let Some(package) = package {
+                    root_names.insert(package.name.clone());
+                    roots.push(package);
+                }
+            }
+        }
+
+        let reachable = lock_file.reachable(roots);
+
+        let keep = lock_file
+            .dependencies
+            .iter()
+            .filter(|(key, _)| reachable.contains(*key))
+            .map(|(_, package)| package.directory_name())
+            .collect::<BTreeSet<_>>();
+
+        let mut removed = 0;
+        let mut saved = 0;
+
+        // node_modules/.volt/<name>@<version>
+        for directory in list_dir(&node_modules.join(".volt"))? {
+            let name = match directory.file_name().and_then(|n| n.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+
+            if keep.contains(&name) || !directory.is_dir() {
+                continue;
+            }
+
+            saved += directory_size(&directory);
+
+            std::fs::remove_dir_all(&directory).into_diagnostic()?;
+
+            println!(
+                "{} {}",
+                "Removed".bright_red().bold(),
+                name.replace('+', "/")
+            );
+
+            removed += 1;
+        }
+
+        // node_modules/<name> and node_modules/@scope/<name>
+        let mut entries = vec![];
+
+        for entry in list_dir(&node_modules)? {
+            match entry.file_name().and_then(|n| n.to_str()) {
+                Some(name) if name.starts_with('.') => {}
+                Some(name) if name.starts_with('@') && !is_link(&entry) => {
+                    entries.extend(list_dir(&entry)?)
+                }
+                Some(_) => entries.push(entry),
+                None => {}
+            }
+        }
+
+        for entry in entries {
+            let name = entry
+                .strip_prefix(&node_modules)
+                .unwrap_or(&entry)
+                .to_string_lossy()
+                .replace('\\', "/");
+
+            if root_names.contains(&name) {
+                continue;
+            }
+
+            if is_link(&entry) {
+                // workspace packages and packages linked with `volt link` point elsewhere
+                if links_into_store(&entry, &node_modules) {
+                    remove_link(&entry)?;
+                }
+            } else {
+                // packages installed by another package manager
+                saved += directory_size(&entry);
+
+                std::fs::remove_dir_all(&entry).into_diagnostic()?;
+
+                println!("{} {}", "Removed".bright_red().bold(), name);
+
+                removed += 1;
+            }
+        }
+
+        // dev dependencies are still declared, so a production prune keeps them locked
+        if !self.production {
+            lock_file
+                .dependencies
+                .retain(|key, _| reachable.contains(key));
+
+            lock_file.direct.retain(|name, _| root_names.contains(name));
+
+            lock_file.save()?;
+        }
+
+        if removed == 0 {
+            println!("{}", "No extraneous packages found!".bright_green());
+        } else {
+            println!(
+                "{} {} packages, saved {}",
+                "Pruned".bright_green().bold(),
+                removed,
+                HumanBytes(saved)
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Entries of a directory, or nothing if it doesn't exist
+fn list_dir(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    read_dir(path)
+        .into_diagnostic()?
+        .map(|entry| entry.map(|e| e.path()).into_diagnostic())
+        .collect()
+}
+
+fn is_link(path: &Path) -> bool {
+    path.symlink_metadata()
+        .map_or(false, |m| m.file_type().is_symlink())
+}
+
+/// Whether a link points into `node_modules/.volt`, where it's resolved without following it,
+/// since the package it points to may have just been removed
+fn links_into_store(link: &Path, node_modules: &Path) -> bool {
+    let target = match std::fs::read_link(link) {
+        Ok(target) => target,
+        Err(_) => return false,
+    };
+
+    let mut resolved = PathBuf::new();
+
+    for component in link.parent().unwrap_or(link).join(target).components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            component => resolved.push(component),
+        }
+    }
+
+    resolved.starts_with(node_modules.join(".volt"))
+}
@@ -14,33 +14,117 @@
     limitations under the License.
 */
 
-//! Check for outdated packages.
+//! Publish a package to the registry.
 
 use async_trait::async_trait;
-use miette::Result;
+use clap::Parser;
+use colored::Colorize;
+use miette::{IntoDiagnostic, Result};
+use serde_json::{json, Value};
 
-use std::sync::Arc;
+use std::fs::read_to_string;
 
-use crate::cli::{VoltCommand, VoltConfig};
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    commands::pack::{pack_project, print_tarball},
+    core::auth::{Access, RegistryClient},
+    core::utils::package::PackageJson,
+};
 
-pub struct Publish {}
+/// Publish the package in the current directory to the registry
+#[derive(Debug, Parser)]
+pub struct Publish {
+    /// The dist-tag to publish the version under
+    #[clap(long, default_value = "latest")]
+    tag: String,
+
+    /// Who can install the package, scoped packages are restricted by default
+    #[clap(long, arg_enum)]
+    access: Option<Access>,
+
+    /// Pack the package and print what would be published without uploading it
+    #[clap(long)]
+    dry_run: bool,
+}
 
 #[async_trait]
 impl VoltCommand for Publish {
-    /// Execute the `volt outdated` command
+    /// Execute the `volt publish` command
     ///
-    /// Check for outdated packages
+    /// Pack the package exactly like `volt pack` and upload the tarball along with its
+    /// manifest. Accounts with two-factor authentication are asked for a one-time password.
     /// ## Arguments
-    /// * `error` - Instance of the command (`Arc<App>`)
+    /// * `config` - The global volt configuration (`VoltConfig`)
     /// ## Examples
     /// ```
-    /// // Check for outdated packages
+    /// // Publish a prerelease without moving `latest`
     /// // .exec() is an async call so you need to await it
-    /// Publish.exec(app).await;
+    /// Publish { tag: "next".into(), access: None, dry_run: false }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
     async fn exec(self, config: VoltConfig) -> Result<()> {
+        let (_, package_path) = PackageJson::get_from_dir(&config.cwd()?)?;
+        let project_dir = package_path.parent().unwrap_or(&package_path);
+
+        let manifest: Value =
+            serde_json::from_str(&read_to_string(&package_path).into_diagnostic()?)
+                .into_diagnostic()?;
+
+        if manifest.get("private").and_then(Value::as_bool) == Some(true) {
+            miette::bail!("package.json is marked private, refusing to publish");
+        }
+
+        let tarball = pack_project(project_dir)?;
+
+        print_tarball(&tarball);
+
+        if self.dry_run {
+            return Ok(());
+        }
+
+        let mut client = RegistryClient::new(&config)?;
+
+        let basename = tarball.name.rsplit('/').next().unwrap_or(&tarball.name);
+
+        let mut version = manifest.clone();
+
+        version["_id"] = json!(format!("{}@{}", tarball.name, tarball.version));
+        version["dist"] = json!({
+            "integrity": tarball.integrity,
+            "shasum": tarball.shasum,
+            "tarball": client.url(&format!(
+                "{}/-/{}-{}.tgz",
+                tarball.name, basename, tarball.version
+            )),
+        });
+
+        let body = json!({
+            "_id": tarball.name,
+            "name": tarball.name,
+            "description": manifest.get("description"),
+            "dist-tags": { &self.tag: tarball.version },
+            "versions": { &tarball.version: version },
+            "access": self.access.map(Access::as_str),
+            "_attachments": {
+                &tarball.filename: {
+                    "content_type": "application/octet-stream",
+                    "data": base64::encode(&tarball.data),
+                    "length": tarball.data.len(),
+                }
+            }
+        });
+
+        client.put(&tarball.name.replace('/', "%2f"), &body).await?;
+
+        println!(
+            "{} {}@{} with the {} tag",
+            "Published".bright_green().bold(),
+            tarball.name,
+            tarball.version,
+            self.tag.bright_cyan()
+        );
+
         Ok(())
     }
 }
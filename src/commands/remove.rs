@@ -16,16 +16,32 @@
 
 //! Remove a package from your direct dependencies.
 
-use crate::cli::{VoltCommand, VoltConfig};
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::model::{lock_file::LockFile, plan::Plan},
+    core::utils::{package::PackageJson, read_bins, remove_link, unlink_bins},
+};
 
 use async_trait::async_trait;
+use clap::Parser;
 use colored::Colorize;
 use miette::Result;
 
-use std::sync::Arc;
+/// Remove a package from your project's dependencies
+#[derive(Debug, Parser)]
+pub struct Remove {
+    /// Packages to remove from the dependencies for your project.
+    #[clap(required = true)]
+    packages: Vec<String>,
 
-/// Struct implementation for the `Remove` command.
-pub struct Remove;
+    /// Remove globally installed packages and their executables
+    #[clap(short, long)]
+    global: bool,
+
+    /// Print the packages that would be removed, without removing them
+    #[clap(long)]
+    dry_run: bool,
+}
 
 #[async_trait]
 impl VoltCommand for Remove {
@@ -33,99 +49,94 @@ impl VoltCommand for Remove {
     ///
     /// Removes a package from your direct dependencies.
     /// ## Arguments
-    /// * `app` - Instance of the command (`Arc<App>`)
-    /// * `packages` - List of packages to add (`Vec<String>`)
-    /// * `flags` - List of flags passed in through the CLI (`Vec<String>`)
+    /// * `config` - The global volt configuration (`VoltConfig`)
     /// ## Examples
     /// ```
-    /// // Remove a package from your direct dependencies with logging level verbose
+    /// // Remove a globally installed package
     /// // .exec() is an async call so you need to await it
-    /// Remove.exec(app, vec![], vec!["--verbose"]).await;
+    /// Remove { packages: vec!["cowsay".into()], global: true, dry_run: false }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
     async fn exec(self, config: VoltConfig) -> Result<()> {
-        // if app.args.len() == 1 {
-        //     println!("{}", Self::help());
-        //     process::exit(1);
-        // }
-
-        // let mut packages = vec![];
-        // for arg in &app.args {
-        //     if arg != "remove" {
-        //         packages.push(arg.clone());
-        //     }
-        // }
-
-        // let package_file = Arc::new(Mutex::new(PackageJson::from("package.json")));
-
-        // // let mut handles = vec![];
-
-        // println!("{}", "Removing dependencies".bright_purple());
-
-        // for package in packages {
-        //     let package_file = package_file.clone();
-        //     let app_new = app.clone();
-
-        //     // handles.push(tokio::spawn(async move {
-        //     let mut package_json_file = package_file.lock().await;
-
-        //     package_json_file.dependencies.remove(&package);
+        let config = config.with_dry_run(self.dry_run);
 
-        //     package_json_file.save();
+        let config = if self.global {
+            config.with_cwd(config.global_dir()?)
+        } else {
+            config
+        };
 
-        //     let mut lock_file = LockFile::load(app_new.lock_file_path.to_path_buf())
-        //         .unwrap_or_else(|_| LockFile::new(app_new.lock_file_path.to_path_buf()));
-
-        //     // let response = get_volt_response(&package).await?;
-
-        //     // let current_version = response.versions.get(&response.version).unwrap();
+        remove_packages(&config, &self.packages, self.global)
+    }
+}
 
-        //     // for object in current_version.values() {
-        //     //     // This is doing nothing? I guess it's still WIP?...
-        //     //     // let mut lock_dependencies: HashMap<String, String> = HashMap::new();
+/// Remove `packages` from the package.json, `node_modules` and lockfile of the project at
+/// `config.cwd()`
+pub fn remove_packages(config: &VoltConfig, packages: &[String], global: bool) -> Result<()> {
+    if config.dry_run() {
+        let before = LockFile::load(config.lockfile()?, global)?;
+        let mut after = before.clone();
 
-        //     //     // if object.dependencies.is_some() {
-        //     //     //     for dep in object.clone().dependencies.unwrap().iter() {
-        //     //     //         // TODO: Change this to real version
-        //     //     //         lock_dependencies.insert(dep.clone(), String::new());
-        //     //     //     }
-        //     //     // }
+        for package in packages {
+            after.direct.remove(package);
+        }
 
-        //     //     lock_file
-        //     //         .dependencies
-        //     //         .remove(&DependencyID(object.clone().name, object.clone().version));
+        after.prune();
 
-        //     //     let scripts = Path::new("node_modules/scripts")
-        //     //         .join(format!("{}.cmd", object.clone().name).as_str());
+        return Plan::between(&before, &after).print(config);
+    }
 
-        //     //     if scripts.exists() {
-        //     //         remove_file(format!(
-        //     //             "node_modules/scripts/{}",
-        //     //             scripts.file_name().unwrap().to_str().unwrap()
-        //     //         ))
-        //     //         .await
-        //     //         .unwrap_and_handle_error();
-        //     //     }
-        //     // }
+    let project_dir = config.cwd()?;
+    let node_modules = config.node_modules()?;
+
+    let (mut manifest, manifest_path) = if global {
+        (
+            PackageJson::load_or_new(&project_dir, "volt-global")?,
+            project_dir.join("package.json"),
+        )
+    } else {
+        PackageJson::get_from_dir(&project_dir)?
+    };
+
+    for package in packages {
+        if !manifest.remove_dependency(package) {
+            println!(
+                "{}: {} is not a dependency",
+                "warning".yellow().bold(),
+                package.bright_yellow()
+            );
+            continue;
+        }
+
+        let package_dir = node_modules.join(package);
+
+        if global {
+            unlink_bins(
+                &config.global_bin()?,
+                read_bins(&package_dir, package)?,
+                &package_dir,
+            )?;
+        }
+
+        if package_dir.symlink_metadata().is_ok() {
+            remove_link(&package_dir)?;
+        }
+
+        println!("{} {}", "Removed".bright_green().bold(), package);
+    }
 
-        //     // lock_file.save().unwrap();
+    manifest.save_to(&manifest_path)?;
 
-        //     // let node_modules_dir = std::env::current_dir().unwrap().join("node_modules");
-        //     // let dep_dir = node_modules_dir.join(&package);
-        //     // if dep_dir.exists() {
-        //     //     remove_dir_all(dep_dir).await.unwrap_and_handle_error();
-        //     // }
-        // }
+    let mut lock_file = LockFile::load(config.lockfile()?, global)?;
 
-        // // if handles.len() > 0 {
-        // //     for handle in handles {
-        // //         handle.await?;
-        // //     }
-        // // }
+    for package in packages {
+        lock_file.direct.remove(package);
+    }
 
-        // println!("{}", "Successfully Removed Packages".bright_blue());
+    // along with the packages only the removed ones depended on
+    lock_file.prune();
+    lock_file.save()?;
 
-        Ok(())
-    }
+    Ok(())
 }
@@ -14,15 +14,20 @@
     limitations under the License.
 */
 
-use crate::cli::{VoltCommand, VoltConfig};
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    commands::add::check_engines,
+    core::{
+        model::lock_file::LockFile,
+        utils::{errors::VoltError, package::PackageJson, scripts::run_script},
+    },
+};
 
 use async_trait::async_trait;
 use clap::Parser;
-use colored::Colorize;
 use miette::Result;
-use std::path::Path;
-use std::process::{Command, Stdio};
-use std::sync::Arc;
+
+use std::collections::HashMap;
 
 /// Run a pre-defined package script
 #[derive(Debug, Parser)]
@@ -33,37 +38,65 @@ pub struct Run {
 
 #[async_trait]
 impl VoltCommand for Run {
-    /// Display a help menu for the `volt run` command.
     /// Execute the `volt run` command
     ///
-    /// Interactively create or update a package.json file for a project.
+    /// Run a script from the project's package.json, or an executable from its
+    /// `node_modules/.bin`, with the node runtime the project pins first on the `PATH`. The
+    /// `engines` of the project and its installed packages are checked first.
     /// ## Arguments
-    /// * `app` - Instance of the command (`Arc<App>`)
-    /// * `packages` - List of packages to add (`Vec<String>`)
-    /// * `flags` - List of flags passed in through the CLI (`Vec<String>`)
+    /// * `config` - The global volt configuration (`VoltConfig`)
     /// ## Examples
     /// ```
-    /// // Run a defined script.
+    /// // Run the build script of the project
     /// // .exec() is an async call so you need to await it
-    /// Run.exec(app, vec![], vec!["--yes"]).await;
+    /// Run { script: "build".into() }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
     async fn exec(self, config: VoltConfig) -> Result<()> {
-        if cfg!(target_os = "windows") {
-            Command::new("cmd").args(&["/C", "babel"]).spawn().unwrap();
-        } else if cfg!(target_os = "linux") {
-            println!("{}", format!("$ {}", self.script).truecolor(156, 156, 156));
-
-            let mut child =
-                Command::new(Path::new("node_modules/").join(".bin/").join(self.script))
-                    .stdout(Stdio::inherit())
-                    .stderr(Stdio::inherit())
-                    .spawn()
-                    .expect("failed to execute child");
-
-            child.wait().unwrap();
+        let (package, path) = PackageJson::get_from_dir(&config.cwd()?)?;
+        let project_dir = path.parent().unwrap_or(&path);
+
+        // the installed packages may want a different node than the one they were installed with
+        let lock_file = LockFile::load(project_dir.join(VoltConfig::VOLT_LOCK), false)?;
+
+        let roots = lock_file
+            .direct
+            .iter()
+            .filter_map(|(name, version)| {
+                lock_file
+                    .dependencies
+                    .get(&format!("{}@{}", name, version))
+                    .cloned()
+            })
+            .collect::<Vec<_>>();
+
+        let tree = lock_file
+            .dependencies
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+
+        check_engines(&config, project_dir, &roots, &tree)?;
+
+        if let Some(script) = package
+            .scripts
+            .as_ref()
+            .and_then(|scripts| scripts.get(&self.script))
+        {
+            return run_script(&config, project_dir, &self.script, script, &[]);
+        }
+
+        // `node_modules/.bin` is on the PATH of scripts, so its executables can be run by name
+        let bin = project_dir.join("node_modules").join(".bin");
+
+        if bin.join(&self.script).exists() || bin.join(format!("{}.cmd", self.script)).exists() {
+            return run_script(&config, project_dir, &self.script, &self.script, &[]);
+        }
+
+        Err(VoltError::ScriptNotFoundError {
+            name: self.script,
+            directory: project_dir.display().to_string(),
         }
-        Ok(())
+        .into())
     }
 }
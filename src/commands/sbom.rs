@@ -0,0 +1,387 @@
+/*
+    Copyright 2021, 2022 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Export a software bill of materials for a project.
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::model::{
+        license::{package_license, LicenseSource},
+        lock_file::{DependencyKind, LockFile},
+    },
+    core::utils::{package::PackageJson, purl, voltapi::VoltPackage},
+};
+
+use async_trait::async_trait;
+use chrono::{SecondsFormat, Utc};
+use clap::{ArgEnum, Parser};
+use miette::{IntoDiagnostic, Result};
+use serde_json::{json, Value};
+use ssri::{Algorithm, Integrity};
+use std::path::{Path, PathBuf};
+
+/// Export a software bill of materials (SBOM) for the project
+#[derive(Debug, Parser)]
+pub struct Sbom {
+    /// The SBOM standard to export
+    #[clap(long, arg_enum, default_value = "cyclonedx")]
+    format: SbomFormat,
+
+    /// Write the SBOM to a file instead of stdout
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    /// Skip devDependencies
+    #[clap(long)]
+    production: bool,
+}
+
+#[derive(Debug, Clone, Copy, ArgEnum)]
+enum SbomFormat {
+    /// CycloneDX 1.4 JSON
+    Cyclonedx,
+    /// SPDX 2.3 JSON
+    SpdxJson,
+}
+
+/// A package in the SBOM along with what it depends on
+struct Component<'a> {
+    package: &'a VoltPackage,
+    license: Option<String>,
+    dependencies: Vec<&'a VoltPackage>,
+}
+
+#[async_trait]
+impl VoltCommand for Sbom {
+    /// Execute the `volt sbom` command
+    ///
+    /// Describe every package reachable from the project, with its purl, integrity hashes and
+    /// license, in a standard SBOM format.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Write an SPDX document for the production dependencies
+    /// // .exec() is an async call so you need to await it
+    /// Sbom { format: SbomFormat::SpdxJson, output: Some("sbom.spdx.json".into()), production: true }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let project_dir = config.cwd()?;
+        let node_modules = config.node_modules()?;
+
+        let (package_file, _) = PackageJson::get_from_dir(&project_dir)?;
+
+        let lock_file = LockFile::load(config.lockfile()?, false)?;
+
+        let roots = lock_file
+            .roots(&package_file)
+            .into_iter()
+            .filter(|(kind, _, _)| !(self.production && *kind == DependencyKind::Dev))
+            .filter_map(|(_, _, package)| package)
+            .collect::<Vec<_>>();
+
+        let components = lock_file
+            .reachable(roots.iter().copied())
+            .iter()
+            .filter_map(|key| lock_file.dependencies.get(key))
+            .map(|package| component(&lock_file, package, &node_modules))
+            .collect::<Vec<_>>();
+
+        let document = match self.format {
+            SbomFormat::Cyclonedx => cyclonedx(&package_file, &roots, &components),
+            SbomFormat::SpdxJson => spdx(&package_file, &roots, &components),
+        };
+
+        let document = serde_json::to_string_pretty(&document).into_diagnostic()?;
+
+        match self.output {
+            Some(path) => std::fs::write(&path, document + "\n").into_diagnostic()?,
+            None => println!("{}", document),
+        }
+
+        Ok(())
+    }
+}
+
+fn component<'a>(
+    lock_file: &'a LockFile,
+    package: &'a VoltPackage,
+    node_modules: &Path,
+) -> Component<'a> {
+    let license = package_license(package, node_modules);
+
+    Component {
+        package,
+        license: match license.source {
+            LicenseSource::Missing => None,
+            _ => Some(license.license),
+        },
+        dependencies: lock_file
+            .children(package)
+            .into_iter()
+            .map(|(_, child)| child)
+            .collect(),
+    }
+}
+
+/// The hashes in an integrity string as (algorithm, hex digest)
+fn hashes(integrity: &str) -> Vec<(Algorithm, String)> {
+    integrity
+        .parse::<Integrity>()
+        .map(|integrity| {
+            integrity
+                .hashes
+                .iter()
+                .filter_map(|hash| {
+                    let digest = base64::decode(&hash.digest).ok()?;
+
+                    Some((hash.algorithm, hex::encode(digest)))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn random_uuid() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+
+    // version 4, variant 1
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = hex::encode(bytes);
+
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+fn cyclonedx(project: &PackageJson, roots: &[&VoltPackage], components: &[Component]) -> Value {
+    let project_ref = purl(&project.name, &project.version);
+
+    let bom_components = components
+        .iter()
+        .map(|component| {
+            let package = component.package;
+
+            let (group, name) = match package.name.split_once('/') {
+                Some((scope, name)) if scope.starts_with('@') => (Some(scope), name),
+                _ => (None, package.name.as_str()),
+            };
+
+            let mut value = json!({
+                "type": "library",
+                "bom-ref": purl(&package.name, &package.version),
+                "name": name,
+                "version": package.version,
+                "purl": purl(&package.name, &package.version),
+                "hashes": hashes(&package.integrity)
+                    .into_iter()
+                    .filter_map(|(algorithm, content)| {
+                        let alg = match algorithm {
+                            Algorithm::Sha512 => "SHA-512",
+                            Algorithm::Sha384 => "SHA-384",
+                            Algorithm::Sha256 => "SHA-256",
+                            Algorithm::Sha1 => "SHA-1",
+                            _ => return None,
+                        };
+
+                        Some(json!({ "alg": alg, "content": content }))
+                    })
+                    .collect::<Vec<_>>(),
+                "externalReferences": [{ "type": "distribution", "url": package.tarball }],
+            });
+
+            if let Some(group) = group {
+                value["group"] = json!(group);
+            }
+
+            if let Some(license) = &component.license {
+                value["licenses"] = if license.contains(' ') {
+                    if license.starts_with("SEE LICENSE") {
+                        json!([{ "license": { "name": license } }])
+                    } else {
+                        json!([{ "expression": license }])
+                    }
+                } else {
+                    json!([{ "license": { "id": license } }])
+                };
+            }
+
+            value
+        })
+        .collect::<Vec<_>>();
+
+    let mut dependencies = vec![json!({
+        "ref": project_ref,
+        "dependsOn": roots
+            .iter()
+            .map(|package| purl(&package.name, &package.version))
+            .collect::<Vec<_>>(),
+    })];
+
+    dependencies.extend(components.iter().map(|component| {
+        json!({
+            "ref": purl(&component.package.name, &component.package.version),
+            "dependsOn": component
+                .dependencies
+                .iter()
+                .map(|package| purl(&package.name, &package.version))
+                .collect::<Vec<_>>(),
+        })
+    }));
+
+    json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.4",
+        "serialNumber": format!("urn:uuid:{}", random_uuid()),
+        "version": 1,
+        "metadata": {
+            "timestamp": Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            "tools": [{ "vendor": "Volt", "name": "volt", "version": env!("CARGO_PKG_VERSION") }],
+            "component": {
+                "type": "application",
+                "bom-ref": project_ref,
+                "name": project.name,
+                "version": project.version,
+                "purl": project_ref,
+            },
+        },
+        "components": bom_components,
+        "dependencies": dependencies,
+    })
+}
+
+/// SPDX element id for a package, which may only contain letters, digits, `.` and `-`
+fn spdx_id(name: &str, version: &str) -> String {
+    let id = format!("{}-{}", name, version)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>();
+
+    format!("SPDXRef-Package-{}", id)
+}
+
+fn spdx(project: &PackageJson, roots: &[&VoltPackage], components: &[Component]) -> Value {
+    let project_id = spdx_id(&project.name, &project.version);
+
+    let mut packages = vec![json!({
+        "name": project.name,
+        "SPDXID": project_id,
+        "versionInfo": project.version,
+        "downloadLocation": "NOASSERTION",
+        "filesAnalyzed": false,
+        "licenseConcluded": "NOASSERTION",
+        "licenseDeclared": project.license.as_deref().unwrap_or("NOASSERTION"),
+        "copyrightText": "NOASSERTION",
+    })];
+
+    let mut relationships = vec![json!({
+        "spdxElementId": "SPDXRef-DOCUMENT",
+        "relationshipType": "DESCRIBES",
+        "relatedSpdxElement": project_id,
+    })];
+
+    relationships.extend(roots.iter().map(|package| {
+        json!({
+            "spdxElementId": project_id,
+            "relationshipType": "DEPENDS_ON",
+            "relatedSpdxElement": spdx_id(&package.name, &package.version),
+        })
+    }));
+
+    for component in components {
+        let package = component.package;
+        let id = spdx_id(&package.name, &package.version);
+
+        // `SEE LICENSE IN <file>` isn't a valid SPDX expression
+        let license = component
+            .license
+            .as_deref()
+            .filter(|license| !license.starts_with("SEE LICENSE"))
+            .unwrap_or("NOASSERTION");
+
+        packages.push(json!({
+            "name": package.name,
+            "SPDXID": id,
+            "versionInfo": package.version,
+            "downloadLocation": package.tarball,
+            "filesAnalyzed": false,
+            "checksums": hashes(&package.integrity)
+                .into_iter()
+                .filter_map(|(algorithm, value)| {
+                    let algorithm = match algorithm {
+                        Algorithm::Sha512 => "SHA512",
+                        Algorithm::Sha384 => "SHA384",
+                        Algorithm::Sha256 => "SHA256",
+                        Algorithm::Sha1 => "SHA1",
+                        _ => return None,
+                    };
+
+                    Some(json!({ "algorithm": algorithm, "checksumValue": value }))
+                })
+                .collect::<Vec<_>>(),
+            "licenseConcluded": "NOASSERTION",
+            "licenseDeclared": license,
+            "copyrightText": "NOASSERTION",
+            "externalRefs": [{
+                "referenceCategory": "PACKAGE-MANAGER",
+                "referenceType": "purl",
+                "referenceLocator": purl(&package.name, &package.version),
+            }],
+        }));
+
+        relationships.extend(component.dependencies.iter().map(|dependency| {
+            json!({
+                "spdxElementId": id,
+                "relationshipType": "DEPENDS_ON",
+                "relatedSpdxElement": spdx_id(&dependency.name, &dependency.version),
+            })
+        }));
+    }
+
+    json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": project.name,
+        "documentNamespace": format!(
+            "https://voltpkg.com/spdx/{}-{}-{}",
+            project.name.replace('/', "-"),
+            project.version,
+            random_uuid()
+        ),
+        "creationInfo": {
+            "created": Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            "creators": [format!("Tool: volt-{}", env!("CARGO_PKG_VERSION"))],
+        },
+        "packages": packages,
+        "relationships": relationships,
+    })
+}
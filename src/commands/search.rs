@@ -16,7 +16,11 @@
 
 //! Search for a package.
 
-use crate::cli::{VoltCommand, VoltConfig};
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::net::search_registry,
+    core::output::print_json,
+};
 
 use async_trait::async_trait;
 use clap::Parser;
@@ -25,69 +29,62 @@ use comfy_table::{
     modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL, Attribute, Cell, Color, ContentArrangement,
     Table,
 };
-use isahc::AsyncReadResponseExt;
 use miette::Result;
-use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-
-#[derive(Serialize, Deserialize)]
-pub struct Objects {
-    objects: Vec<SearchResults>,
-}
-
-#[derive(Serialize, Deserialize)]
-pub struct SearchResults {
-    package: SearchResult,
-}
-
-#[derive(Serialize, Deserialize)]
-pub struct SearchResult {
-    name: String,
-    version: String,
-    description: String,
-}
 
 /// Searches for a package
 #[derive(Debug, Parser)]
 pub struct Search {
     /// Search query
     query: String,
-}
 
-#[derive(Debug, Serialize, Deserialize, Default)]
-pub struct SearchData {
-    pub name: String,
-    pub version: String,
-    pub description: Option<String>,
+    /// How many results to show per page, at most 250
+    #[clap(long, default_value = "20")]
+    limit: usize,
+
+    /// Which page of results to show
+    #[clap(long, default_value = "1")]
+    page: usize,
 }
 
 #[async_trait]
 impl VoltCommand for Search {
     /// Execute the `volt search` command
     ///
-    /// Search for a package
+    /// Search the registry for packages matching a query
     /// ## Arguments
-    /// * `error` - Instance of the command (`Arc<App>`)
+    /// * `config` - The global volt configuration (`VoltConfig`)
     /// ## Examples
     /// ```
-    /// // Search for a package
+    /// // Show the second page of results for `react router`
     /// // .exec() is an async call so you need to await it
-    /// Search.exec(app).await;
+    /// Search { query: "react router".into(), limit: 20, page: 2 }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
-    async fn exec(self, app: VoltConfig) -> Result<()> {
-        let response = isahc::get_async(format!(
-            "https://registry.npmjs.org/-/v1/search?text={}&popularity=1.0",
-            self.query
-        ))
-        .await
-        .unwrap()
-        .text()
-        .await
-        .unwrap();
-
-        let s: Objects = serde_json::from_str(&response).unwrap();
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        if self.limit == 0 || self.limit > 250 {
+            miette::bail!("--limit must be between 1 and 250");
+        }
+
+        if self.page == 0 {
+            miette::bail!("--page starts at 1");
+        }
+
+        let from = (self.page - 1) * self.limit;
+
+        let results =
+            search_registry(&reqwest::Client::new(), &self.query, self.limit, from).await?;
+
+        if config.json() {
+            print_json(&results)?;
+
+            return Ok(());
+        }
+
+        if results.objects.is_empty() {
+            println!("No packages found for {}", self.query.bright_cyan());
+            return Ok(());
+        }
 
         let mut table = Table::new();
 
@@ -96,7 +93,7 @@ impl VoltCommand for Search {
             .apply_modifier(UTF8_ROUND_CORNERS)
             .set_content_arrangement(ContentArrangement::DynamicFullWidth);
 
-        table.set_header(vec![
+        let mut header = vec![
             Cell::new("Name")
                 .fg(Color::Green)
                 .add_attribute(Attribute::Bold),
@@ -106,25 +103,87 @@ impl VoltCommand for Search {
             Cell::new("Description")
                 .fg(Color::Yellow)
                 .add_attribute(Attribute::Bold),
-        ]);
+            Cell::new("Published").add_attribute(Attribute::Bold),
+        ];
+
+        // older registries don't report downloads, so only add the column when they do
+        let downloads = results
+            .objects
+            .iter()
+            .any(|result| result.downloads.is_some());
+
+        if downloads {
+            header.push(Cell::new("Weekly downloads").add_attribute(Attribute::Bold));
+        }
 
-        for i in &s.objects {
-            let mut description: String = i.package.description.clone();
+        table.set_header(header);
 
-            if i.package.description.len() > 150 {
-                description.truncate(147);
-                description = format!("{}...", description);
+        for result in &results.objects {
+            let mut description = result.package.description.clone().unwrap_or_default();
+
+            if description.chars().count() > 150 {
+                description = format!("{}...", description.chars().take(147).collect::<String>());
             }
 
-            table.add_row(vec![
-                Cell::new(&i.package.name),
-                Cell::new(&i.package.version),
+            let mut row = vec![
+                Cell::new(&result.package.name),
+                Cell::new(&result.package.version),
                 Cell::new(description),
-            ]);
+                Cell::new(
+                    result
+                        .package
+                        .date
+                        .as_deref()
+                        .and_then(|date| date.split('T').next())
+                        .unwrap_or_default(),
+                ),
+            ];
+
+            if downloads {
+                row.push(Cell::new(
+                    result
+                        .downloads
+                        .as_ref()
+                        .map(|downloads| thousands(downloads.weekly))
+                        .unwrap_or_default(),
+                ));
+            }
+
+            table.add_row(row);
         }
 
         println!("{}", table);
 
+        println!(
+            "Showing {}-{} of {} results",
+            from + 1,
+            from + results.objects.len(),
+            results.total
+        );
+
+        if from + results.objects.len() < results.total {
+            println!(
+                "Run with {} for more",
+                format!("--page {}", self.page + 1).bright_cyan()
+            );
+        }
+
         Ok(())
     }
 }
+
+/// Format a count with thousands separators, like `1,234,567`
+pub fn thousands(count: u64) -> String {
+    let digits = count.to_string();
+    let mut formatted = String::with_capacity(digits.len() + digits.len() / 3);
+
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index) % 3 == 0 {
+            formatted.push(',');
+        }
+
+        formatted.push(digit);
+    }
+
+    formatted
+}
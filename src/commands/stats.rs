@@ -0,0 +1,290 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Report how much space node_modules takes and where it goes.
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::{model::lock_file::LockFile, output::print_json},
+};
+
+use async_trait::async_trait;
+use clap::Parser;
+use colored::Colorize;
+use comfy_table::{
+    modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL, Attribute, Cell, CellAlignment,
+    ContentArrangement, Table,
+};
+use indicatif::HumanBytes;
+use miette::Result;
+use serde::Serialize;
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Component, Path},
+};
+
+/// Show the size of node_modules, the largest packages and duplicated versions
+#[derive(Debug, Parser)]
+pub struct Stats {
+    /// How many of the largest packages to list
+    #[clap(long, default_value = "10")]
+    top: usize,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Report {
+    /// Size of every file in node_modules, in bytes
+    total_size: u64,
+    packages: usize,
+    /// The largest packages, largest first
+    largest: Vec<PackageSize>,
+    /// Versions of each package installed more than once
+    duplicates: BTreeMap<String, Vec<String>>,
+    /// Packages shipping compiled `.node` addons or building them with node-gyp
+    native: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct PackageSize {
+    name: String,
+    version: String,
+    size: u64,
+    files: u64,
+}
+
+#[async_trait]
+impl VoltCommand for Stats {
+    /// Execute the `volt stats` command
+    ///
+    /// Walk node_modules once, attributing every file to the package in `.volt` it belongs to,
+    /// and combine the sizes with the lock file to report the largest packages, duplicated
+    /// versions and native addons.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Show the 20 largest packages
+    /// // .exec() is an async call so you need to await it
+    /// Stats { top: 20 }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let node_modules = config.node_modules()?;
+
+        if !node_modules.exists() {
+            miette::bail!("node_modules does not exist, run `volt install` first");
+        }
+
+        let lock_file = LockFile::load(config.lockfile()?, false)?;
+
+        // node_modules/.volt/{directory_name}/node_modules/{name}
+        let directories = lock_file
+            .dependencies
+            .values()
+            .map(|package| (package.directory_name(), package))
+            .collect::<HashMap<_, _>>();
+
+        let mut report = Report {
+            packages: lock_file.dependencies.len(),
+            ..Default::default()
+        };
+
+        let mut sizes: HashMap<String, PackageSize> = HashMap::new();
+        let mut native = HashSet::new();
+
+        // links aren't followed, so every package is only counted once
+        for entry in jwalk::WalkDir::new(&node_modules)
+            .skip_hidden(false)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+        {
+            let path = entry.path();
+            let size = path.symlink_metadata().map_or(0, |metadata| metadata.len());
+
+            report.total_size += size;
+
+            let package = match store_directory(&node_modules, &path)
+                .and_then(|directory| directories.get(&directory))
+            {
+                Some(package) => package,
+                None => continue,
+            };
+
+            let stats = sizes.entry(package.key()).or_insert_with(|| PackageSize {
+                name: package.name.clone(),
+                version: package.version.clone(),
+                ..Default::default()
+            });
+
+            stats.size += size;
+            stats.files += 1;
+
+            let file_name = entry.file_name().to_string_lossy();
+
+            if file_name == "binding.gyp" || file_name.ends_with(".node") {
+                native.insert(package.key());
+            }
+        }
+
+        // addons built on install don't have their binding.gyp published in every case
+        for package in lock_file.dependencies.values() {
+            let builds = ["preinstall", "install", "postinstall"]
+                .iter()
+                .filter_map(|script| package.scripts.as_ref()?.get(*script))
+                .any(|script| script.contains("node-gyp") || script.contains("prebuild-install"));
+
+            if builds {
+                native.insert(package.key());
+            }
+        }
+
+        let mut versions: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+
+        for package in lock_file.dependencies.values() {
+            versions
+                .entry(package.name.as_str())
+                .or_default()
+                .push(package.version.as_str());
+        }
+
+        report.duplicates = versions
+            .into_iter()
+            .filter(|(_, versions)| versions.len() > 1)
+            .map(|(name, versions)| {
+                (
+                    name.to_string(),
+                    versions.into_iter().map(String::from).collect(),
+                )
+            })
+            .collect();
+
+        let mut largest = sizes.into_values().collect::<Vec<_>>();
+
+        largest.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+        largest.truncate(self.top);
+
+        report.largest = largest;
+
+        report.native = native.into_iter().collect();
+        report.native.sort();
+
+        if config.json() {
+            print_json(&report)?;
+
+            return Ok(());
+        }
+
+        print_report(&report);
+
+        Ok(())
+    }
+}
+
+/// The directory in `node_modules/.volt` a file belongs to, if it's inside the store
+fn store_directory(node_modules: &Path, path: &Path) -> Option<String> {
+    let mut components = path.strip_prefix(node_modules).ok()?.components();
+
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(store)), Some(Component::Normal(directory)))
+            if store == ".volt" =>
+        {
+            Some(directory.to_string_lossy().to_string())
+        }
+        _ => None,
+    }
+}
+
+fn print_report(report: &Report) {
+    let extra_versions = report
+        .duplicates
+        .values()
+        .map(|versions| versions.len() - 1)
+        .sum::<usize>();
+
+    println!(
+        "{}: {}",
+        "node_modules".bold(),
+        HumanBytes(report.total_size).to_string().bright_cyan()
+    );
+    println!(
+        "{}: {}",
+        "packages".bold(),
+        report.packages.to_string().bright_cyan()
+    );
+    println!(
+        "{}: {} ({} extra versions)",
+        "duplicated".bold(),
+        report.duplicates.len().to_string().bright_cyan(),
+        extra_versions
+    );
+    println!(
+        "{}: {}",
+        "native addons".bold(),
+        report.native.len().to_string().bright_cyan()
+    );
+
+    if !report.largest.is_empty() {
+        let mut table = Table::new();
+
+        table
+            .load_preset(UTF8_FULL)
+            .apply_modifier(UTF8_ROUND_CORNERS)
+            .set_content_arrangement(ContentArrangement::Dynamic);
+
+        table.set_header(
+            ["Package", "Version", "Files", "Size", "Share"]
+                .iter()
+                .map(|header| Cell::new(header).add_attribute(Attribute::Bold)),
+        );
+
+        for package in &report.largest {
+            let share = package.size as f64 * 100.0 / report.total_size.max(1) as f64;
+
+            table.add_row(vec![
+                Cell::new(&package.name),
+                Cell::new(&package.version),
+                Cell::new(package.files).set_alignment(CellAlignment::Right),
+                Cell::new(HumanBytes(package.size)).set_alignment(CellAlignment::Right),
+                Cell::new(format!("{:.1}%", share)).set_alignment(CellAlignment::Right),
+            ]);
+        }
+
+        println!("\n{}", table);
+    }
+
+    if !report.duplicates.is_empty() {
+        println!("\n{}", "Duplicated packages".bold());
+
+        for (name, versions) in &report.duplicates {
+            println!(
+                "  {} {}",
+                name,
+                versions.join(", ").truecolor(156, 156, 156)
+            );
+        }
+    }
+
+    if !report.native.is_empty() {
+        println!("\n{}", "Native addons".bold());
+
+        for package in &report.native {
+            println!("  {}", package);
+        }
+    }
+}
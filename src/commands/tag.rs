@@ -14,33 +14,124 @@
     limitations under the License.
 */
 
-//! Check for outdated packages.
+//! Manage the distribution tags of a package.
 
-use crate::cli::{VoltCommand, VoltConfig};
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::{auth::RegistryClient, net::get_registry_package, utils::package::PackageJson},
+};
 
 use async_trait::async_trait;
+use clap::{Parser, Subcommand};
+use colored::Colorize;
 use miette::Result;
+use serde_json::json;
 
-use std::sync::Arc;
+/// Manage the distribution tags of a package
+#[derive(Debug, Parser)]
+pub struct DistTag {
+    #[clap(subcommand)]
+    command: DistTagCommand,
+}
 
-pub struct Tag {}
+#[derive(Debug, Subcommand)]
+enum DistTagCommand {
+    /// Point a tag at a published version
+    Add {
+        /// The package and version (`react@18.0.0`)
+        spec: String,
+        /// The tag to set
+        #[clap(default_value = "latest")]
+        tag: String,
+    },
+    /// Remove a tag from a package
+    Rm {
+        /// The package to remove the tag from
+        package: String,
+        /// The tag to remove
+        tag: String,
+    },
+    /// List the tags of a package, the current package by default
+    Ls {
+        /// The package to list the tags of
+        package: Option<String>,
+    },
+}
 
 #[async_trait]
-impl VoltCommand for Tag {
-    /// Execute the `volt outdated` command
+impl VoltCommand for DistTag {
+    /// Execute the `volt dist-tag` command
     ///
-    /// Check for outdated packages
+    /// Add, remove or list the tags that point at published versions of a package.
     /// ## Arguments
-    /// * `error` - Instance of the command (`Arc<App>`)
+    /// * `config` - The global volt configuration (`VoltConfig`)
     /// ## Examples
     /// ```
-    /// // Check for outdated packages
+    /// // Move `next` to a release candidate
     /// // .exec() is an async call so you need to await it
-    /// Tag.exec(app).await;
+    /// DistTag { command: DistTagCommand::Add { spec: "volt@2.0.0-rc.0".into(), tag: "next".into() } }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
     async fn exec(self, config: VoltConfig) -> Result<()> {
+        match self.command {
+            DistTagCommand::Add { spec, tag } => {
+                let (name, version) = match spec.rfind('@') {
+                    Some(index) if index > 0 => (&spec[..index], &spec[index + 1..]),
+                    _ => miette::bail!("expected a package and version like `{}@1.0.0`", spec),
+                };
+
+                let package = get_registry_package(&reqwest::Client::new(), name).await?;
+
+                if !package.versions.contains_key(version) {
+                    miette::bail!("{}@{} has not been published", name, version);
+                }
+
+                let mut client = RegistryClient::new(&config)?;
+
+                client.put(&tag_path(name, &tag), &json!(version)).await?;
+
+                println!(
+                    "{} {}: {}@{}",
+                    "+".bright_green().bold(),
+                    tag,
+                    name,
+                    version
+                );
+            }
+            DistTagCommand::Rm { package, tag } => {
+                let mut client = RegistryClient::new(&config)?;
+
+                client.delete(&tag_path(&package, &tag)).await?;
+
+                println!("{} {}: {}", "-".bright_red().bold(), tag, package);
+            }
+            DistTagCommand::Ls { package } => {
+                let name = match package {
+                    Some(package) => package,
+                    None => PackageJson::get_from_dir(&config.cwd()?)?.0.name,
+                };
+
+                let package = get_registry_package(&reqwest::Client::new(), &name).await?;
+
+                let mut tags = package.dist_tags.into_iter().collect::<Vec<_>>();
+
+                tags.sort();
+
+                for (tag, version) in tags {
+                    println!("{}: {}", tag.bright_cyan(), version);
+                }
+            }
+        }
+
         Ok(())
     }
 }
+
+fn tag_path(name: &str, tag: &str) -> String {
+    format!(
+        "-/package/{}/dist-tags/{}",
+        name.replace('/', "%2f"),
+        urlencoding::encode(tag)
+    )
+}
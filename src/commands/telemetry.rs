@@ -0,0 +1,153 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Opt into or out of anonymous usage statistics.
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::{
+        config::{read_file, write_file, Source},
+        output::{print_json, status},
+        telemetry::{self, ENDPOINT},
+    },
+};
+
+use async_trait::async_trait;
+use clap::{Parser, Subcommand};
+use colored::Colorize;
+use miette::Result;
+use serde_json::json;
+
+/// Opt into or out of anonymous usage statistics
+#[derive(Debug, Parser)]
+pub struct Telemetry {
+    #[clap(subcommand)]
+    command: TelemetryCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum TelemetryCommand {
+    /// Show whether telemetry is enabled and what it collects
+    Status,
+    /// Send anonymous usage statistics
+    Enable,
+    /// Stop sending usage statistics and delete the ones that weren't sent yet
+    Disable,
+}
+
+#[async_trait]
+impl VoltCommand for Telemetry {
+    /// Execute the `volt telemetry` command
+    ///
+    /// Enable or disable telemetry in `~/.volt/config.toml`, or show its status.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Opt into telemetry
+    /// // .exec() is an async call so you need to await it
+    /// Telemetry { command: TelemetryCommand::Enable }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        match self.command {
+            TelemetryCommand::Status => {
+                let queued = telemetry::queued(&telemetry::queue_file(&config)?).len();
+
+                let source = config
+                    .options()
+                    .get("telemetry")
+                    .map_or(Source::Default, |(_, source)| source);
+
+                if config.json() {
+                    return print_json(&json!({
+                        "enabled": config.telemetry(),
+                        "source": source,
+                        "queued": queued,
+                        "endpoint": ENDPOINT,
+                    }));
+                }
+
+                let state = if config.telemetry() {
+                    "enabled".bright_green().bold()
+                } else {
+                    "disabled".bright_red().bold()
+                };
+
+                println!("Telemetry is {} ({})", state, source);
+                println!("{} records waiting to be sent to {}", queued, ENDPOINT);
+                println!();
+                println!("Each run records:");
+                println!("  - the command, without its arguments");
+                println!("  - how long it took");
+                println!("  - the share of packages copied from the store");
+                println!("  - the OS and CPU architecture");
+                println!("  - the volt version");
+                println!("  - the code of the error it failed with");
+                println!();
+                println!("Package names, paths and anything identifying you are never recorded.");
+
+                if source == Source::Project {
+                    eprintln!(
+                        "{}: the project's settings can't enable telemetry, run {}",
+                        "warning".bright_yellow().bold(),
+                        "volt telemetry enable".bright_cyan()
+                    );
+                }
+            }
+            TelemetryCommand::Enable | TelemetryCommand::Disable => {
+                let enable = matches!(self.command, TelemetryCommand::Enable);
+
+                let path = config.user_config_file()?;
+
+                let mut table = read_file(&path)?;
+                table.insert(String::from("telemetry"), toml::Value::Boolean(enable));
+                write_file(&path, &table)?;
+
+                if enable {
+                    status(
+                        &config,
+                        format!(
+                            "{} telemetry, thank you for helping improve volt",
+                            "Enabled".bright_green().bold()
+                        ),
+                    );
+                } else {
+                    telemetry::clear(&config)?;
+
+                    status(
+                        &config,
+                        format!(
+                            "{} telemetry and deleted the records that weren't sent",
+                            "Disabled".bright_green().bold()
+                        ),
+                    );
+                }
+
+                if let Ok(overridden) = std::env::var("VOLT_TELEMETRY") {
+                    eprintln!(
+                        "{}: VOLT_TELEMETRY is set to {}, which takes precedence",
+                        "warning".bright_yellow().bold(),
+                        overridden
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
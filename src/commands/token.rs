@@ -0,0 +1,236 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Manage registry access tokens.
+
+use async_trait::async_trait;
+use clap::{Parser, Subcommand};
+use colored::Colorize;
+use comfy_table::{
+    modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL, Attribute, Cell, ContentArrangement, Table,
+};
+use miette::{IntoDiagnostic, Result};
+use serde::Deserialize;
+use serde_json::json;
+
+use std::net::IpAddr;
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::{auth::RegistryClient, prompt::prompts::Secret},
+};
+
+/// Manage the access tokens of your registry account
+#[derive(Debug, Parser)]
+pub struct Token {
+    #[clap(subcommand)]
+    command: TokenCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum TokenCommand {
+    /// List your tokens
+    #[clap(alias = "ls")]
+    List,
+    /// Create a new token
+    Create {
+        /// Only allow the token to install packages, not publish them
+        #[clap(long)]
+        read_only: bool,
+
+        /// Only accept the token from these IP ranges (`192.168.0.1/24`), comma separated
+        #[clap(long, use_value_delimiter = true)]
+        cidr: Vec<String>,
+    },
+    /// Revoke tokens by their id, or a unique prefix of it
+    #[clap(alias = "rm")]
+    Revoke {
+        #[clap(required = true)]
+        ids: Vec<String>,
+    },
+}
+
+/// A token as listed by the registry, which only reveals part of the token itself
+#[derive(Debug, Deserialize)]
+struct TokenInfo {
+    key: String,
+    token: String,
+    #[serde(default)]
+    readonly: bool,
+    #[serde(default)]
+    cidr_whitelist: Option<Vec<String>>,
+    created: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenPage {
+    objects: Vec<TokenInfo>,
+    #[serde(default)]
+    urls: TokenUrls,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TokenUrls {
+    next: Option<String>,
+}
+
+#[async_trait]
+impl VoltCommand for Token {
+    /// Execute the `volt token` command
+    ///
+    /// List, create or revoke the tokens of the logged in account, for example to give CI a
+    /// read-only token restricted to its IP range.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Create a read-only token for CI
+    /// // .exec() is an async call so you need to await it
+    /// Token { command: TokenCommand::Create { read_only: true, cidr: vec!["10.0.0.0/8".into()] } }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let mut client = RegistryClient::new(&config)?;
+
+        match self.command {
+            TokenCommand::List => {
+                let tokens = list_tokens(&mut client).await?;
+
+                let mut table = Table::new();
+
+                table
+                    .load_preset(UTF8_FULL)
+                    .apply_modifier(UTF8_ROUND_CORNERS)
+                    .set_content_arrangement(ContentArrangement::Dynamic);
+
+                table.set_header(
+                    ["id", "token", "created", "read-only", "CIDR whitelist"]
+                        .iter()
+                        .map(|header| Cell::new(header).add_attribute(Attribute::Bold)),
+                );
+
+                for token in tokens {
+                    table.add_row(vec![
+                        Cell::new(&token.key[..token.key.len().min(6)]),
+                        Cell::new(format!("{}…", token.token)),
+                        Cell::new(token.created.split('T').next().unwrap_or_default()),
+                        Cell::new(if token.readonly { "yes" } else { "no" }),
+                        Cell::new(token.cidr_whitelist.unwrap_or_default().join(", ")),
+                    ]);
+                }
+
+                println!("{}", table);
+            }
+            TokenCommand::Create { read_only, cidr } => {
+                for range in &cidr {
+                    validate_cidr(range)?;
+                }
+
+                let password = Secret {
+                    message: "Password".into(),
+                    confirm: None,
+                    error: None,
+                    allow_empty: false,
+                }
+                .run()
+                .into_diagnostic()?;
+
+                let created = client
+                    .post(
+                        "-/npm/v1/tokens",
+                        &json!({
+                            "password": password,
+                            "readonly": read_only,
+                            "cidr_whitelist": cidr,
+                        }),
+                    )
+                    .await?;
+
+                println!(
+                    "{} {}",
+                    "Created".bright_green().bold(),
+                    created["token"].as_str().unwrap_or_default().bold()
+                );
+
+                println!("This token won't be shown again, store it somewhere safe");
+            }
+            TokenCommand::Revoke { ids } => {
+                let tokens = list_tokens(&mut client).await?;
+
+                for id in &ids {
+                    let matches = tokens
+                        .iter()
+                        .filter(|token| token.key.starts_with(id.as_str()))
+                        .collect::<Vec<_>>();
+
+                    let token = match matches.as_slice() {
+                        [token] => token,
+                        [] => miette::bail!("no token matches `{}`", id),
+                        _ => miette::bail!("`{}` matches more than one token", id),
+                    };
+
+                    client
+                        .delete(&format!("-/npm/v1/tokens/token/{}", token.key))
+                        .await?;
+
+                    println!("{} {}", "Revoked".bright_red().bold(), id);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Fetch every page of the account's tokens
+async fn list_tokens(client: &mut RegistryClient) -> Result<Vec<TokenInfo>> {
+    let mut tokens = vec![];
+    let mut path = String::from("-/npm/v1/tokens");
+
+    loop {
+        let page: TokenPage = serde_json::from_value(client.get(&path).await?).into_diagnostic()?;
+
+        tokens.extend(page.objects);
+
+        match page.urls.next {
+            Some(next) => path = next.replace(&client.url(""), ""),
+            None => break,
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn validate_cidr(range: &str) -> Result<()> {
+    let valid = match range.split_once('/') {
+        Some((address, prefix)) => match (address.parse::<IpAddr>(), prefix.parse::<u8>()) {
+            (Ok(IpAddr::V4(_)), Ok(prefix)) => prefix <= 32,
+            (Ok(IpAddr::V6(_)), Ok(prefix)) => prefix <= 128,
+            _ => false,
+        },
+        None => false,
+    };
+
+    if !valid {
+        miette::bail!(
+            "`{}` is not a valid CIDR range like `192.168.0.1/24`",
+            range
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,184 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Find the dependencies a project declares but never imports.
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    commands::remove::remove_packages,
+    core::{
+        model::usage,
+        output::print_json,
+        settings::Settings,
+        utils::{errors::VoltError, package::PackageJson, read_bins},
+    },
+};
+
+use async_trait::async_trait;
+use clap::Parser;
+use colored::Colorize;
+use miette::Result;
+use serde::Serialize;
+
+use std::collections::BTreeSet;
+
+/// Find dependencies that are declared but never imported
+#[derive(Debug, Parser)]
+pub struct Unused {
+    /// Remove the unused dependencies from package.json, node_modules and the lockfile
+    #[clap(long)]
+    fix: bool,
+
+    /// Don't report these packages, either names or prefixes ending in `*`
+    #[clap(long)]
+    ignore: Vec<String>,
+
+    /// With `--fix`, print the packages that would be removed without removing them
+    #[clap(long)]
+    dry_run: bool,
+}
+
+/// A declared dependency nothing imports
+#[derive(Debug, Serialize)]
+struct UnusedDependency {
+    name: String,
+    range: String,
+    dev: bool,
+}
+
+#[async_trait]
+impl VoltCommand for Unused {
+    /// Execute the `volt unused` command
+    ///
+    /// Scan the project's sources for imports and requires, and report the dependencies that
+    /// aren't imported, run by a script or ignored in the `[unused]` table of volt.toml. With
+    /// `--fix` they're removed instead.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Remove the dependencies nothing imports
+    /// // .exec() is an async call so you need to await it
+    /// Unused { fix: true, ignore: vec![], dry_run: false }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let config = config.with_dry_run(self.dry_run);
+
+        let project_dir = config.cwd()?;
+
+        let (package_file, _) = PackageJson::get_from_dir(&project_dir)?;
+
+        let mut settings = Settings::load(&project_dir)?.unused;
+        settings.ignore.extend(self.ignore);
+
+        let imports = usage::scan(&project_dir)?;
+
+        let node_modules = config.node_modules()?;
+
+        // the words of every script, which run packages through their executables
+        let words = package_file
+            .scripts
+            .iter()
+            .flatten()
+            .flat_map(|(_, script)| {
+                script
+                    .split(|c: char| c.is_whitespace() || "&|;()".contains(c))
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<BTreeSet<_>>();
+
+        let mut unused = vec![];
+
+        for (dev, dependencies) in [
+            (false, &package_file.dependencies),
+            (true, &package_file.dev_dependencies),
+        ] {
+            for (name, range) in dependencies.iter().flatten() {
+                if settings.ignores(name) || imports.contains_key(name) {
+                    continue;
+                }
+
+                // `@types/react` is used with `react`, `@types/babel__core` with `@babel/core`
+                if let Some(typed) = name.strip_prefix("@types/") {
+                    let typed = match typed.split_once("__") {
+                        Some((scope, name)) => format!("@{}/{}", scope, name),
+                        None => typed.to_string(),
+                    };
+
+                    // node itself is what `@types/node` types
+                    if typed == "node" || imports.contains_key(&typed) {
+                        continue;
+                    }
+                }
+
+                let runs = read_bins(&node_modules.join(name), name)?
+                    .into_keys()
+                    .any(|command| words.contains(&command));
+
+                if runs || words.contains(name) {
+                    continue;
+                }
+
+                unused.push(UnusedDependency {
+                    name: name.clone(),
+                    range: range.clone(),
+                    dev,
+                });
+            }
+        }
+
+        if config.json() && !self.fix {
+            print_json(&unused)?;
+        } else {
+            for dependency in &unused {
+                eprintln!(
+                    "{}: {}{} is never imported",
+                    "unused".bright_yellow().bold(),
+                    dependency.name,
+                    if dependency.dev { " (dev)" } else { "" }
+                );
+            }
+        }
+
+        if unused.is_empty() {
+            if !config.json() {
+                println!(
+                    "{} every dependency is used",
+                    "Checked".bright_green().bold()
+                );
+            }
+
+            return Ok(());
+        }
+
+        if self.fix {
+            let names = unused
+                .into_iter()
+                .map(|dependency| dependency.name)
+                .collect::<Vec<_>>();
+
+            return remove_packages(&config, &names, false);
+        }
+
+        Err(VoltError::UnusedDependenciesError {
+            count: unused.len(),
+        }
+        .into())
+    }
+}
@@ -14,56 +14,236 @@
     limitations under the License.
 */
 
-use crate::cli::{VoltCommand, VoltConfig};
+//! Update the dependencies of a project.
+
+use std::{borrow::Cow, fmt};
 
 use async_trait::async_trait;
-use colored::Colorize;
-use miette::Result;
+use clap::Parser;
+use colored::{ColoredString, Colorize};
+use miette::{IntoDiagnostic, Result};
+use node_semver::Version;
+use package_spec::PackageSpec;
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    commands::{add::install_packages, outdated::find_outdated},
+    core::model::lock_file::LockFile,
+    core::prompt::prompts::MultiSelect,
+    core::utils::{errors::VoltError, package::PackageJson},
+};
+
+/// Update dependencies to newer versions
+#[derive(Debug, Parser)]
+pub struct Update {
+    /// Only update these packages
+    packages: Vec<String>,
+
+    /// Update to the latest versions, ignoring the ranges in package.json
+    #[clap(short = 'L', long)]
+    latest: bool,
+
+    /// Choose the packages to update from a list
+    #[clap(short, long)]
+    interactive: bool,
+
+    /// Resolve and print the packages that would change, without updating them
+    #[clap(long)]
+    dry_run: bool,
+}
+
+/// The part of a version that an update changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Bump {
+    Patch,
+    Minor,
+    Major,
+}
+
+impl Bump {
+    fn between(current: Option<&str>, target: &Version) -> Self {
+        match current.and_then(|current| current.parse::<Version>().ok()) {
+            Some(current) if current.major != target.major => Self::Major,
+            Some(current) if current.minor != target.minor => Self::Minor,
+            Some(_) => Self::Patch,
+            // treat packages that aren't installed yet like a major update
+            None => Self::Major,
+        }
+    }
+
+    fn colorize(self, text: &str) -> ColoredString {
+        match self {
+            Self::Patch => text.bright_green(),
+            Self::Minor => text.bright_yellow(),
+            Self::Major => text.bright_red(),
+        }
+    }
+}
 
-use std::sync::Arc;
+impl fmt::Display for Bump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Patch => "patch",
+            Self::Minor => "minor",
+            Self::Major => "major",
+        })
+    }
+}
 
-/// Struct implementation for the `Update` command.
-pub struct Update;
+/// A dependency that will be updated
+#[derive(Debug)]
+struct Candidate {
+    name: String,
+    current: Option<String>,
+    target: Version,
+    bump: Bump,
+}
 
 #[async_trait]
 impl VoltCommand for Update {
-    /// Display a help menu for the `volt update` command.
-    //     fn help() -> String {
-    //         format!(
-    //             r#"volt {}
-    //
-    // Update project dependencies
-    //
-    // Usage: {} {} {}
-    //
-    // Options:
-    //
-    //   {} {} Output verbose messages on internal operations."#,
-    //             VERSION.bright_green().bold(),
-    //             "volt".bright_green().bold(),
-    //             "update".bright_purple(),
-    //             "file-name".white(),
-    //             "--verbose".blue(),
-    //             "(-v)".yellow()
-    //         )
-    //     }
-
     /// Execute the `volt update` command
     ///
-    /// update project dependencies
+    /// Update the direct dependencies of a project within their ranges, or to their latest versions.
     /// ## Arguments
-    /// * `app` - Instance of the command (`Arc<App>`)
-    /// * `packages` - List of packages to add (`Vec<String>`)
-    /// * `flags` - List of flags passed in through the CLI (`Vec<String>`)
+    /// * `config` - The global volt configuration (`VoltConfig`)
     /// ## Examples
     /// ```
-    /// // update project dependencies
+    /// // Pick which dependencies to move to their latest versions
     /// // .exec() is an async call so you need to await it
-    /// update.exec(app, vec![], vec!["--yes"]).await;
+    /// Update { packages: vec![], latest: true, interactive: true, dry_run: false }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
     async fn exec(self, config: VoltConfig) -> Result<()> {
+        let config = config.with_dry_run(self.dry_run);
+
+        let project_dir = config.cwd()?;
+
+        let (mut package_file, package_path) = PackageJson::get_from_dir(&project_dir)?;
+
+        let lock_file = LockFile::load(config.lockfile()?, false)?;
+
+        let outdated = find_outdated(&config, &package_file, &lock_file, &self.packages).await?;
+
+        let mut candidates = vec![];
+
+        // workspaces are installed with the project, so only its own dependencies are updated
+        for package in outdated.into_iter().filter(|p| p.location == ".") {
+            let target = if self.latest {
+                Some(package.latest)
+            } else {
+                package.wanted
+            };
+
+            let target = match target.and_then(|t| t.parse::<Version>().ok()) {
+                Some(target) => target,
+                None => continue,
+            };
+
+            if package.current.as_deref() == Some(target.to_string().as_str()) {
+                continue;
+            }
+
+            candidates.push(Candidate {
+                bump: Bump::between(package.current.as_deref(), &target),
+                name: package.name,
+                current: package.current,
+                target,
+            });
+        }
+
+        if candidates.is_empty() {
+            println!("{}", "All packages are up to date!".bright_green());
+            return Ok(());
+        }
+
+        candidates.sort_by(|a, b| (a.bump, &a.name).cmp(&(b.bump, &b.name)));
+
+        if self.interactive {
+            candidates = select_candidates(candidates)?;
+
+            if candidates.is_empty() {
+                return Ok(());
+            }
+        }
+
+        let specs = candidates
+            .iter()
+            .map(|candidate| {
+                let spec = format!("{}@{}", candidate.name, candidate.target);
+
+                spec.parse::<PackageSpec>()
+                    .map_err(|_| VoltError::PackageSpecificationError { spec })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let installed = install_packages(&config, &specs, false).await?;
+
+        if config.dry_run() {
+            return Ok(());
+        }
+
+        // within their ranges the requested versions are unchanged, otherwise they're rewritten
+        if self.latest {
+            for package in &installed {
+                package_file.bump_dependency(&package.name, &package.version);
+            }
+
+            package_file.save_to(&package_path)?;
+        }
+
+        for candidate in &candidates {
+            println!(
+                "{} {} {} → {}",
+                "Updated".bright_green().bold(),
+                candidate.name,
+                candidate.current.as_deref().unwrap_or("MISSING"),
+                candidate.bump.colorize(&candidate.target.to_string()),
+            );
+        }
+
         Ok(())
     }
 }
+
+/// Let the user check which updates to apply, grouped by patch, minor and major updates
+fn select_candidates(candidates: Vec<Candidate>) -> Result<Vec<Candidate>> {
+    let name_width = candidates.iter().map(|c| c.name.len()).max().unwrap_or(0);
+
+    let current_width = candidates
+        .iter()
+        .map(|c| c.current.as_deref().unwrap_or("MISSING").len())
+        .max()
+        .unwrap_or(0);
+
+    let items = candidates
+        .iter()
+        .map(|candidate| {
+            let item = format!(
+                "{}  {:<name_width$}  {:>current_width$} ❯ {}",
+                candidate.bump.colorize(&format!("{:<5}", candidate.bump)),
+                candidate.name,
+                candidate.current.as_deref().unwrap_or("MISSING"),
+                candidate.bump.colorize(&candidate.target.to_string()),
+                name_width = name_width,
+                current_width = current_width,
+            );
+
+            (Cow::Owned(item), false)
+        })
+        .collect();
+
+    let select = MultiSelect {
+        message: "Choose which packages to update".into(),
+        items,
+    };
+
+    let selected = select.run().into_diagnostic()?;
+
+    Ok(candidates
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| selected.contains(index))
+        .map(|(_, candidate)| candidate)
+        .collect())
+}
@@ -0,0 +1,269 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Bump the version of a package.
+
+use async_trait::async_trait;
+use clap::Parser;
+use colored::Colorize;
+use miette::{IntoDiagnostic, Result};
+use regex::Regex;
+
+use std::{
+    fs::{read_to_string, write},
+    path::Path,
+    process::Command,
+};
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::utils::{errors::VoltError, package::PackageJson, scripts::run_script},
+};
+
+/// Bump the version of the package, then commit and tag it
+#[derive(Debug, Parser)]
+pub struct Version {
+    /// major, minor, patch, premajor, preminor, prepatch, prerelease or an exact version
+    new_version: String,
+
+    /// Identifier for prerelease versions, `--preid beta` turns 1.0.0 into 1.0.1-beta.0
+    #[clap(long)]
+    preid: Option<String>,
+
+    /// Bump the version even if the git working tree has uncommitted changes
+    #[clap(short, long)]
+    force: bool,
+
+    /// Commit and tag message, `%s` is replaced with the new version
+    #[clap(short, long, default_value = "v%s")]
+    message: String,
+
+    /// Only update package.json, without committing or tagging
+    #[clap(long)]
+    no_git_tag_version: bool,
+
+    /// GPG-sign the tag
+    #[clap(long)]
+    sign_git_tag: bool,
+
+    /// Prefix of the tag name
+    #[clap(long, default_value = "v")]
+    tag_version_prefix: String,
+}
+
+#[async_trait]
+impl VoltCommand for Version {
+    /// Execute the `volt version` command
+    ///
+    /// Update the version in package.json and run the `preversion`, `version` and
+    /// `postversion` scripts around it, committing and tagging the change when the project is
+    /// a git repository.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Start a beta for the next minor release
+    /// // .exec() is an async call so you need to await it
+    /// Version { new_version: "preminor".into(), preid: Some("beta".into()), .. }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let (package_file, package_path) = PackageJson::get_from_dir(&config.cwd()?)?;
+        let project_dir = package_path.parent().unwrap_or(&package_path);
+
+        let current = package_file
+            .version
+            .parse::<node_semver::Version>()
+            .map_err(|_| miette::miette!("`{}` is not a valid version", package_file.version))?;
+
+        let new_version = match self.new_version.parse::<node_semver::Version>() {
+            Ok(version) => version.to_string(),
+            Err(_) => {
+                bump(&current, &self.new_version, self.preid.as_deref()).ok_or_else(|| {
+                    miette::miette!("`{}` is not a valid version or bump", self.new_version)
+                })?
+            }
+        };
+
+        if new_version == current.to_string() {
+            miette::bail!("the version is already {}", new_version);
+        }
+
+        let use_git = !self.no_git_tag_version && is_git_repository(project_dir);
+
+        if use_git && !self.force && is_dirty(project_dir)? {
+            miette::bail!(
+                "the git working tree has uncommitted changes, commit or stash them or pass --force"
+            );
+        }
+
+        let scripts = package_file.scripts.clone().unwrap_or_default();
+        let old_version = current.to_string();
+
+        let env = [
+            ("npm_old_version", old_version.as_str()),
+            ("npm_new_version", new_version.as_str()),
+        ];
+
+        let hook = |name: &str| match scripts.get(name) {
+            Some(script) => run_script(&config, project_dir, name, script, &env),
+            None => Ok(()),
+        };
+
+        hook("preversion")?;
+
+        write_version(&package_path, &new_version)?;
+
+        hook("version")?;
+
+        let tag = format!("{}{}", self.tag_version_prefix, new_version);
+
+        if use_git {
+            let message = self.message.replace("%s", &new_version);
+
+            git(project_dir, &["add", "package.json"])?;
+            git(project_dir, &["commit", "-m", &message])?;
+
+            let sign = if self.sign_git_tag { "-s" } else { "-a" };
+
+            git(project_dir, &["tag", sign, &tag, "-m", &message])?;
+        }
+
+        hook("postversion")?;
+
+        println!("{}", tag.bright_green().bold());
+
+        Ok(())
+    }
+}
+
+/// Apply a bump like `minor` or `prerelease` the way npm does
+fn bump(current: &node_semver::Version, kind: &str, preid: Option<&str>) -> Option<String> {
+    let (major, minor, patch) = (current.major, current.minor, current.patch);
+
+    let version = current.to_string();
+    let version = version.split('+').next().unwrap_or_default();
+
+    let pre = version
+        .split_once('-')
+        .map(|(_, pre)| pre.split('.').map(String::from).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let start = |major: u64, minor: u64, patch: u64| match preid {
+        Some(preid) => format!("{}.{}.{}-{}.0", major, minor, patch, preid),
+        None => format!("{}.{}.{}-0", major, minor, patch),
+    };
+
+    Some(match kind {
+        // a prerelease of the next major, minor or patch graduates instead of skipping ahead
+        "major" if !pre.is_empty() && minor == 0 && patch == 0 => format!("{}.0.0", major),
+        "major" => format!("{}.0.0", major + 1),
+        "minor" if !pre.is_empty() && patch == 0 => format!("{}.{}.0", major, minor),
+        "minor" => format!("{}.{}.0", major, minor + 1),
+        "patch" if !pre.is_empty() => format!("{}.{}.{}", major, minor, patch),
+        "patch" => format!("{}.{}.{}", major, minor, patch + 1),
+        "premajor" => start(major + 1, 0, 0),
+        "preminor" => start(major, minor + 1, 0),
+        "prepatch" => start(major, minor, patch + 1),
+        "prerelease" if pre.is_empty() => start(major, minor, patch + 1),
+        "prerelease" if preid.map_or(false, |preid| pre[0] != preid) => start(major, minor, patch),
+        "prerelease" => {
+            let mut pre = pre;
+
+            match pre.iter().rposition(|id| id.parse::<u64>().is_ok()) {
+                Some(index) => pre[index] = (pre[index].parse::<u64>().ok()? + 1).to_string(),
+                None => pre.push(String::from("0")),
+            }
+
+            format!("{}.{}.{}-{}", major, minor, patch, pre.join("."))
+        }
+        _ => return None,
+    })
+}
+
+/// Replace the version in package.json without reformatting the rest of the file
+fn write_version(path: &Path, version: &str) -> Result<()> {
+    let data = read_to_string(path).map_err(|e| VoltError::ReadFileError {
+        source: e,
+        name: path.display().to_string(),
+    })?;
+
+    let pattern = Regex::new(r#"("version"\s*:\s*)"[^"]*""#).into_diagnostic()?;
+
+    let data = pattern.replace(&data, format!(r#"${{1}}"{}""#, version).as_str());
+
+    write(path, data.as_bytes()).map_err(|e| VoltError::WriteFileError {
+        source: e,
+        name: path.display().to_string(),
+    })?;
+
+    Ok(())
+}
+
+fn is_git_repository(dir: &Path) -> bool {
+    git(dir, &["rev-parse", "--is-inside-work-tree"]).is_ok()
+}
+
+/// Whether tracked files have uncommitted changes
+fn is_dirty(dir: &Path) -> Result<bool> {
+    Ok(git(dir, &["status", "--porcelain"])?
+        .lines()
+        .any(|line| !line.starts_with("??")))
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .into_diagnostic()?;
+
+    if !output.status.success() {
+        miette::bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bumped(version: &str, kind: &str, preid: Option<&str>) -> String {
+        bump(&version.parse().unwrap(), kind, preid).unwrap()
+    }
+
+    #[test]
+    fn bumps_like_npm() {
+        assert_eq!(bumped("1.2.3", "patch", None), "1.2.4");
+        assert_eq!(bumped("1.2.3", "minor", None), "1.3.0");
+        assert_eq!(bumped("1.2.3", "major", None), "2.0.0");
+        assert_eq!(bumped("1.2.3", "prepatch", Some("beta")), "1.2.4-beta.0");
+        assert_eq!(bumped("1.2.3", "prerelease", None), "1.2.4-0");
+        assert_eq!(bumped("1.2.4-beta.0", "prerelease", None), "1.2.4-beta.1");
+        assert_eq!(
+            bumped("1.2.4-beta.1", "prerelease", Some("rc")),
+            "1.2.4-rc.0"
+        );
+        assert_eq!(bumped("1.2.4-beta.1", "patch", None), "1.2.4");
+        assert_eq!(bumped("2.0.0-rc.1", "major", None), "2.0.0");
+    }
+}
@@ -0,0 +1,295 @@
+/*
+    Copyright 2021, 2022 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Explain why a package is installed.
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::model::lock_file::{DependencyKind, LockFile},
+    core::output::print_json,
+    core::utils::{package::PackageJson, voltapi::VoltPackage},
+};
+
+use async_trait::async_trait;
+use clap::Parser;
+use colored::Colorize;
+use miette::Result;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fmt,
+};
+
+/// Explain why a package is installed
+#[derive(Debug, Parser)]
+pub struct Why {
+    /// Package to explain, optionally with a version (`react@18.0.0`)
+    package: String,
+}
+
+/// The package being searched for
+struct Target<'a> {
+    name: &'a str,
+    version: Option<&'a str>,
+}
+
+impl Target<'_> {
+    fn matches(&self, package: &VoltPackage) -> bool {
+        package.name == self.name && self.version.map_or(true, |v| package.version == v)
+    }
+}
+
+/// A dependency chain from the project or a workspace to the target
+#[derive(Debug, Serialize)]
+struct Chain {
+    root: String,
+    path: Vec<Link>,
+}
+
+#[derive(Debug, Serialize)]
+struct Link {
+    name: String,
+    version: String,
+    #[serde(rename = "type")]
+    kind: DependencyKind,
+}
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.root)?;
+
+        for link in &self.path {
+            write!(
+                f,
+                " > {}@{} {}",
+                link.name,
+                link.version,
+                format!("({})", link.kind).truecolor(156, 156, 156)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl VoltCommand for Why {
+    /// Execute the `volt why` command
+    ///
+    /// Print the shortest dependency chain from each direct dependency of the project (and its
+    /// workspaces) to a package.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Explain why react is installed
+    /// // .exec() is an async call so you need to await it
+    /// Why { package: "react".into() }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        // `@scope/name@version` splits on the last `@` that isn't the scope's
+        let target = match self.package.rfind('@') {
+            Some(index) if index > 0 => Target {
+                name: &self.package[..index],
+                version: Some(&self.package[index + 1..]),
+            },
+            _ => Target {
+                name: &self.package,
+                version: None,
+            },
+        };
+
+        let project_dir = config.cwd()?;
+
+        let (package_file, _) = PackageJson::get_from_dir(&project_dir)?;
+
+        let lock_file = LockFile::load(config.lockfile()?, false)?;
+
+        let mut roots = vec![(
+            format!("{}@{}", package_file.name, package_file.version),
+            package_file.clone(),
+        )];
+
+        for (directory, workspace) in package_file.workspace_packages(&project_dir)? {
+            let location = directory
+                .strip_prefix(&project_dir)
+                .unwrap_or(&directory)
+                .display()
+                .to_string();
+
+            roots.push((
+                format!("{}@{} ({})", workspace.name, workspace.version, location),
+                workspace,
+            ));
+        }
+
+        // chains grouped by the version of the package they lead to
+        let mut chains: BTreeMap<String, Vec<Chain>> = BTreeMap::new();
+        let reachable = ancestors_of(&lock_file, &target);
+
+        for (label, manifest) in &roots {
+            for (kind, _, package) in lock_file.roots(manifest) {
+                if let Some(package) = package {
+                    find_chains(
+                        &lock_file,
+                        &target,
+                        label,
+                        (kind, package),
+                        &reachable,
+                        &mut chains,
+                    );
+                }
+            }
+        }
+
+        if config.json() {
+            // an empty object when nothing depends on the package
+            print_json(&chains)?;
+
+            return Ok(());
+        }
+
+        if chains.is_empty() {
+            if lock_file
+                .dependencies
+                .values()
+                .any(|package| target.matches(package))
+            {
+                println!(
+                    "{} is installed, but nothing in the project depends on it",
+                    self.package.bright_yellow()
+                );
+            } else {
+                println!("{} is not installed", self.package.bright_yellow());
+            }
+
+            return Ok(());
+        }
+
+        for (version, version_chains) in chains {
+            println!(
+                "{}@{}",
+                target.name.bright_green().bold(),
+                version.bright_green()
+            );
+
+            for chain in version_chains {
+                println!("  {}", chain);
+            }
+
+            println!();
+        }
+
+        Ok(())
+    }
+}
+
+/// Every package (`name@version`) from which the target can be reached
+fn ancestors_of(lock_file: &LockFile, target: &Target) -> HashSet<String> {
+    let mut dependents: HashMap<String, Vec<String>> = HashMap::new();
+
+    for (key, package) in &lock_file.dependencies {
+        for (_, child) in lock_file.children(package) {
+            dependents
+                .entry(format!("{}@{}", child.name, child.version))
+                .or_default()
+                .push(key.clone());
+        }
+    }
+
+    let mut queue = lock_file
+        .dependencies
+        .iter()
+        .filter(|(_, package)| target.matches(package))
+        .map(|(key, _)| key.clone())
+        .collect::<Vec<_>>();
+
+    let mut ancestors = queue.iter().cloned().collect::<HashSet<_>>();
+
+    while let Some(key) = queue.pop() {
+        for dependent in dependents.get(&key).into_iter().flatten() {
+            if ancestors.insert(dependent.clone()) {
+                queue.push(dependent.clone());
+            }
+        }
+    }
+
+    ancestors
+}
+
+/// Record the shortest chain from a direct dependency of `root` to each version of the target
+///
+/// Following every path instead grows exponentially with the size of the tree.
+fn find_chains<'a>(
+    lock_file: &'a LockFile,
+    target: &Target,
+    root: &str,
+    start: (DependencyKind, &'a VoltPackage),
+    reachable: &HashSet<String>,
+    chains: &mut BTreeMap<String, Vec<Chain>>,
+) {
+    let key = |package: &VoltPackage| format!("{}@{}", package.name, package.version);
+
+    // how each package was first reached, which is the shortest way there
+    let mut visited: HashMap<String, (DependencyKind, &VoltPackage, Option<String>)> =
+        HashMap::new();
+
+    visited.insert(key(start.1), (start.0, start.1, None));
+
+    let mut queue = VecDeque::from([start.1]);
+
+    while let Some(current) = queue.pop_front() {
+        if target.matches(current) {
+            let mut path = vec![];
+            let mut next = Some(key(current));
+
+            while let Some((kind, package, parent)) = next.and_then(|k| visited.get(&k)) {
+                path.push(Link {
+                    name: package.name.clone(),
+                    version: package.version.clone(),
+                    kind: *kind,
+                });
+
+                next = parent.clone();
+            }
+
+            path.reverse();
+
+            chains
+                .entry(current.version.clone())
+                .or_default()
+                .push(Chain {
+                    root: root.to_string(),
+                    path,
+                });
+
+            continue;
+        }
+
+        for (kind, child) in lock_file.children(current) {
+            let child_key = key(child);
+
+            if !reachable.contains(&child_key) || visited.contains_key(&child_key) {
+                continue;
+            }
+
+            visited.insert(child_key, (kind, child, Some(key(current))));
+            queue.push_back(child);
+        }
+    }
+}
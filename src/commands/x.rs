@@ -0,0 +1,212 @@
+/*
+    Copyright 2021, 2022 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Run a package's executable without adding it to the project.
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::{
+        net::fetch_dep_tree,
+        progress::InstallProgress,
+        runtime::search_path,
+        utils::{errors::VoltError, install_tree, voltapi::VoltPackage},
+    },
+};
+
+use async_trait::async_trait;
+use clap::{AppSettings, Parser};
+use colored::Colorize;
+use miette::{IntoDiagnostic, Result};
+use package_spec::PackageSpec;
+use std::{fs, path::Path, process::Command};
+
+/// File in each cached environment recording the package it was created for
+const ENVIRONMENT_MANIFEST: &str = "volt-x.json";
+
+/// Run an executable from a package without adding it to your project
+#[derive(Debug, Parser)]
+#[clap(setting = AppSettings::TrailingVarArg)]
+pub struct X {
+    /// Package providing the executable (defaults to the command's name)
+    #[clap(short, long)]
+    package: Option<PackageSpec>,
+
+    /// Executable to run, optionally with a version (`cowsay@1.5.0`)
+    command: String,
+
+    /// Arguments passed through to the executable
+    #[clap(multiple_values = true, allow_hyphen_values = true)]
+    args: Vec<String>,
+}
+
+#[async_trait]
+impl VoltCommand for X {
+    /// Execute the `volt x` command
+    ///
+    /// Resolve a package into a cached environment under `~/.volt/x` and run one of its
+    /// executables. Later invocations with the same package reuse the environment.
+    /// ## Arguments
+    /// * `config` - The global volt configuration (`VoltConfig`)
+    /// ## Examples
+    /// ```
+    /// // Run `cowsay hello` without installing cowsay into the project
+    /// // .exec() is an async call so you need to await it
+    /// X { package: None, command: "cowsay".into(), args: vec!["hello".into()] }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let (spec, command) = match self.package {
+            Some(spec) => (spec, self.command),
+            None => {
+                let spec: PackageSpec =
+                    self.command
+                        .parse()
+                        .map_err(|_| VoltError::PackageSpecificationError {
+                            spec: self.command.clone(),
+                        })?;
+
+                let command = match &spec {
+                    PackageSpec::Npm { name, .. } => {
+                        name.rsplit('/').next().unwrap_or(name).to_string()
+                    }
+                    _ => self.command.clone(),
+                };
+
+                (spec, command)
+            }
+        };
+
+        let environment = config.volt_home()?.join("x").join(
+            spec.to_string()
+                .replace(|c: char| matches!(c, '/' | '\\' | ':'), "+"),
+        );
+
+        let environment_config = config.with_cwd(environment.clone());
+
+        let package = match read_environment(&environment)? {
+            Some(package) => package,
+            None => create_environment(&environment_config, &environment, &spec).await?,
+        };
+
+        let bins = package.bins();
+
+        let script = match bins.get(&command) {
+            Some(script) => script,
+            // packages with a single executable can be run by their package name
+            None if bins.len() == 1 => bins.values().next().unwrap(),
+            None => {
+                let mut available = bins.keys().cloned().collect::<Vec<_>>();
+                available.sort();
+
+                return Err(VoltError::BinNotFoundError {
+                    package: package.name,
+                    command,
+                    available: available.join(", "),
+                }
+                .into());
+            }
+        };
+
+        let script = package
+            .package_directory(&environment_config.node_modules()?)
+            .join(script);
+
+        // the node of the project volt x was run in, not the one of the environment
+        let status = Command::new("node")
+            .env("PATH", search_path(&config, &config.cwd()?, &[])?)
+            .arg(script)
+            .args(&self.args)
+            .status()
+            .into_diagnostic()?;
+
+        if !status.success() {
+            std::process::exit(status.code().unwrap_or(1));
+        }
+
+        Ok(())
+    }
+}
+
+/// Read the package of a previously created environment
+fn read_environment(environment: &Path) -> Result<Option<VoltPackage>> {
+    let manifest = environment.join(ENVIRONMENT_MANIFEST);
+
+    if !manifest.exists() {
+        return Ok(None);
+    }
+
+    let data = fs::read_to_string(&manifest).map_err(|e| VoltError::ReadFileError {
+        source: e,
+        name: manifest.display().to_string(),
+    })?;
+
+    Ok(serde_json::from_str(&data).ok())
+}
+
+/// Resolve and install a package into a fresh environment
+async fn create_environment(
+    config: &VoltConfig,
+    environment: &Path,
+    spec: &PackageSpec,
+) -> Result<VoltPackage> {
+    let progress = InstallProgress::new(config);
+
+    let response = fetch_dep_tree(std::slice::from_ref(spec), progress.resolving())
+        .await?
+        .remove(0);
+
+    progress.resolved(response.tree.len());
+
+    let package = response
+        .tree
+        .get(&format!("{}@{}", response.name, response.version))
+        .cloned()
+        .ok_or_else(|| VoltError::VersionLookupError {
+            name: response.name.clone(),
+        })?;
+
+    progress.suspend(|| {
+        println!(
+            "{} {}@{}",
+            "Preparing".bright_purple().bold(),
+            package.name,
+            package.version
+        )
+    });
+
+    // a leftover environment from an interrupted install is started over
+    if environment.exists() {
+        fs::remove_dir_all(environment).into_diagnostic()?;
+    }
+
+    fs::create_dir_all(environment).map_err(VoltError::CreateDirError)?;
+
+    install_tree(config, response.tree, &progress).await?;
+
+    progress.finish();
+
+    fs::write(
+        environment.join(ENVIRONMENT_MANIFEST),
+        serde_json::to_string(&package).into_diagnostic()?,
+    )
+    .map_err(|e| VoltError::WriteFileError {
+        source: e,
+        name: ENVIRONMENT_MANIFEST.to_string(),
+    })?;
+
+    Ok(package)
+}
@@ -0,0 +1,323 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Authenticated requests to the registry, including two-factor challenges.
+
+use clap::ArgEnum;
+use colored::Colorize;
+use miette::{IntoDiagnostic, Result};
+use reqwest::{
+    header::{RETRY_AFTER, WWW_AUTHENTICATE},
+    Client, Method, StatusCode,
+};
+use serde_json::Value;
+
+use std::{fs::read_to_string, path::Path, time::Duration};
+
+use crate::{
+    cli::VoltConfig,
+    core::{prompt::prompts::Input, utils::errors::VoltError},
+};
+
+/// Number of one-time passwords tried before giving up on a request
+const MAX_OTP_ATTEMPTS: usize = 3;
+
+/// How long to wait for a web authentication challenge to be completed
+const WEB_AUTH_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Who can install a package
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum Access {
+    Public,
+    Restricted,
+}
+
+impl Access {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Public => "public",
+            Self::Restricted => "restricted",
+        }
+    }
+}
+
+/// A registry client that authenticates with the user's token
+///
+/// When the registry asks for a one-time password the client retries with the `--otp` code,
+/// a prompted code, or the result of a web authentication challenge, and keeps using it for
+/// later requests.
+pub struct RegistryClient {
+    client: Client,
+    registry: String,
+    token: String,
+    otp: Option<String>,
+}
+
+impl RegistryClient {
+    pub fn new(config: &VoltConfig) -> Result<Self> {
+        let registry = config.registry().to_string();
+
+        let token = auth_token(&config.cwd()?, &registry).ok_or_else(|| {
+            VoltError::AuthenticationRequired {
+                registry: registry.clone(),
+            }
+        })?;
+
+        Ok(Self {
+            client: Client::new(),
+            registry,
+            token,
+            otp: config.otp().map(String::from),
+        })
+    }
+
+    /// The registry URL of a path like `-/package/react/dist-tags`
+    pub fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.registry, path.trim_start_matches('/'))
+    }
+
+    pub async fn get(&mut self, path: &str) -> Result<Value> {
+        self.request(Method::GET, path, None).await
+    }
+
+    pub async fn put(&mut self, path: &str, body: &Value) -> Result<Value> {
+        self.request(Method::PUT, path, Some(body)).await
+    }
+
+    pub async fn post(&mut self, path: &str, body: &Value) -> Result<Value> {
+        self.request(Method::POST, path, Some(body)).await
+    }
+
+    pub async fn delete(&mut self, path: &str) -> Result<Value> {
+        self.request(Method::DELETE, path, None).await
+    }
+
+    /// Send a request, answering one-time password challenges, and parse the JSON response
+    pub async fn request(
+        &mut self,
+        method: Method,
+        path: &str,
+        body: Option<&Value>,
+    ) -> Result<Value> {
+        let url = self.url(path);
+
+        for _ in 0..MAX_OTP_ATTEMPTS {
+            let mut request = self
+                .client
+                .request(method.clone(), &url)
+                .bearer_auth(&self.token);
+
+            if let Some(otp) = &self.otp {
+                request = request.header("npm-otp", otp);
+            }
+
+            if let Some(body) = body {
+                request = request.json(body);
+            }
+
+            let response = request.send().await.into_diagnostic()?;
+            let status = response.status();
+
+            let otp_challenge = status == StatusCode::UNAUTHORIZED
+                && response
+                    .headers()
+                    .get(WWW_AUTHENTICATE)
+                    .and_then(|header| header.to_str().ok())
+                    .map_or(false, |header| header.to_lowercase().contains("otp"));
+
+            let text = response.text().await.into_diagnostic()?;
+            let json = serde_json::from_str(&text).unwrap_or(Value::Null);
+
+            if status.is_success() {
+                return Ok(json);
+            }
+
+            if !otp_challenge && !text.contains("one-time pass") {
+                return Err(VoltError::RegistryRequestError {
+                    method: method.to_string(),
+                    url,
+                    code: status.as_str().to_string(),
+                    message: error_message(&json).unwrap_or(text),
+                }
+                .into());
+            }
+
+            self.otp = Some(self.answer_challenge(&method, &url, &json).await?);
+        }
+
+        Err(VoltError::OtpRequired {
+            method: method.to_string(),
+            url,
+        }
+        .into())
+    }
+
+    /// Get a one-time password, through the browser when the registry offers a web challenge
+    async fn answer_challenge(&self, method: &Method, url: &str, body: &Value) -> Result<String> {
+        let web_challenge = body
+            .get("authUrl")
+            .and_then(Value::as_str)
+            .zip(body.get("doneUrl").and_then(Value::as_str));
+
+        if let Some((auth_url, done_url)) = web_challenge {
+            return self.web_challenge(auth_url, done_url).await;
+        }
+
+        if self.otp.is_some() {
+            eprintln!(
+                "{}: the one-time password was rejected",
+                "warning".yellow().bold()
+            );
+        }
+
+        Input {
+            message: "One-time password".into(),
+            default: None,
+            allow_empty: false,
+        }
+        .run()
+        .map_err(|_| {
+            VoltError::OtpRequired {
+                method: method.to_string(),
+                url: url.to_string(),
+            }
+            .into()
+        })
+    }
+
+    /// Send the user to `auth_url` and poll `done_url` until the challenge is completed
+    async fn web_challenge(&self, auth_url: &str, done_url: &str) -> Result<String> {
+        println!(
+            "Authenticate your account at:\n{}",
+            auth_url.bright_cyan().underline()
+        );
+
+        let _ = webbrowser::open(auth_url);
+
+        let deadline = std::time::Instant::now() + WEB_AUTH_TIMEOUT;
+
+        while std::time::Instant::now() < deadline {
+            let response = self
+                .client
+                .get(done_url)
+                .bearer_auth(&self.token)
+                .send()
+                .await
+                .into_diagnostic()?;
+
+            match response.status() {
+                StatusCode::OK => {
+                    let body: Value = response.json().await.into_diagnostic()?;
+
+                    if let Some(token) = body.get("token").and_then(Value::as_str) {
+                        return Ok(token.to_string());
+                    }
+                }
+                StatusCode::ACCEPTED => {
+                    let wait = response
+                        .headers()
+                        .get(RETRY_AFTER)
+                        .and_then(|header| header.to_str().ok())
+                        .and_then(|header| header.parse().ok())
+                        .unwrap_or(1);
+
+                    tokio::time::sleep(Duration::from_secs(wait)).await;
+                }
+                status => {
+                    return Err(VoltError::RegistryRequestError {
+                        method: String::from("GET"),
+                        url: done_url.to_string(),
+                        code: status.as_str().to_string(),
+                        message: String::from("web authentication failed"),
+                    }
+                    .into())
+                }
+            }
+        }
+
+        Err(VoltError::OtpRequired {
+            method: String::from("GET"),
+            url: done_url.to_string(),
+        }
+        .into())
+    }
+}
+
+/// Find the auth token for a registry in the project's `.npmrc`, then in `~/.npmrc`
+pub fn auth_token(project_dir: &Path, registry: &str) -> Option<String> {
+    // `//registry.npmjs.org/:_authToken=...`
+    let key = format!(
+        "{}/:_authToken",
+        registry
+            .trim_start_matches("https:")
+            .trim_start_matches("http:")
+            .trim_end_matches('/')
+    );
+
+    let mut files = vec![project_dir.join(".npmrc")];
+
+    files.extend(dirs::home_dir().map(|home| home.join(".npmrc")));
+
+    files
+        .iter()
+        .filter_map(|path| read_to_string(path).ok())
+        .find_map(|data| npmrc_value(&data, &key))
+}
+
+/// Read a key from `.npmrc` data, expanding `${VAR}` references to environment variables
+fn npmrc_value(data: &str, key: &str) -> Option<String> {
+    data.lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#') && !line.starts_with(';'))
+        .filter_map(|line| line.split_once('='))
+        .find(|(name, _)| name.trim() == key)
+        .map(|(_, value)| expand_env(value.trim().trim_matches('"')))
+        .filter(|value| !value.is_empty())
+}
+
+fn expand_env(value: &str) -> String {
+    let mut expanded = String::new();
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+
+        match rest[start..].find('}') {
+            Some(end) => {
+                let name = &rest[start + 2..start + end];
+
+                expanded.push_str(&std::env::var(name).unwrap_or_default());
+                rest = &rest[start + end + 1..];
+            }
+            None => {
+                expanded.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+
+    expanded.push_str(rest);
+
+    expanded
+}
+
+/// The message in a registry error body (`{ "error": "..." }`)
+fn error_message(body: &Value) -> Option<String> {
+    ["error", "message", "reason"]
+        .iter()
+        .find_map(|field| body.get(field).and_then(Value::as_str))
+        .map(String::from)
+}
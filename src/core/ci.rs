@@ -0,0 +1,180 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Detection of continuous integration environments.
+//!
+//! In CI volt never prompts, replaces progress bars with a status line every few seconds, and
+//! folds long output into collapsible groups on the providers that support them. On GitHub
+//! Actions problems are also printed as annotations, which show up inline on pull requests.
+
+use crate::cli::VoltConfig;
+
+use std::{
+    env,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ci {
+    GitHubActions,
+    GitLab,
+    /// Any other provider setting the `CI` variable most of them agree on
+    Other,
+}
+
+impl Ci {
+    /// The CI environment volt is running in, if any
+    pub fn detect() -> Option<Self> {
+        let set = |name: &str| env::var(name).map_or(false, |value| !value.is_empty());
+
+        if set("GITHUB_ACTIONS") {
+            Some(Self::GitHubActions)
+        } else if set("GITLAB_CI") {
+            Some(Self::GitLab)
+        } else if env::var("CI")
+            .map_or(false, |value| !matches!(value.as_str(), "" | "0" | "false"))
+        {
+            Some(Self::Other)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the provider's log viewer renders ANSI colors
+    pub fn supports_color(self) -> bool {
+        matches!(self, Self::GitHubActions | Self::GitLab)
+    }
+}
+
+/// A collapsible section of the CI log, closed when dropped
+pub struct Group {
+    ci: Option<Ci>,
+    id: String,
+}
+
+/// Start a collapsible section titled `title`, which only does something on GitHub Actions
+/// and GitLab
+pub fn group(config: &VoltConfig, title: &str) -> Group {
+    let ci = config.ci().filter(|_| !config.quiet());
+
+    // section ids can't contain spaces
+    let id = title.to_lowercase().replace(' ', "_");
+
+    match ci {
+        Some(Ci::GitHubActions) => eprintln!("::group::{}", title),
+        Some(Ci::GitLab) => eprintln!(
+            "\x1b[0Ksection_start:{}:{}[collapsed=true]\r\x1b[0K{}",
+            timestamp(),
+            id,
+            title
+        ),
+        _ => {}
+    }
+
+    Group { ci, id }
+}
+
+impl Drop for Group {
+    fn drop(&mut self) {
+        match self.ci {
+            Some(Ci::GitHubActions) => eprintln!("::endgroup::"),
+            Some(Ci::GitLab) => {
+                eprintln!("\x1b[0Ksection_end:{}:{}\r\x1b[0K", timestamp(), self.id)
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Annotation {
+    Warning,
+    Error,
+}
+
+/// Print a GitHub Actions annotation, pointing at `file` when it's inside the checkout
+///
+/// Annotations aren't hidden by `-q`, since they only repeat warnings and errors.
+pub fn annotate(annotation: Annotation, title: &str, file: Option<&Path>, message: &str) {
+    if Ci::detect() != Some(Ci::GitHubActions) {
+        return;
+    }
+
+    let command = match annotation {
+        Annotation::Warning => "warning",
+        Annotation::Error => "error",
+    };
+
+    let mut properties = vec![];
+
+    // annotations are only attached to files given relative to the checkout
+    let file = file.and_then(|file| {
+        let workspace = env::var_os("GITHUB_WORKSPACE")?;
+        Some(
+            file.strip_prefix(workspace)
+                .ok()?
+                .to_string_lossy()
+                .replace('\\', "/"),
+        )
+    });
+
+    if let Some(file) = file {
+        properties.push(format!("file={}", escape_property(&file)));
+    }
+
+    properties.push(format!("title={}", escape_property(title)));
+
+    eprintln!(
+        "::{} {}::{}",
+        command,
+        properties.join(","),
+        escape_data(message)
+    );
+}
+
+/// https://github.com/actions/toolkit/blob/main/packages/core/src/command.ts
+fn escape_data(data: &str) -> String {
+    data.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(property: &str) -> String {
+    escape_data(property)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
+
+fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_workflow_commands() {
+        assert_eq!(escape_data("50% of\nlines"), "50%25 of%0Alines");
+        assert_eq!(
+            escape_property("lodash@4.17.20: prototype pollution, again"),
+            "lodash@4.17.20%3A prototype pollution%2C again"
+        );
+    }
+}
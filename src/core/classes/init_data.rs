@@ -15,7 +15,7 @@
 */
 
 use serde::{Deserialize, Serialize};
-use serde_json::to_string_pretty;
+use serde_json::{to_string_pretty, Map, Value};
 
 use std::fmt;
 
@@ -48,6 +48,22 @@ impl License {
             Self::Other => "Other",
         }
     }
+
+    /// The SPDX identifier written to the `license` field of package.json
+    pub const fn spdx_id(&self) -> &'static str {
+        match self {
+            Self::Mit => "MIT",
+            Self::Apache2 => "Apache-2.0",
+            Self::BSD3 => "BSD-3-Clause",
+            Self::BSD2 => "BSD-2-Clause",
+            Self::Gpl => "GPL-3.0-or-later",
+            Self::Lgpl => "LGPL-3.0-or-later",
+            Self::Mpl => "MPL-2.0",
+            Self::Cddl => "CDDL-1.0",
+            Self::Unlicense => "Unlicense",
+            Self::Other => "SEE LICENSE IN LICENSE",
+        }
+    }
 }
 
 impl fmt::Display for License {
@@ -98,10 +114,9 @@ impl TryFrom<&str> for License {
     type Error = ();
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        License::OPTIONS
-            .iter()
-            .position(|e| &value == e)
-            .and_then(License::from_index)
+        (0..License::OPTIONS.len())
+            .filter_map(License::from_index)
+            .find(|l| l.spdx_id() == value || l.as_str() == value)
             .ok_or(())
     }
 }
@@ -111,7 +126,7 @@ impl Serialize for License {
     where
         S: serde::Serializer,
     {
-        serializer.serialize_str(self.as_str())
+        serializer.serialize_str(self.spdx_id())
     }
 }
 
@@ -150,14 +165,22 @@ pub struct InitData {
     pub main: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub author: Option<String>,
-    pub license: License,
+    /// `None` when the existing package.json has a license `volt init` doesn't know, which is
+    /// then written back unchanged from `rest`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<License>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub private: Option<bool>,
+    /// Fields of an existing package.json that `volt init` doesn't prompt for
+    #[serde(flatten)]
+    pub rest: Map<String, Value>,
 }
 
 impl InitData {
     pub fn into_string(self) -> String {
-        to_string_pretty(&self).expect("Valid serialization state")
+        let mut data = to_string_pretty(&self).expect("Valid serialization state");
+        data.push('\n');
+        data
     }
 }
 
@@ -169,7 +192,7 @@ mod tests {
     fn check_serialization_is_correct() {
         for i in 0..=9 {
             let l = License::from_index(i).unwrap();
-            let l_quoted_string = format!("\"{}\"", l.as_str().replace("\"", "\\\""));
+            let l_quoted_string = format!("\"{}\"", l.spdx_id());
 
             let serialization = serde_json::to_string(&l).unwrap();
 
@@ -179,6 +202,18 @@ mod tests {
 
     #[test]
     fn check_deserialization_is_correct() {
+        for i in 0..=9 {
+            let l = License::from_index(i).unwrap();
+            let l_quoted_string = format!("\"{}\"", l.spdx_id());
+
+            let deserialization: License = serde_json::from_str(&l_quoted_string).unwrap();
+
+            assert_eq!(deserialization, l);
+        }
+    }
+
+    #[test]
+    fn check_deserialization_accepts_display_names() {
         for i in 0..=9 {
             let l = License::from_index(i).unwrap();
             let l_quoted_string = format!("\"{}\"", l.as_str().replace("\"", "\\\""));
@@ -0,0 +1,306 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Layered configuration.
+//!
+//! Every option is read from these places, each overriding the ones before it:
+//!
+//! 1. the built-in default
+//! 2. the user configuration in `~/.volt/config.toml`
+//! 3. the project's `volt.toml` (or `.voltrc`), committed so it applies to the whole team
+//! 4. a `VOLT_*` environment variable, `VOLT_REGISTRY` for `registry`
+//! 5. a command line flag, for the options that have one
+
+use miette::Result;
+use serde::Serialize;
+
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
+use crate::core::{settings::Settings, utils::errors::VoltError};
+
+/// The type of value an option takes
+#[derive(Debug, Clone, Copy)]
+pub enum Kind {
+    String,
+    Integer,
+    Boolean,
+    Choice(&'static [&'static str]),
+}
+
+/// An option that can be configured
+#[derive(Debug)]
+pub struct Key {
+    pub name: &'static str,
+    pub kind: Kind,
+    pub default: &'static str,
+    pub description: &'static str,
+}
+
+pub const KEYS: &[Key] = &[
+    Key {
+        name: "registry",
+        kind: Kind::String,
+        default: "https://registry.npmjs.org",
+        description: "Registry packages are installed from and published to",
+    },
+    Key {
+        name: "concurrency",
+        kind: Kind::Integer,
+        default: "16",
+        description: "How many packages are downloaded and extracted at once",
+    },
+    Key {
+        name: "color",
+        kind: Kind::Choice(&["auto", "always", "never"]),
+        default: "auto",
+        description: "When to print colors and progress bars",
+    },
+    Key {
+        name: "verify-provenance",
+        kind: Kind::Boolean,
+        default: "false",
+        description: "Check the attestations of packages that publish them",
+    },
+    Key {
+        name: "engine-strict",
+        kind: Kind::Boolean,
+        default: "false",
+        description:
+            "Fail instead of warning when the node or volt version breaks an `engines` range",
+    },
+    Key {
+        name: "package-manager-strict",
+        kind: Kind::Boolean,
+        default: "false",
+        description: "Fail instead of warning when package.json's packageManager isn't this volt",
+    },
+    Key {
+        name: "package-manager-switch",
+        kind: Kind::Boolean,
+        default: "true",
+        description: "Run the volt version pinned in packageManager when it's installed",
+    },
+    Key {
+        name: "ci",
+        kind: Kind::Choice(&["auto", "always", "never"]),
+        default: "auto",
+        description: "Whether to never prompt and print status lines, `auto` detects CI",
+    },
+    Key {
+        name: "telemetry",
+        kind: Kind::Boolean,
+        default: "false",
+        description: "Send anonymous usage statistics, see `volt telemetry status`",
+    },
+];
+
+impl Key {
+    /// The option called `name`
+    pub fn find(name: &str) -> Result<&'static Self> {
+        KEYS.iter().find(|key| key.name == name).ok_or_else(|| {
+            VoltError::UnknownConfigKeyError {
+                key: name.to_string(),
+            }
+            .into()
+        })
+    }
+
+    /// The environment variable overriding the option
+    pub fn env(&self) -> String {
+        format!("VOLT_{}", self.name.to_uppercase().replace('-', "_"))
+    }
+
+    /// Check a value given as text, returning it as it's stored in a configuration file
+    pub fn parse(&self, value: &str) -> Result<toml::Value> {
+        let invalid = |expected: String| VoltError::ConfigValueError {
+            key: self.name.to_string(),
+            value: value.to_string(),
+            expected,
+        };
+
+        Ok(match self.kind {
+            Kind::String => toml::Value::String(value.to_string()),
+            Kind::Integer => toml::Value::Integer(
+                value
+                    .parse::<u32>()
+                    .map_err(|_| invalid(String::from("a positive number")))?
+                    .into(),
+            ),
+            Kind::Boolean => toml::Value::Boolean(
+                value
+                    .parse()
+                    .map_err(|_| invalid(String::from("true or false")))?,
+            ),
+            Kind::Choice(choices) => {
+                if !choices.contains(&value) {
+                    return Err(invalid(format!("one of {}", choices.join(", "))).into());
+                }
+
+                toml::Value::String(value.to_string())
+            }
+        })
+    }
+}
+
+/// Where the value of an option came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Default,
+    User,
+    Project,
+    Env,
+    Flag,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::User => write!(f, "user"),
+            Self::Project => write!(f, "project"),
+            Self::Env => write!(f, "env"),
+            Self::Flag => write!(f, "flag"),
+        }
+    }
+}
+
+/// The value of every option after all the layers are applied
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    values: BTreeMap<&'static str, (String, Source)>,
+}
+
+impl Options {
+    /// Read the options of a project in `project_dir` for a user with `user_file`
+    pub fn load(user_file: &Path, project_dir: &Path) -> Result<Self> {
+        let mut options = Self::default();
+
+        for key in KEYS {
+            options
+                .values
+                .insert(key.name, (key.default.to_string(), Source::Default));
+        }
+
+        options.apply_file(user_file, Source::User)?;
+        options.apply_file(&Settings::path(project_dir), Source::Project)?;
+
+        for key in KEYS {
+            if let Ok(value) = std::env::var(key.env()) {
+                key.parse(&value)?;
+
+                options.values.insert(key.name, (value, Source::Env));
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// Apply the options in a configuration file, the other settings in it are left alone
+    fn apply_file(&mut self, path: &Path, source: Source) -> Result<()> {
+        for (name, value) in read_file(path)? {
+            if let Ok(key) = Key::find(&name) {
+                let value = display_value(&value);
+
+                // catch typos in hand-edited files before they're used
+                key.parse(&value)?;
+
+                self.values.insert(key.name, (value, source));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Override an option with a command line flag
+    pub fn set_flag(&mut self, name: &'static str, value: String) {
+        self.values.insert(name, (value, Source::Flag));
+    }
+
+    /// The value of an option and where it came from
+    pub fn get(&self, name: &str) -> Option<(&str, Source)> {
+        self.values
+            .get(name)
+            .map(|(value, source)| (value.as_str(), *source))
+    }
+
+    /// The value of an option, which was validated when it was loaded
+    pub fn value(&self, name: &str) -> &str {
+        self.get(name).map_or("", |(value, _)| value)
+    }
+}
+
+/// The values in a configuration file, or none if it doesn't exist
+pub fn read_file(path: &Path) -> Result<toml::value::Table> {
+    if !path.exists() {
+        return Ok(toml::value::Table::new());
+    }
+
+    let data = fs::read_to_string(path).map_err(|e| VoltError::ReadFileError {
+        source: e,
+        name: path.display().to_string(),
+    })?;
+
+    Ok(
+        toml::from_str(&data).map_err(|e| VoltError::SettingsParseError {
+            source: e,
+            name: path.display().to_string(),
+        })?,
+    )
+}
+
+/// Write the values of a configuration file, which doesn't keep its comments
+pub fn write_file(path: &Path, table: &toml::value::Table) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(VoltError::CreateDirError)?;
+    }
+
+    // plain values have to come before tables, which `Value` orders for us
+    let data = toml::to_string_pretty(&toml::Value::Table(table.clone()))
+        .map_err(|e| miette::miette!("failed to serialize {}: {}", path.display(), e))?;
+
+    fs::write(path, data).map_err(|e| VoltError::WriteFileError {
+        source: e,
+        name: path.display().to_string(),
+    })?;
+
+    Ok(())
+}
+
+/// A value from a configuration file as it would be typed on the command line
+fn display_value(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_values_by_kind() {
+        let concurrency = Key::find("concurrency").unwrap();
+        let color = Key::find("color").unwrap();
+
+        assert_eq!(concurrency.parse("8").unwrap(), toml::Value::Integer(8));
+        assert!(concurrency.parse("-1").is_err());
+        assert!(color.parse("never").is_ok());
+        assert!(color.parse("sometimes").is_err());
+        assert!(Key::find("colour").is_err());
+    }
+}
@@ -0,0 +1,712 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! `volt daemon`, a background process keeping the caches of recently used projects warm.
+//!
+//! Every few minutes the daemon revalidates the registry metadata of the dependencies of the
+//! projects volt was used in lately, and downloads the newest version in range of each direct
+//! dependency into the store, so the next install restores it instead of downloading it.
+//!
+//! The CLI talks to it over a Unix socket at `~/.volt/daemon/daemon.sock` (on Windows, a port
+//! on localhost written to `~/.volt/daemon/daemon.port`). Each connection carries one request
+//! and one response, both JSON on a single line:
+//!
+//! ```text
+//! > {"request":"touch","project":"/home/me/app"}
+//! < {"response":"ok"}
+//! > {"request":"metadata","registry":"https://registry.npmjs.org","name":"react"}
+//! < {"response":"metadata","document":"{\"name\":\"react\",...}"}
+//! ```
+
+use crate::{
+    cli::VoltConfig,
+    core::{
+        config::Options,
+        io::cache_tarball,
+        model::lock_file::LockFile,
+        net::RegistryPackage,
+        utils::{
+            decompress_gzip, errors::VoltError, package::PackageJson, verify_checksum,
+            voltapi::VoltPackage,
+        },
+    },
+};
+
+use futures_util::{stream, StreamExt};
+use lazy_static::lazy_static;
+use miette::{IntoDiagnostic, Result};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    sync::Notify,
+};
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// How often the caches are refreshed
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// How long a project is kept warm after volt was last used in it, in seconds
+const RETENTION: u64 = 14 * 24 * 60 * 60;
+
+/// How many packages are revalidated or downloaded at once
+const CONCURRENCY: usize = 8;
+
+/// How long the CLI waits for the daemon before going to the registry itself
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+lazy_static! {
+    static ref ADDRESS: RwLock<Option<PathBuf>> = RwLock::new(None);
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "request", rename_all = "camelCase")]
+pub enum Request {
+    Status,
+    /// Volt was used in a project
+    Touch {
+        project: PathBuf,
+    },
+    /// The abbreviated metadata of a package from a registry, without a trailing slash
+    Metadata {
+        registry: String,
+        name: String,
+    },
+    Stop,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "response", rename_all = "camelCase")]
+pub enum Response {
+    Ok,
+    Status(Status),
+    /// `None` when the daemon doesn't have the package
+    Metadata {
+        document: Option<String>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// What the daemon is doing, times are Unix timestamps in seconds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    pub pid: u32,
+    pub version: String,
+    pub started: u64,
+    pub last_refresh: Option<u64>,
+    /// The projects kept warm, with when volt was last used in them
+    pub projects: BTreeMap<PathBuf, u64>,
+    /// How many packages' metadata is cached
+    pub packages: usize,
+    pub revalidated: u64,
+    pub prefetched: u64,
+}
+
+/// Metadata of a package as the registry returned it
+struct Metadata {
+    etag: Option<String>,
+    document: String,
+}
+
+struct State {
+    status: Status,
+    /// Keyed by `(registry, name)`, a name can be a different package on another registry
+    metadata: HashMap<(String, String), Metadata>,
+}
+
+/// The directory the daemon keeps its socket, log and projects in (`~/.volt/daemon`)
+pub fn dir(config: &VoltConfig) -> Result<PathBuf> {
+    Ok(config.volt_home()?.join("daemon"))
+}
+
+pub fn log_file(dir: &Path) -> PathBuf {
+    dir.join("daemon.log")
+}
+
+fn address(dir: &Path) -> PathBuf {
+    dir.join(if cfg!(windows) {
+        "daemon.port"
+    } else {
+        "daemon.sock"
+    })
+}
+
+fn projects_file(dir: &Path) -> PathBuf {
+    dir.join("projects.json")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+/// Talk to the daemon for the rest of the run if it's running, set once the configuration is
+/// loaded
+pub fn set_address(config: &VoltConfig) {
+    let address = match dir(config) {
+        Ok(dir) => address(&dir),
+        Err(_) => return,
+    };
+
+    if address.exists() {
+        if let Ok(mut current) = ADDRESS.write() {
+            *current = Some(address);
+        }
+    }
+}
+
+/// Send a request to the daemon of `config`, failing when it isn't running
+pub fn request_to(config: &VoltConfig, request: &Request) -> Result<Response> {
+    request_at(&address(&dir(config)?), request).into_diagnostic()
+}
+
+/// Send a request to the running daemon, `None` when there's none
+pub fn request(request: &Request) -> Option<Response> {
+    let address = ADDRESS.read().ok()?.clone()?;
+
+    request_at(&address, request).ok()
+}
+
+fn request_at(address: &Path, request: &Request) -> io::Result<Response> {
+    let mut stream = connect(address)?;
+
+    let mut line = serde_json::to_vec(request)?;
+    line.push(b'\n');
+
+    stream.write_all(&line)?;
+
+    let mut response = String::new();
+    io::BufReader::new(stream).read_line(&mut response)?;
+
+    Ok(serde_json::from_str(&response)?)
+}
+
+#[cfg(unix)]
+fn connect(address: &Path) -> io::Result<std::os::unix::net::UnixStream> {
+    let stream = std::os::unix::net::UnixStream::connect(address)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    Ok(stream)
+}
+
+#[cfg(not(unix))]
+fn connect(address: &Path) -> io::Result<std::net::TcpStream> {
+    let port = fs::read_to_string(address)?
+        .trim()
+        .parse::<u16>()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let stream = std::net::TcpStream::connect_timeout(&([127, 0, 0, 1], port).into(), TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    Ok(stream)
+}
+
+/// The metadata the daemon keeps for a package from `registry`, if it's running and has it
+pub async fn cached_metadata(registry: &str, name: &str) -> Option<String> {
+    if ADDRESS.read().ok()?.is_none() {
+        return None;
+    }
+
+    let metadata = Request::Metadata {
+        registry: registry.trim_end_matches('/').to_string(),
+        name: name.to_string(),
+    };
+
+    // the socket blocks, so it's kept off the runtime's threads
+    let response = tokio::task::spawn_blocking(move || request(&metadata))
+        .await
+        .ok()??;
+
+    match response {
+        Response::Metadata { document } => document,
+        _ => None,
+    }
+}
+
+/// Tell the daemon volt was used in a project, so it keeps the project's caches warm
+pub fn touch(project: &Path) {
+    let _ = request(&Request::Touch {
+        project: project
+            .canonicalize()
+            .unwrap_or_else(|_| project.to_path_buf()),
+    });
+}
+
+#[cfg(unix)]
+type Listener = tokio::net::UnixListener;
+
+#[cfg(not(unix))]
+type Listener = tokio::net::TcpListener;
+
+#[cfg(unix)]
+async fn listen(address: &Path) -> io::Result<Listener> {
+    // a socket left behind by a daemon that was killed
+    let _ = fs::remove_file(address);
+
+    Listener::bind(address)
+}
+
+#[cfg(not(unix))]
+async fn listen(address: &Path) -> io::Result<Listener> {
+    let listener = Listener::bind(("127.0.0.1", 0)).await?;
+
+    fs::write(address, listener.local_addr()?.port().to_string())?;
+
+    Ok(listener)
+}
+
+/// Run the daemon until it's asked to stop
+pub async fn serve(config: &VoltConfig) -> Result<()> {
+    let dir = dir(config)?;
+    let address = address(&dir);
+
+    fs::create_dir_all(&dir).map_err(VoltError::CreateDirError)?;
+
+    if let Ok(Response::Status(status)) = request_at(&address, &Request::Status) {
+        return Err(VoltError::DaemonRunningError { pid: status.pid }.into());
+    }
+
+    let projects = fs::read_to_string(projects_file(&dir))
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default();
+
+    let state = Arc::new(Mutex::new(State {
+        status: Status {
+            pid: std::process::id(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            started: now(),
+            last_refresh: None,
+            projects,
+            packages: 0,
+            revalidated: 0,
+            prefetched: 0,
+        },
+        metadata: HashMap::new(),
+    }));
+
+    let listener = listen(&address)
+        .await
+        .map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: address.display().to_string(),
+        })?;
+
+    tracing::info!("listening on {}", address.display());
+
+    let stop = Arc::new(Notify::new());
+    // a project that wasn't kept warm yet was touched
+    let wake = Arc::new(Notify::new());
+
+    let acceptor = async {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    tracing::warn!("failed to accept a connection: {}", e);
+                    continue;
+                }
+            };
+
+            let (state, stop, wake, dir) = (state.clone(), stop.clone(), wake.clone(), dir.clone());
+
+            tokio::spawn(async move {
+                if let Err(e) = handle(stream, &state, &stop, &wake, &dir).await {
+                    tracing::warn!("failed to answer a request: {}", e);
+                }
+            });
+        }
+    };
+
+    let refresher = async {
+        let client = Client::new();
+
+        loop {
+            refresh(config, &client, &state, &dir).await;
+
+            tokio::select! {
+                _ = tokio::time::sleep(REFRESH_INTERVAL) => {}
+                _ = wake.notified() => {}
+            }
+        }
+    };
+
+    tokio::select! {
+        _ = acceptor => {}
+        _ = refresher => {}
+        _ = stop.notified() => {}
+    }
+
+    tracing::info!("stopping");
+
+    let _ = fs::remove_file(&address);
+
+    Ok(())
+}
+
+async fn handle<S>(
+    stream: S,
+    state: &Mutex<State>,
+    stop: &Notify,
+    wake: &Notify,
+    dir: &Path,
+) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+
+    let mut line = String::new();
+    BufReader::new(reader).read_line(&mut line).await?;
+
+    let request = serde_json::from_str::<Request>(&line);
+    let stopping = matches!(request, Ok(Request::Stop));
+
+    let response = match request {
+        Ok(Request::Status) => {
+            let state = state.lock().unwrap();
+
+            Response::Status(Status {
+                packages: state.metadata.len(),
+                ..state.status.clone()
+            })
+        }
+        Ok(Request::Touch { project }) => {
+            let projects = {
+                let mut state = state.lock().unwrap();
+
+                if state.status.projects.insert(project, now()).is_none() {
+                    wake.notify_one();
+                }
+
+                state.status.projects.clone()
+            };
+
+            save_projects(dir, &projects);
+
+            Response::Ok
+        }
+        Ok(Request::Metadata { registry, name }) => Response::Metadata {
+            document: state
+                .lock()
+                .unwrap()
+                .metadata
+                .get(&(registry, name))
+                .map(|metadata| metadata.document.clone()),
+        },
+        Ok(Request::Stop) => Response::Ok,
+        Err(e) => Response::Error {
+            message: e.to_string(),
+        },
+    };
+
+    let mut data = serde_json::to_vec(&response)?;
+    data.push(b'\n');
+
+    writer.write_all(&data).await?;
+    writer.flush().await?;
+
+    // only once the client knows, it would see a closed connection otherwise
+    if stopping {
+        stop.notify_one();
+    }
+
+    Ok(())
+}
+
+fn save_projects(dir: &Path, projects: &BTreeMap<PathBuf, u64>) {
+    let saved = serde_json::to_vec_pretty(projects)
+        .map_err(io::Error::from)
+        .and_then(|data| fs::write(projects_file(dir), data));
+
+    if let Err(e) = saved {
+        tracing::warn!("failed to save the projects: {}", e);
+    }
+}
+
+/// Revalidate the metadata of every dependency of the recent projects, then download the
+/// newest version in range of their direct dependencies
+async fn refresh(config: &VoltConfig, client: &Client, state: &Mutex<State>, dir: &Path) {
+    let projects = {
+        let mut state = state.lock().unwrap();
+
+        let cutoff = now().saturating_sub(RETENTION);
+        state.status.projects.retain(|_, used| *used >= cutoff);
+
+        state.status.projects.clone()
+    };
+
+    save_projects(dir, &projects);
+
+    let mut names = BTreeSet::new();
+    let mut direct = BTreeSet::new();
+
+    for project in projects.keys() {
+        match dependencies(config, project) {
+            Ok((registry, project_names, project_direct)) => {
+                names.extend(
+                    project_names
+                        .into_iter()
+                        .map(|name| (registry.clone(), name)),
+                );
+                direct.extend(
+                    project_direct
+                        .into_iter()
+                        .map(|(name, range, locked)| (registry.clone(), name, range, locked)),
+                );
+            }
+            Err(e) => tracing::warn!("skipping {}: {}", project.display(), e),
+        }
+    }
+
+    tracing::info!(
+        "revalidating {} packages of {} projects",
+        names.len(),
+        projects.len()
+    );
+
+    stream::iter(names)
+        .map(|(registry, name)| async move {
+            if let Err(e) = revalidate(client, state, &registry, &name).await {
+                tracing::warn!("failed to revalidate {} from {}: {}", name, registry, e);
+            }
+        })
+        .buffer_unordered(CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+
+    stream::iter(direct)
+        .map(|(registry, name, range, locked)| async move {
+            if let Err(e) = prefetch(config, client, state, &registry, &name, &range, locked).await
+            {
+                tracing::warn!("failed to prefetch {}@{}: {}", name, range, e);
+            }
+        })
+        .buffer_unordered(CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+
+    state.lock().unwrap().status.last_refresh = Some(now());
+}
+
+/// The registry a project installs from, the name of every package it depends on, and its
+/// direct dependencies as `(name, range, locked version)`
+#[allow(clippy::type_complexity)]
+fn dependencies(
+    config: &VoltConfig,
+    project: &Path,
+) -> Result<(
+    String,
+    BTreeSet<String>,
+    Vec<(String, String, Option<String>)>,
+)> {
+    let registry = Options::load(&config.user_config_file()?, project)?
+        .value("registry")
+        .trim_end_matches('/')
+        .to_string();
+
+    let package = PackageJson::read(&project.join("package.json"))?;
+    let lock_file = LockFile::load(project.join(VoltConfig::VOLT_LOCK), false)?;
+
+    let direct = package
+        .dependencies
+        .iter()
+        .chain(package.dev_dependencies.iter())
+        .flatten()
+        .map(|(name, range)| {
+            (
+                name.clone(),
+                range.clone(),
+                lock_file.direct.get(name).cloned(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let names = lock_file
+        .dependencies
+        .values()
+        .map(|package| package.name.clone())
+        .chain(direct.iter().map(|(name, ..)| name.clone()))
+        .collect();
+
+    Ok((registry, names, direct))
+}
+
+/// Fetch the metadata of a package again, unless the registry says it didn't change
+async fn revalidate(
+    client: &Client,
+    state: &Mutex<State>,
+    registry: &str,
+    name: &str,
+) -> Result<()> {
+    let url = format!("{}/{}", registry, name.replace('/', "%2f"));
+    let key = (registry.to_string(), name.to_string());
+
+    let etag = state
+        .lock()
+        .unwrap()
+        .metadata
+        .get(&key)
+        .and_then(|metadata| metadata.etag.clone());
+
+    let mut request = client
+        .get(&url)
+        .header("Accept", "application/vnd.npm.install-v1+json");
+
+    if let Some(etag) = etag {
+        request = request.header("If-None-Match", etag);
+    }
+
+    let response = request.send().await.into_diagnostic()?;
+
+    match response.status() {
+        StatusCode::NOT_MODIFIED => {}
+        StatusCode::OK => {
+            let etag = response
+                .headers()
+                .get("etag")
+                .and_then(|etag| etag.to_str().ok())
+                .map(String::from);
+
+            let document = response.text().await.into_diagnostic()?;
+
+            state
+                .lock()
+                .unwrap()
+                .metadata
+                .insert(key, Metadata { etag, document });
+        }
+        status => {
+            return Err(VoltError::NetworkUnknownError {
+                url,
+                package_name: name.to_string(),
+                code: status.as_str().to_string(),
+            }
+            .into())
+        }
+    }
+
+    state.lock().unwrap().status.revalidated += 1;
+
+    Ok(())
+}
+
+/// Download the newest version of a direct dependency in `range` into the store, if it's not
+/// the locked version and isn't in the store yet
+async fn prefetch(
+    config: &VoltConfig,
+    client: &Client,
+    state: &Mutex<State>,
+    registry: &str,
+    name: &str,
+    range: &str,
+    locked: Option<String>,
+) -> Result<()> {
+    let key = (registry.to_string(), name.to_string());
+
+    let document = match state.lock().unwrap().metadata.get(&key) {
+        Some(metadata) => metadata.document.clone(),
+        None => return Ok(()),
+    };
+
+    let metadata = serde_json::from_str::<RegistryPackage>(&document).into_diagnostic()?;
+
+    let version = match metadata.max_satisfying(range) {
+        Some(version) => version.to_string(),
+        None => return Ok(()),
+    };
+
+    // installed versions are in the store already
+    if locked.as_deref() == Some(version.as_str()) {
+        return Ok(());
+    }
+
+    let dist = match metadata.versions.get(&version) {
+        Some(manifest) => manifest.dist.clone(),
+        None => return Ok(()),
+    };
+
+    let package = match (dist.integrity, dist.tarball) {
+        (Some(integrity), Some(tarball)) => VoltPackage {
+            name: name.to_string(),
+            version,
+            integrity,
+            tarball,
+            ..VoltPackage::default()
+        },
+        _ => return Ok(()),
+    };
+
+    let volt_home = config.volt_home()?;
+
+    if cacache::metadata_sync(&volt_home, package.cacache_key())
+        .into_diagnostic()?
+        .is_some()
+    {
+        return Ok(());
+    }
+
+    let data = client
+        .get(&package.tarball)
+        .send()
+        .await
+        .into_diagnostic()?
+        .error_for_status()
+        .into_diagnostic()?
+        .bytes()
+        .await
+        .into_diagnostic()?;
+
+    let key = package.key();
+
+    tokio::task::spawn_blocking(move || -> Result<()> {
+        let (verified, checksum) = verify_checksum(&data, &package.integrity)?;
+
+        if !verified {
+            return Err(VoltError::TarballIntegrityError {
+                package: package.key(),
+                expected: package.integrity.clone(),
+                actual: checksum.unwrap_or_default(),
+            }
+            .into());
+        }
+
+        cache_tarball(decompress_gzip(&data)?, &package, &volt_home)
+    })
+    .await
+    .into_diagnostic()??;
+
+    tracing::info!("prefetched {}", key);
+
+    state.lock().unwrap().status.prefetched += 1;
+
+    Ok(())
+}
@@ -0,0 +1,159 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Events streamed by `--emit-events`, one JSON object per line, for editors and GUIs to
+//! follow an install live.
+//!
+//! ```text
+//! {"time":1650000000000,"event":"resolutionStarted","packages":["react@^18"]}
+//! {"time":1650000000420,"event":"packageFetched","name":"react","version":"18.0.0","bytes":81203,"cached":false}
+//! ```
+
+use crate::core::utils::errors::VoltError;
+
+use miette::Result;
+use serde::Serialize;
+
+use std::{
+    fmt,
+    io::{self, Write},
+    net::TcpStream,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "camelCase")]
+pub enum Event<'a> {
+    #[serde(rename_all = "camelCase")]
+    ResolutionStarted { packages: Vec<String> },
+    /// A package was downloaded, or found in the store when `cached`
+    #[serde(rename_all = "camelCase")]
+    PackageFetched {
+        name: &'a str,
+        version: &'a str,
+        bytes: u64,
+        cached: bool,
+    },
+    #[serde(rename_all = "camelCase")]
+    ExtractProgress {
+        name: &'a str,
+        version: &'a str,
+        extracted: u64,
+        total: u64,
+    },
+    /// A line printed by a script, `stream` is `stdout` or `stderr`
+    #[serde(rename_all = "camelCase")]
+    ScriptOutput {
+        script: &'a str,
+        stream: &'a str,
+        line: &'a str,
+    },
+    #[serde(rename_all = "camelCase")]
+    InstallCompleted { packages: usize, duration_ms: u128 },
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    time: u128,
+    #[serde(flatten)]
+    event: Event<'a>,
+}
+
+/// Where events are written, which does nothing unless `--emit-events` was passed
+///
+/// Clones share the connection, so events from concurrent installs are written whole.
+#[derive(Clone, Default)]
+pub struct Events {
+    sink: Option<Arc<Mutex<Box<dyn Write + Send>>>>,
+    stdout: bool,
+}
+
+impl fmt::Debug for Events {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Events")
+            .field("enabled", &self.sink.is_some())
+            .field("stdout", &self.stdout)
+            .finish()
+    }
+}
+
+impl Events {
+    /// Connect to `target`: `-` for stdout, `tcp://host:port`, or the path of a Unix socket
+    pub fn connect(target: &str) -> Result<Self> {
+        let error = |source| VoltError::EventsConnectError {
+            target: target.to_string(),
+            source,
+        };
+
+        let sink: Box<dyn Write + Send> = if target == "-" {
+            Box::new(io::stdout())
+        } else if let Some(address) = target.strip_prefix("tcp://") {
+            Box::new(TcpStream::connect(address).map_err(error)?)
+        } else {
+            Self::connect_unix(target).map_err(error)?
+        };
+
+        Ok(Self {
+            sink: Some(Arc::new(Mutex::new(sink))),
+            stdout: target == "-",
+        })
+    }
+
+    #[cfg(unix)]
+    fn connect_unix(path: &str) -> io::Result<Box<dyn Write + Send>> {
+        Ok(Box::new(std::os::unix::net::UnixStream::connect(path)?))
+    }
+
+    #[cfg(not(unix))]
+    fn connect_unix(_path: &str) -> io::Result<Box<dyn Write + Send>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Unix sockets aren't supported on this platform, use tcp://host:port",
+        ))
+    }
+
+    /// Whether `--emit-events` was passed
+    pub fn enabled(&self) -> bool {
+        self.sink.is_some()
+    }
+
+    /// Whether events are written to stdout, which then has to be kept free of other output
+    pub fn on_stdout(&self) -> bool {
+        self.stdout
+    }
+
+    /// Write an event, a client that went away doesn't stop the install
+    pub fn emit(&self, event: Event<'_>) {
+        let sink = match &self.sink {
+            Some(sink) => sink,
+            None => return,
+        };
+
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_millis());
+
+        let line = match serde_json::to_string(&Line { time, event }) {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+
+        if let Ok(mut sink) = sink.lock() {
+            let _ = writeln!(sink, "{}", line).and_then(|_| sink.flush());
+        }
+    }
+}
@@ -0,0 +1,166 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Commands run around the phases of an install.
+//!
+//! Hooks are set in the `[hooks]` table of `~/.volt/config.toml` or the project's `volt.toml`,
+//! the project's taking precedence. Each one is run with a shell in the project directory and
+//! gets a JSON description of the install on stdin. A hook exiting with an error stops the
+//! install, which is how custom policy checks reject a resolved tree.
+//!
+//! ```toml
+//! [hooks]
+//! post-resolve = "node scripts/check-licenses.js"
+//! post-install = "curl -s -X POST -d @- https://hooks.example.com/installed"
+//! ```
+
+use crate::{
+    cli::VoltConfig,
+    core::{config::read_file, output::status, utils::errors::VoltError},
+};
+
+use colored::Colorize;
+use miette::{IntoDiagnostic, Result};
+use serde::{Deserialize, Serialize};
+
+use std::{
+    io::{self, Write},
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    /// Before anything is resolved, with the requested packages
+    PreInstall,
+    /// Once the tree is resolved, before anything is downloaded
+    PostResolve,
+    /// Once the packages are linked and the lockfile is saved
+    PostInstall,
+}
+
+impl Hook {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::PreInstall => "pre-install",
+            Self::PostResolve => "post-resolve",
+            Self::PostInstall => "post-install",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct Hooks {
+    pub pre_install: Option<String>,
+    pub post_resolve: Option<String>,
+    pub post_install: Option<String>,
+}
+
+impl Hooks {
+    /// The hooks of the user in `user_file`, with the ones set by the project in `project`
+    /// replacing them
+    pub fn load(user_file: &Path, project: &Hooks) -> Result<Self> {
+        let user = match read_file(user_file)?.remove("hooks") {
+            Some(table) => table
+                .try_into::<Self>()
+                .map_err(|e| VoltError::SettingsParseError {
+                    source: e,
+                    name: user_file.display().to_string(),
+                })?,
+            None => Self::default(),
+        };
+
+        Ok(Self {
+            pre_install: project.pre_install.clone().or(user.pre_install),
+            post_resolve: project.post_resolve.clone().or(user.post_resolve),
+            post_install: project.post_install.clone().or(user.post_install),
+        })
+    }
+
+    pub fn command(&self, hook: Hook) -> Option<&str> {
+        match hook {
+            Hook::PreInstall => self.pre_install.as_deref(),
+            Hook::PostResolve => self.post_resolve.as_deref(),
+            Hook::PostInstall => self.post_install.as_deref(),
+        }
+    }
+
+    /// Run a hook if it's set, writing `context` to its stdin
+    pub fn run<T: Serialize>(&self, config: &VoltConfig, hook: Hook, context: &T) -> Result<()> {
+        let script = match self.command(hook) {
+            Some(script) => script,
+            None => return Ok(()),
+        };
+
+        status(
+            config,
+            format!("{} {}", format!("[{}]", hook.name()).bold(), script).truecolor(156, 156, 156),
+        );
+
+        let input = serde_json::to_vec(context).into_diagnostic()?;
+
+        let (shell, flag) = if cfg!(windows) {
+            ("cmd", "/C")
+        } else {
+            ("sh", "-c")
+        };
+
+        // the hook's output can't get mixed into `--json` or events on stdout
+        let stdout = if config.stdout_reserved() {
+            Stdio::piped()
+        } else {
+            Stdio::inherit()
+        };
+
+        let mut child = Command::new(shell)
+            .args([flag, script])
+            .current_dir(config.cwd()?)
+            .env("VOLT_HOOK", hook.name())
+            .stdin(Stdio::piped())
+            .stdout(stdout)
+            .spawn()
+            .into_diagnostic()?;
+
+        let forwarder = child.stdout.take().map(|mut stdout| {
+            thread::spawn(move || {
+                let _ = io::copy(&mut stdout, &mut io::stderr());
+            })
+        });
+
+        // hooks that don't read their input close stdin early, which isn't an error
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(&input);
+        }
+
+        let exit = child.wait().into_diagnostic()?;
+
+        if let Some(forwarder) = forwarder {
+            let _ = forwarder.join();
+        }
+
+        if exit.success() {
+            return Ok(());
+        }
+
+        Err(VoltError::HookFailedError {
+            hook: hook.name().to_string(),
+            code: exit.code().unwrap_or(1),
+        }
+        .into())
+    }
+}
@@ -16,7 +16,10 @@
 
 use crate::{
     cli::VoltConfig,
-    core::{classes::meta::Meta, utils::voltapi::VoltPackage},
+    core::{
+        classes::meta::Meta,
+        utils::{errors::VoltError, voltapi::VoltPackage},
+    },
 };
 
 use colored::Colorize;
@@ -27,7 +30,7 @@ use tar::Archive;
 use std::{
     collections::HashMap,
     io::{Cursor, Read, Write},
-    path::PathBuf,
+    path::{Component, Path, PathBuf},
 };
 
 pub fn write(text: &str, metadata: &Meta) {
@@ -89,14 +92,24 @@ pub fn extract_tarball(
     for entry in node_archive.entries().into_diagnostic()? {
         let mut entry = entry.into_diagnostic()?;
 
+        // directories are created along with the files in them
+        if entry.header().entry_type().is_dir() {
+            continue;
+        }
+
         // Read the contents of the entry
         let mut buffer = Vec::with_capacity(entry.size() as usize);
         entry.read_to_end(&mut buffer).into_diagnostic()?;
 
-        let entry_path = entry.path().unwrap();
+        let entry_path = entry.path().into_diagnostic()?.to_path_buf();
+
+        let invalid_path = || VoltError::TarballPathError {
+            package: package.key(),
+            path: entry_path.display().to_string(),
+        };
 
-        // Remove `package/` from `package/lib/index.js`
-        let cleaned_entry_path_string = entry_path.strip_prefix("package/").unwrap();
+        let file_name = package_file_name(package, &entry_path)?;
+        let cleaned_entry_path_string = Path::new(&file_name);
 
         // Create the path to the local .volt directory
         let mut package_directory = config.node_modules()?.join(VoltConfig::VOLT_HOME);
@@ -117,7 +130,7 @@ pub fn extract_tarball(
         entry_path.push(cleaned_entry_path_string);
 
         // Get the entry's parent
-        let entry_path_parent = entry_path.parent().unwrap();
+        let entry_path_parent = entry_path.parent().ok_or_else(invalid_path)?;
 
         // If we haven't created this directory yet, create it
         if !created_directories.iter().any(|p| p == entry_path_parent) {
@@ -132,7 +145,11 @@ pub fn extract_tarball(
         file_path.push(cleaned_entry_path_string);
 
         // Write the contents to node_modules
-        let mut file = std::fs::File::create(&file_path).unwrap();
+        let mut file =
+            std::fs::File::create(&file_path).map_err(|e| VoltError::WriteFileError {
+                source: e,
+                name: file_path.display().to_string(),
+            })?;
 
         file.write_all(&buffer).into_diagnostic()?;
 
@@ -141,16 +158,86 @@ pub fn extract_tarball(
         let sri = cacache::write_hash_sync(&config.volt_home()?, &buffer).into_diagnostic()?;
 
         // Insert the name of the file and map it to the hash of the file
-        cas_file_map.insert(cleaned_entry_path_string.to_str().unwrap().to_string(), sri);
+        cas_file_map.insert(file_name, sri);
     }
 
+    let cas_file_map = serde_json::to_string(&cas_file_map).into_diagnostic()?;
+
     // Write the file, shasum map to the content-addressable store
-    cacache::write_sync(
-        &config.volt_home()?,
-        &package.cacache_key(),
-        serde_json::to_string(&cas_file_map).into_diagnostic()?,
-    )
-    .into_diagnostic()?;
+    cacache::write_sync(&config.volt_home()?, &package.cacache_key(), &cas_file_map)
+        .into_diagnostic()?;
+
+    write_files_manifest(package, config, cas_file_map.as_bytes())
+}
+
+/// Store the files of a package in the content-addressable store without installing it, so the
+/// next install restores it from there
+pub fn cache_tarball(data: Vec<u8>, package: &VoltPackage, volt_home: &Path) -> miette::Result<()> {
+    let mut archive = Archive::new(Cursor::new(data));
+
+    let mut cas_file_map: HashMap<String, Integrity> = HashMap::new();
+
+    for entry in archive.entries().into_diagnostic()? {
+        let mut entry = entry.into_diagnostic()?;
+
+        if entry.header().entry_type().is_dir() {
+            continue;
+        }
+
+        let mut buffer = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut buffer).into_diagnostic()?;
+
+        let file_name = package_file_name(package, &entry.path().into_diagnostic()?)?;
+
+        let sri = cacache::write_hash_sync(volt_home, &buffer).into_diagnostic()?;
+
+        cas_file_map.insert(file_name, sri);
+    }
+
+    let cas_file_map = serde_json::to_string(&cas_file_map).into_diagnostic()?;
+
+    cacache::write_sync(volt_home, &package.cacache_key(), &cas_file_map).into_diagnostic()?;
+
+    Ok(())
+}
+
+/// The path of a tarball entry inside the package, `lib/index.js` for `package/lib/index.js`
+fn package_file_name(package: &VoltPackage, entry_path: &Path) -> miette::Result<String> {
+    let invalid_path = || VoltError::TarballPathError {
+        package: package.key(),
+        path: entry_path.display().to_string(),
+    };
+
+    // Remove `package/` from `package/lib/index.js`, a few packages use another directory
+    let mut components = entry_path.components();
+    components.next();
+
+    let cleaned = components.as_path();
+
+    if cleaned.as_os_str().is_empty()
+        || cleaned
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return Err(invalid_path().into());
+    }
+
+    Ok(cleaned.to_str().ok_or_else(invalid_path)?.to_string())
+}
+
+/// Record the integrity of every installed file next to the package, so `volt check` can
+/// detect edits without the store
+pub fn write_files_manifest(
+    package: &VoltPackage,
+    config: &VoltConfig,
+    cas_file_map: &[u8],
+) -> miette::Result<()> {
+    let path = package.files_manifest(&config.node_modules()?);
+
+    std::fs::write(&path, cas_file_map).map_err(|e| VoltError::WriteFileError {
+        source: e,
+        name: path.display().to_string(),
+    })?;
 
     Ok(())
 }
@@ -0,0 +1,112 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Logging to the terminal, and the debug log of every run kept for bug reports.
+//!
+//! The terminal shows `volt` logs at the level picked with `-q`/`-v`/`-vv` (or `RUST_LOG`),
+//! while the debug log always records them at debug level, like npm's `_logs`.
+
+use crate::cli::VoltConfig;
+
+use chrono::Utc;
+use tracing::Level;
+use tracing_subscriber::{filter::Targets, fmt, prelude::*};
+
+use std::{
+    fs::{self, File},
+    io,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+/// How many debug logs to keep in `~/.volt/logs`
+const LOGS_MAX: usize = 10;
+
+/// Start logging for this run, returning the path of the debug log if one could be created
+pub fn init(config: &VoltConfig, color: bool) -> Option<PathBuf> {
+    let terminal_filter = std::env::var("RUST_LOG")
+        .ok()
+        .and_then(|filter| filter.parse::<Targets>().ok())
+        .unwrap_or_else(|| Targets::new().with_target("volt", config.log_level()));
+
+    let terminal = fmt::layer()
+        .with_writer(io::stderr)
+        .with_ansi(color)
+        .without_time()
+        .with_filter(terminal_filter);
+
+    let log = match config.log_file() {
+        Some(path) => Some(path.to_path_buf()),
+        None => debug_log_path(config),
+    }
+    .and_then(|path| Some((File::create(&path).ok()?, path)));
+
+    let (file, path) = match log {
+        Some((file, path)) => (Some(file), Some(path)),
+        None => (None, None),
+    };
+
+    let debug_log = file.map(|file| {
+        fmt::layer()
+            .with_writer(Mutex::new(file))
+            .with_ansi(false)
+            .with_filter(Targets::new().with_target("volt", Level::DEBUG))
+    });
+
+    tracing_subscriber::registry()
+        .with(terminal)
+        .with(debug_log)
+        .init();
+
+    tracing::debug!(
+        "volt {} {}",
+        env!("CARGO_PKG_VERSION"),
+        std::env::args().skip(1).collect::<Vec<_>>().join(" ")
+    );
+
+    path
+}
+
+/// A new file in `~/.volt/logs`, removing the oldest logs past `LOGS_MAX`
+fn debug_log_path(config: &VoltConfig) -> Option<PathBuf> {
+    let logs_dir = config.logs_dir().ok()?;
+
+    fs::create_dir_all(&logs_dir).ok()?;
+
+    // the timestamps sort the logs from oldest to newest
+    let mut logs = fs::read_dir(&logs_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .map_or(false, |extension| extension == "log")
+        })
+        .collect::<Vec<_>>();
+
+    logs.sort();
+
+    let excess = (logs.len() + 1).saturating_sub(LOGS_MAX);
+
+    for log in logs.iter().take(excess) {
+        let _ = fs::remove_file(log);
+    }
+
+    Some(logs_dir.join(format!(
+        "{}-debug.log",
+        Utc::now().format("%Y-%m-%dT%H_%M_%S_%3fZ")
+    )))
+}
@@ -0,0 +1,189 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! The `packageManager` field of package.json, as corepack reads it.
+//!
+//! A project declaring another package manager, or a volt version this one isn't compatible
+//! with, gets a warning, or an error with `package-manager-strict`. When the pinned volt version
+//! is installed in `~/.volt/versions/<version>/bin` (the layout of
+//! `cargo install --root`), volt runs it instead.
+
+use crate::{
+    cli::VoltConfig,
+    core::utils::{errors::VoltError, package::PackageJson},
+};
+
+use colored::Colorize;
+use miette::{IntoDiagnostic, Result};
+use node_semver::{Range, Version};
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+};
+
+/// Set for a volt switched to, so it doesn't switch again
+const SWITCHED_ENV: &str = "VOLT_SWITCHED";
+
+/// A `packageManager` field, like `volt@0.0.3` or `pnpm@8.6.0+sha224.a1b2c3`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageManager {
+    pub name: String,
+    pub version: Version,
+    /// The `<algorithm>.<hex>` hash corepack checks downloads against, unused by volt
+    pub hash: Option<String>,
+}
+
+impl FromStr for PackageManager {
+    type Err = VoltError;
+
+    fn from_str(field: &str) -> Result<Self, Self::Err> {
+        let invalid = || VoltError::PackageManagerFieldError {
+            field: field.to_string(),
+        };
+
+        let (name, rest) = field.trim().split_once('@').ok_or_else(invalid)?;
+
+        let (version, hash) = match rest.split_once('+') {
+            Some((version, hash)) => (version, Some(hash.to_string())),
+            None => (rest, None),
+        };
+
+        if name.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            version: version.parse().map_err(|_| invalid())?,
+            hash,
+        })
+    }
+}
+
+/// The package manager pinned by the closest package.json declaring one, and that package.json
+pub fn pinned(dir: &Path) -> Result<Option<(PackageManager, PathBuf)>> {
+    for parent in dir.ancestors() {
+        let path = parent.join("package.json");
+
+        if !path.exists() {
+            continue;
+        }
+
+        if let Some(field) = PackageJson::read(&path)?.package_manager {
+            return Ok(Some((field.parse()?, path)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// The volt binary of a version installed in `~/.volt/versions`
+fn installed_binary(config: &VoltConfig, version: &Version) -> Result<Option<PathBuf>> {
+    let binary = config
+        .volt_home()?
+        .join("versions")
+        .join(version.to_string())
+        .join("bin")
+        .join(if cfg!(windows) { "volt.exe" } else { "volt" });
+
+    Ok(binary.exists().then(|| binary))
+}
+
+/// Check the running volt against the project's `packageManager`, running the pinned version
+/// instead when it's installed
+pub fn enforce(config: &VoltConfig) -> Result<()> {
+    let (pinned, path) = match pinned(&config.cwd()?)? {
+        Some(pinned) => pinned,
+        None => return Ok(()),
+    };
+
+    let current = env!("CARGO_PKG_VERSION")
+        .parse::<Version>()
+        .into_diagnostic()?;
+
+    let problem = if pinned.name != "volt" {
+        VoltError::PackageManagerMismatchError {
+            name: pinned.name,
+            path: path.display().to_string(),
+        }
+    } else {
+        if pinned.version == current {
+            return Ok(());
+        }
+
+        if config.package_manager_switch() && env::var_os(SWITCHED_ENV).is_none() {
+            if let Some(binary) = installed_binary(config, &pinned.version)? {
+                tracing::debug!("switching to volt {}", pinned.version);
+
+                let status = Command::new(&binary)
+                    .args(env::args_os().skip(1))
+                    .env(SWITCHED_ENV, pinned.version.to_string())
+                    .status()
+                    .into_diagnostic()?;
+
+                std::process::exit(status.code().unwrap_or(1));
+            }
+        }
+
+        // `0.0.3` is only compatible with itself, like a caret range
+        let compatible = format!("^{}", pinned.version)
+            .parse::<Range>()
+            .map_or(false, |range| current.satisfies(&range));
+
+        if compatible {
+            return Ok(());
+        }
+
+        VoltError::VoltVersionMismatchError {
+            wanted: pinned.version.to_string(),
+            current: current.to_string(),
+            path: path.display().to_string(),
+        }
+    };
+
+    if config.package_manager_strict() {
+        return Err(problem.into());
+    }
+
+    eprintln!("{}: {}", "warning".yellow().bold(), problem);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_fields() {
+        let volt = "volt@0.0.3".parse::<PackageManager>().unwrap();
+        assert_eq!(volt.name, "volt");
+        assert_eq!(volt.version.to_string(), "0.0.3");
+        assert_eq!(volt.hash, None);
+
+        let pnpm = "pnpm@8.6.0+sha224.a1b2c3"
+            .parse::<PackageManager>()
+            .unwrap();
+        assert_eq!(pnpm.name, "pnpm");
+        assert_eq!(pnpm.hash.as_deref(), Some("sha224.a1b2c3"));
+
+        assert!("yarn".parse::<PackageManager>().is_err());
+        assert!("yarn@latest".parse::<PackageManager>().is_err());
+    }
+}
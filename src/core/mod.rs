@@ -16,8 +16,22 @@
 
 #[macro_use]
 pub mod utils;
+pub mod auth;
+pub mod ci;
 pub mod classes;
+pub mod config;
+pub mod daemon;
+pub mod events;
+pub mod hooks;
 pub mod io;
+pub mod logging;
+pub mod manager;
 pub mod model;
 pub mod net;
+pub mod output;
+pub mod pack;
+pub mod progress;
 pub mod prompt;
+pub mod runtime;
+pub mod settings;
+pub mod telemetry;
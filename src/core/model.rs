@@ -14,5 +14,16 @@
     limitations under the License.
 */
 
+pub mod audit;
+pub mod conflict;
+pub mod engines;
 pub mod http_manager;
+pub mod import;
+pub mod license;
 pub mod lock_file;
+pub mod plan;
+pub mod policy;
+pub mod provenance;
+pub mod report;
+pub mod signature;
+pub mod usage;
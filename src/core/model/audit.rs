@@ -0,0 +1,330 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Security advisories affecting the packages installed in a project.
+
+use chrono::NaiveDate;
+use clap::ArgEnum;
+use colored::{ColoredString, Colorize};
+use miette::Result;
+use node_semver::{Range, Version};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use crate::core::{
+    model::lock_file::{DependencyKind, LockFile},
+    net::get_advisories,
+    utils::{package::PackageJson, voltapi::VoltPackage},
+};
+
+/// How severe a vulnerability is, as rated by the advisory database
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, ArgEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Low,
+    Moderate,
+    High,
+    Critical,
+}
+
+impl Severity {
+    pub const ALL: [Self; 5] = [
+        Self::Critical,
+        Self::High,
+        Self::Moderate,
+        Self::Low,
+        Self::Info,
+    ];
+
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Low => "low",
+            Self::Moderate => "moderate",
+            Self::High => "high",
+            Self::Critical => "critical",
+        }
+    }
+
+    pub fn colorize(&self, text: &str) -> ColoredString {
+        match self {
+            Self::Info => text.bright_blue(),
+            Self::Low => text.bright_white(),
+            Self::Moderate => text.bright_yellow(),
+            Self::High => text.bright_red(),
+            Self::Critical => text.red().bold(),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// An advisory returned by the registry's bulk advisory endpoint
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Advisory {
+    pub id: u64,
+    pub url: String,
+    pub title: String,
+    pub severity: Severity,
+    /// Range of the versions affected by the advisory
+    pub vulnerable_versions: String,
+    #[serde(default)]
+    pub cwe: Vec<String>,
+}
+
+impl Advisory {
+    pub fn affects(&self, version: &str) -> bool {
+        match (
+            self.vulnerable_versions.parse::<Range>(),
+            version.parse::<Version>(),
+        ) {
+            (Ok(range), Ok(version)) => range.satisfies(&version),
+            // err on the side of reporting advisories that can't be matched
+            _ => true,
+        }
+    }
+}
+
+/// An installed package affected by an advisory
+#[derive(Clone, Debug, Serialize)]
+pub struct Vulnerability {
+    pub name: String,
+    pub version: String,
+    pub advisory: Advisory,
+    /// Chains of dependencies leading to the package (`app > mkdirp@0.5.5 > minimist@1.2.5`)
+    pub paths: Vec<String>,
+}
+
+/// An advisory accepted for a project, configured under `audit-exceptions` in `volt.toml`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuditException {
+    /// The id of the advisory, as printed by `volt audit --json`
+    pub id: u64,
+    pub package: String,
+    /// The last day the exception applies
+    #[serde(default)]
+    pub expires: Option<toml::value::Datetime>,
+    #[serde(default)]
+    pub reason: String,
+}
+
+impl AuditException {
+    pub fn matches(&self, vulnerability: &Vulnerability) -> bool {
+        self.id == vulnerability.advisory.id && self.package == vulnerability.name
+    }
+
+    pub fn expiry(&self) -> Option<NaiveDate> {
+        let expires = self.expires.as_ref()?.to_string();
+
+        NaiveDate::parse_from_str(expires.get(..10)?, "%Y-%m-%d").ok()
+    }
+
+    pub fn is_expired(&self, today: NaiveDate) -> bool {
+        self.expiry().map_or(false, |expiry| expiry < today)
+    }
+}
+
+/// A problem with an audit exception that should be cleaned up
+#[derive(Debug)]
+pub enum ExceptionWarning<'a> {
+    /// The exception has expired, so the vulnerability is reported again
+    Expired(&'a AuditException),
+    /// Nothing in the project is affected by the advisory anymore
+    Unused(&'a AuditException),
+}
+
+impl fmt::Display for ExceptionWarning<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Expired(exception) => write!(
+                f,
+                "the audit exception for advisory {} ({}) expired on {}",
+                exception.id,
+                exception.package,
+                exception
+                    .expiry()
+                    .map_or_else(String::new, |expiry| expiry.to_string())
+            ),
+            Self::Unused(exception) => write!(
+                f,
+                "the audit exception for advisory {} ({}) no longer matches any vulnerability and can be removed",
+                exception.id, exception.package
+            ),
+        }
+    }
+}
+
+/// The vulnerabilities found in a project
+#[derive(Debug, Default, Serialize)]
+pub struct AuditReport {
+    /// Number of packages that were audited
+    pub dependencies: usize,
+    pub vulnerabilities: Vec<Vulnerability>,
+    /// Vulnerabilities covered by an audit exception
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ignored: Vec<Vulnerability>,
+}
+
+impl AuditReport {
+    /// Number of vulnerabilities with the given severity
+    pub fn count(&self, severity: Severity) -> usize {
+        self.vulnerabilities
+            .iter()
+            .filter(|v| v.advisory.severity == severity)
+            .count()
+    }
+
+    /// Move the vulnerabilities covered by an unexpired exception into `ignored`
+    pub fn apply_exceptions<'a>(
+        &mut self,
+        exceptions: &'a [AuditException],
+        today: NaiveDate,
+    ) -> Vec<ExceptionWarning<'a>> {
+        let mut used = vec![false; exceptions.len()];
+
+        for vulnerability in std::mem::take(&mut self.vulnerabilities) {
+            let mut ignored = false;
+
+            for (index, exception) in exceptions.iter().enumerate() {
+                if exception.matches(&vulnerability) {
+                    used[index] = true;
+                    ignored |= !exception.is_expired(today);
+                }
+            }
+
+            if ignored {
+                self.ignored.push(vulnerability);
+            } else {
+                self.vulnerabilities.push(vulnerability);
+            }
+        }
+
+        exceptions
+            .iter()
+            .zip(used)
+            .filter_map(|(exception, used)| {
+                if exception.is_expired(today) {
+                    Some(ExceptionWarning::Expired(exception))
+                } else if !used {
+                    Some(ExceptionWarning::Unused(exception))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Whether any vulnerability is at least as severe as `level`
+    pub fn fails(&self, level: Severity) -> bool {
+        self.vulnerabilities
+            .iter()
+            .any(|v| v.advisory.severity >= level)
+    }
+}
+
+/// Check every package reachable from the manifests against the registry's advisories
+pub async fn audit(
+    client: &Client,
+    lock_file: &LockFile,
+    manifests: &[PackageJson],
+    production: bool,
+) -> Result<AuditReport> {
+    // (manifest name, direct dependency)
+    let mut roots: Vec<(&str, &VoltPackage)> = vec![];
+
+    for manifest in manifests {
+        for (kind, _, package) in lock_file.roots(manifest) {
+            if production && kind == DependencyKind::Dev {
+                continue;
+            }
+
+            if let Some(package) = package {
+                roots.push((manifest.name.as_str(), package));
+            }
+        }
+    }
+
+    let reachable = lock_file.reachable(roots.iter().map(|(_, package)| *package));
+
+    let mut versions: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+
+    for key in &reachable {
+        if let Some(package) = lock_file.dependencies.get(key) {
+            versions
+                .entry(package.name.clone())
+                .or_default()
+                .insert(package.version.clone());
+        }
+    }
+
+    let advisories = get_advisories(client, &versions).await?;
+
+    let mut vulnerabilities = vec![];
+
+    for (name, advisories) in advisories {
+        for version in versions.get(&name).into_iter().flatten() {
+            let key = format!("{}@{}", name, version);
+
+            for advisory in advisories.iter().filter(|a| a.affects(version)) {
+                let mut paths = roots
+                    .iter()
+                    .filter_map(|(label, root)| {
+                        let path = lock_file.path_to(root, &key)?;
+
+                        Some(
+                            std::iter::once(label.to_string())
+                                .chain(path.iter().map(|package| package.key()))
+                                .collect::<Vec<_>>()
+                                .join(" > "),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                paths.sort_by_key(|path| path.len());
+                paths.dedup();
+
+                vulnerabilities.push(Vulnerability {
+                    name: name.clone(),
+                    version: version.clone(),
+                    advisory: advisory.clone(),
+                    paths,
+                });
+            }
+        }
+    }
+
+    vulnerabilities.sort_by(|a, b| {
+        (b.advisory.severity, &a.name, &a.version).cmp(&(a.advisory.severity, &b.name, &b.version))
+    });
+
+    Ok(AuditReport {
+        dependencies: reachable.len(),
+        vulnerabilities,
+        ..Default::default()
+    })
+}
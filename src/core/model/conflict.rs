@@ -0,0 +1,263 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Peer dependencies the project's direct dependencies don't satisfy.
+//!
+//! Peers aren't installed for the packages that ask for them, they're resolved from the
+//! project's `node_modules`, so the version the project depends on has to satisfy every range.
+
+use crate::core::{model::lock_file::LockFile, utils::voltapi::VoltPackage};
+
+use node_semver::{Range, Version};
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+};
+
+/// A range a package requires its peer to satisfy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constraint {
+    /// The `name@version` of the package with the peer dependency
+    pub dependent: String,
+    pub range: String,
+    /// Chain of packages from a direct dependency to the dependent
+    pub path: Vec<String>,
+    pub satisfied: bool,
+}
+
+/// A peer dependency with at least one range the installed version doesn't satisfy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub peer: String,
+    /// The version the project depends on, if it depends on the peer at all
+    pub installed: Option<String>,
+    /// Every range the peer is required to satisfy, including the satisfied ones
+    pub constraints: Vec<Constraint>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unsatisfied = self.unsatisfied().count();
+        let packages = if unsatisfied == 1 {
+            "package"
+        } else {
+            "packages"
+        };
+
+        match &self.installed {
+            Some(version) => write!(
+                f,
+                "{}@{} doesn't satisfy the peer dependency of {} {}",
+                self.peer, version, unsatisfied, packages
+            ),
+            None => write!(
+                f,
+                "{} is a missing peer dependency of {} {}",
+                self.peer, unsatisfied, packages
+            ),
+        }
+    }
+}
+
+impl Conflict {
+    pub fn unsatisfied(&self) -> impl Iterator<Item = &Constraint> {
+        self.constraints.iter().filter(|c| !c.satisfied)
+    }
+
+    /// The newest of `versions` satisfying every constraint, `None` when the ranges don't overlap
+    pub fn newest_satisfying<'a, I>(&self, versions: I) -> Option<Version>
+    where
+        I: IntoIterator<Item = &'a Version>,
+    {
+        let ranges = self
+            .constraints
+            .iter()
+            .filter_map(|c| c.range.parse::<Range>().ok())
+            .collect::<Vec<_>>();
+
+        newest(versions, |version| {
+            ranges.iter().all(|range| range.satisfies(version))
+        })
+    }
+}
+
+/// The newest stable version of `versions` accepted by `matches`
+pub fn newest<'a, I, F>(versions: I, matches: F) -> Option<Version>
+where
+    I: IntoIterator<Item = &'a Version>,
+    F: Fn(&Version) -> bool,
+{
+    versions
+        .into_iter()
+        .filter(|version| version.pre_release.is_empty() && matches(version))
+        .max()
+        .cloned()
+}
+
+/// Find the unmet peer dependencies of the packages in `dependents`
+///
+/// Peers with an entry in the project's `overrides` are left alone, the override records the
+/// version the project settled on.
+pub fn find_conflicts<'a, I>(
+    lock_file: &LockFile,
+    dependents: I,
+    overrides: &BTreeMap<String, String>,
+) -> Vec<Conflict>
+where
+    I: IntoIterator<Item = &'a VoltPackage>,
+{
+    let dependents = dependents
+        .into_iter()
+        .map(VoltPackage::key)
+        .collect::<HashSet<_>>();
+
+    let roots = lock_file
+        .direct
+        .iter()
+        .filter_map(|(name, version)| lock_file.find(name, version))
+        .collect::<Vec<_>>();
+
+    let mut conflicts = BTreeMap::<&str, Conflict>::new();
+
+    for key in lock_file.reachable(roots.iter().copied()) {
+        let package = match lock_file.dependencies.get(&key) {
+            Some(package) => package,
+            None => continue,
+        };
+
+        for (peer, range) in package.peer_dependencies.iter().flatten() {
+            // a package that also depends on its peer gets its own copy
+            if overrides.contains_key(peer)
+                || package
+                    .dependencies
+                    .as_ref()
+                    .map_or(false, |dependencies| dependencies.contains_key(peer))
+            {
+                continue;
+            }
+
+            let installed = lock_file.direct.get(peer);
+
+            let satisfied = match (installed, range.parse::<Range>()) {
+                (Some(version), Ok(range)) => version
+                    .parse::<Version>()
+                    .map_or(false, |version| range.satisfies(&version)),
+                // a range volt can't read is left to the package
+                (Some(_), Err(_)) => true,
+                (None, _) => false,
+            };
+
+            let path = roots
+                .iter()
+                .find_map(|root| lock_file.path_to(root, &key))
+                .map(|path| path.iter().map(|p| p.key()).collect())
+                .unwrap_or_else(|| vec![key.clone()]);
+
+            conflicts
+                .entry(peer.as_str())
+                .or_insert_with(|| Conflict {
+                    peer: peer.clone(),
+                    installed: installed.cloned(),
+                    constraints: vec![],
+                })
+                .constraints
+                .push(Constraint {
+                    dependent: key.clone(),
+                    range: range.clone(),
+                    path,
+                    satisfied,
+                });
+        }
+    }
+
+    // only the packages being installed are reported, older conflicts were reported already
+    conflicts
+        .into_values()
+        .filter(|conflict| {
+            conflict
+                .unsatisfied()
+                .any(|c| dependents.contains(&c.dependent))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    fn package(name: &str, version: &str, peers: &[(&str, &str)]) -> VoltPackage {
+        VoltPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            peer_dependencies: Some(
+                peers
+                    .iter()
+                    .map(|(name, range)| (name.to_string(), range.to_string()))
+                    .collect::<HashMap<_, _>>(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn finds_unmet_peers() {
+        let mut lock_file = LockFile::default();
+
+        let packages = [
+            package("react", "17.0.2", &[]),
+            package("react-dom", "18.0.0", &[("react", "^18.0.0")]),
+            package("react-router", "6.3.0", &[("react", ">=16.8")]),
+        ];
+
+        for package in &packages {
+            lock_file
+                .direct
+                .insert(package.name.clone(), package.version.clone());
+            lock_file
+                .dependencies
+                .insert(package.key(), package.clone());
+        }
+
+        let conflicts = find_conflicts(&lock_file, &packages[1..], &BTreeMap::new());
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].peer, "react");
+        assert_eq!(
+            conflicts[0]
+                .unsatisfied()
+                .map(|c| c.dependent.as_str())
+                .collect::<Vec<_>>(),
+            ["react-dom@18.0.0"]
+        );
+
+        let versions = ["17.0.2", "18.0.0", "18.2.0", "19.0.0-rc.0"]
+            .iter()
+            .map(|v| v.parse::<Version>().unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            conflicts[0].newest_satisfying(&versions),
+            Some("18.2.0".parse().unwrap())
+        );
+
+        let overrides = BTreeMap::from([("react".to_string(), "17.0.2".to_string())]);
+
+        assert!(find_conflicts(&lock_file, &packages[1..], &overrides).is_empty());
+    }
+}
@@ -0,0 +1,189 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! The `engines` of packages, checked against the node and volt versions in use.
+
+use node_semver::{Range, Version};
+
+use std::{collections::HashMap, fmt};
+
+use crate::core::{
+    model::lock_file::LockFile,
+    utils::voltapi::{Engine, VoltPackage},
+};
+
+/// The engines that are checked, npm and yarn ranges don't apply to volt
+const CHECKED: [&str; 2] = ["node", "volt"];
+
+/// The versions of the engines in use, `None` when there's no node to run
+#[derive(Debug, Clone)]
+pub struct Engines {
+    pub node: Option<Version>,
+    pub volt: Version,
+}
+
+/// A package whose `engines` the versions in use don't satisfy
+#[derive(Debug)]
+pub struct Mismatch {
+    /// Chain of packages from the project to the package with the `engines`
+    pub path: Vec<String>,
+    pub engine: String,
+    pub wanted: String,
+    pub current: Version,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} wants {} {} but {} is in use",
+            self.path.last().map(String::as_str).unwrap_or_default(),
+            self.engine,
+            self.wanted,
+            self.current
+        )
+    }
+}
+
+impl Engines {
+    pub fn new(node: Option<Version>) -> Self {
+        Self {
+            node,
+            volt: env!("CARGO_PKG_VERSION")
+                .parse()
+                .expect("the crate version is valid semver"),
+        }
+    }
+
+    /// The engines in `ranges` (engine to range) that the versions in use don't satisfy, as
+    /// `(engine, range, version)`
+    pub fn unsatisfied<'a, I>(&self, ranges: I) -> Vec<(String, String, Version)>
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        ranges
+            .into_iter()
+            .filter_map(|(engine, wanted)| {
+                let current = match engine {
+                    "node" => self.node.as_ref()?,
+                    "volt" => &self.volt,
+                    _ => return None,
+                };
+
+                // ranges nobody can satisfy, like `please use node 14`, are ignored like npm does
+                let range = wanted.trim().parse::<Range>().ok()?;
+
+                (!current.satisfies(&range))
+                    .then(|| (engine.to_string(), wanted.to_string(), current.clone()))
+            })
+            .collect()
+    }
+
+    /// Check the `engines` of every package in a resolved tree
+    pub fn check(
+        &self,
+        root: &str,
+        roots: &[VoltPackage],
+        tree: &HashMap<String, VoltPackage>,
+    ) -> Vec<Mismatch> {
+        let graph = LockFile {
+            dependencies: tree.clone().into_iter().collect(),
+            ..Default::default()
+        };
+
+        let mut mismatches = vec![];
+
+        for (key, package) in tree {
+            let entries = package.engines.as_ref().map(ranges).unwrap_or_default();
+
+            for (engine, wanted, current) in self.unsatisfied(
+                entries
+                    .iter()
+                    .map(|(engine, range)| (engine.as_str(), range.as_str())),
+            ) {
+                let path = roots
+                    .iter()
+                    .find_map(|root| graph.path_to(root, key))
+                    .map(|path| path.iter().map(|package| package.key()).collect::<Vec<_>>())
+                    .unwrap_or_else(|| vec![key.clone()]);
+
+                mismatches.push(Mismatch {
+                    path: std::iter::once(root.to_string()).chain(path).collect(),
+                    engine,
+                    wanted,
+                    current,
+                });
+            }
+        }
+
+        mismatches.sort_by(|a, b| a.path.cmp(&b.path));
+
+        mismatches
+    }
+}
+
+/// The `(engine, range)` pairs of an `engines` field
+///
+/// Old packages list them as strings like `node >= 0.8`, instead of an object.
+pub fn ranges(engine: &Engine) -> Vec<(String, String)> {
+    let parse = |entry: &str| {
+        let (name, range) = entry.trim().split_once(char::is_whitespace)?;
+        Some((name.to_string(), range.trim().to_string()))
+    };
+
+    let ranges = match engine {
+        Engine::Map(map) => map
+            .iter()
+            .map(|(name, range)| (name.clone(), range.clone()))
+            .collect(),
+        Engine::String(entry) => parse(entry).into_iter().collect(),
+        Engine::List(entries) => entries.iter().filter_map(|entry| parse(entry)).collect(),
+    };
+
+    ranges
+        .into_iter()
+        .filter(|(name, _): &(String, String)| CHECKED.contains(&name.as_str()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_unsatisfied_engines() {
+        let engines = Engines {
+            node: Some("16.20.0".parse().unwrap()),
+            volt: "1.0.0".parse().unwrap(),
+        };
+
+        let unsatisfied = engines.unsatisfied([
+            ("node", ">=18"),
+            ("node", "^14 || ^16"),
+            ("volt", ">=1"),
+            ("npm", ">=9"),
+        ]);
+
+        assert_eq!(unsatisfied.len(), 1);
+        assert_eq!(unsatisfied[0].1, ">=18");
+
+        let legacy = Engine::List(vec![String::from("node >= 0.8"), String::from("npm 1")]);
+        assert_eq!(
+            ranges(&legacy),
+            vec![(String::from("node"), String::from(">= 0.8"))]
+        );
+    }
+}
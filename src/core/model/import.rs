@@ -0,0 +1,716 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Read the lockfiles of npm, yarn and pnpm.
+//!
+//! Each lockfile is reduced to the exact version of every installed package and of the
+//! dependencies it resolved to, which is what `volt.lock` pins. Package metadata like bins and
+//! engines comes from the registry instead, since not every lockfile records it.
+
+use clap::ArgEnum;
+use miette::{IntoDiagnostic, Result};
+use node_semver::Version;
+use serde::Deserialize;
+use serde_json::Value;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::Path,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum PackageManager {
+    Npm,
+    Yarn,
+    Pnpm,
+}
+
+impl PackageManager {
+    /// The lockfiles each package manager writes, in order of preference
+    pub fn lockfiles(self) -> &'static [&'static str] {
+        match self {
+            Self::Npm => &["package-lock.json", "npm-shrinkwrap.json"],
+            Self::Yarn => &["yarn.lock"],
+            Self::Pnpm => &["pnpm-lock.yaml"],
+        }
+    }
+
+    /// The package managers with a lockfile in `dir`
+    pub fn detect(dir: &Path) -> Vec<Self> {
+        [Self::Npm, Self::Yarn, Self::Pnpm]
+            .into_iter()
+            .filter(|manager| manager.lockfile(dir).is_some())
+            .collect()
+    }
+
+    /// The name of the lockfile this package manager wrote in `dir`
+    pub fn lockfile(self, dir: &Path) -> Option<&'static str> {
+        self.lockfiles()
+            .iter()
+            .copied()
+            .find(|lockfile| dir.join(lockfile).exists())
+    }
+}
+
+impl fmt::Display for PackageManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Npm => write!(f, "npm"),
+            Self::Yarn => write!(f, "yarn"),
+            Self::Pnpm => write!(f, "pnpm"),
+        }
+    }
+}
+
+/// A package pinned by another package manager's lockfile
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedPackage {
+    pub name: String,
+    pub version: String,
+    /// Only yarn berry doesn't record a Subresource Integrity
+    pub integrity: Option<String>,
+    pub optional: bool,
+    /// The exact version each dependency resolved to
+    pub dependencies: HashMap<String, String>,
+}
+
+/// Everything volt needs from another package manager's lockfile
+#[derive(Debug, Default)]
+pub struct ImportedLock {
+    /// Resolved version of each direct dependency (`name` -> `version`)
+    pub direct: BTreeMap<String, String>,
+    /// Every installed package, keyed by `name@version`
+    pub packages: BTreeMap<String, ImportedPackage>,
+}
+
+impl ImportedLock {
+    fn insert(&mut self, package: ImportedPackage) {
+        self.packages
+            .insert(format!("{}@{}", package.name, package.version), package);
+    }
+}
+
+/// Read a lockfile, resolving direct dependencies against the project's `manifest`
+pub fn import(manager: PackageManager, contents: &str, manifest: &Value) -> Result<ImportedLock> {
+    match manager {
+        PackageManager::Npm => import_npm(contents, manifest),
+        PackageManager::Yarn if contents.contains("__metadata:") => {
+            import_yarn_berry(contents, manifest)
+        }
+        PackageManager::Yarn => import_yarn(contents, manifest),
+        PackageManager::Pnpm => import_pnpm(contents),
+    }
+}
+
+/// The dependencies of every kind declared by a package.json
+fn declared_dependencies(manifest: &Value) -> Vec<(&str, &str)> {
+    ["dependencies", "devDependencies", "optionalDependencies"]
+        .iter()
+        .filter_map(|field| manifest[field].as_object())
+        .flatten()
+        .filter_map(|(name, range)| Some((name.as_str(), range.as_str()?)))
+        .collect()
+}
+
+/// Split `name@version`, keeping the `@` of a scope in the name
+fn split_spec(spec: &str) -> Option<(&str, &str)> {
+    match spec.rfind('@') {
+        Some(index) if index > 0 => Some((&spec[..index], &spec[index + 1..])),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct NpmLock {
+    #[serde(default)]
+    packages: BTreeMap<String, NpmEntry>,
+    /// The nested tree of lockfile version 1
+    #[serde(default)]
+    dependencies: BTreeMap<String, NpmLegacyEntry>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NpmEntry {
+    name: Option<String>,
+    version: Option<String>,
+    integrity: Option<String>,
+    #[serde(default)]
+    optional: bool,
+    #[serde(default)]
+    link: bool,
+    #[serde(default)]
+    dependencies: BTreeMap<String, String>,
+    #[serde(default)]
+    optional_dependencies: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NpmLegacyEntry {
+    version: String,
+    integrity: Option<String>,
+    #[serde(default)]
+    optional: bool,
+    #[serde(default)]
+    requires: BTreeMap<String, String>,
+    #[serde(default)]
+    dependencies: BTreeMap<String, NpmLegacyEntry>,
+}
+
+/// Read a `package-lock.json` or `npm-shrinkwrap.json`
+fn import_npm(contents: &str, manifest: &Value) -> Result<ImportedLock> {
+    let lock: NpmLock = serde_json::from_str(contents).into_diagnostic()?;
+
+    let mut packages = lock.packages;
+
+    if packages.is_empty() {
+        flatten_legacy(&lock.dependencies, "", &mut packages);
+    }
+
+    let mut imported = ImportedLock::default();
+
+    for (path, entry) in &packages {
+        // the project itself, workspaces and links to them
+        if !path.starts_with("node_modules/") || entry.link {
+            continue;
+        }
+
+        let version = match &entry.version {
+            Some(version) => version.clone(),
+            None => continue,
+        };
+
+        let name = entry
+            .name
+            .clone()
+            .unwrap_or_else(|| npm_name(path).to_string());
+
+        let dependencies = entry
+            .dependencies
+            .keys()
+            .chain(entry.optional_dependencies.keys())
+            .filter_map(|dependency| {
+                let (_, resolved) = resolve_npm(&packages, path, dependency)?;
+
+                Some((dependency.clone(), resolved.version.clone()?))
+            })
+            .collect();
+
+        // top level packages are the direct dependencies
+        if !path["node_modules/".len()..].contains("/node_modules/") {
+            imported.direct.insert(name.clone(), version.clone());
+        }
+
+        imported.insert(ImportedPackage {
+            name,
+            version,
+            integrity: entry.integrity.clone(),
+            optional: entry.optional,
+            dependencies,
+        });
+    }
+
+    // everything hoisted is top level, but only what the project declares is direct
+    let declared = declared_dependencies(manifest);
+
+    imported.direct.retain(|name, _| {
+        declared
+            .iter()
+            .any(|(declared, _)| *declared == name.as_str())
+    });
+
+    Ok(imported)
+}
+
+/// Convert the nested tree of lockfile version 1 into the `node_modules/...` paths of later versions
+fn flatten_legacy(
+    dependencies: &BTreeMap<String, NpmLegacyEntry>,
+    prefix: &str,
+    packages: &mut BTreeMap<String, NpmEntry>,
+) {
+    for (name, entry) in dependencies {
+        let path = format!("{}node_modules/{}", prefix, name);
+
+        flatten_legacy(&entry.dependencies, &format!("{}/", path), packages);
+
+        packages.insert(
+            path,
+            NpmEntry {
+                name: Some(name.clone()),
+                version: Some(entry.version.clone()),
+                integrity: entry.integrity.clone(),
+                optional: entry.optional,
+                dependencies: entry.requires.clone(),
+                ..Default::default()
+            },
+        );
+    }
+}
+
+/// The package name at a `node_modules/a/node_modules/@scope/b` path
+fn npm_name(path: &str) -> &str {
+    path.rsplit_once("node_modules/")
+        .map_or(path, |(_, name)| name)
+}
+
+/// Find the package node would load for `dependency` from the package at `from`, walking up
+/// the nested `node_modules` directories
+fn resolve_npm<'a>(
+    packages: &'a BTreeMap<String, NpmEntry>,
+    from: &str,
+    dependency: &str,
+) -> Option<(String, &'a NpmEntry)> {
+    let mut base = from.to_string();
+
+    loop {
+        let candidate = if base.is_empty() {
+            format!("node_modules/{}", dependency)
+        } else {
+            format!("{}/node_modules/{}", base, dependency)
+        };
+
+        if let Some(entry) = packages.get(&candidate) {
+            return Some((candidate, entry));
+        }
+
+        if base.is_empty() {
+            return None;
+        }
+
+        base = match base.rfind("/node_modules/") {
+            Some(index) => base[..index].to_string(),
+            None => String::new(),
+        };
+    }
+}
+
+/// An entry of a yarn lockfile, listing every `name@range` it satisfies
+#[derive(Debug, Default)]
+struct YarnEntry {
+    descriptors: Vec<String>,
+    version: String,
+    integrity: Option<String>,
+    dependencies: BTreeMap<String, String>,
+    optional_dependencies: BTreeMap<String, String>,
+}
+
+/// Read a yarn 1 `yarn.lock`, which uses its own indented format
+fn import_yarn(contents: &str, manifest: &Value) -> Result<ImportedLock> {
+    let mut entries: Vec<YarnEntry> = vec![];
+    let mut section = None;
+
+    let unquote = |value: &str| value.trim().trim_matches('"').to_string();
+
+    for line in contents.lines() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let indent = line.len() - line.trim_start().len();
+        let line = line.trim();
+
+        match indent {
+            0 => {
+                entries.push(YarnEntry {
+                    descriptors: line
+                        .trim_end_matches(':')
+                        .split(", ")
+                        .map(unquote)
+                        .collect(),
+                    ..Default::default()
+                });
+
+                section = None;
+            }
+            2 => {
+                let entry = entries
+                    .last_mut()
+                    .ok_or_else(|| miette::miette!("yarn.lock starts with an indented line"))?;
+
+                if let Some(name) = line.strip_suffix(':') {
+                    section = Some(name.to_string());
+                    continue;
+                }
+
+                section = None;
+
+                let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+
+                match key {
+                    "version" => entry.version = unquote(value),
+                    "integrity" => entry.integrity = Some(unquote(value)),
+                    _ => {}
+                }
+            }
+            _ => {
+                let entry = entries
+                    .last_mut()
+                    .ok_or_else(|| miette::miette!("yarn.lock starts with an indented line"))?;
+
+                // ranges can contain spaces, names can't
+                let (name, range) = line
+                    .split_once(' ')
+                    .map(|(name, range)| (unquote(name), unquote(range)))
+                    .ok_or_else(|| miette::miette!("unexpected line in yarn.lock: {}", line))?;
+
+                match section.as_deref() {
+                    Some("dependencies") => {
+                        entry.dependencies.insert(name, range);
+                    }
+                    Some("optionalDependencies") => {
+                        entry.optional_dependencies.insert(name, range);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    Ok(resolve_yarn(entries, manifest))
+}
+
+#[derive(Debug, Deserialize)]
+struct BerryEntry {
+    version: String,
+    resolution: String,
+    #[serde(default)]
+    dependencies: BTreeMap<String, String>,
+    #[serde(default, rename = "optionalDependencies")]
+    optional_dependencies: BTreeMap<String, String>,
+}
+
+/// Read a yarn 2+ `yarn.lock`, which is YAML
+fn import_yarn_berry(contents: &str, manifest: &Value) -> Result<ImportedLock> {
+    let lock: BTreeMap<String, serde_yaml::Value> =
+        serde_yaml::from_str(contents).into_diagnostic()?;
+
+    let mut entries = vec![];
+
+    for (descriptors, entry) in lock {
+        if descriptors == "__metadata" {
+            continue;
+        }
+
+        let entry: BerryEntry = match serde_yaml::from_value(entry) {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+
+        // workspaces, patches and git dependencies can't be pinned to a registry version
+        if !entry.resolution.contains("@npm:") {
+            continue;
+        }
+
+        entries.push(YarnEntry {
+            descriptors: descriptors.split(", ").map(String::from).collect(),
+            version: entry.version,
+            integrity: None,
+            dependencies: entry.dependencies,
+            optional_dependencies: entry.optional_dependencies,
+        });
+    }
+
+    Ok(resolve_yarn(entries, manifest))
+}
+
+/// Resolve the `name@range` dependencies of yarn entries to versions
+fn resolve_yarn(entries: Vec<YarnEntry>, manifest: &Value) -> ImportedLock {
+    let mut versions = HashMap::new();
+
+    for entry in &entries {
+        for descriptor in &entry.descriptors {
+            versions.insert(descriptor.clone(), entry.version.clone());
+        }
+    }
+
+    // yarn 2+ prefixes registry ranges with `npm:`, in descriptors but not always elsewhere
+    let resolve = |name: &str, range: &str| {
+        versions
+            .get(&format!("{}@{}", name, range))
+            .or_else(|| versions.get(&format!("{}@npm:{}", name, range)))
+            .cloned()
+    };
+
+    let mut imported = ImportedLock::default();
+
+    for (name, range) in declared_dependencies(manifest) {
+        if let Some(version) = resolve(name, range) {
+            imported.direct.insert(name.to_string(), version);
+        }
+    }
+
+    for entry in &entries {
+        let name = match entry.descriptors.first().and_then(|d| split_spec(d)) {
+            Some((name, _)) => name.to_string(),
+            None => continue,
+        };
+
+        let dependencies = entry
+            .dependencies
+            .iter()
+            .chain(&entry.optional_dependencies)
+            .filter_map(|(dependency, range)| {
+                Some((dependency.clone(), resolve(dependency, range)?))
+            })
+            .collect();
+
+        imported.insert(ImportedPackage {
+            name,
+            version: entry.version.clone(),
+            integrity: entry.integrity.clone(),
+            optional: false,
+            dependencies,
+        });
+    }
+
+    // a package is optional when only reachable through optionalDependencies
+    for entry in &entries {
+        for (dependency, range) in &entry.optional_dependencies {
+            if let Some(version) = resolve(dependency, range) {
+                if let Some(package) = imported
+                    .packages
+                    .get_mut(&format!("{}@{}", dependency, version))
+                {
+                    package.optional = true;
+                }
+            }
+        }
+    }
+
+    imported
+}
+
+#[derive(Debug, Deserialize)]
+struct PnpmLock {
+    #[serde(default)]
+    importers: BTreeMap<String, PnpmImporter>,
+    #[serde(flatten)]
+    root: PnpmImporter,
+    #[serde(default)]
+    packages: BTreeMap<String, PnpmEntry>,
+    /// Lockfile version 9 moved dependencies out of `packages`
+    #[serde(default)]
+    snapshots: BTreeMap<String, PnpmEntry>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PnpmImporter {
+    #[serde(default)]
+    dependencies: BTreeMap<String, PnpmVersion>,
+    #[serde(default)]
+    dev_dependencies: BTreeMap<String, PnpmVersion>,
+    #[serde(default)]
+    optional_dependencies: BTreeMap<String, PnpmVersion>,
+}
+
+/// Lockfile version 5 lists versions, later versions `{ specifier, version }`
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum PnpmVersion {
+    Version(String),
+    Specified { version: String },
+}
+
+impl PnpmVersion {
+    fn version(&self) -> &str {
+        match self {
+            Self::Version(version) | Self::Specified { version } => version,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PnpmEntry {
+    #[serde(default)]
+    resolution: PnpmResolution,
+    #[serde(default)]
+    optional: bool,
+    #[serde(default)]
+    dependencies: BTreeMap<String, String>,
+    #[serde(default)]
+    optional_dependencies: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PnpmResolution {
+    integrity: Option<String>,
+}
+
+/// Read a `pnpm-lock.yaml`
+fn import_pnpm(contents: &str) -> Result<ImportedLock> {
+    let lock: PnpmLock = serde_yaml::from_str(contents).into_diagnostic()?;
+
+    let mut imported = ImportedLock::default();
+
+    let root = lock.importers.get(".").unwrap_or(&lock.root);
+
+    for (name, version) in root
+        .dependencies
+        .iter()
+        .chain(&root.dev_dependencies)
+        .chain(&root.optional_dependencies)
+    {
+        if let Some(version) = pnpm_version(version.version()) {
+            imported.direct.insert(name.clone(), version);
+        }
+    }
+
+    // version 9 keys snapshots by the peers they were resolved with
+    let snapshots = lock
+        .snapshots
+        .iter()
+        .filter_map(|(key, snapshot)| Some((pnpm_key(key)?, snapshot)))
+        .collect::<HashMap<_, _>>();
+
+    for (key, entry) in &lock.packages {
+        let (name, version) = match pnpm_key(key) {
+            Some(spec) => spec,
+            None => continue,
+        };
+
+        let snapshot = snapshots
+            .get(&(name.clone(), version.clone()))
+            .unwrap_or(&entry);
+
+        let dependencies = snapshot
+            .dependencies
+            .iter()
+            .chain(&snapshot.optional_dependencies)
+            .filter_map(|(dependency, version)| Some((dependency.clone(), pnpm_version(version)?)))
+            .collect();
+
+        imported.insert(ImportedPackage {
+            name,
+            version,
+            integrity: entry.resolution.integrity.clone(),
+            optional: entry.optional || snapshot.optional,
+            dependencies,
+        });
+    }
+
+    Ok(imported)
+}
+
+/// The version in `1.0.0(react@18.0.0)` or `1.0.0_react@18.0.0`, skipping `link:` and the like
+fn pnpm_version(version: &str) -> Option<String> {
+    let version = version.split(['(', '_']).next()?;
+
+    version.parse::<Version>().ok()?;
+
+    Some(version.to_string())
+}
+
+/// The name and version in `/name/1.0.0` (version 5), `/name@1.0.0` (version 6) or
+/// `name@1.0.0(peer@1.0.0)` (version 9)
+fn pnpm_key(key: &str) -> Option<(String, String)> {
+    let key = key.trim_start_matches('/');
+    let key = key.split('(').next()?;
+
+    // a version 5 peer suffix like `_react@18.0.0` has an `@` too, so try its `/` first
+    let (name, version) = key
+        .rsplit_once('/')
+        .filter(|(_, version)| pnpm_version(version).is_some())
+        .or_else(|| split_spec(key))?;
+
+    Some((name.to_string(), pnpm_version(version)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn npm_dependencies_resolve_through_nested_node_modules() {
+        let lock = r#"{
+            "lockfileVersion": 2,
+            "packages": {
+                "": { "dependencies": { "a": "^1.0.0" } },
+                "node_modules/a": { "version": "1.0.0", "dependencies": { "b": "^2.0.0", "c": "^1.0.0" } },
+                "node_modules/a/node_modules/b": { "version": "2.0.0" },
+                "node_modules/b": { "version": "1.0.0" },
+                "node_modules/c": { "version": "1.1.0", "optional": true }
+            }
+        }"#;
+
+        let manifest = json!({ "dependencies": { "a": "^1.0.0" } });
+
+        let imported = import_npm(lock, &manifest).unwrap();
+
+        assert_eq!(
+            imported.direct,
+            BTreeMap::from([("a".to_string(), "1.0.0".to_string())])
+        );
+        assert_eq!(
+            imported.packages["a@1.0.0"].dependencies,
+            HashMap::from([
+                ("b".to_string(), "2.0.0".to_string()),
+                ("c".to_string(), "1.1.0".to_string())
+            ])
+        );
+        assert!(imported.packages["c@1.1.0"].optional);
+    }
+
+    #[test]
+    fn yarn_ranges_resolve_to_versions() {
+        let lock = r#"# THIS IS AN AUTOGENERATED FILE. DO NOT EDIT THIS FILE DIRECTLY.
+# yarn lockfile v1
+
+
+"@babel/core@^7.0.0", "@babel/core@^7.1.0":
+  version "7.1.0"
+  resolved "https://registry.yarnpkg.com/@babel/core/-/core-7.1.0.tgz#abc"
+  integrity sha512-core
+  dependencies:
+    debug "^4.1.0"
+
+debug@^4.1.0:
+  version "4.3.4"
+  integrity sha512-debug
+"#;
+
+        let manifest = json!({ "devDependencies": { "@babel/core": "^7.1.0" } });
+
+        let imported = import_yarn(lock, &manifest).unwrap();
+
+        assert_eq!(imported.direct["@babel/core"], "7.1.0");
+        assert_eq!(
+            imported.packages["@babel/core@7.1.0"].dependencies["debug"],
+            "4.3.4"
+        );
+        assert_eq!(
+            imported.packages["debug@4.3.4"].integrity.as_deref(),
+            Some("sha512-debug")
+        );
+    }
+
+    #[test]
+    fn pnpm_keys_of_every_lockfile_version() {
+        for key in [
+            "/@babel/core/7.1.0",
+            "/@babel/core@7.1.0",
+            "@babel/core@7.1.0(debug@4.3.4)",
+        ] {
+            assert_eq!(
+                pnpm_key(key),
+                Some(("@babel/core".to_string(), "7.1.0".to_string()))
+            );
+        }
+    }
+}
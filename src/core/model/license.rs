@@ -0,0 +1,186 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Licenses of the packages installed in a project.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use std::{fs::read_to_string, path::Path};
+
+use crate::core::{
+    model::lock_file::{DependencyKind, LockFile},
+    utils::{package::PackageJson, voltapi::VoltPackage},
+};
+
+/// License text snippets used to identify a license file, most specific first
+const LICENSE_PATTERNS: &[(&str, &str)] = &[
+    ("apache license", "Apache-2.0"),
+    ("mozilla public license", "MPL-2.0"),
+    ("gnu lesser general public license", "LGPL-3.0-or-later"),
+    ("gnu general public license", "GPL-3.0-or-later"),
+    ("the unlicense", "Unlicense"),
+    ("this is free and unencumbered software", "Unlicense"),
+    ("isc license", "ISC"),
+    ("permission to use, copy, modify, and/or distribute", "ISC"),
+    ("mit license", "MIT"),
+    ("permission is hereby granted, free of charge", "MIT"),
+    ("neither the name of", "BSD-3-Clause"),
+    (
+        "redistribution and use in source and binary forms",
+        "BSD-2-Clause",
+    ),
+];
+
+/// Where the license of a package was found
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LicenseSource {
+    /// The `license` (or legacy `licenses`) field of package.json
+    Manifest,
+    /// Guessed from a LICENSE file in the package
+    File,
+    /// Neither could be found
+    Missing,
+}
+
+impl LicenseSource {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Manifest => "manifest",
+            Self::File => "file",
+            Self::Missing => "missing",
+        }
+    }
+}
+
+/// The license of an installed package
+#[derive(Clone, Debug, Serialize)]
+pub struct PackageLicense {
+    pub name: String,
+    pub version: String,
+    /// An SPDX identifier or expression, or `UNKNOWN`
+    pub license: String,
+    pub source: LicenseSource,
+}
+
+/// Find the license of every package reachable from the manifests
+pub fn collect_licenses(
+    lock_file: &LockFile,
+    manifests: &[PackageJson],
+    node_modules: &Path,
+    production: bool,
+) -> Vec<PackageLicense> {
+    let roots = manifests
+        .iter()
+        .flat_map(|manifest| lock_file.roots(manifest))
+        .filter(|(kind, _, _)| !(production && *kind == DependencyKind::Dev))
+        .filter_map(|(_, _, package)| package);
+
+    lock_file
+        .reachable(roots)
+        .iter()
+        .filter_map(|key| lock_file.dependencies.get(key))
+        .map(|package| package_license(package, node_modules))
+        .collect()
+}
+
+/// Read the license of an installed package
+pub fn package_license(package: &VoltPackage, node_modules: &Path) -> PackageLicense {
+    let directory = package.package_directory(node_modules);
+
+    let (license, source) = match manifest_license(&directory) {
+        Some(license) => (license, LicenseSource::Manifest),
+        None => match file_license(&directory) {
+            Some(license) => (license, LicenseSource::File),
+            None => (String::from("UNKNOWN"), LicenseSource::Missing),
+        },
+    };
+
+    PackageLicense {
+        name: package.name.clone(),
+        version: package.version.clone(),
+        license,
+        source,
+    }
+}
+
+/// The `license` field, or the legacy `{ "type": "MIT" }` object and `licenses` array
+fn manifest_license(directory: &Path) -> Option<String> {
+    let manifest: Value =
+        serde_json::from_str(&read_to_string(directory.join("package.json")).ok()?).ok()?;
+
+    let license_type = |value: &Value| -> Option<String> {
+        match value {
+            Value::String(license) if !license.trim().is_empty() => {
+                Some(license.trim().to_string())
+            }
+            Value::Object(object) => Some(object.get("type")?.as_str()?.to_string()),
+            _ => None,
+        }
+    };
+
+    if let Some(license) = manifest.get("license").and_then(license_type) {
+        return Some(license);
+    }
+
+    let licenses = manifest
+        .get("licenses")?
+        .as_array()?
+        .iter()
+        .filter_map(license_type)
+        .collect::<Vec<_>>();
+
+    match licenses.len() {
+        0 => None,
+        1 => Some(licenses[0].clone()),
+        _ => Some(format!("({})", licenses.join(" OR "))),
+    }
+}
+
+/// Guess the license from the text of a `LICENSE`, `LICENCE` or `COPYING` file
+fn file_license(directory: &Path) -> Option<String> {
+    let entries = std::fs::read_dir(directory).ok()?;
+
+    for entry in entries.filter_map(Result::ok) {
+        let file_name = entry.file_name().to_string_lossy().to_lowercase();
+
+        if !["license", "licence", "copying"]
+            .iter()
+            .any(|prefix| file_name.starts_with(prefix))
+        {
+            continue;
+        }
+
+        let text = match read_to_string(entry.path()) {
+            Ok(text) => text.to_lowercase(),
+            Err(_) => continue,
+        };
+
+        return LICENSE_PATTERNS
+            .iter()
+            .find(|(pattern, _)| text.contains(pattern))
+            .map(|(_, id)| id.to_string())
+            .or_else(|| {
+                Some(format!(
+                    "SEE LICENSE IN {}",
+                    entry.file_name().to_string_lossy()
+                ))
+            });
+    }
+
+    None
+}
@@ -14,62 +14,152 @@
     limitations under the License.
 */
 
-use miette::Result;
+use miette::{Diagnostic, Result};
+use node_semver::{Range, Version};
 use serde::{Deserialize, Serialize};
-use speedy::{Readable, Writable};
 use thiserror::Error;
 
 use std::{
-    collections::HashMap,
-    fs::File,
-    io::{self, BufReader, Write},
+    collections::{hash_map::Entry, BTreeMap, BTreeSet, HashMap, VecDeque},
+    fmt,
+    fs::{read_to_string, File},
+    io::{self, BufWriter},
     path::Path,
 };
 
-use crate::core::utils::voltapi::VoltPackage;
+use crate::core::utils::{package::PackageJson, voltapi::VoltPackage};
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Diagnostic)]
 pub enum LockFileError {
     #[error("unable to read lock file")]
-    IO(io::Error),
+    #[diagnostic(code("VOLT_E_LOCKFILE"))]
+    IO(#[source] io::Error),
     #[error("unable to deserialize lock file")]
-    #[allow(dead_code)]
-    Decode(serde_json::Error),
-    // #[error("unable to serialize lock file")]
-    // Encode(serde_json::Error),
+    #[diagnostic(
+        code("VOLT_E_LOCKFILE"),
+        help("delete volt.lock and run `volt install` to recreate it")
+    )]
+    Decode(#[source] serde_json::Error),
+    #[error("unable to serialize lock file")]
+    #[diagnostic(code("VOLT_E_LOCKFILE"))]
+    Encode(#[source] serde_json::Error),
+}
+
+/// The two sides of a file with git conflict markers, or `None` if it has none
+///
+/// The common ancestor of a diff3 style conflict is left out of both.
+fn conflict_sides(data: &str) -> Option<(String, String)> {
+    #[derive(PartialEq)]
+    enum Side {
+        Both,
+        Ours,
+        Base,
+        Theirs,
+    }
+
+    let mut side = Side::Both;
+    let mut conflicted = false;
+
+    let mut ours = String::new();
+    let mut theirs = String::new();
+
+    for line in data.lines() {
+        if line.starts_with("<<<<<<<") {
+            side = Side::Ours;
+            conflicted = true;
+            continue;
+        }
+
+        if side != Side::Both {
+            if line.starts_with("|||||||") {
+                side = Side::Base;
+                continue;
+            }
+
+            if line.starts_with("=======") {
+                side = Side::Theirs;
+                continue;
+            }
+
+            if line.starts_with(">>>>>>>") {
+                side = Side::Both;
+                continue;
+            }
+        }
+
+        if matches!(side, Side::Both | Side::Ours) {
+            ours.push_str(line);
+            ours.push('\n');
+        }
+
+        if matches!(side, Side::Both | Side::Theirs) {
+            theirs.push_str(line);
+            theirs.push('\n');
+        }
+    }
+
+    conflicted.then(|| (ours, theirs))
+}
+
+/// The kind of edge between a package and one of its dependencies
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyKind {
+    Prod,
+    Dev,
+    Peer,
+    Optional,
+}
+
+impl DependencyKind {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Prod => "prod",
+            Self::Dev => "dev",
+            Self::Peer => "peer",
+            Self::Optional => "optional",
+        }
+    }
+}
+
+impl fmt::Display for DependencyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
 }
 
 /// The lock file is responsible for locking/pinning dependency versions in a given project.
-/// It stores a list of dependencies along with their resolved version, registry url, and sha1 checksum.
+/// It stores the resolved version of every direct dependency along with the flattened tree of
+/// every installed package, which together form the project's dependency graph.
 ///
 /// ## Examples
 ///
 /// ```
-/// // Load the lock file for the current project or create new lock file
-/// let mut lock_file = LockFile::load(lock_file_path)
-///     .unwrap_or_else(|| LockFile::new(lock_file_path));
+/// // Load the lock file for the current project (empty if it doesn't exist yet)
+/// let mut lock_file = LockFile::load(config.lockfile()?, false)?;
 ///
-/// // Add dependency
-/// lock_file.add(
-///     ("react".to_string(), "^1.0.0".to_string()),
-///     DependencyLock {
-///         name: "react".to_string(),
-///         version: "1.2.6".to_string(),
-///         tarball: String::new(),
-///         sha1: String::new(),
-///     }
-/// );
+/// // Add the requested packages along with their flattened trees
+/// lock_file.add(&root_packages, tree);
 ///
 /// // Save changes to disk
-/// lock_file.save().expect("Unable to save lock file");
+/// lock_file.save()?;
 /// ```
-#[derive(Clone, Debug, Writable, Readable, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct LockFile {
+    #[serde(skip)]
     pub path: String,
-    #[speedy(skip)]
+    #[serde(skip)]
     pub global: bool,
-    #[speedy(skip)]
-    pub dependencies: HashMap<String, VoltPackage>,
+    /// Resolved version of each direct dependency (`name` -> `version`)
+    #[serde(default)]
+    pub direct: BTreeMap<String, String>,
+    /// Every installed package, keyed by `name@version`
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, VoltPackage>,
+    /// Direct dependencies the two sides of a merge conflict disagree on, with the version each
+    /// side resolved them to. Installing re-resolves them against package.json
+    #[serde(skip)]
+    pub conflicts: BTreeMap<String, BTreeSet<String>>,
 }
 
 impl LockFile {
@@ -77,53 +167,255 @@ impl LockFile {
     /// It can be saved to the file by calling [`Self::save()`].
     pub fn new<P: AsRef<Path>>(path: P, global: bool) -> Self {
         Self {
-            path: path.as_ref().to_str().unwrap().to_string(),
+            path: path.as_ref().to_string_lossy().to_string(),
             global,
-            dependencies: HashMap::with_capacity(1), // We will be installing at least 1 dependency
+            ..Default::default()
         }
     }
 
-    pub fn add(package: VoltPackage) {}
+    /// Record direct dependencies along with every package they depend on
+    pub fn add(&mut self, roots: &[VoltPackage], tree: HashMap<String, VoltPackage>) {
+        for package in roots {
+            self.direct
+                .insert(package.name.clone(), package.version.clone());
+        }
+
+        self.dependencies.extend(tree);
+    }
 
-    /// Loads a lock file from the given path.
+    /// Loads a lock file from the given path, or an empty one if it doesn't exist.
     pub fn load<P: AsRef<Path>>(path: P, global: bool) -> Result<Self, LockFileError> {
         let path = path.as_ref();
 
-        let dependencies = if path.exists() {
-            let f = File::open(path).map_err(LockFileError::IO)?;
-            let reader = BufReader::new(f);
+        if !path.exists() {
+            return Ok(Self::new(path, global));
+        }
 
-            if global {
-                LockFile::read_from_buffer(reader.buffer()).unwrap()
-            } else {
-                serde_json::from_reader(reader).unwrap()
-            }
-        } else {
-            LockFile {
-                path: path.to_str().unwrap().to_string(),
-                global,
-                dependencies: HashMap::new(),
-            }
+        let data = read_to_string(path).map_err(LockFileError::IO)?;
+
+        let mut lock_file = match conflict_sides(&data) {
+            Some((ours, theirs)) => Self::merge(
+                serde_json::from_str(&ours).map_err(LockFileError::Decode)?,
+                serde_json::from_str(&theirs).map_err(LockFileError::Decode)?,
+            ),
+            None => serde_json::from_str(&data).map_err(LockFileError::Decode)?,
         };
 
-        Ok(dependencies)
+        lock_file.path = path.to_string_lossy().to_string();
+        lock_file.global = global;
+
+        Ok(lock_file)
+    }
+
+    /// Merge the two sides of a conflicted lock file, keeping the direct dependencies they agree
+    /// on and recording the others in [`Self::conflicts`]
+    fn merge(ours: Self, theirs: Self) -> Self {
+        let mut merged = Self {
+            dependencies: ours.dependencies,
+            ..Default::default()
+        };
+
+        // a `name@version` key is the same package on both sides
+        merged.dependencies.extend(theirs.dependencies);
+
+        for name in ours.direct.keys().chain(theirs.direct.keys()) {
+            match (ours.direct.get(name), theirs.direct.get(name)) {
+                (Some(a), Some(b)) if a == b => {
+                    merged.direct.insert(name.clone(), a.clone());
+                }
+                (a, b) => {
+                    merged
+                        .conflicts
+                        .insert(name.clone(), a.into_iter().chain(b).cloned().collect());
+                }
+            }
+        }
+
+        merged
     }
 
-    // Saves a lock file dumping pretty, formatted json
-    // pub fn save_pretty(&self) -> Result<(), LockFileError> {
-    //     let lock_file = File::create(&self.path).map_err(LockFileError::IO)?;
-    //     let writer = BufWriter::new(lock_file);
-    //     serde_json::to_writer_pretty(writer, &self.dependencies).map_err(LockFileError::Encode)
-    // }
+    /// Drop the packages no direct dependency leads to anymore
+    pub fn prune(&mut self) {
+        let reachable = self.reachable(
+            self.direct
+                .iter()
+                .filter_map(|(name, version)| {
+                    self.dependencies.get(&format!("{}@{}", name, version))
+                })
+                .collect::<Vec<_>>(),
+        );
+
+        self.dependencies.retain(|key, _| reachable.contains(key));
+    }
 
     // Saves a lock file to the same path it was opened from.
     pub fn save(&self) -> Result<()> {
-        let mut lock_file = File::create(&self.path).unwrap();
+        let lock_file = File::create(&self.path).map_err(LockFileError::IO)?;
 
-        lock_file
-            .write_all(&self.dependencies.write_to_vec().unwrap())
-            .unwrap();
+        serde_json::to_writer_pretty(BufWriter::new(lock_file), self)
+            .map_err(LockFileError::Encode)?;
 
         Ok(())
     }
+
+    /// Find the installed package for a dependency, given either its exact version or a range
+    pub fn find(&self, name: &str, requested: &str) -> Option<&VoltPackage> {
+        if let Some(package) = self.dependencies.get(&format!("{}@{}", name, requested)) {
+            return Some(package);
+        }
+
+        let range = requested.parse::<Range>().ok();
+
+        self.dependencies
+            .values()
+            .filter(|p| p.name == name)
+            .filter_map(|p| Some((p.version.parse::<Version>().ok()?, p)))
+            .filter(|(version, _)| range.as_ref().map_or(true, |r| r.satisfies(version)))
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, p)| p)
+    }
+
+    /// The direct dependencies of a project, as declared in its package.json
+    pub fn roots<'a>(
+        &'a self,
+        manifest: &PackageJson,
+    ) -> Vec<(DependencyKind, String, Option<&'a VoltPackage>)> {
+        let mut roots = vec![];
+
+        let declared = manifest
+            .dependencies
+            .iter()
+            .flatten()
+            .map(|(name, range)| (DependencyKind::Prod, name, range))
+            .chain(
+                manifest
+                    .dev_dependencies
+                    .iter()
+                    .flatten()
+                    .map(|(name, range)| (DependencyKind::Dev, name, range)),
+            );
+
+        for (kind, name, range) in declared {
+            let package = match self.direct.get(name) {
+                Some(version) => self.find(name, version),
+                None => self.find(name, range),
+            };
+
+            roots.push((kind, format!("{}@{}", name, range), package));
+        }
+
+        roots.sort_by(|a, b| a.1.cmp(&b.1));
+        roots
+    }
+
+    /// The packages a package depends on, along with the kind of each dependency
+    pub fn children(&self, package: &VoltPackage) -> Vec<(DependencyKind, &VoltPackage)> {
+        let edges = [
+            (DependencyKind::Prod, &package.dependencies),
+            (DependencyKind::Optional, &package.optional_dependencies),
+            (DependencyKind::Peer, &package.peer_dependencies),
+        ];
+
+        let mut children = vec![];
+
+        for (kind, dependencies) in edges {
+            for (name, requested) in dependencies.iter().flatten() {
+                if let Some(child) = self.find(name, requested) {
+                    children.push((kind, child));
+                }
+            }
+        }
+
+        children.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+        children
+    }
+
+    /// The `name@version` keys of every package reachable from `roots`
+    pub fn reachable<'a, I>(&'a self, roots: I) -> BTreeSet<String>
+    where
+        I: IntoIterator<Item = &'a VoltPackage>,
+    {
+        let mut reachable = BTreeSet::new();
+        let mut queue = roots.into_iter().collect::<Vec<_>>();
+
+        while let Some(package) = queue.pop() {
+            if reachable.insert(format!("{}@{}", package.name, package.version)) {
+                queue.extend(self.children(package).into_iter().map(|(_, child)| child));
+            }
+        }
+
+        reachable
+    }
+
+    /// The shortest chain of packages leading from `root` to the package `key` (`name@version`)
+    pub fn path_to<'a>(&'a self, root: &'a VoltPackage, key: &str) -> Option<Vec<&'a VoltPackage>> {
+        // the package each visited package was first reached from
+        let mut parents: HashMap<String, Option<&VoltPackage>> =
+            HashMap::from([(root.key(), None)]);
+        let mut queue = VecDeque::from([root]);
+
+        while let Some(package) = queue.pop_front() {
+            if package.key() == key {
+                let mut path = vec![package];
+
+                while let Some(Some(parent)) = parents.get(&path[path.len() - 1].key()) {
+                    path.push(*parent);
+                }
+
+                path.reverse();
+                return Some(path);
+            }
+
+            for (_, child) in self.children(package) {
+                if let Entry::Vacant(entry) = parents.entry(child.key()) {
+                    entry.insert(Some(package));
+                    queue.push_back(child);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_conflicted_lock_files() {
+        let data = r#"{
+  "direct": {
+<<<<<<< HEAD
+    "lodash": "4.17.21",
+    "react": "18.0.0"
+||||||| base
+    "lodash": "4.17.21",
+    "react": "17.0.2"
+=======
+    "lodash": "4.17.21",
+    "react": "18.2.0"
+>>>>>>> feature
+  }
+}
+"#;
+
+        let (ours, theirs) = conflict_sides(data).unwrap();
+
+        let merged = LockFile::merge(
+            serde_json::from_str(&ours).unwrap(),
+            serde_json::from_str(&theirs).unwrap(),
+        );
+
+        assert_eq!(
+            merged.direct.get("lodash").map(String::as_str),
+            Some("4.17.21")
+        );
+        assert_eq!(
+            merged.conflicts["react"],
+            BTreeSet::from([String::from("18.0.0"), String::from("18.2.0")])
+        );
+
+        assert!(conflict_sides("{}").is_none());
+    }
 }
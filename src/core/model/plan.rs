@@ -0,0 +1,285 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! What an install would change, printed by `--dry-run`.
+
+use crate::{
+    cli::VoltConfig,
+    core::{
+        model::lock_file::LockFile,
+        output::{print_json, status},
+        utils::voltapi::VoltPackage,
+    },
+};
+
+use colored::Colorize;
+use futures::{stream, StreamExt};
+use indicatif::HumanBytes;
+use miette::Result;
+use node_semver::Version;
+use reqwest::{header::CONTENT_LENGTH, Client};
+use serde::Serialize;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Upgraded,
+    Downgraded,
+}
+
+/// The versions of a package before and after the install, a package can be installed at
+/// several versions when dependents need incompatible ones
+#[derive(Debug, Serialize)]
+pub struct Change {
+    pub name: String,
+    pub kind: ChangeKind,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Plan {
+    pub changes: Vec<Change>,
+    /// Size of the tarballs that aren't in the store yet, as reported by the registry
+    pub download_size: u64,
+    /// How many tarballs would be downloaded
+    pub downloads: usize,
+}
+
+impl Plan {
+    /// Compare the packages reachable from the direct dependencies of two lockfiles
+    pub fn between(before: &LockFile, after: &LockFile) -> Self {
+        let before = versions(before);
+        let after = versions(after);
+
+        let names = before.keys().chain(after.keys()).collect::<BTreeSet<_>>();
+
+        let changes = names
+            .into_iter()
+            .filter_map(|name| {
+                let before = before.get(name).cloned().unwrap_or_default();
+                let after = after.get(name).cloned().unwrap_or_default();
+
+                let kind = match (newest(&before), newest(&after)) {
+                    _ if before == after => return None,
+                    (None, _) => ChangeKind::Added,
+                    (_, None) => ChangeKind::Removed,
+                    (Some(old), Some(new)) if new < old => ChangeKind::Downgraded,
+                    _ => ChangeKind::Upgraded,
+                };
+
+                Some(Change {
+                    name: name.clone(),
+                    kind,
+                    before: before.into_iter().collect(),
+                    after: after.into_iter().collect(),
+                })
+            })
+            .collect();
+
+        Self {
+            changes,
+            ..Self::default()
+        }
+    }
+
+    /// Ask the registry for the size of the tarballs of `packages` that aren't cached
+    pub async fn estimate_downloads<'a, I>(
+        &mut self,
+        config: &VoltConfig,
+        packages: I,
+    ) -> Result<()>
+    where
+        I: IntoIterator<Item = &'a VoltPackage>,
+    {
+        let volt_home = config.volt_home()?;
+        let client = Client::new();
+
+        let missing = packages
+            .into_iter()
+            .filter(|package| {
+                !matches!(
+                    cacache::metadata_sync(&volt_home, package.cacache_key()),
+                    Ok(Some(_))
+                )
+            })
+            .collect::<Vec<_>>();
+
+        self.downloads = missing.len();
+
+        // a size the registry doesn't report only makes the estimate lower
+        self.download_size = stream::iter(missing.into_iter().map(|package| {
+            let request = client.head(&package.tarball).send();
+
+            async move {
+                let response = request.await.ok()?;
+
+                response
+                    .headers()
+                    .get(CONTENT_LENGTH)?
+                    .to_str()
+                    .ok()?
+                    .parse::<u64>()
+                    .ok()
+            }
+        }))
+        .buffer_unordered(config.concurrency())
+        .filter_map(|size| async move { size })
+        .fold(0, |total, size| async move { total + size })
+        .await;
+
+        Ok(())
+    }
+
+    /// Print the plan, as JSON with `--json`
+    pub fn print(&self, config: &VoltConfig) -> Result<()> {
+        if config.json() {
+            return print_json(self);
+        }
+
+        if self.changes.is_empty() {
+            status(config, "No packages would change");
+            return Ok(());
+        }
+
+        for change in &self.changes {
+            let line = match change.kind {
+                ChangeKind::Added => format!(
+                    "{} {}@{}",
+                    "+".bright_green().bold(),
+                    change.name,
+                    change.after.join(", ")
+                ),
+                ChangeKind::Removed => format!(
+                    "{} {}@{}",
+                    "-".bright_red().bold(),
+                    change.name,
+                    change.before.join(", ")
+                ),
+                ChangeKind::Upgraded | ChangeKind::Downgraded => format!(
+                    "{} {} {} → {}",
+                    "~".bright_yellow().bold(),
+                    change.name,
+                    change.before.join(", "),
+                    change.after.join(", ")
+                ),
+            };
+
+            status(config, line);
+        }
+
+        let count = |kind| self.changes.iter().filter(|c| c.kind == kind).count();
+
+        status(
+            config,
+            format!(
+                "\n{} {} added, {} removed, {} changed, {} to download ({} tarballs)",
+                "Dry run:".bold(),
+                count(ChangeKind::Added),
+                count(ChangeKind::Removed),
+                count(ChangeKind::Upgraded) + count(ChangeKind::Downgraded),
+                HumanBytes(self.download_size),
+                self.downloads
+            ),
+        );
+
+        Ok(())
+    }
+}
+
+/// The installed versions of every package reachable from the direct dependencies
+fn versions(lock_file: &LockFile) -> BTreeMap<String, BTreeSet<String>> {
+    let roots = lock_file
+        .direct
+        .iter()
+        .filter_map(|(name, version)| lock_file.find(name, version));
+
+    let mut versions = BTreeMap::<String, BTreeSet<String>>::new();
+
+    for key in lock_file.reachable(roots) {
+        if let Some(package) = lock_file.dependencies.get(&key) {
+            versions
+                .entry(package.name.clone())
+                .or_default()
+                .insert(package.version.clone());
+        }
+    }
+
+    versions
+}
+
+fn newest(versions: &BTreeSet<String>) -> Option<Version> {
+    versions
+        .iter()
+        .filter_map(|version| version.parse::<Version>().ok())
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, version: &str) -> VoltPackage {
+        VoltPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn lock_file(direct: &[(&str, &str)]) -> LockFile {
+        let mut lock_file = LockFile::default();
+
+        for (name, version) in direct {
+            let package = package(name, version);
+
+            lock_file
+                .direct
+                .insert(package.name.clone(), package.version.clone());
+            lock_file.dependencies.insert(package.key(), package);
+        }
+
+        lock_file
+    }
+
+    #[test]
+    fn classifies_changes() {
+        let before = lock_file(&[("react", "17.0.2"), ("left-pad", "1.3.0"), ("ms", "2.1.3")]);
+        let after = lock_file(&[("react", "18.0.0"), ("ms", "2.1.3"), ("debug", "4.3.4")]);
+
+        let plan = Plan::between(&before, &after);
+
+        let kinds = plan
+            .changes
+            .iter()
+            .map(|change| (change.name.as_str(), change.kind))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            kinds,
+            [
+                ("debug", ChangeKind::Added),
+                ("left-pad", ChangeKind::Removed),
+                ("react", ChangeKind::Upgraded),
+            ]
+        );
+    }
+}
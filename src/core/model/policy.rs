@@ -0,0 +1,269 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Rules restricting which packages can be installed in a project.
+
+use node_semver::{Range, Version};
+use serde::{Deserialize, Serialize};
+
+use std::{collections::HashMap, fmt};
+
+use crate::core::{model::lock_file::LockFile, net::RegistryManifest, utils::voltapi::VoltPackage};
+
+/// The policy configured under `[policy]` in `volt.toml`
+///
+/// ```toml
+/// [policy]
+/// deny = ["event-stream@3.3.6", "left-pad"]
+/// allow-licenses = ["MIT", "ISC", "Apache-2.0", "BSD-3-Clause"]
+/// deny-deprecated = true
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct Policy {
+    /// Packages that may not be installed, optionally limited to a range (`lodash@<4.17.21`)
+    pub deny: Vec<String>,
+    /// SPDX identifiers that packages must be licensed under, any license is allowed when empty
+    pub allow_licenses: Vec<String>,
+    /// SPDX identifiers that packages may not be licensed under
+    pub deny_licenses: Vec<String>,
+    /// Block versions that have been deprecated on the registry
+    pub deny_deprecated: bool,
+}
+
+/// A package in a resolved tree that breaks the policy
+#[derive(Debug)]
+pub struct Violation {
+    /// Chain of packages from the requested package to the violating one
+    pub path: Vec<String>,
+    pub reason: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}",
+            self.path.last().map(String::as_str).unwrap_or_default(),
+            self.reason
+        )
+    }
+}
+
+impl Policy {
+    pub fn is_empty(&self) -> bool {
+        self.deny.is_empty() && !self.checks_manifests()
+    }
+
+    /// Whether the policy needs the registry manifest of every package
+    pub fn checks_manifests(&self) -> bool {
+        !self.allow_licenses.is_empty() || !self.deny_licenses.is_empty() || self.deny_deprecated
+    }
+
+    /// Check every package in a resolved tree against the policy
+    ///
+    /// `manifests` holds the registry manifests of the tree, which are only read when
+    /// [`Policy::checks_manifests`] is set.
+    pub fn check(
+        &self,
+        roots: &[VoltPackage],
+        tree: &HashMap<String, VoltPackage>,
+        manifests: &HashMap<String, RegistryManifest>,
+    ) -> Vec<Violation> {
+        // (key, reason)
+        let mut reasons = vec![];
+
+        for (key, package) in tree {
+            if let Some(rule) = self.deny.iter().find(|rule| denies(rule, package)) {
+                reasons.push((key.clone(), format!("is denied by the rule `{}`", rule)));
+            }
+        }
+
+        for (key, manifest) in manifests {
+            if self.deny_deprecated {
+                if let Some(message) = &manifest.deprecated {
+                    reasons.push((key.clone(), format!("is deprecated: {}", message)));
+                }
+            }
+
+            if self.allow_licenses.is_empty() && self.deny_licenses.is_empty() {
+                continue;
+            }
+
+            match manifest.license() {
+                Some(license) => {
+                    if !expression_allowed(&license, &|id| self.license_allowed(id)) {
+                        reasons.push((
+                            key.clone(),
+                            format!("is licensed under `{}` which is not allowed", license),
+                        ));
+                    }
+                }
+                None if !self.allow_licenses.is_empty() => {
+                    reasons.push((key.clone(), String::from("has no license")));
+                }
+                None => {}
+            }
+        }
+
+        let graph = LockFile {
+            dependencies: tree.clone().into_iter().collect(),
+            ..Default::default()
+        };
+
+        let mut violations = reasons
+            .into_iter()
+            .map(|(key, reason)| {
+                let path = roots
+                    .iter()
+                    .find_map(|root| graph.path_to(root, &key))
+                    .map(|path| path.iter().map(|package| package.key()).collect())
+                    .unwrap_or_else(|| vec![key.clone()]);
+
+                Violation { path, reason }
+            })
+            .collect::<Vec<_>>();
+
+        violations.sort_by(|a, b| a.path.cmp(&b.path));
+
+        violations
+    }
+
+    fn license_allowed(&self, id: &str) -> bool {
+        let matches = |license: &String| license.eq_ignore_ascii_case(id);
+
+        (self.allow_licenses.is_empty() || self.allow_licenses.iter().any(matches))
+            && !self.deny_licenses.iter().any(matches)
+    }
+}
+
+/// Whether a `name` or `name@range` rule matches a package
+fn denies(rule: &str, package: &VoltPackage) -> bool {
+    // the first `@` of a scoped package is part of its name
+    let (name, range) = match rule.rfind('@') {
+        Some(index) if index > 0 => (&rule[..index], Some(&rule[index + 1..])),
+        _ => (rule, None),
+    };
+
+    if name != package.name {
+        return false;
+    }
+
+    match (
+        range.map(str::parse::<Range>),
+        package.version.parse::<Version>(),
+    ) {
+        (None, _) => true,
+        (Some(Ok(range)), Ok(version)) => range.satisfies(&version),
+        (Some(_), _) => range == Some(package.version.as_str()),
+    }
+}
+
+/// Evaluate an SPDX license expression (`(MIT OR Apache-2.0) AND BSD-3-Clause`), where each
+/// license is accepted or rejected by `allowed`
+pub fn expression_allowed(expression: &str, allowed: &dyn Fn(&str) -> bool) -> bool {
+    let expression = expression.replace('(', " ( ").replace(')', " ) ");
+    let tokens = expression.split_whitespace().collect::<Vec<_>>();
+
+    let mut position = 0;
+
+    or_expression(&tokens, &mut position, allowed)
+}
+
+fn or_expression(tokens: &[&str], position: &mut usize, allowed: &dyn Fn(&str) -> bool) -> bool {
+    let mut result = and_expression(tokens, position, allowed);
+
+    while tokens
+        .get(*position)
+        .map_or(false, |t| t.eq_ignore_ascii_case("OR"))
+    {
+        *position += 1;
+        // evaluate both sides so the position moves past the whole expression
+        let right = and_expression(tokens, position, allowed);
+        result = result || right;
+    }
+
+    result
+}
+
+fn and_expression(tokens: &[&str], position: &mut usize, allowed: &dyn Fn(&str) -> bool) -> bool {
+    let mut result = license(tokens, position, allowed);
+
+    while tokens
+        .get(*position)
+        .map_or(false, |t| t.eq_ignore_ascii_case("AND"))
+    {
+        *position += 1;
+        let right = license(tokens, position, allowed);
+        result = result && right;
+    }
+
+    result
+}
+
+fn license(tokens: &[&str], position: &mut usize, allowed: &dyn Fn(&str) -> bool) -> bool {
+    match tokens.get(*position) {
+        Some(&"(") => {
+            *position += 1;
+
+            let result = or_expression(tokens, position, allowed);
+
+            // closing parenthesis
+            *position += 1;
+
+            result
+        }
+        Some(id) => {
+            *position += 1;
+
+            // `GPL-2.0-only WITH Classpath-exception-2.0` is judged by its license
+            if tokens
+                .get(*position)
+                .map_or(false, |t| t.eq_ignore_ascii_case("WITH"))
+            {
+                *position += 2;
+            }
+
+            allowed(id)
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allow_mit(id: &str) -> bool {
+        id == "MIT"
+    }
+
+    #[test]
+    fn evaluates_spdx_expressions() {
+        assert!(expression_allowed("MIT", &allow_mit));
+        assert!(!expression_allowed("GPL-3.0-only", &allow_mit));
+        assert!(expression_allowed("(MIT OR GPL-3.0-only)", &allow_mit));
+        assert!(!expression_allowed("MIT AND GPL-3.0-only", &allow_mit));
+        assert!(expression_allowed(
+            "(GPL-3.0-only AND Apache-2.0) OR MIT",
+            &allow_mit
+        ));
+        assert!(!expression_allowed(
+            "GPL-2.0-only WITH Classpath-exception-2.0",
+            &allow_mit
+        ));
+    }
+}
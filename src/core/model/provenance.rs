@@ -0,0 +1,357 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Verification of the Sigstore attestations published alongside npm packages.
+//!
+//! Packages published with `npm publish --provenance` carry a SLSA provenance statement signed
+//! with a short-lived Fulcio certificate, and the registry adds a publish attestation signed
+//! with its own key. Both are DSSE envelopes over in-toto statements naming the tarball digest.
+//!
+//! The envelope signatures and statement subjects are checked here. The Fulcio certificate
+//! chain and the Rekor inclusion proof aren't verified against the Sigstore trust root, so
+//! build provenance only proves the tarball matches what its own certificate signed, and is
+//! always reported as unverified.
+
+use futures::{stream::FuturesUnordered, StreamExt};
+use miette::Result;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+use ssri::{Algorithm, Integrity};
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use std::{collections::HashMap, fmt};
+
+use crate::core::{
+    model::signature::{verify_ecdsa, RegistryKey},
+    net::{get_attestations, RegistryManifest},
+    utils::{purl, voltapi::VoltPackage},
+};
+
+/// Why build provenance that passes every check here is still reported as unverified
+pub const UNVERIFIED_NOTE: &str =
+    "build provenance certificates aren't checked against the Sigstore trust root";
+
+/// The attestations the registry serves for a package version
+#[derive(Debug, Clone, Deserialize)]
+pub struct Attestations {
+    #[serde(default)]
+    pub attestations: Vec<Attestation>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Attestation {
+    pub predicate_type: String,
+    pub bundle: Bundle,
+}
+
+/// A Sigstore bundle
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bundle {
+    pub verification_material: VerificationMaterial,
+    pub dsse_envelope: DsseEnvelope,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationMaterial {
+    /// Set when the envelope is signed with a registry key
+    pub public_key: Option<PublicKeyHint>,
+    /// Bundles up to v0.1 carry the whole chain
+    pub x509_certificate_chain: Option<CertificateChain>,
+    /// Later bundles only carry the signing certificate
+    pub certificate: Option<RawBytes>,
+    #[serde(default)]
+    pub tlog_entries: Vec<Value>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PublicKeyHint {
+    pub hint: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CertificateChain {
+    pub certificates: Vec<RawBytes>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawBytes {
+    /// Base64 encoded DER
+    pub raw_bytes: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DsseEnvelope {
+    /// Base64 encoded in-toto statement
+    pub payload: String,
+    pub payload_type: String,
+    pub signatures: Vec<DsseSignature>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DsseSignature {
+    pub sig: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Statement {
+    subject: Vec<Subject>,
+    #[serde(default)]
+    predicate: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct Subject {
+    name: String,
+    #[serde(default)]
+    digest: HashMap<String, String>,
+}
+
+/// Where a package claims it was built, taken from a SLSA provenance statement
+#[derive(Debug, Clone, Default)]
+pub struct BuildProvenance {
+    /// The source repository the build ran from
+    pub source: Option<String>,
+    /// The builder that produced the package, usually a CI workflow runner
+    pub builder: Option<String>,
+}
+
+/// Why a package's attestations failed verification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProvenanceProblem {
+    /// An attestation couldn't be decoded
+    Malformed(String),
+    /// An attestation has no certificate or known key to verify it with
+    MissingKey(String),
+    /// An envelope signature doesn't match its statement
+    InvalidSignature(String),
+    /// An attestation was never recorded in the transparency log
+    NotLogged(String),
+    /// An attestation describes a different tarball than the one being installed
+    SubjectMismatch(String),
+}
+
+impl fmt::Display for ProvenanceProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed(kind) => write!(f, "has a malformed {} attestation", kind),
+            Self::MissingKey(kind) => write!(f, "has an unverifiable {} attestation", kind),
+            Self::InvalidSignature(kind) => {
+                write!(f, "has an invalid signature on its {} attestation", kind)
+            }
+            Self::NotLogged(kind) => write!(
+                f,
+                "has a {} attestation missing from the transparency log",
+                kind
+            ),
+            Self::SubjectMismatch(kind) => {
+                write!(f, "has a {} attestation for a different tarball", kind)
+            }
+        }
+    }
+}
+
+/// The verification outcome for a package that publishes attestations
+#[derive(Debug)]
+pub struct ProvenanceCheck<'a> {
+    pub package: &'a VoltPackage,
+    /// `None` when the package only has the registry's publish attestation
+    pub outcome: Result<Option<BuildProvenance>, ProvenanceProblem>,
+}
+
+/// Fetch and verify the attestations of every package whose manifest advertises them
+///
+/// Packages without attestations are left out of the result.
+pub async fn check_provenance<'a>(
+    client: &Client,
+    keys: &[RegistryKey],
+    packages: &[(&'a VoltPackage, &'a RegistryManifest)],
+) -> Result<Vec<ProvenanceCheck<'a>>> {
+    let mut requests = packages
+        .iter()
+        .filter_map(|(package, manifest)| {
+            let url = &manifest.dist.attestations.as_ref()?.url;
+
+            Some(async move { (*package, *manifest, get_attestations(client, url).await) })
+        })
+        .collect::<FuturesUnordered<_>>();
+
+    let mut checks = vec![];
+
+    while let Some((package, manifest, attestations)) = requests.next().await {
+        // check against the tarball being installed, unless it was resolved with only a sha-1
+        let integrity = match &manifest.dist.integrity {
+            Some(integrity) if !package.integrity.starts_with("sha512-") => integrity,
+            _ => &package.integrity,
+        };
+
+        checks.push(ProvenanceCheck {
+            package,
+            outcome: verify_attestations(keys, package, integrity, &attestations?.attestations),
+        });
+    }
+
+    checks.sort_by(|a, b| a.package.key().cmp(&b.package.key()));
+
+    Ok(checks)
+}
+
+/// Verify the attestations of a package with the given integrity
+pub fn verify_attestations(
+    keys: &[RegistryKey],
+    package: &VoltPackage,
+    integrity: &str,
+    attestations: &[Attestation],
+) -> Result<Option<BuildProvenance>, ProvenanceProblem> {
+    let mut provenance = None;
+
+    for attestation in attestations {
+        let kind = attestation_kind(&attestation.predicate_type);
+        let statement = verify_bundle(keys, &attestation.bundle, kind)?;
+
+        let digest = sha512_hex(integrity);
+        let name = purl(&package.name, &package.version);
+
+        let matches = statement.subject.iter().any(|subject| {
+            subject.name == name
+                && digest.is_some()
+                && subject.digest.get("sha512") == digest.as_ref()
+        });
+
+        if !matches {
+            return Err(ProvenanceProblem::SubjectMismatch(kind.to_string()));
+        }
+
+        if attestation
+            .predicate_type
+            .starts_with("https://slsa.dev/provenance/")
+        {
+            provenance = Some(build_provenance(&statement.predicate));
+        }
+    }
+
+    Ok(provenance)
+}
+
+/// Verify the envelope signature of a bundle and decode its statement
+fn verify_bundle(
+    keys: &[RegistryKey],
+    bundle: &Bundle,
+    kind: &str,
+) -> Result<Statement, ProvenanceProblem> {
+    let material = &bundle.verification_material;
+    let envelope = &bundle.dsse_envelope;
+
+    let malformed = || ProvenanceProblem::Malformed(kind.to_string());
+
+    if material.tlog_entries.is_empty() {
+        return Err(ProvenanceProblem::NotLogged(kind.to_string()));
+    }
+
+    let public_key = match (&material.public_key, signing_certificate(material)) {
+        (Some(hint), _) => keys
+            .iter()
+            .find(|key| key.keyid == hint.hint)
+            .and_then(|key| base64::decode(&key.key).ok()),
+        (None, Some(certificate)) => {
+            let der = base64::decode(&certificate.raw_bytes).map_err(|_| malformed())?;
+
+            X509Certificate::from_der(&der)
+                .ok()
+                .map(|(_, certificate)| certificate.public_key().raw.to_vec())
+        }
+        (None, None) => None,
+    }
+    .ok_or_else(|| ProvenanceProblem::MissingKey(kind.to_string()))?;
+
+    let payload = base64::decode(&envelope.payload).map_err(|_| malformed())?;
+    let message = pre_authentication_encoding(&envelope.payload_type, &payload);
+
+    if !envelope
+        .signatures
+        .iter()
+        .any(|signature| verify_ecdsa(&public_key, &message, &signature.sig))
+    {
+        return Err(ProvenanceProblem::InvalidSignature(kind.to_string()));
+    }
+
+    serde_json::from_slice(&payload).map_err(|_| malformed())
+}
+
+fn signing_certificate(material: &VerificationMaterial) -> Option<&RawBytes> {
+    material.certificate.as_ref().or_else(|| {
+        material
+            .x509_certificate_chain
+            .as_ref()
+            .and_then(|chain| chain.certificates.first())
+    })
+}
+
+/// The DSSE pre-authentication encoding that envelope signatures are made over
+fn pre_authentication_encoding(payload_type: &str, payload: &[u8]) -> Vec<u8> {
+    let mut message = format!(
+        "DSSEv1 {} {} {} ",
+        payload_type.len(),
+        payload_type,
+        payload.len()
+    )
+    .into_bytes();
+
+    message.extend_from_slice(payload);
+
+    message
+}
+
+fn attestation_kind(predicate_type: &str) -> &'static str {
+    if predicate_type.starts_with("https://slsa.dev/provenance/") {
+        "provenance"
+    } else {
+        "publish"
+    }
+}
+
+/// The hex sha512 digest in an integrity string
+fn sha512_hex(integrity: &str) -> Option<String> {
+    let integrity = integrity.parse::<Integrity>().ok()?;
+
+    let hash = integrity
+        .hashes
+        .iter()
+        .find(|hash| hash.algorithm == Algorithm::Sha512)?;
+
+    base64::decode(&hash.digest).ok().map(hex::encode)
+}
+
+/// Read the source repository and builder from a SLSA v0.2 or v1 predicate
+fn build_provenance(predicate: &Value) -> BuildProvenance {
+    let text = |pointer: &str| {
+        predicate
+            .pointer(pointer)
+            .and_then(Value::as_str)
+            .map(String::from)
+    };
+
+    BuildProvenance {
+        source: text("/buildDefinition/externalParameters/workflow/repository")
+            .or_else(|| text("/invocation/configSource/uri")),
+        builder: text("/runDetails/builder/id").or_else(|| text("/builder/id")),
+    }
+}
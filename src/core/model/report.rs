@@ -0,0 +1,115 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! The JSON report of an install written by `--report`.
+
+use crate::core::utils::{errors::VoltError, voltapi::VoltPackage};
+
+use miette::{IntoDiagnostic, Result};
+use serde::Serialize;
+
+use std::{collections::HashMap, fs, path::Path, time::Instant};
+
+/// What an install did and how long each part of it took
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallReport {
+    pub volt_version: &'static str,
+    /// The command line, without the program name
+    pub args: Vec<String>,
+    pub packages: Vec<ReportedPackage>,
+    pub phases: Vec<PhaseTiming>,
+    pub cache: CacheStats,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReportedPackage {
+    pub name: String,
+    pub version: String,
+    pub integrity: String,
+    pub tarball: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhaseTiming {
+    pub phase: &'static str,
+    pub duration_ms: u128,
+}
+
+/// Packages copied from the store are hits, downloaded packages are misses
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub downloaded_bytes: u64,
+}
+
+impl Default for InstallReport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InstallReport {
+    pub fn new() -> Self {
+        Self {
+            volt_version: env!("CARGO_PKG_VERSION"),
+            args: std::env::args().skip(1).collect(),
+            packages: vec![],
+            phases: vec![],
+            cache: CacheStats::default(),
+            warnings: vec![],
+        }
+    }
+
+    /// Record that `phase` ran from `start` until now
+    pub fn phase(&mut self, phase: &'static str, start: Instant) {
+        self.phases.push(PhaseTiming {
+            phase,
+            duration_ms: start.elapsed().as_millis(),
+        });
+    }
+
+    /// Record the installed tree, sorted so reports of the same install can be diffed
+    pub fn packages(&mut self, tree: &HashMap<String, VoltPackage>) {
+        self.packages = tree
+            .values()
+            .map(|package| ReportedPackage {
+                name: package.name.clone(),
+                version: package.version.clone(),
+                integrity: package.integrity.clone(),
+                tarball: package.tarball.clone(),
+            })
+            .collect();
+
+        self.packages
+            .sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let data = serde_json::to_string_pretty(self).into_diagnostic()?;
+
+        fs::write(path, data).map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
+
+        Ok(())
+    }
+}
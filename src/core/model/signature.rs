@@ -0,0 +1,323 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Verification of the ECDSA signatures the registry attaches to every published version.
+
+use chrono::{DateTime, FixedOffset};
+use p256::{
+    ecdsa::{signature::Verifier, Signature, VerifyingKey},
+    pkcs8::DecodePublicKey,
+};
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+use crate::core::{net::RegistryManifest, utils::voltapi::VoltPackage};
+
+/// What to do when a package has a missing or invalid registry signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureMode {
+    /// Don't check signatures
+    Off,
+    /// Print a warning and continue installing
+    Warn,
+    /// Abort the install
+    Fail,
+}
+
+impl Default for SignatureMode {
+    fn default() -> Self {
+        Self::Off
+    }
+}
+
+/// A registry signing key, as listed at `/-/npm/v1/keys`
+#[derive(Debug, Clone, Deserialize)]
+pub struct RegistryKey {
+    pub keyid: String,
+    pub keytype: String,
+    /// Base64 encoded DER SubjectPublicKeyInfo
+    pub key: String,
+    /// When the key was rotated out, signatures made before this are still trusted
+    pub expires: Option<String>,
+}
+
+/// Why a package failed signature verification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureProblem {
+    /// The registry has no signature for the version
+    Missing,
+    /// None of the signatures were made with a known registry key
+    UnknownKey(String),
+    /// The signature doesn't match the package
+    Invalid,
+    /// The resolved integrity differs from the one the registry signed
+    IntegrityMismatch { expected: String, actual: String },
+    /// The signing key was rotated out before the version was published
+    ExpiredKey(String),
+}
+
+impl fmt::Display for SignatureProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing => write!(f, "has no registry signature"),
+            Self::UnknownKey(keyid) => {
+                write!(f, "is signed with an unknown registry key `{}`", keyid)
+            }
+            Self::Invalid => write!(f, "has an invalid registry signature"),
+            Self::IntegrityMismatch { expected, actual } => write!(
+                f,
+                "resolved with integrity `{}` but the registry signed `{}`",
+                actual, expected
+            ),
+            Self::ExpiredKey(keyid) => write!(
+                f,
+                "was published after the registry key `{}` it's signed with expired",
+                keyid
+            ),
+        }
+    }
+}
+
+/// Whether any signature of a manifest was made with a key that expires, so checking it needs
+/// the time the version was published
+pub fn needs_publish_time(keys: &[RegistryKey], manifest: &RegistryManifest) -> bool {
+    manifest.dist.signatures.iter().any(|signature| {
+        keys.iter()
+            .any(|key| key.keyid == signature.keyid && key.expires.is_some())
+    })
+}
+
+/// Verify the registry signature of a resolved package against its registry manifest
+///
+/// The registry signs `name@version:integrity` with ECDSA P-256 over SHA-256. The package has
+/// to resolve to the signed integrity, so the signature covers the bytes it's installed from.
+/// `published` is the RFC 3339 publish time of the version, needed when the key expires.
+pub fn verify_signature(
+    keys: &[RegistryKey],
+    package: &VoltPackage,
+    manifest: &RegistryManifest,
+    published: Option<&str>,
+) -> Result<(), SignatureProblem> {
+    let integrity = match &manifest.dist.integrity {
+        Some(integrity) if !manifest.dist.signatures.is_empty() => integrity,
+        _ => return Err(SignatureProblem::Missing),
+    };
+
+    if package.integrity != *integrity {
+        return Err(SignatureProblem::IntegrityMismatch {
+            expected: integrity.clone(),
+            actual: package.integrity.clone(),
+        });
+    }
+
+    let message = format!("{}@{}:{}", package.name, package.version, integrity);
+
+    let mut unknown = None;
+    let mut problem = None;
+
+    // a version may be signed with several keys, it's trusted if any of them verifies
+    for signature in &manifest.dist.signatures {
+        let key = match keys.iter().find(|key| key.keyid == signature.keyid) {
+            Some(key) => key,
+            None => {
+                unknown = Some(signature.keyid.clone());
+                continue;
+            }
+        };
+
+        // without a publish time there's no telling the version was signed before the key expired
+        if let Some(expires) = &key.expires {
+            let expired = match (parse_time(expires), published.and_then(parse_time)) {
+                (Some(expires), Some(published)) => published > expires,
+                _ => true,
+            };
+
+            if expired {
+                problem = Some(SignatureProblem::ExpiredKey(key.keyid.clone()));
+                continue;
+            }
+        }
+
+        if verify(key, &message, &signature.sig) {
+            return Ok(());
+        }
+
+        problem = Some(SignatureProblem::Invalid);
+    }
+
+    Err(problem
+        .unwrap_or_else(|| unknown.map_or(SignatureProblem::Missing, SignatureProblem::UnknownKey)))
+}
+
+fn parse_time(time: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(time).ok()
+}
+
+fn verify(key: &RegistryKey, message: &str, signature: &str) -> bool {
+    match base64::decode(&key.key) {
+        Ok(der) => verify_ecdsa(&der, message.as_bytes(), signature),
+        Err(_) => false,
+    }
+}
+
+/// Verify a base64 encoded DER ECDSA P-256 signature with a DER SubjectPublicKeyInfo
+pub fn verify_ecdsa(public_key: &[u8], message: &[u8], signature: &str) -> bool {
+    let key = VerifyingKey::from_public_key_der(public_key).ok();
+
+    let signature = base64::decode(signature)
+        .ok()
+        .and_then(|der| Signature::from_der(&der).ok());
+
+    match (key, signature) {
+        (Some(key), Some(signature)) => key.verify(message, &signature).is_ok(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::net::RegistrySignature;
+
+    const KEY: &str = "MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEtZ+NC0q/HcG9mIFq2oYzdWCW3euqFyRivmzi62MsTHULf2hM+pzWDb6x3jaonO0CwoN5U8Hb25mE1f42OwaB+A==";
+
+    const INTEGRITY: &str = "sha512-WG0K2FW5Naw+RF/dEJ8PywBjRoiC766woobtTZvE2osIJCxMBnIr0Xp8P5+554G4iGXOhc3oCOA4GhRk/FAMoA==";
+
+    /// `volt-test@1.0.0:<INTEGRITY>` signed with the private half of `KEY`
+    const SIGNATURE: &str = "MEQCIFXwORVvOu2qInzMcPMtg07lYx1TWTe9WqmdCVB+AqXJAiB1gTym/+6nDBnPwA5R85erjW7cLzxDolDAWkcpdHdT8w==";
+
+    fn key(expires: Option<&str>) -> RegistryKey {
+        RegistryKey {
+            keyid: String::from("SHA256:test"),
+            keytype: String::from("ecdsa-sha2-nistp256"),
+            key: String::from(KEY),
+            expires: expires.map(String::from),
+        }
+    }
+
+    fn package(version: &str, integrity: &str) -> VoltPackage {
+        VoltPackage {
+            name: String::from("volt-test"),
+            version: version.to_string(),
+            integrity: integrity.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn manifest(version: &str) -> RegistryManifest {
+        serde_json::from_value(serde_json::json!({
+            "name": "volt-test",
+            "version": version,
+            "dist": {
+                "integrity": INTEGRITY,
+                "signatures": [{ "keyid": "SHA256:test", "sig": SIGNATURE }],
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn verifies_signatures() {
+        let keys = [key(None)];
+
+        assert_eq!(
+            verify_signature(
+                &keys,
+                &package("1.0.0", INTEGRITY),
+                &manifest("1.0.0"),
+                None
+            ),
+            Ok(())
+        );
+
+        // the same signature over another version
+        assert_eq!(
+            verify_signature(
+                &keys,
+                &package("1.0.1", INTEGRITY),
+                &manifest("1.0.1"),
+                None
+            ),
+            Err(SignatureProblem::Invalid)
+        );
+
+        // a sha-1 resolution isn't what the registry signed
+        assert!(matches!(
+            verify_signature(
+                &keys,
+                &package("1.0.0", "sha1-Vqvx1vMaE4qP7rLDUK2vsJiw5y0="),
+                &manifest("1.0.0"),
+                None
+            ),
+            Err(SignatureProblem::IntegrityMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_signatures_after_the_key_expired() {
+        let keys = [key(Some("2025-01-29T00:00:00.000Z"))];
+        let package = package("1.0.0", INTEGRITY);
+        let manifest = manifest("1.0.0");
+
+        assert!(needs_publish_time(&keys, &manifest));
+
+        assert_eq!(
+            verify_signature(&keys, &package, &manifest, Some("2024-06-01T12:00:00.000Z")),
+            Ok(())
+        );
+
+        for published in [Some("2025-03-01T12:00:00.000Z"), None] {
+            assert_eq!(
+                verify_signature(&keys, &package, &manifest, published),
+                Err(SignatureProblem::ExpiredKey(String::from("SHA256:test")))
+            );
+        }
+    }
+
+    #[test]
+    fn accepts_any_verifying_signature() {
+        let keys = [
+            RegistryKey {
+                keyid: String::from("SHA256:old"),
+                ..key(Some("2020-01-01T00:00:00.000Z"))
+            },
+            key(None),
+        ];
+
+        // the first signature was made with a key that expired before the version was published
+        let mut manifest = manifest("1.0.0");
+
+        manifest.dist.signatures.insert(
+            0,
+            RegistrySignature {
+                keyid: String::from("SHA256:old"),
+                sig: String::from(SIGNATURE),
+            },
+        );
+
+        assert_eq!(
+            verify_signature(
+                &keys,
+                &package("1.0.0", INTEGRITY),
+                &manifest,
+                Some("2024-06-01T12:00:00.000Z")
+            ),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,199 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! The packages a project's source files import or require.
+//!
+//! Sources are matched with a regex instead of being parsed, which also finds imports in
+//! comments and strings, but works for every flavour of JavaScript and TypeScript alike.
+
+use ignore::WalkBuilder;
+use lazy_static::lazy_static;
+use miette::Result;
+use regex::Regex;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+/// Extensions of the files scanned for imports
+const EXTENSIONS: &[&str] = &[
+    "js", "jsx", "mjs", "cjs", "ts", "tsx", "mts", "cts", "vue", "svelte", "astro",
+];
+
+/// Modules built into node, which can be imported without the `node:` prefix
+const BUILTINS: &[&str] = &[
+    "assert",
+    "async_hooks",
+    "buffer",
+    "child_process",
+    "cluster",
+    "console",
+    "constants",
+    "crypto",
+    "dgram",
+    "diagnostics_channel",
+    "dns",
+    "domain",
+    "events",
+    "fs",
+    "http",
+    "http2",
+    "https",
+    "inspector",
+    "module",
+    "net",
+    "os",
+    "path",
+    "perf_hooks",
+    "process",
+    "punycode",
+    "querystring",
+    "readline",
+    "repl",
+    "stream",
+    "string_decoder",
+    "sys",
+    "timers",
+    "tls",
+    "trace_events",
+    "tty",
+    "url",
+    "util",
+    "v8",
+    "vm",
+    "wasi",
+    "worker_threads",
+    "zlib",
+];
+
+lazy_static! {
+    // `from 'x'`, `import 'x'`, `import('x')`, `require('x')` and `require.resolve('x')`
+    static ref SPECIFIER: Regex = Regex::new(
+        r#"(?:\bfrom|\bimport\s*\(?|\brequire(?:\.resolve)?\s*\()\s*['"]([^'"\s]+)['"]"#
+    )
+    .unwrap();
+}
+
+/// The module specifiers imported or required by a source file
+pub fn specifiers(source: &str) -> impl Iterator<Item = &str> {
+    SPECIFIER
+        .captures_iter(source)
+        .filter_map(|captures| captures.get(1))
+        .map(|specifier| specifier.as_str())
+}
+
+/// The package a specifier imports from, like `lodash` for `lodash/fp` or `@babel/core` for
+/// `@babel/core/lib/config`. `None` for relative paths, aliases and node's builtins
+pub fn package_name(specifier: &str) -> Option<&str> {
+    if specifier.starts_with(['.', '/', '#', '~']) || specifier.contains(':') {
+        return None;
+    }
+
+    let mut segments = specifier.splitn(3, '/');
+
+    let first = segments.next()?;
+
+    let length = if first.starts_with('@') {
+        // `@/components` is an alias, not a scope
+        let name = segments.next().filter(|_| first.len() > 1)?;
+        first.len() + 1 + name.len()
+    } else {
+        first.len()
+    };
+
+    if BUILTINS.contains(&first) {
+        return None;
+    }
+
+    Some(&specifier[..length])
+}
+
+/// The packages imported by the sources of the project in `dir`, and the files importing each
+///
+/// Ignored files, `node_modules` and directories with their own package.json, like
+/// workspaces, aren't scanned.
+pub fn scan(dir: &Path) -> Result<BTreeMap<String, BTreeSet<PathBuf>>> {
+    let mut imports: BTreeMap<String, BTreeSet<PathBuf>> = BTreeMap::new();
+
+    let root = dir.to_path_buf();
+
+    let walker = WalkBuilder::new(dir)
+        .filter_entry(move |entry| {
+            let path = entry.path();
+
+            !(entry.file_type().map_or(false, |t| t.is_dir())
+                && path != root
+                && (entry.file_name() == "node_modules" || path.join("package.json").exists()))
+        })
+        .build();
+
+    for entry in walker.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+
+        let scanned = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map_or(false, |extension| EXTENSIONS.contains(&extension));
+
+        if !scanned || !path.is_file() {
+            continue;
+        }
+
+        // generated or binary files that happen to have a source extension
+        let source = match read_to_string(path) {
+            Ok(source) => source,
+            Err(_) => continue,
+        };
+
+        let file = path.strip_prefix(dir).unwrap_or(path).to_path_buf();
+
+        for name in specifiers(&source).filter_map(package_name) {
+            imports
+                .entry(name.to_string())
+                .or_default()
+                .insert(file.clone());
+        }
+    }
+
+    Ok(imports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_imported_packages() {
+        let source = r#"
+            import React from "react";
+            import { join } from 'node:path';
+            import './styles.css';
+            export * from "@scope/pkg/sub";
+            const fp = require('lodash/fp');
+            const lazy = await import("chalk");
+            const fs = require("fs/promises");
+            import Button from "@/components/Button";
+        "#;
+
+        let names = specifiers(source)
+            .filter_map(package_name)
+            .collect::<Vec<_>>();
+
+        assert_eq!(names, vec!["react", "@scope/pkg", "lodash", "chalk"]);
+    }
+}
@@ -1,21 +1,442 @@
-use std::time::Instant;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::RwLock,
+    time::Instant,
+};
 
 use crate::core::{
+    daemon,
+    model::{audit::Advisory, provenance::Attestations, signature::RegistryKey},
     utils::constants::MAX_RETRIES,
     utils::errors::VoltError,
-    utils::voltapi::{VoltPackage, VoltResponse},
+    utils::voltapi::{Bin, Engine, VoltPackage, VoltResponse},
     utils::State,
 };
 
 use colored::Colorize;
-use futures_util::{stream::FuturesUnordered, StreamExt};
+use futures_util::{
+    stream::{self, FuturesUnordered},
+    StreamExt,
+};
 use indicatif::ProgressBar;
 use isahc::AsyncReadResponseExt;
+use lazy_static::lazy_static;
 use miette::{IntoDiagnostic, Result};
+use node_semver::{Range, Version};
 use package_spec::PackageSpec;
-use reqwest::StatusCode;
+use reqwest::{Client, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use speedy::Readable;
 
+/// The public npm registry
+pub const NPM_REGISTRY: &str = "https://registry.npmjs.org";
+
+lazy_static! {
+    static ref REGISTRY: RwLock<String> = RwLock::new(NPM_REGISTRY.to_string());
+}
+
+/// The configured registry that metadata, keys and advisories are fetched from
+pub fn registry() -> String {
+    REGISTRY
+        .read()
+        .map_or_else(|_| NPM_REGISTRY.to_string(), |registry| registry.clone())
+}
+
+/// Use `registry` for the rest of the run, set once the configuration is loaded
+pub fn set_registry(registry: &str) {
+    if let Ok(mut current) = REGISTRY.write() {
+        *current = registry.to_string();
+    }
+}
+
+/// Abbreviated package metadata from the npm registry, requested with
+/// `Accept: application/vnd.npm.install-v1+json` to keep responses small
+#[derive(Deserialize, Debug, Clone)]
+pub struct RegistryPackage {
+    pub name: String,
+    #[serde(rename = "dist-tags", default)]
+    pub dist_tags: HashMap<String, String>,
+    #[serde(default)]
+    pub versions: HashMap<String, RegistryVersion>,
+    /// Publish time of each version, only present in the full metadata
+    #[serde(default)]
+    pub time: HashMap<String, String>,
+}
+
+/// A single version in the abbreviated package metadata
+#[derive(Deserialize, Debug, Clone)]
+pub struct RegistryVersion {
+    pub version: String,
+    #[serde(default)]
+    pub dependencies: HashMap<String, String>,
+    #[serde(default)]
+    pub deprecated: Option<String>,
+    #[serde(default)]
+    pub dist: RegistryDist,
+}
+
+/// The full manifest of a single version, as published to the npm registry
+#[derive(Deserialize, Debug, Clone)]
+pub struct RegistryManifest {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub license: Option<serde_json::Value>,
+    #[serde(default)]
+    pub deprecated: Option<String>,
+    #[serde(default)]
+    pub dist: RegistryDist,
+    #[serde(default)]
+    pub bin: Option<Bin>,
+    #[serde(default)]
+    pub scripts: Option<HashMap<String, String>>,
+    #[serde(default)]
+    pub engines: Option<Engine>,
+    #[serde(default)]
+    pub os: Option<Vec<String>>,
+    #[serde(default)]
+    pub cpu: Option<Vec<String>>,
+}
+
+/// The `dist` field of a version manifest
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct RegistryDist {
+    pub integrity: Option<String>,
+    pub tarball: Option<String>,
+    #[serde(default)]
+    pub signatures: Vec<RegistrySignature>,
+    /// Set when the version was published with Sigstore attestations
+    pub attestations: Option<RegistryAttestations>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RegistryAttestations {
+    pub url: String,
+}
+
+/// A registry signature over `name@version:integrity`
+#[derive(Deserialize, Debug, Clone)]
+pub struct RegistrySignature {
+    pub keyid: String,
+    pub sig: String,
+}
+
+impl RegistryManifest {
+    /// The `license` field, which older packages publish as `{ "type": "MIT" }`
+    pub fn license(&self) -> Option<String> {
+        match self.license.as_ref()? {
+            serde_json::Value::String(license) => Some(license.clone()),
+            serde_json::Value::Object(object) => Some(object.get("type")?.as_str()?.to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl RegistryPackage {
+    /// The version the `latest` dist-tag points to
+    pub fn latest(&self) -> Option<&str> {
+        self.dist_tags.get("latest").map(String::as_str)
+    }
+
+    /// The highest published version satisfying a range
+    pub fn max_satisfying(&self, range: &str) -> Option<Version> {
+        let range: Range = range.parse().ok()?;
+
+        self.versions
+            .keys()
+            .filter_map(|v| v.parse::<Version>().ok())
+            .filter(|v| range.satisfies(v))
+            .max()
+    }
+}
+
+/// Fetch the abbreviated metadata of a package from the npm registry
+pub async fn get_registry_package(client: &Client, name: &str) -> Result<RegistryPackage> {
+    fetch_registry_package(client, name, "application/vnd.npm.install-v1+json").await
+}
+
+/// Fetch the abbreviated metadata of a package from `volt daemon` when it's running, which keeps
+/// it revalidated, or from the npm registry
+///
+/// It can be a few minutes old, commands changing a package's metadata use
+/// [`get_registry_package`].
+pub async fn get_cached_registry_package(client: &Client, name: &str) -> Result<RegistryPackage> {
+    if let Some(document) = daemon::cached_metadata(&registry(), name).await {
+        if let Ok(package) = serde_json::from_str(&document) {
+            return Ok(package);
+        }
+    }
+
+    get_registry_package(client, name).await
+}
+
+/// Fetch the full metadata of a package from the npm registry, which includes publish times
+pub async fn get_registry_packument(client: &Client, name: &str) -> Result<RegistryPackage> {
+    fetch_registry_package(client, name, "application/json").await
+}
+
+/// Fetch the full metadata of a package as JSON, keeping every field the registry returns
+pub async fn get_registry_document(client: &Client, name: &str) -> Result<serde_json::Value> {
+    fetch_registry_package(client, name, "application/json").await
+}
+
+async fn fetch_registry_package<T: DeserializeOwned>(
+    client: &Client,
+    name: &str,
+    accept: &str,
+) -> Result<T> {
+    let url = format!("{}/{}", registry(), name.replace('/', "%2f"));
+
+    let response = client
+        .get(&url)
+        .header("Accept", accept)
+        .send()
+        .await
+        .into_diagnostic()?;
+
+    match response.status() {
+        StatusCode::OK => Ok(response.json().await.into_diagnostic()?),
+        StatusCode::NOT_FOUND => Err(VoltError::PackageNotFound {
+            url,
+            package_name: name.to_string(),
+        }
+        .into()),
+        StatusCode::TOO_MANY_REQUESTS => Err(VoltError::TooManyRequests { url }.into()),
+        status => Err(VoltError::NetworkUnknownError {
+            url,
+            package_name: name.to_string(),
+            code: status.as_str().to_string(),
+        }
+        .into()),
+    }
+}
+
+/// Fetch the manifest of a single version of a package from the npm registry
+pub async fn get_version_manifest(
+    client: &Client,
+    name: &str,
+    version: &str,
+) -> Result<RegistryManifest> {
+    let url = format!("{}/{}/{}", registry(), name.replace('/', "%2f"), version);
+
+    let response = client.get(&url).send().await.into_diagnostic()?;
+
+    match response.status() {
+        StatusCode::OK => Ok(response.json().await.into_diagnostic()?),
+        StatusCode::NOT_FOUND => Err(VoltError::PackageNotFound {
+            url,
+            package_name: name.to_string(),
+        }
+        .into()),
+        StatusCode::TOO_MANY_REQUESTS => Err(VoltError::TooManyRequests { url }.into()),
+        status => Err(VoltError::NetworkUnknownError {
+            url,
+            package_name: name.to_string(),
+            code: status.as_str().to_string(),
+        }
+        .into()),
+    }
+}
+
+/// Fetch the registry manifest of every package in a resolved tree, keyed like the tree,
+/// `concurrency` at a time
+pub async fn get_version_manifests(
+    client: &Client,
+    tree: &HashMap<String, VoltPackage>,
+    concurrency: usize,
+) -> Result<HashMap<String, RegistryManifest>> {
+    let mut requests = stream::iter(tree)
+        .map(|(key, package)| async move {
+            (
+                key.clone(),
+                get_version_manifest(client, &package.name, &package.version).await,
+            )
+        })
+        .buffer_unordered(concurrency);
+
+    let mut manifests = HashMap::with_capacity(tree.len());
+
+    while let Some((key, manifest)) = requests.next().await {
+        manifests.insert(key, manifest?);
+    }
+
+    Ok(manifests)
+}
+
+/// Fetch when each of `packages` was published, keyed by `name@version`, `concurrency` at a time
+pub async fn get_publish_times<'a, I>(
+    client: &Client,
+    packages: I,
+    concurrency: usize,
+) -> Result<HashMap<String, String>>
+where
+    I: IntoIterator<Item = &'a VoltPackage>,
+{
+    let mut versions: HashMap<&str, Vec<&str>> = HashMap::new();
+
+    for package in packages {
+        versions
+            .entry(package.name.as_str())
+            .or_default()
+            .push(package.version.as_str());
+    }
+
+    let mut requests = stream::iter(versions)
+        .map(|(name, versions)| async move {
+            (name, versions, get_registry_packument(client, name).await)
+        })
+        .buffer_unordered(concurrency);
+
+    let mut times = HashMap::new();
+
+    while let Some((name, versions, packument)) = requests.next().await {
+        let mut packument = packument?;
+
+        for version in versions {
+            if let Some(time) = packument.time.remove(version) {
+                times.insert(format!("{}@{}", name, version), time);
+            }
+        }
+    }
+
+    Ok(times)
+}
+
+/// Fetch the keys the registry signs packages with, `None` for registries that don't sign
+pub async fn get_registry_keys(client: &Client) -> Result<Option<Vec<RegistryKey>>> {
+    #[derive(Deserialize)]
+    struct Keys {
+        keys: Vec<RegistryKey>,
+    }
+
+    let url = format!("{}/-/npm/v1/keys", registry());
+
+    let response = client.get(&url).send().await.into_diagnostic()?;
+
+    match response.status() {
+        StatusCode::OK => Ok(Some(response.json::<Keys>().await.into_diagnostic()?.keys)),
+        StatusCode::NOT_FOUND => Ok(None),
+        StatusCode::TOO_MANY_REQUESTS => Err(VoltError::TooManyRequests { url }.into()),
+        status => Err(VoltError::NetworkUnknownError {
+            url,
+            package_name: String::from("registry keys"),
+            code: status.as_str().to_string(),
+        }
+        .into()),
+    }
+}
+
+/// Fetch the Sigstore attestations advertised in a version manifest
+pub async fn get_attestations(client: &Client, url: &str) -> Result<Attestations> {
+    let response = client.get(url).send().await.into_diagnostic()?;
+
+    match response.status() {
+        StatusCode::OK => Ok(response.json().await.into_diagnostic()?),
+        StatusCode::TOO_MANY_REQUESTS => Err(VoltError::TooManyRequests {
+            url: url.to_string(),
+        }
+        .into()),
+        status => Err(VoltError::NetworkUnknownError {
+            url: url.to_string(),
+            package_name: String::from("attestations"),
+            code: status.as_str().to_string(),
+        }
+        .into()),
+    }
+}
+
+/// A page of registry search results
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SearchPage {
+    pub objects: Vec<SearchObject>,
+    pub total: usize,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SearchObject {
+    pub package: SearchPackage,
+    /// Only returned by registries that track downloads
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub downloads: Option<SearchDownloads>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SearchPackage {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// When the version was published
+    #[serde(default)]
+    pub date: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SearchDownloads {
+    pub weekly: u64,
+}
+
+/// Search the registry, returning `size` results starting at the `from`th one
+pub async fn search_registry(
+    client: &Client,
+    query: &str,
+    size: usize,
+    from: usize,
+) -> Result<SearchPage> {
+    let url = format!(
+        "{}/-/v1/search?text={}&size={}&from={}",
+        registry(),
+        urlencoding::encode(query),
+        size,
+        from
+    );
+
+    let response = client.get(&url).send().await.into_diagnostic()?;
+
+    match response.status() {
+        StatusCode::OK => Ok(response.json().await.into_diagnostic()?),
+        StatusCode::BAD_REQUEST => Err(VoltError::BadRequest { url }.into()),
+        StatusCode::TOO_MANY_REQUESTS => Err(VoltError::TooManyRequests { url }.into()),
+        status => Err(VoltError::NetworkUnknownError {
+            url,
+            package_name: String::from("search"),
+            code: status.as_str().to_string(),
+        }
+        .into()),
+    }
+}
+
+/// Look up the advisories affecting any of the given versions of each package
+pub async fn get_advisories(
+    client: &Client,
+    packages: &BTreeMap<String, BTreeSet<String>>,
+) -> Result<HashMap<String, Vec<Advisory>>> {
+    if packages.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let url = format!("{}/-/npm/v1/security/advisories/bulk", registry());
+
+    let response = client
+        .post(&url)
+        .json(packages)
+        .send()
+        .await
+        .into_diagnostic()?;
+
+    match response.status() {
+        StatusCode::OK => Ok(response.json().await.into_diagnostic()?),
+        StatusCode::BAD_REQUEST => Err(VoltError::BadRequest { url }.into()),
+        StatusCode::TOO_MANY_REQUESTS => Err(VoltError::TooManyRequests { url }.into()),
+        status => Err(VoltError::NetworkUnknownError {
+            url,
+            package_name: String::from("security advisories"),
+            code: status.as_str().to_string(),
+        }
+        .into()),
+    }
+}
+
 pub async fn get_volt_response_multi(
     packages: &[PackageSpec],
     progress_bar: &ProgressBar,
@@ -27,11 +448,9 @@ pub async fn get_volt_response_multi(
                 name, requested, ..
             } = spec
             {
-                let mut version: String = "latest".to_string();
-
-                if requested.is_some() {
-                    version = requested.as_ref().unwrap().to_string();
-                };
+                let version = requested
+                    .as_ref()
+                    .map_or_else(|| String::from("latest"), |r| r.to_string());
 
                 progress_bar.set_message(format!("{}@{}", name, version.truecolor(125, 125, 125)));
             }
@@ -59,12 +478,20 @@ pub async fn get_volt_response(package_spec: &PackageSpec) -> Result<VoltRespons
                     .await
                     .map_err(VoltError::NetworkError)?;
 
+            tracing::debug!(
+                "GET http://registry.voltpkg.com/{}.sp - {}",
+                package_spec,
+                response.status()
+            );
+
             // check the status of the response
             match response.status() {
                 // 200 (OK)
                 StatusCode::OK => {
-                    let mut response: VoltResponse =
-                        VoltResponse::read_from_buffer(&response.bytes().await.unwrap()).unwrap();
+                    let bytes = response.bytes().await.map_err(VoltError::IoTextRecError)?;
+
+                    let mut response = VoltResponse::read_from_buffer(&bytes)
+                        .map_err(|_| VoltError::DeserializeError)?;
 
                     response.name = name.to_string();
 
@@ -114,6 +541,8 @@ pub async fn get_volt_response(package_spec: &PackageSpec) -> Result<VoltRespons
 
 /// downloads and extracts tarball file from package
 pub async fn fetch_tarball(package: &VoltPackage, state: State) -> Result<bytes::Bytes> {
+    tracing::debug!("GET {}", package.tarball);
+
     // Recieve the tarball from the npm registry
     let response = state
         .http_client
@@ -142,11 +571,9 @@ pub async fn fetch_dep_tree(
             name, requested, ..
         } = &data[0]
         {
-            let mut version: String = "latest".to_string();
-
-            if requested.is_some() {
-                version = requested.as_ref().unwrap().to_string();
-            };
+            let version = requested
+                .as_ref()
+                .map_or_else(|| String::from("latest"), |r| r.to_string());
 
             progress_bar.set_message(format!("{}@{}", name, version.truecolor(125, 125, 125)));
         }
@@ -155,14 +582,14 @@ pub async fn fetch_dep_tree(
     }
 }
 
-pub async fn ping() {
+pub async fn ping() -> Result<()> {
     let ping = Instant::now();
 
     println!("PING! http://registry.voltpkg.com/");
 
-    let mut response = isahc::get_async("http://registry.voltpkg.com/ping")
+    let response = isahc::get_async("http://registry.voltpkg.com/ping")
         .await
-        .unwrap();
+        .map_err(VoltError::NetworkError)?;
 
     match response.status() {
         StatusCode::OK => {
@@ -182,9 +609,9 @@ pub async fn ping() {
 
     println!("PING! https://registry.npmjs.org/");
 
-    let mut response = isahc::get_async("https://registry.npmjs.org/")
+    let response = isahc::get_async("https://registry.npmjs.org/")
         .await
-        .unwrap();
+        .map_err(VoltError::NetworkError)?;
 
     match response.status() {
         StatusCode::OK => {
@@ -199,4 +626,6 @@ pub async fn ping() {
             println!("Ping failed");
         }
     }
+
+    Ok(())
 }
@@ -0,0 +1,58 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Output for humans and for tools.
+//!
+//! With `--json`, a command prints exactly one JSON document to stdout and everything meant for
+//! people (progress, summaries, hints) goes to stderr, so volt can be wrapped by other tooling.
+
+use crate::cli::VoltConfig;
+
+use indicatif::{ProgressBar, ProgressDrawTarget};
+use miette::{IntoDiagnostic, Result};
+use serde::Serialize;
+
+use std::fmt::Display;
+
+/// Print the JSON document of a command to stdout
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value).into_diagnostic()?);
+
+    Ok(())
+}
+
+/// Print a line meant for people, on stderr when stdout is reserved for `--json` or events,
+/// and not at all with `-q`
+pub fn status(config: &VoltConfig, line: impl Display) {
+    if config.quiet() {
+        return;
+    }
+
+    if config.stdout_reserved() {
+        eprintln!("{}", line);
+    } else {
+        println!("{}", line);
+    }
+}
+
+/// Hide a progress bar unless output goes to a terminal and `-q` wasn't passed
+pub fn progress(config: &VoltConfig, bar: ProgressBar) -> ProgressBar {
+    if !config.show_progress() {
+        bar.set_draw_target(ProgressDrawTarget::hidden());
+    }
+
+    bar
+}
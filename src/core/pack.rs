@@ -0,0 +1,1607 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! The `node_modules.pack` archive, a whole `node_modules` in a single file.
+//!
+//! ```text
+//! +--------------------+  0
+//! | header             |  magic, format version, codec, where the index is and its hash
+//! +--------------------+  HEADER_SIZE
+//! | file data          |  every file compressed on its own, one after the other
+//! +--------------------+  index_offset
+//! | index              |  compressed JSON listing every package and its files
+//! +--------------------+
+//! ```
+//!
+//! All integers in the header are little endian. Each file in the index records the offset and
+//! length of its data, its size, permissions and SHA-256, so a single package or file can be
+//! read without decompressing anything else. The header holds the SHA-256 of the rest of the
+//! header and the compressed index, which is the hash of the whole archive since the index holds
+//! the hash of every file. Symbolic links, like the ones into the store, are recorded with
+//! their target and have no data. Empty directories aren't recorded.
+//!
+//! Updating an archive appends the packages that changed and a new index after the old one,
+//! so the data of the packages replaced or removed stays in the archive, unreferenced, until
+//! it's compacted.
+
+use crate::core::utils::{errors::VoltError, remove_link};
+
+use clap::ArgEnum;
+use indicatif::ProgressBar;
+use miette::{IntoDiagnostic, Result};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    ops::RangeInclusive,
+    path::{Component, Path, PathBuf},
+};
+
+pub const MAGIC: &[u8; 8] = b"VOLTPACK";
+
+/// Bumped whenever a reader of the previous version couldn't read the archive
+pub const VERSION: u16 = 1;
+
+pub const HEADER_SIZE: u64 = 72;
+
+/// The archive `volt compress` writes next to `node_modules`
+pub const FILE_NAME: &str = "node_modules.pack";
+
+/// How file data and the index are compressed, recorded in the header so readers don't have
+/// to be told
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ArgEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    /// Not compressed, the fastest to write and read
+    Store,
+    /// Raw DEFLATE (RFC 1951)
+    Deflate,
+    /// Zstandard, much faster than DEFLATE at a similar ratio
+    Zstd,
+}
+
+impl Codec {
+    /// The byte identifying the codec in the header
+    pub const fn id(self) -> u8 {
+        match self {
+            Self::Store => 0,
+            Self::Deflate => 1,
+            Self::Zstd => 2,
+        }
+    }
+
+    pub const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::Store),
+            1 => Some(Self::Deflate),
+            2 => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    /// The compression levels the codec supports
+    pub const fn levels(self) -> RangeInclusive<i32> {
+        match self {
+            Self::Store => 0..=0,
+            Self::Deflate => 1..=12,
+            Self::Zstd => 1..=22,
+        }
+    }
+
+    pub const fn default_level(self) -> i32 {
+        match self {
+            Self::Store => 0,
+            Self::Deflate => 6,
+            Self::Zstd => 3,
+        }
+    }
+
+    /// The most the codec can expand data, which bounds the sizes an archive can claim
+    const fn max_ratio(self) -> u64 {
+        match self {
+            Self::Store => 1,
+            Self::Deflate => 1032,
+            // a one byte run fills a whole 128 KiB block
+            Self::Zstd => 32_768,
+        }
+    }
+
+    /// Decompress `data`, which was `size` bytes before it was compressed
+    ///
+    /// The size comes from the archive, so it's checked against what `data` could hold before
+    /// anything is allocated for it.
+    pub fn decompress(self, data: &[u8], size: u64) -> Result<Vec<u8>> {
+        if size > (data.len() as u64).saturating_mul(self.max_ratio()) {
+            miette::bail!("{} compressed bytes can't hold {} bytes", data.len(), size);
+        }
+
+        match self {
+            Self::Store => Ok(data.to_vec()),
+            Self::Deflate => {
+                let mut decompressed = vec![0; size as usize];
+
+                let length = libdeflater::Decompressor::new()
+                    .deflate_decompress(data, &mut decompressed)
+                    .into_diagnostic()?;
+
+                decompressed.truncate(length);
+
+                Ok(decompressed)
+            }
+            Self::Zstd => {
+                // streamed, so memory only grows with the data that's really there
+                let mut decompressed = vec![];
+
+                zstd::stream::read::Decoder::new(data)
+                    .into_diagnostic()?
+                    .take(size)
+                    .read_to_end(&mut decompressed)
+                    .into_diagnostic()?;
+
+                Ok(decompressed)
+            }
+        }
+    }
+}
+
+/// A codec and the level to compress at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compression {
+    pub codec: Codec,
+    pub level: i32,
+}
+
+impl Compression {
+    /// Compress with `codec` at `level`, or the codec's default level
+    pub fn new(codec: Codec, level: Option<i32>) -> Result<Self> {
+        let levels = codec.levels();
+        let level = level.unwrap_or_else(|| codec.default_level());
+
+        if !levels.contains(&level) {
+            return Err(VoltError::ConfigValueError {
+                key: String::from("level"),
+                value: level.to_string(),
+                expected: format!(
+                    "{} to {} for {}",
+                    levels.start(),
+                    levels.end(),
+                    codec
+                        .to_possible_value()
+                        .map_or("", |value| value.get_name())
+                ),
+            }
+            .into());
+        }
+
+        Ok(Self { codec, level })
+    }
+
+    pub fn compress(self, data: &[u8]) -> Result<Vec<u8>> {
+        match self.codec {
+            Codec::Store => Ok(data.to_vec()),
+            Codec::Deflate => {
+                let level = libdeflater::CompressionLvl::new(self.level).unwrap_or_default();
+                let mut compressor = libdeflater::Compressor::new(level);
+
+                let mut compressed = vec![0; compressor.deflate_compress_bound(data.len())];
+                let length = compressor
+                    .deflate_compress(data, &mut compressed)
+                    .into_diagnostic()?;
+
+                compressed.truncate(length);
+
+                Ok(compressed)
+            }
+            Codec::Zstd => zstd::bulk::compress(data, self.level).into_diagnostic(),
+        }
+    }
+}
+
+/// The fixed-size start of an archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub version: u16,
+    pub codec: Codec,
+    pub index_offset: u64,
+    /// Compressed length of the index
+    pub index_length: u64,
+    /// Size of the index once decompressed
+    pub index_size: u64,
+    /// SHA-256 of the rest of the header and the compressed index
+    pub index_sha256: [u8; 32],
+}
+
+impl Header {
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE as usize] {
+        let mut bytes = [0; HEADER_SIZE as usize];
+
+        bytes[0..8].copy_from_slice(MAGIC);
+        bytes[8..10].copy_from_slice(&self.version.to_le_bytes());
+        bytes[10] = self.codec.id();
+        // 11..16 are reserved for flags
+        bytes[16..24].copy_from_slice(&self.index_offset.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.index_length.to_le_bytes());
+        bytes[32..40].copy_from_slice(&self.index_size.to_le_bytes());
+        bytes[40..72].copy_from_slice(&self.index_sha256);
+
+        bytes
+    }
+
+    /// The SHA-256 of the header, without the hash itself, followed by the compressed index
+    pub fn digest(&self, compressed_index: &[u8]) -> [u8; 32] {
+        let mut bytes = self.to_bytes();
+        bytes[40..72].fill(0);
+
+        let mut hasher = Sha256::new();
+        hasher.update(bytes);
+        hasher.update(compressed_index);
+
+        hasher.finalize().into()
+    }
+
+    /// Parse a header, `None` if it isn't one this version of volt can read
+    pub fn from_bytes(bytes: &[u8; HEADER_SIZE as usize]) -> Option<Self> {
+        let u64_at = |start: usize| {
+            let mut value = [0; 8];
+            value.copy_from_slice(&bytes[start..start + 8]);
+            u64::from_le_bytes(value)
+        };
+
+        if &bytes[0..8] != MAGIC {
+            return None;
+        }
+
+        let version = u16::from_le_bytes([bytes[8], bytes[9]]);
+
+        if version != VERSION {
+            return None;
+        }
+
+        let mut index_sha256 = [0; 32];
+        index_sha256.copy_from_slice(&bytes[40..72]);
+
+        Some(Self {
+            version,
+            codec: Codec::from_id(bytes[10])?,
+            index_offset: u64_at(16),
+            index_length: u64_at(24),
+            index_size: u64_at(32),
+            index_sha256,
+        })
+    }
+}
+
+/// Every package in the archive
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Index {
+    pub packages: Vec<PackageEntry>,
+}
+
+impl Index {
+    /// The first entry whose path goes through a link of the archive
+    pub fn inside_link(&self) -> Option<String> {
+        let links = self
+            .packages
+            .iter()
+            .flat_map(|package| {
+                package
+                    .files
+                    .iter()
+                    .filter(|file| file.link.is_some())
+                    .map(move |file| Path::new(&package.path).join(&file.path))
+            })
+            .collect::<HashSet<_>>();
+
+        self.packages
+            .iter()
+            .flat_map(|package| {
+                package
+                    .files
+                    .iter()
+                    .map(move |file| Path::new(&package.path).join(&file.path))
+            })
+            .find(|path| path.ancestors().skip(1).any(|dir| links.contains(dir)))
+            .map(|path| slash_path(&path))
+    }
+}
+
+/// A package directory and the files in it
+///
+/// Files outside of any package, like the links in `node_modules/.bin`, belong to an entry
+/// with an empty name and path.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageEntry {
+    pub name: String,
+    pub version: String,
+    /// The package directory relative to `node_modules`, with `/` separators
+    pub path: String,
+    pub files: Vec<FileEntry>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileEntry {
+    /// Relative to the package directory, with `/` separators
+    pub path: String,
+    /// Unix permission bits
+    pub mode: u32,
+    /// Offset of the compressed data from the start of the archive
+    pub offset: u64,
+    /// Length of the compressed data
+    pub length: u64,
+    /// Size once decompressed
+    pub size: u64,
+    /// Hex SHA-256 of the decompressed data
+    pub sha256: String,
+    /// Target of a symbolic link, which has no data, relative to `node_modules` with `/`
+    /// separators
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub link: Option<String>,
+}
+
+/// A file compressed and hashed, ready to be appended to an archive
+#[derive(Debug)]
+pub struct Encoded {
+    pub entry: FileEntry,
+    pub data: Vec<u8>,
+}
+
+/// Compress and hash a file, which is independent of the rest of the archive and can be done
+/// on any thread
+pub fn encode(compression: Compression, path: String, mode: u32, data: &[u8]) -> Result<Encoded> {
+    Ok(Encoded {
+        entry: FileEntry {
+            path,
+            mode,
+            size: data.len() as u64,
+            sha256: hex::encode(Sha256::digest(data)),
+            ..FileEntry::default()
+        },
+        data: compression.compress(data)?,
+    })
+}
+
+/// Writes the data of an archive, then its index and header
+pub struct PackWriter<W: Write + Seek> {
+    out: W,
+    compression: Compression,
+    offset: u64,
+}
+
+impl<W: Write + Seek> PackWriter<W> {
+    /// Start an archive, leaving room for the header written by [`Self::finish`]
+    pub fn new(mut out: W, compression: Compression) -> io::Result<Self> {
+        out.write_all(&[0; HEADER_SIZE as usize])?;
+
+        Ok(Self {
+            out,
+            compression,
+            offset: HEADER_SIZE,
+        })
+    }
+
+    /// Continue an archive after its end, the header rewritten by [`Self::finish`] pointing at
+    /// the new index
+    pub fn resume(mut out: W, compression: Compression) -> io::Result<Self> {
+        let offset = out.seek(SeekFrom::End(0))?;
+
+        Ok(Self {
+            out,
+            compression,
+            offset,
+        })
+    }
+
+    pub const fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Write the data of an encoded file, returning its entry for the index
+    pub fn append(&mut self, encoded: Encoded) -> io::Result<FileEntry> {
+        let mut entry = encoded.entry;
+
+        if entry.link.is_none() {
+            self.out.write_all(&encoded.data)?;
+
+            entry.offset = self.offset;
+            entry.length = encoded.data.len() as u64;
+
+            self.offset += entry.length;
+        }
+
+        Ok(entry)
+    }
+
+    /// Write the index after the data and point the header at it
+    pub fn finish(mut self, index: &Index) -> Result<(Header, W)> {
+        let json = serde_json::to_vec(index).into_diagnostic()?;
+        let compressed = self.compression.compress(&json)?;
+
+        let mut header = Header {
+            version: VERSION,
+            codec: self.compression.codec,
+            index_offset: self.offset,
+            index_length: compressed.len() as u64,
+            index_size: json.len() as u64,
+            index_sha256: [0; 32],
+        };
+
+        header.index_sha256 = header.digest(&compressed);
+
+        self.out.write_all(&compressed).into_diagnostic()?;
+        self.out.seek(SeekFrom::Start(0)).into_diagnostic()?;
+        self.out.write_all(&header.to_bytes()).into_diagnostic()?;
+        self.out.flush().into_diagnostic()?;
+
+        Ok((header, self.out))
+    }
+}
+
+/// A file or link found in `node_modules`
+#[derive(Debug, Clone)]
+pub struct Source {
+    /// Index of the package it belongs to in the packages returned by [`scan`]
+    pub package: usize,
+    /// Relative to the package directory, with `/` separators
+    pub path: String,
+    pub absolute: PathBuf,
+    pub is_link: bool,
+}
+
+/// Find every package in `node_modules` and the files in them, without following links
+pub fn scan(node_modules: &Path) -> Result<(Vec<PackageEntry>, Vec<Source>)> {
+    let mut entries = jwalk::WalkDir::new(node_modules)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !entry.file_type().is_dir() || entry.path_is_symlink())
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+
+    entries.sort();
+
+    // the loose files outside of any package
+    let mut packages = vec![PackageEntry::default()];
+    let mut by_path = BTreeMap::<PathBuf, usize>::new();
+    let mut sources = vec![];
+
+    for absolute in entries {
+        let relative = match absolute.strip_prefix(node_modules) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => continue,
+        };
+
+        let is_link = absolute
+            .symlink_metadata()
+            .map_or(false, |metadata| metadata.file_type().is_symlink());
+
+        // links out of node_modules, like packages linked with `volt link`, are left out
+        if is_link && !links_inside(node_modules, &absolute) {
+            continue;
+        }
+
+        // a link can't own files, it's a file of the package it's in
+        let parent = relative.parent().unwrap_or_else(|| Path::new(""));
+
+        let (package, path) = match package_dir(parent) {
+            Some(dir) => {
+                let package = *by_path.entry(dir.clone()).or_insert_with(|| {
+                    packages.push(package_entry(node_modules, &dir));
+                    packages.len() - 1
+                });
+
+                (
+                    package,
+                    slash_path(relative.strip_prefix(&dir).unwrap_or(&relative)),
+                )
+            }
+            None => (0, slash_path(&relative)),
+        };
+
+        sources.push(Source {
+            package,
+            path,
+            absolute,
+            is_link,
+        });
+    }
+
+    Ok((packages, sources))
+}
+
+/// The directory of the innermost package containing `dir`, relative to `node_modules`
+fn package_dir(dir: &Path) -> Option<PathBuf> {
+    let components = dir.components().collect::<Vec<_>>();
+
+    let mut root = None;
+
+    // `react`, `.volt/react@18.0.0/node_modules/react`, `@babel/core/node_modules/debug`
+    let mut i = 0;
+
+    while i < components.len() {
+        let name = components[i].as_os_str().to_string_lossy();
+
+        if name.starts_with('.') {
+            // `.volt/<key>/node_modules`, `.bin`
+            i += if name == ".volt" { 3 } else { 1 };
+            continue;
+        }
+
+        let end = if name.starts_with('@') { i + 2 } else { i + 1 };
+
+        if end > components.len() {
+            break;
+        }
+
+        root = Some(components[..end].iter().collect::<PathBuf>());
+
+        // the package's own dependencies are in its `node_modules`
+        match components[end..]
+            .iter()
+            .position(|component| component.as_os_str() == "node_modules")
+        {
+            Some(position) => i = end + position + 1,
+            None => break,
+        }
+    }
+
+    root
+}
+
+fn package_entry(node_modules: &Path, dir: &Path) -> PackageEntry {
+    let manifest = fs::read_to_string(node_modules.join(dir).join("package.json"))
+        .ok()
+        .and_then(|data| serde_json::from_str::<serde_json::Value>(&data).ok());
+
+    let field = |name: &str| {
+        manifest
+            .as_ref()
+            .and_then(|manifest| manifest.get(name)?.as_str().map(String::from))
+    };
+
+    let fallback = dir
+        .components()
+        .rev()
+        .take(2)
+        .map(|component| component.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+
+    PackageEntry {
+        name: field("name").unwrap_or_else(|| match fallback.as_slice() {
+            [name, scope] if scope.starts_with('@') => format!("{}/{}", scope, name),
+            [name, ..] => name.clone(),
+            [] => String::new(),
+        }),
+        version: field("version").unwrap_or_default(),
+        path: slash_path(dir),
+        files: vec![],
+    }
+}
+
+/// A relative path with `/` separators, the same on every platform
+pub fn slash_path(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Where a link inside of `node_modules` points, relative to `node_modules`
+fn link_target(node_modules: &Path, link: &Path, target: &Path) -> String {
+    let absolute = normalize(&link.parent().unwrap_or(link).join(target));
+
+    slash_path(absolute.strip_prefix(node_modules).unwrap_or(&absolute))
+}
+
+/// Whether a link points into `node_modules`
+fn links_inside(node_modules: &Path, link: &Path) -> bool {
+    fs::read_link(link).map_or(false, |target| {
+        normalize(&link.parent().unwrap_or(link).join(target)).starts_with(node_modules)
+    })
+}
+
+/// Resolve the `.` and `..` in a path without touching the filesystem
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+#[cfg(unix)]
+pub fn mode(path: &Path) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.symlink_metadata()
+        .map_or(0o644, |metadata| metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+pub fn mode(_path: &Path) -> u32 {
+    0o644
+}
+
+/// What a written archive holds
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Summary {
+    pub packages: usize,
+    /// Packages compressed and written, the others were already in the archive
+    pub written: usize,
+    pub files: usize,
+    /// Total size of the files
+    pub size: u64,
+    /// Size of the archive
+    pub packed_size: u64,
+    /// Bytes of the archive its index no longer points to, which compacting reclaims
+    pub garbage: u64,
+}
+
+/// Write every package in `node_modules` to the archive at `output`
+///
+/// An existing archive compressed with the same codec is updated in place: the packages that
+/// changed are appended to it with a new index, the ones that didn't keep their data, and the
+/// data of the replaced and removed ones is left behind until [`compact`]. The header is
+/// written last, so an interrupted update leaves the previous index in charge.
+///
+/// A new archive is written next to `output` and renamed over it once complete, so an
+/// interrupted run never leaves a truncated archive behind.
+///
+/// Files are compressed on every core, `bar` counting the bytes compressed.
+pub fn write_pack(
+    node_modules: &Path,
+    output: &Path,
+    compression: Compression,
+    bar: &ProgressBar,
+) -> Result<Summary> {
+    let (packages, sources) = scan(node_modules)?;
+
+    let previous = PackReader::open(output)
+        .ok()
+        .filter(|reader| reader.header.codec == compression.codec);
+
+    let (mut summary, used) = match previous {
+        Some(previous) => {
+            let write_error = |e| VoltError::WriteFileError {
+                source: e,
+                name: output.display().to_string(),
+            };
+
+            let file = OpenOptions::new()
+                .write(true)
+                .open(output)
+                .map_err(write_error)?;
+
+            let writer =
+                PackWriter::resume(BufWriter::new(file), compression).map_err(write_error)?;
+
+            pack_into(
+                node_modules,
+                output,
+                packages,
+                sources,
+                writer,
+                Some(&previous.index),
+                bar,
+            )?
+        }
+        None => {
+            let partial = output.with_extension("pack.partial");
+
+            let file = File::create(&partial).map_err(|e| VoltError::WriteFileError {
+                source: e,
+                name: partial.display().to_string(),
+            })?;
+
+            let writer = PackWriter::new(BufWriter::new(file), compression).map_err(|e| {
+                VoltError::WriteFileError {
+                    source: e,
+                    name: partial.display().to_string(),
+                }
+            })?;
+
+            let packed = pack_into(node_modules, &partial, packages, sources, writer, None, bar)?;
+
+            fs::rename(&partial, output).map_err(|e| VoltError::WriteFileError {
+                source: e,
+                name: output.display().to_string(),
+            })?;
+
+            packed
+        }
+    };
+
+    summary.packed_size = fs::metadata(output).map_or(0, |metadata| metadata.len());
+    summary.garbage = summary.packed_size.saturating_sub(used);
+
+    Ok(summary)
+}
+
+/// Write the packages found by [`scan`], reusing the data of the unchanged ones in `previous`,
+/// returning how many bytes of the archive are in use
+fn pack_into<W: Write + Seek + Send>(
+    node_modules: &Path,
+    output: &Path,
+    mut packages: Vec<PackageEntry>,
+    sources: Vec<Source>,
+    mut writer: PackWriter<W>,
+    previous: Option<&Index>,
+    bar: &ProgressBar,
+) -> Result<(Summary, u64)> {
+    let previous = previous
+        .map(|index| {
+            index
+                .packages
+                .iter()
+                .map(|package| (package.path.as_str(), package))
+                .collect::<HashMap<_, _>>()
+        })
+        .unwrap_or_default();
+
+    let mut grouped = vec![vec![]; packages.len()];
+
+    for source in sources {
+        grouped[source.package].push(source);
+    }
+
+    let described = grouped
+        .par_iter()
+        .map(|sources| {
+            sources
+                .iter()
+                .map(|source| describe(node_modules, source))
+                .collect::<Result<Vec<_>>>()
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut summary = Summary::default();
+    let mut jobs = vec![];
+
+    for (i, (sources, files)) in grouped.iter().zip(described).enumerate() {
+        let package = &mut packages[i];
+
+        summary.files += files.len();
+        summary.size += files.iter().map(|file| file.size).sum::<u64>();
+
+        if let Some(old) = previous.get(package.path.as_str()) {
+            if unchanged(old, package, &files) {
+                package.files = old.files.clone();
+                continue;
+            }
+        }
+
+        if !package.name.is_empty() {
+            summary.written += 1;
+        }
+
+        jobs.extend(sources.iter().zip(files).map(|(source, entry)| Job {
+            package: i,
+            source,
+            entry,
+        }));
+    }
+
+    bar.set_length(jobs.iter().map(|job| job.entry.size).sum());
+
+    let compression = writer.compression();
+
+    // each batch is compressed by every core while the one before it is written, in order
+    let mut pending = vec![];
+
+    for batch in jobs.chunks(BATCH_SIZE) {
+        let (written, encoded) = rayon::join(
+            || write_batch(&mut writer, &mut packages, pending, output, bar),
+            || {
+                batch
+                    .par_iter()
+                    .map(|job| Ok((job.package, job.encode(compression)?)))
+                    .collect::<Vec<Result<_>>>()
+            },
+        );
+
+        written?;
+        pending = encoded;
+    }
+
+    write_batch(&mut writer, &mut packages, pending, output, bar)?;
+
+    // the loose files are only worth an entry if there are any
+    packages.retain(|package| !package.path.is_empty() || !package.files.is_empty());
+
+    summary.packages = packages.iter().filter(|p| !p.name.is_empty()).count();
+
+    let (header, _) = writer.finish(&Index {
+        packages: packages.clone(),
+    })?;
+
+    let used = HEADER_SIZE
+        + header.index_length
+        + packages
+            .iter()
+            .flat_map(|package| &package.files)
+            .map(|file| file.length)
+            .sum::<u64>();
+
+    Ok((summary, used))
+}
+
+/// How many files are compressed at once, which bounds the memory used by the data waiting to
+/// be written
+const BATCH_SIZE: usize = 1024;
+
+/// A file of a package that changed, to compress and write
+struct Job<'a> {
+    package: usize,
+    source: &'a Source,
+    entry: FileEntry,
+}
+
+impl Job<'_> {
+    fn encode(&self, compression: Compression) -> Result<Encoded> {
+        if self.entry.link.is_some() {
+            return Ok(Encoded {
+                entry: self.entry.clone(),
+                data: vec![],
+            });
+        }
+
+        let data = fs::read(&self.source.absolute).map_err(|e| VoltError::ReadFileError {
+            source: e,
+            name: self.source.absolute.display().to_string(),
+        })?;
+
+        encode(compression, self.entry.path.clone(), self.entry.mode, &data)
+    }
+}
+
+/// Append compressed files to the archive in the order they were found
+fn write_batch<W: Write + Seek>(
+    writer: &mut PackWriter<W>,
+    packages: &mut [PackageEntry],
+    batch: Vec<Result<(usize, Encoded)>>,
+    output: &Path,
+    bar: &ProgressBar,
+) -> Result<()> {
+    for encoded in batch {
+        let (package, encoded) = encoded?;
+        let size = encoded.entry.size;
+
+        let entry = writer
+            .append(encoded)
+            .map_err(|e| VoltError::WriteFileError {
+                source: e,
+                name: output.display().to_string(),
+            })?;
+
+        packages[package].files.push(entry);
+        bar.inc(size);
+    }
+
+    Ok(())
+}
+
+/// A file's entry, without the data which is only read if its package changed
+fn describe(node_modules: &Path, source: &Source) -> Result<FileEntry> {
+    let read_error = |e| VoltError::ReadFileError {
+        source: e,
+        name: source.absolute.display().to_string(),
+    };
+
+    if source.is_link {
+        let target = fs::read_link(&source.absolute).map_err(read_error)?;
+
+        return Ok(FileEntry {
+            path: source.path.clone(),
+            mode: 0o777,
+            link: Some(link_target(node_modules, &source.absolute, &target)),
+            ..FileEntry::default()
+        });
+    }
+
+    Ok(FileEntry {
+        path: source.path.clone(),
+        mode: mode(&source.absolute),
+        size: source
+            .absolute
+            .symlink_metadata()
+            .map_err(read_error)?
+            .len(),
+        ..FileEntry::default()
+    })
+}
+
+/// Whether a package in the archive is the same as the one found in `node_modules`
+///
+/// Packages are immutable once installed, so the same version with the same files of the
+/// same sizes is the same package, without reading any of them.
+fn unchanged(old: &PackageEntry, new: &PackageEntry, files: &[FileEntry]) -> bool {
+    old.name == new.name
+        && old.version == new.version
+        && old.files.len() == files.len()
+        && old.files.iter().zip(files).all(|(old, new)| {
+            old.path == new.path
+                && old.mode == new.mode
+                && old.size == new.size
+                && old.link == new.link
+        })
+}
+
+/// Rewrite an archive without the data its index no longer points to
+pub fn compact(path: &Path) -> Result<Summary> {
+    let mut reader = PackReader::open(path)?;
+    let mut index = reader.index.clone();
+
+    let partial = path.with_extension("pack.partial");
+
+    let write_error = |e| VoltError::WriteFileError {
+        source: e,
+        name: partial.display().to_string(),
+    };
+
+    let file = File::create(&partial).map_err(write_error)?;
+
+    // the data is copied as it is, only the index is compressed again
+    let compression = Compression::new(reader.header.codec, None)?;
+    let mut writer = PackWriter::new(BufWriter::new(file), compression).map_err(write_error)?;
+
+    let mut summary = Summary {
+        packages: index.packages.iter().filter(|p| !p.name.is_empty()).count(),
+        ..Summary::default()
+    };
+
+    for entry in index.packages.iter_mut().flat_map(|p| &mut p.files) {
+        let data = if entry.link.is_some() {
+            vec![]
+        } else {
+            reader.read_raw(entry)?
+        };
+
+        summary.files += 1;
+        summary.size += entry.size;
+
+        *entry = writer
+            .append(Encoded {
+                entry: entry.clone(),
+                data,
+            })
+            .map_err(write_error)?;
+    }
+
+    writer.finish(&index)?;
+    drop(reader);
+
+    fs::rename(&partial, path).map_err(|e| VoltError::WriteFileError {
+        source: e,
+        name: path.display().to_string(),
+    })?;
+
+    summary.packed_size = fs::metadata(path).map_or(0, |metadata| metadata.len());
+
+    Ok(summary)
+}
+
+/// Reads the index of an archive and the files in it
+pub struct PackReader {
+    file: File,
+    path: PathBuf,
+    length: u64,
+    header: Header,
+    index: Index,
+}
+
+impl PackReader {
+    /// Open an archive, checking its header and that its index only points inside of it
+    pub fn open(path: &Path) -> Result<Self> {
+        let read_error = |e| VoltError::ReadFileError {
+            source: e,
+            name: path.display().to_string(),
+        };
+
+        let mut file = File::open(path).map_err(read_error)?;
+        let length = file.metadata().map_err(read_error)?.len();
+
+        let mut bytes = [0; HEADER_SIZE as usize];
+
+        // a file too short for a header isn't an archive either
+        let header = file
+            .read_exact(&mut bytes)
+            .ok()
+            .and_then(|_| Header::from_bytes(&bytes))
+            .ok_or_else(|| {
+                corrupt(
+                    path,
+                    "it isn't an archive, or one written by another version of volt",
+                )
+            })?;
+
+        // an interrupted update leaves data after the index, which is fine
+        if header.index_offset < HEADER_SIZE
+            || header
+                .index_offset
+                .checked_add(header.index_length)
+                .map_or(true, |end| end > length)
+        {
+            return Err(corrupt(path, "the index is outside of the archive").into());
+        }
+
+        file.seek(SeekFrom::Start(header.index_offset))
+            .map_err(read_error)?;
+
+        let mut compressed = vec![0; header.index_length as usize];
+        file.read_exact(&mut compressed).map_err(read_error)?;
+
+        if header.digest(&compressed) != header.index_sha256 {
+            return Err(corrupt(path, "the index doesn't match its hash").into());
+        }
+
+        let index = header
+            .codec
+            .decompress(&compressed, header.index_size)
+            .ok()
+            .and_then(|json| serde_json::from_slice::<Index>(&json).ok())
+            .ok_or_else(|| corrupt(path, "the index can't be read"))?;
+
+        for package in &index.packages {
+            for file in &package.files {
+                let name = format!("{}/{}", package.path, file.path);
+
+                if checked_path(&package.path).is_none() || checked_path(&file.path).is_none() {
+                    return Err(
+                        corrupt(path, format!("`{}` is outside of node_modules", name)).into(),
+                    );
+                }
+
+                let end = file.offset.checked_add(file.length);
+
+                // a link out of node_modules would let the files extracted after it escape too
+                if file.link.as_deref().map_or(false, |target| {
+                    target.is_empty() || checked_path(target).is_none()
+                }) {
+                    return Err(
+                        corrupt(path, format!("`{}` links outside of node_modules", name)).into(),
+                    );
+                }
+
+                if file.link.is_none()
+                    && (file.offset < HEADER_SIZE
+                        || end.map_or(true, |end| end > header.index_offset))
+                {
+                    return Err(corrupt(
+                        path,
+                        format!("the data of `{}` is outside of the archive", name),
+                    )
+                    .into());
+                }
+            }
+        }
+
+        // links are created after the files, so nothing may be extracted through one
+        if let Some(inside) = index.inside_link() {
+            return Err(corrupt(path, format!("`{}` is inside of a link", inside)).into());
+        }
+
+        Ok(Self {
+            file,
+            path: path.to_path_buf(),
+            length,
+            header,
+            index,
+        })
+    }
+
+    pub const fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub const fn index(&self) -> &Index {
+        &self.index
+    }
+
+    /// Bytes of the archive its index doesn't point to, left by updates
+    pub fn garbage(&self) -> u64 {
+        let used = HEADER_SIZE
+            + self.header.index_length
+            + self
+                .index
+                .packages
+                .iter()
+                .flat_map(|package| &package.files)
+                .map(|file| file.length)
+                .sum::<u64>();
+
+        self.length.saturating_sub(used)
+    }
+
+    /// The data of a file as it is stored in the archive
+    pub fn read_raw(&mut self, entry: &FileEntry) -> Result<Vec<u8>> {
+        let read_error = |e| VoltError::ReadFileError {
+            source: e,
+            name: self.path.display().to_string(),
+        };
+
+        self.file
+            .seek(SeekFrom::Start(entry.offset))
+            .map_err(read_error)?;
+
+        let mut compressed = vec![0; entry.length as usize];
+        self.file.read_exact(&mut compressed).map_err(read_error)?;
+
+        Ok(compressed)
+    }
+
+    /// Read and decompress a file, checking it against its hash
+    pub fn read(&mut self, entry: &FileEntry) -> Result<Vec<u8>> {
+        let compressed = self.read_raw(entry)?;
+
+        match self.header.codec.decompress(&compressed, entry.size) {
+            Ok(data)
+                if data.len() as u64 == entry.size
+                    && hex::encode(Sha256::digest(&data)) == entry.sha256 =>
+            {
+                Ok(data)
+            }
+            _ => Err(corrupt(
+                &self.path,
+                format!("`{}` doesn't match its hash", entry.path),
+            )
+            .into()),
+        }
+    }
+
+    /// The packages called `spec`, which is a name or `name@version`
+    ///
+    /// A package installed in several places, like different versions of it, is found in
+    /// each of them.
+    pub fn find(&self, spec: &str) -> Vec<&PackageEntry> {
+        // the `@` of a scope isn't a version
+        let (name, version) = match spec.rfind('@') {
+            Some(at) if at > 0 => (&spec[..at], Some(&spec[at + 1..])),
+            _ => (spec, None),
+        };
+
+        self.index
+            .packages
+            .iter()
+            .filter(|package| {
+                package.name == name && version.map_or(true, |version| package.version == version)
+            })
+            .collect()
+    }
+
+    /// Recreate every package of the archive in `dest`
+    ///
+    /// Links are created relative to the directory they're in, so `dest` can be renamed once
+    /// extracted. Windows junctions need an absolute target though, so they point into `root`,
+    /// where `dest` ends up.
+    pub fn extract(&mut self, dest: &Path, root: &Path, bar: &ProgressBar) -> Result<Summary> {
+        let paths = self
+            .index
+            .packages
+            .iter()
+            .map(|package| package.path.clone())
+            .collect();
+
+        self.extract_packages(&paths, dest, root, bar)
+    }
+
+    /// Recreate the packages at `paths` in `dest`, with the links outside of any package that
+    /// point into them, replacing the files already there
+    ///
+    /// Only the data of those packages is read, however big the archive is.
+    pub fn extract_packages(
+        &mut self,
+        paths: &HashSet<String>,
+        dest: &Path,
+        root: &Path,
+        bar: &ProgressBar,
+    ) -> Result<Summary> {
+        let points_into = |target: &str| {
+            paths.iter().any(|path| {
+                target == path
+                    || (target.starts_with(path.as_str()) && target[path.len()..].starts_with('/'))
+            })
+        };
+
+        let packages = self
+            .index
+            .packages
+            .iter()
+            .filter_map(|package| {
+                if paths.contains(&package.path) {
+                    return Some(package.clone());
+                }
+
+                if !package.path.is_empty() {
+                    return None;
+                }
+
+                // like `node_modules/react` or `node_modules/.bin/tsc`
+                let files = package
+                    .files
+                    .iter()
+                    .filter(|file| file.link.as_deref().map_or(false, points_into))
+                    .cloned()
+                    .collect::<Vec<_>>();
+
+                (!files.is_empty()).then(|| PackageEntry {
+                    files,
+                    ..package.clone()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        bar.set_length(packages.iter().map(|p| p.files.len() as u64).sum());
+
+        let mut summary = Summary {
+            packages: packages.iter().filter(|p| !p.name.is_empty()).count(),
+            packed_size: self.length,
+            ..Summary::default()
+        };
+
+        let mut links = vec![];
+
+        for package in &packages {
+            for entry in &package.files {
+                // both were checked when the archive was opened
+                let relative = checked_path(&package.path)
+                    .unwrap_or_default()
+                    .join(checked_path(&entry.path).unwrap_or_default());
+
+                if let Some(target) = &entry.link {
+                    links.push((relative, target.clone()));
+                    continue;
+                }
+
+                let data = self.read(entry)?;
+                let path = dest.join(&relative);
+
+                self.create_parent(dest, &relative)?;
+
+                // writing through a link left in an existing tree could land anywhere
+                if path
+                    .symlink_metadata()
+                    .map_or(false, |metadata| metadata.file_type().is_symlink())
+                {
+                    remove_link(&path)?;
+                }
+
+                fs::write(&path, &data).map_err(|e| VoltError::WriteFileError {
+                    source: e,
+                    name: path.display().to_string(),
+                })?;
+
+                set_mode(&path, entry.mode)?;
+
+                summary.files += 1;
+                summary.size += entry.size;
+                bar.inc(1);
+            }
+        }
+
+        // the targets of the links exist once every file is extracted
+        for (relative, target) in links {
+            let path = dest.join(&relative);
+
+            self.create_parent(dest, &relative)?;
+
+            if path.symlink_metadata().is_ok() {
+                remove_link(&path)?;
+            }
+
+            create_link(&relative, &target, &path, root)?;
+
+            summary.files += 1;
+            bar.inc(1);
+        }
+
+        Ok(summary)
+    }
+
+    /// Create the directory of a file extracted to `dest`, refusing to follow a link already in
+    /// `dest`, like one left in the tree `--only` extracts into, out of it
+    fn create_parent(&self, dest: &Path, relative: &Path) -> Result<()> {
+        let parent = match dest.join(relative).parent() {
+            Some(parent) => parent.to_path_buf(),
+            None => return Ok(()),
+        };
+
+        fs::create_dir_all(dest).map_err(VoltError::CreateDirError)?;
+
+        let existing = parent
+            .ancestors()
+            .find(|dir| dir.exists())
+            .unwrap_or(dest)
+            .to_path_buf();
+
+        let inside = match (fs::canonicalize(dest), fs::canonicalize(&existing)) {
+            (Ok(dest), Ok(existing)) => existing.starts_with(dest),
+            _ => false,
+        };
+
+        if !inside {
+            return Err(corrupt(
+                &self.path,
+                format!(
+                    "`{}` would be extracted through a link out of node_modules",
+                    slash_path(relative)
+                ),
+            )
+            .into());
+        }
+
+        fs::create_dir_all(&parent).map_err(VoltError::CreateDirError)?;
+
+        Ok(())
+    }
+}
+
+/// The result of checking an archive against its hashes
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Verification {
+    pub path: PathBuf,
+    pub valid: bool,
+    pub version: Option<u16>,
+    pub codec: Option<Codec>,
+    /// Hex SHA-256 of the index, which holds the hash of every file
+    pub archive_hash: Option<String>,
+    pub packages: usize,
+    pub files: usize,
+    pub problems: Vec<Problem>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Problem {
+    /// The file relative to `node_modules`, `None` for the archive itself
+    pub file: Option<String>,
+    pub reason: String,
+}
+
+/// Check the header, the index and every file of an archive against their hashes, reporting
+/// every problem found instead of stopping at the first one
+pub fn verify(path: &Path, bar: &ProgressBar) -> Verification {
+    let mut verification = Verification {
+        path: path.to_path_buf(),
+        valid: false,
+        version: None,
+        codec: None,
+        archive_hash: None,
+        packages: 0,
+        files: 0,
+        problems: vec![],
+    };
+
+    let mut reader = match PackReader::open(path) {
+        Ok(reader) => reader,
+        Err(e) => {
+            verification.problems.push(Problem {
+                file: None,
+                reason: e.to_string(),
+            });
+
+            return verification;
+        }
+    };
+
+    let index = reader.index.clone();
+
+    verification.version = Some(reader.header.version);
+    verification.codec = Some(reader.header.codec);
+    verification.archive_hash = Some(hex::encode(reader.header.index_sha256));
+    verification.packages = index.packages.iter().filter(|p| !p.name.is_empty()).count();
+
+    bar.set_length(index.packages.iter().map(|p| p.files.len() as u64).sum());
+
+    for package in &index.packages {
+        for file in &package.files {
+            verification.files += 1;
+            bar.inc(1);
+
+            if file.link.is_some() {
+                continue;
+            }
+
+            if let Err(e) = reader.read(file) {
+                verification.problems.push(Problem {
+                    file: Some(
+                        [package.path.as_str(), file.path.as_str()]
+                            .iter()
+                            .filter(|part| !part.is_empty())
+                            .copied()
+                            .collect::<Vec<_>>()
+                            .join("/"),
+                    ),
+                    reason: e.to_string(),
+                });
+            }
+        }
+    }
+
+    verification.valid = verification.problems.is_empty();
+
+    verification
+}
+
+fn corrupt(path: &Path, reason: impl Into<String>) -> VoltError {
+    VoltError::PackCorruptError {
+        path: path.display().to_string(),
+        reason: reason.into(),
+    }
+}
+
+/// A path from an archive, `None` if it could escape the directory it's extracted to
+pub fn checked_path(path: &str) -> Option<PathBuf> {
+    let path = Path::new(path);
+
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_)))
+        .then(|| path.to_path_buf())
+}
+
+#[cfg(unix)]
+fn create_link(relative: &Path, target: &str, link: &Path, _root: &Path) -> Result<()> {
+    // up from the directory of the link to node_modules, then down to the target
+    let depth = relative.components().count().saturating_sub(1);
+
+    let target = std::iter::repeat(Path::new(".."))
+        .take(depth)
+        .collect::<PathBuf>()
+        .join(target);
+
+    std::os::unix::fs::symlink(&target, link).map_err(|e| VoltError::WriteFileError {
+        source: e,
+        name: link.display().to_string(),
+    })?;
+
+    Ok(())
+}
+
+#[cfg(windows)]
+fn create_link(_relative: &Path, target: &str, link: &Path, root: &Path) -> Result<()> {
+    junction::create(root.join(target), link).into_diagnostic()
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(mode)).map_err(|e| {
+        VoltError::WriteFileError {
+            source: e,
+            name: path.display().to_string(),
+        }
+    })?;
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_round_trips() {
+        let header = Header {
+            version: VERSION,
+            codec: Codec::Deflate,
+            index_offset: 81_920,
+            index_length: 512,
+            index_size: 4_096,
+            index_sha256: [7; 32],
+        };
+
+        assert_eq!(Header::from_bytes(&header.to_bytes()), Some(header));
+
+        let mut future = header.to_bytes();
+        future[8] = 0xff;
+        assert_eq!(Header::from_bytes(&future), None);
+    }
+
+    #[test]
+    fn codecs_round_trip() {
+        let data = b"module.exports = require('./lib/index.js');\n".repeat(64);
+
+        for codec in Codec::value_variants() {
+            let compression = Compression::new(*codec, None).unwrap();
+            let compressed = compression.compress(&data).unwrap();
+
+            assert_eq!(
+                codec.decompress(&compressed, data.len() as u64).unwrap(),
+                data
+            );
+
+            // a corrupted size is an error rather than an allocation of that size
+            assert!(codec.decompress(&compressed, u64::MAX).is_err());
+        }
+
+        assert!(Compression::new(Codec::Zstd, Some(23)).is_err());
+        assert!(Compression::new(Codec::Store, Some(1)).is_err());
+    }
+
+    #[test]
+    fn finds_package_directories() {
+        let dir = |path: &str| package_dir(Path::new(path)).map(|dir| slash_path(&dir));
+
+        assert_eq!(dir("react/cjs"), Some(String::from("react")));
+        assert_eq!(
+            dir(".volt/react@18.0.0/node_modules/react/cjs"),
+            Some(String::from(".volt/react@18.0.0/node_modules/react"))
+        );
+        assert_eq!(
+            dir("@babel/core/node_modules/debug/src"),
+            Some(String::from("@babel/core/node_modules/debug"))
+        );
+        assert_eq!(dir("@babel/core/lib"), Some(String::from("@babel/core")));
+        assert_eq!(dir(".bin"), None);
+        assert_eq!(dir(".volt/react@18.0.0"), None);
+    }
+
+    #[test]
+    fn rejects_escaping_paths() {
+        assert!(checked_path("react/index.js").is_some());
+        assert!(checked_path("../../etc/passwd").is_none());
+        assert!(checked_path("/etc/passwd").is_none());
+        assert!(checked_path("react/../../x").is_none());
+    }
+
+    #[test]
+    fn finds_entries_inside_of_links() {
+        let entry = |path: &str, link: Option<&str>| FileEntry {
+            path: path.to_string(),
+            link: link.map(String::from),
+            ..FileEntry::default()
+        };
+
+        let mut index = Index {
+            packages: vec![PackageEntry {
+                files: vec![entry("k/l", Some("m")), entry("react", Some("k/l"))],
+                ..PackageEntry::default()
+            }],
+        };
+
+        assert_eq!(index.inside_link(), None);
+
+        index.packages[0].files.push(entry("k/l/s/t", Some("x")));
+        assert_eq!(index.inside_link(), Some(String::from("k/l/s/t")));
+
+        index.packages[0].files.pop();
+        index.packages.push(PackageEntry {
+            path: String::from("k/l/s"),
+            files: vec![entry("index.js", None)],
+            ..PackageEntry::default()
+        });
+        assert_eq!(index.inside_link(), Some(String::from("k/l/s/index.js")));
+    }
+}
@@ -0,0 +1,312 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Progress of an install, broken down by phase.
+
+use crate::{
+    cli::VoltConfig,
+    core::{
+        events::{Event, Events},
+        utils::voltapi::VoltPackage,
+    },
+};
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use terminal_size::{terminal_size, Width};
+
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// Terminals narrower than this get a single line instead of a line per phase
+const COMPACT_WIDTH: u16 = 80;
+
+/// How often a status line is printed in CI, where the bars are hidden
+const STATUS_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy)]
+pub enum Phase {
+    Resolving,
+    Downloading,
+    Extracting,
+    Linking,
+    /// Steps after a package is linked, like applying patches
+    Building,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Resolving => write!(f, "resolving"),
+            Self::Downloading => write!(f, "downloading"),
+            Self::Extracting => write!(f, "extracting"),
+            Self::Linking => write!(f, "linking"),
+            Self::Building => write!(f, "building"),
+        }
+    }
+}
+
+/// An overall bar with a counter for every phase below it, or only the overall bar with the
+/// current phase in its message on narrow terminals.
+///
+/// In CI the bars are replaced by a plain status line every `STATUS_INTERVAL`.
+///
+/// Cloning is cheap and every clone updates the same display, so it can be handed to the
+/// tasks installing packages.
+#[derive(Clone)]
+pub struct InstallProgress {
+    overall: ProgressBar,
+    resolving: ProgressBar,
+    /// Counts bytes, so the bar can show the aggregate download speed
+    downloading: ProgressBar,
+    extracting: ProgressBar,
+    linking: ProgressBar,
+    building: ProgressBar,
+    downloads: Arc<AtomicUsize>,
+    compact: bool,
+    /// Print status lines instead, set in CI
+    plain: bool,
+    installed: Arc<AtomicUsize>,
+    total: Arc<AtomicUsize>,
+    last_status: Arc<Mutex<Instant>>,
+    events: Events,
+}
+
+impl InstallProgress {
+    pub fn new(config: &VoltConfig) -> Self {
+        let multi = MultiProgress::new();
+
+        if !config.show_progress() {
+            multi.set_draw_target(ProgressDrawTarget::hidden());
+        }
+
+        let compact = terminal_size().map_or(false, |(Width(width), _)| width < COMPACT_WIDTH);
+
+        let overall = multi.add(
+            ProgressBar::new(0).with_style(
+                ProgressStyle::default_bar()
+                    .template("{prefix:>11.bold} [{bar:30.cyan/blue}] {pos}/{len} {wide_msg}")
+                    .progress_chars("=>-"),
+            ),
+        );
+
+        overall.set_prefix("installing");
+
+        let phase = |phase: Phase, template: &str| {
+            let bar = if compact {
+                ProgressBar::hidden()
+            } else {
+                multi.add(
+                    ProgressBar::new(0).with_style(ProgressStyle::default_bar().template(template)),
+                )
+            };
+
+            bar.set_prefix(phase.to_string());
+            bar
+        };
+
+        let counter = "{prefix:>11.cyan.bold} {pos}/{len} {wide_msg}";
+
+        Self {
+            resolving: phase(
+                Phase::Resolving,
+                "{prefix:>11.cyan.bold} {spinner:.cyan} {wide_msg}",
+            ),
+            downloading: phase(
+                Phase::Downloading,
+                "{prefix:>11.cyan.bold} {msg} {bytes} ({binary_bytes_per_sec})",
+            ),
+            extracting: phase(Phase::Extracting, counter),
+            linking: phase(Phase::Linking, counter),
+            building: phase(Phase::Building, counter),
+            overall,
+            downloads: Arc::default(),
+            compact,
+            plain: config.ci().is_some() && !config.quiet(),
+            installed: Arc::default(),
+            total: Arc::default(),
+            last_status: Arc::new(Mutex::new(Instant::now())),
+            events: config.events().clone(),
+        }
+    }
+
+    /// The bar resolution reports the packages it fetches on
+    pub fn resolving(&self) -> &ProgressBar {
+        let bar = if self.compact {
+            self.overall.set_prefix(Phase::Resolving.to_string());
+            &self.overall
+        } else {
+            &self.resolving
+        };
+
+        bar.enable_steady_tick(100);
+        bar
+    }
+
+    /// Resolution is done, with `total` packages in the tree
+    pub fn resolved(&self, total: usize) {
+        self.resolving
+            .finish_with_message(format!("{} packages", total));
+
+        if self.compact {
+            self.overall.disable_steady_tick();
+            self.overall.set_prefix("installing");
+            self.overall.set_message("");
+        }
+    }
+
+    /// `total` packages are about to be downloaded or copied from the store
+    pub fn installing(&self, total: usize) {
+        // installs of a lock file skip resolution
+        self.resolving.finish();
+
+        for bar in [&self.overall, &self.extracting, &self.linking] {
+            bar.set_length(total as u64);
+        }
+
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    pub fn downloaded(&self, package: &VoltPackage, bytes: u64) {
+        let count = self.downloads.fetch_add(1, Ordering::Relaxed) + 1;
+
+        self.downloading.inc(bytes);
+        self.downloading.set_message(format!("{} tarballs", count));
+
+        self.events.emit(Event::PackageFetched {
+            name: &package.name,
+            version: &package.version,
+            bytes,
+            cached: false,
+        });
+
+        self.report(Phase::Downloading, package);
+    }
+
+    /// How many tarballs were downloaded, the rest came from the store
+    pub fn downloads(&self) -> usize {
+        self.downloads.load(Ordering::Relaxed)
+    }
+
+    pub fn downloaded_bytes(&self) -> u64 {
+        self.downloading.position()
+    }
+
+    /// The package was found in the store, so it wasn't downloaded
+    pub fn restored(&self, package: &VoltPackage) {
+        self.events.emit(Event::PackageFetched {
+            name: &package.name,
+            version: &package.version,
+            bytes: 0,
+            cached: true,
+        });
+    }
+
+    pub fn extracted(&self, package: &VoltPackage) {
+        self.extracting.inc(1);
+        self.report(Phase::Extracting, package);
+
+        self.events.emit(Event::ExtractProgress {
+            name: &package.name,
+            version: &package.version,
+            extracted: self.extracting.position(),
+            total: self.total.load(Ordering::Relaxed) as u64,
+        });
+    }
+
+    /// The package is linked into node_modules, which completes it
+    pub fn linked(&self, package: &VoltPackage) {
+        self.linking.inc(1);
+        self.overall.inc(1);
+        self.installed.fetch_add(1, Ordering::Relaxed);
+        self.report(Phase::Linking, package);
+    }
+
+    /// `total` packages need a step after linking
+    pub fn building(&self, total: usize) {
+        self.building.set_length(total as u64);
+    }
+
+    pub fn built(&self, package: &VoltPackage) {
+        self.building.inc(1);
+        self.report(Phase::Building, package);
+    }
+
+    /// Run `f` with the bars cleared, so lines printed by it aren't drawn over
+    pub fn suspend<F: FnOnce() -> R, R>(&self, f: F) -> R {
+        self.overall.suspend(f)
+    }
+
+    pub fn finish(&self) {
+        for bar in [
+            &self.resolving,
+            &self.downloading,
+            &self.extracting,
+            &self.linking,
+            &self.building,
+            &self.overall,
+        ] {
+            bar.finish_and_clear();
+        }
+    }
+
+    /// Show the last package a phase got to, on the single line when compact
+    fn report(&self, phase: Phase, package: &VoltPackage) {
+        if self.plain {
+            self.print_status(phase, package);
+        }
+
+        if self.compact {
+            self.overall
+                .set_message(format!("{} {}", phase, package.key()));
+            return;
+        }
+
+        match phase {
+            Phase::Extracting => self.extracting.set_message(package.key()),
+            Phase::Linking => self.linking.set_message(package.key()),
+            Phase::Building => self.building.set_message(package.key()),
+            _ => {}
+        }
+    }
+
+    /// Print where the install is at, unless a line was printed in the last `STATUS_INTERVAL`
+    fn print_status(&self, phase: Phase, package: &VoltPackage) {
+        let mut last_status = match self.last_status.lock() {
+            Ok(last_status) => last_status,
+            Err(_) => return,
+        };
+
+        if last_status.elapsed() < STATUS_INTERVAL {
+            return;
+        }
+
+        *last_status = Instant::now();
+
+        eprintln!(
+            "{}/{} packages installed, {} {}",
+            self.installed.load(Ordering::Relaxed),
+            self.total.load(Ordering::Relaxed),
+            phase,
+            package.key()
+        );
+    }
+}
@@ -14,10 +14,38 @@
     limitations under the License.
 */
 
-use crate::core::prompt::input;
+use crate::{cli::VoltConfig, core::prompt::input};
 
 use dialoguer::{console, theme::ColorfulTheme};
-use std::{borrow::Cow, io::Result};
+use std::{
+    borrow::Cow,
+    io::{Error, ErrorKind, Result},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Whether volt runs in CI, resolved from the configuration by [`set_ci`]
+static IN_CI: AtomicBool = AtomicBool::new(false);
+
+/// Never prompt for the rest of the run when `config` is in CI, set once the configuration is
+/// loaded
+pub fn set_ci(config: &VoltConfig) {
+    IN_CI.store(config.ci().is_some(), Ordering::Relaxed);
+}
+
+/// Nobody can answer a prompt in CI, so fail instead of waiting until the job times out
+fn ensure_interactive(message: &str) -> Result<()> {
+    if IN_CI.load(Ordering::Relaxed) {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!(
+                "\"{}\" needs an answer, but volt never prompts in CI",
+                message.trim_end_matches(|c: char| c == ':' || c == '?' || c.is_whitespace())
+            ),
+        ));
+    }
+
+    Ok(())
+}
 
 /// Prompt that returns `true` or `false` (as strings)
 #[derive(Debug)]
@@ -35,6 +63,8 @@ pub struct Confirm<'i> {
 
 impl<'i> Confirm<'i> {
     pub fn run(&self) -> Result<bool> {
+        ensure_interactive(&self.message)?;
+
         let theme = ColorfulTheme {
             defaults_style: console::Style::new(),
             prompt_style: console::Style::new(),
@@ -81,6 +111,8 @@ pub struct Input<'i> {
 
 impl Input<'_> {
     pub fn run(&self) -> Result<String> {
+        ensure_interactive(&self.message)?;
+
         let theme = ColorfulTheme {
             defaults_style: console::Style::new(),
             prompt_style: console::Style::new(),
@@ -137,6 +169,8 @@ pub struct Secret<'i> {
 impl<'i> Secret<'i> {
     #[allow(dead_code)]
     pub fn run(&self) -> Result<String> {
+        ensure_interactive(&self.message)?;
+
         let theme = ColorfulTheme::default();
         let mut input = dialoguer::Password::with_theme(&theme);
 
@@ -178,6 +212,8 @@ impl<'i> Select<'i> {
             return Ok(0);
         }
 
+        ensure_interactive(&self.message)?;
+
         let theme = ColorfulTheme {
             defaults_style: console::Style::new(),
             prompt_style: console::Style::new().bold(),
@@ -213,3 +249,57 @@ impl<'i> Select<'i> {
         input.interact()
     }
 }
+
+/// Prompt that allows the user to check any number of items from a list
+#[derive(Debug)]
+pub struct MultiSelect<'i> {
+    /// Message for the prompt
+    pub message: Cow<'i, str>,
+
+    /// Items that can be selected, along with whether they are checked by default
+    pub items: Vec<(Cow<'i, str>, bool)>,
+}
+
+impl<'i> MultiSelect<'i> {
+    pub fn run(&self) -> Result<Vec<usize>> {
+        if self.items.is_empty() {
+            return Ok(vec![]);
+        }
+
+        ensure_interactive(&self.message)?;
+
+        let theme = ColorfulTheme {
+            defaults_style: console::Style::new(),
+            prompt_style: console::Style::new().bold(),
+            prompt_prefix: console::style(String::from("?")).yellow().bright(),
+            prompt_suffix: console::style(String::from(">")).blue().dim(),
+            success_prefix: console::style(String::from("✔")).green().bright(),
+            success_suffix: console::style(String::from("·")).blue().dim(),
+            error_prefix: console::style(String::from("❌")).bright().red(),
+            error_style: console::Style::new(),
+            hint_style: console::Style::new().bold(),
+            values_style: console::Style::new(),
+            active_item_style: console::Style::new().cyan(),
+            inactive_item_style: console::Style::new(),
+            active_item_prefix: console::style(String::from("❯")).cyan(),
+            inactive_item_prefix: console::style(String::from(" ")),
+            checked_item_prefix: console::style(String::from("◉")).green(),
+            unchecked_item_prefix: console::style(String::from("◯")),
+            picked_item_prefix: console::style(String::from("")),
+            unpicked_item_prefix: console::style(String::from("")),
+            inline_selections: false,
+        };
+
+        let mut input = dialoguer::MultiSelect::with_theme(&theme);
+
+        input.with_prompt(self.message.clone()).items_checked(
+            &self
+                .items
+                .iter()
+                .map(|(item, checked)| (item.as_ref(), *checked))
+                .collect::<Vec<_>>(),
+        );
+
+        input.interact()
+    }
+}
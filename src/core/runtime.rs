@@ -0,0 +1,544 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Node.js runtimes downloaded from nodejs.org into `~/.volt/node`.
+//!
+//! A project pins its runtime with a `.node-version` file, or the `node` range in the `engines`
+//! of its package.json. The scripts volt runs get the newest installed version matching it
+//! first on their `PATH`, and projects that don't pin one get the default set with
+//! `volt node use --default`.
+
+use crate::{
+    cli::VoltConfig,
+    core::utils::{decompress_gzip, errors::VoltError, package::PackageJson},
+};
+
+use colored::Colorize;
+use miette::{IntoDiagnostic, Result};
+use node_semver::{Range, Version};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+    sync::Once,
+};
+
+pub const MIRROR: &str = "https://nodejs.org/dist";
+
+/// The file pinning the runtime of a project
+pub const VERSION_FILE: &str = ".node-version";
+
+/// The file in `~/.volt/node` holding the version used outside of pinned projects
+const DEFAULT_FILE: &str = "default";
+
+/// The file in an installed runtime's directory describing the release it came from
+const RELEASE_FILE: &str = "volt-release.json";
+
+/// The runtime is looked up several times per command, its warnings are only printed once
+static UNREADABLE_PIN_WARNING: Once = Once::new();
+static MISSING_PIN_WARNING: Once = Once::new();
+
+/// A release listed in the mirror's `index.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Release {
+    /// Like `v18.12.1`
+    pub version: String,
+    /// The codename of LTS releases, `false` for the others
+    #[serde(default)]
+    pub lts: serde_json::Value,
+    /// The builds published for the release, like `linux-x64` or `win-x64-exe`
+    #[serde(default)]
+    pub files: Vec<String>,
+}
+
+impl Release {
+    pub fn semver(&self) -> Option<Version> {
+        self.version.trim_start_matches('v').parse().ok()
+    }
+
+    pub fn lts(&self) -> Option<&str> {
+        self.lts.as_str()
+    }
+}
+
+/// A runtime in `~/.volt/node`
+#[derive(Debug, Clone, Serialize)]
+pub struct Installed {
+    pub version: Version,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lts: Option<String>,
+    pub dir: PathBuf,
+}
+
+impl Installed {
+    /// The directory holding the `node` executable
+    pub fn bin_dir(&self) -> PathBuf {
+        if cfg!(windows) {
+            self.dir.clone()
+        } else {
+            self.dir.join("bin")
+        }
+    }
+}
+
+/// The version a project asks for and the file asking for it
+#[derive(Debug, Clone, Serialize)]
+pub struct Pin {
+    pub spec: String,
+    pub source: PathBuf,
+}
+
+/// The versions a `.node-version`, `engines` range or command line argument asks for
+#[derive(Debug, Clone)]
+pub enum Spec {
+    Version(Version),
+    Range(Range),
+    /// Any LTS release, or the ones with a codename
+    Lts(Option<String>),
+}
+
+impl FromStr for Spec {
+    type Err = VoltError;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let trimmed = spec.trim();
+
+        // `v18.12.1` like the releases are named
+        let trimmed = match trimmed.strip_prefix('v') {
+            Some(rest) if rest.starts_with(|c: char| c.is_ascii_digit()) => rest,
+            _ => trimmed,
+        };
+
+        match trimmed.to_lowercase().as_str() {
+            "lts" | "lts/*" => return Ok(Self::Lts(None)),
+            "node" | "latest" | "current" => return Ok(Self::Range(Range::any())),
+            lowercase => {
+                if let Some(codename) = lowercase.strip_prefix("lts/") {
+                    return Ok(Self::Lts(Some(codename.to_string())));
+                }
+            }
+        }
+
+        if let Ok(version) = trimmed.parse::<Version>() {
+            return Ok(Self::Version(version));
+        }
+
+        trimmed
+            .parse::<Range>()
+            .map(Self::Range)
+            .map_err(|_| VoltError::NodeVersionError {
+                spec: spec.to_string(),
+            })
+    }
+}
+
+impl Spec {
+    pub fn matches(&self, version: &Version, lts: Option<&str>) -> bool {
+        match self {
+            Self::Version(wanted) => wanted == version,
+            Self::Range(range) => version.satisfies(range),
+            Self::Lts(None) => lts.is_some(),
+            Self::Lts(Some(codename)) => {
+                lts.map_or(false, |lts| lts.eq_ignore_ascii_case(codename))
+            }
+        }
+    }
+}
+
+/// The platform as nodejs.org names it, like `linux` and `x64`
+pub fn platform() -> Result<(&'static str, &'static str)> {
+    let os = match std::env::consts::OS {
+        "linux" => Some("linux"),
+        "macos" => Some("darwin"),
+        "windows" => Some("win"),
+        _ => None,
+    };
+
+    let arch = match std::env::consts::ARCH {
+        "x86_64" => Some("x64"),
+        "aarch64" => Some("arm64"),
+        "x86" => Some("x86"),
+        "arm" => Some("armv7l"),
+        "powerpc64" => Some("ppc64le"),
+        "s390x" => Some("s390x"),
+        _ => None,
+    };
+
+    match (os, arch) {
+        (Some(os), Some(arch)) => Ok((os, arch)),
+        _ => Err(VoltError::NodePlatformError {
+            platform: format!("{} {}", std::env::consts::OS, std::env::consts::ARCH),
+        }
+        .into()),
+    }
+}
+
+/// How the release's build for this platform is named in `index.json`
+fn build_name(os: &str, arch: &str) -> String {
+    match os {
+        "darwin" => format!("osx-{}-tar", arch),
+        "win" => format!("win-{}-exe", arch),
+        _ => format!("{}-{}", os, arch),
+    }
+}
+
+/// The file downloaded for a release, relative to its directory on the mirror
+///
+/// Windows only needs `node.exe`, other platforms get the whole tarball.
+fn download_name(version: &Version, os: &str, arch: &str) -> String {
+    match os {
+        "win" => format!("win-{}/node.exe", arch),
+        _ => format!("node-v{}-{}-{}.tar.gz", version, os, arch),
+    }
+}
+
+/// Every release on the mirror, newest first
+pub async fn releases(client: &Client) -> Result<Vec<Release>> {
+    client
+        .get(format!("{}/index.json", MIRROR))
+        .send()
+        .await
+        .into_diagnostic()?
+        .error_for_status()
+        .into_diagnostic()?
+        .json()
+        .await
+        .into_diagnostic()
+}
+
+/// The newest release matching `spec` that's published for this platform
+pub fn resolve<'a>(releases: &'a [Release], spec: &str) -> Result<&'a Release> {
+    let (os, arch) = platform()?;
+    let build = build_name(os, arch);
+    let parsed = spec.parse::<Spec>()?;
+
+    releases
+        .iter()
+        .filter(|release| release.files.contains(&build))
+        .filter_map(|release| Some((release.semver()?, release)))
+        .filter(|(version, release)| parsed.matches(version, release.lts()))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, release)| release)
+        .ok_or_else(|| {
+            VoltError::NodeReleaseNotFoundError {
+                spec: spec.to_string(),
+                platform: format!("{}-{}", os, arch),
+            }
+            .into()
+        })
+}
+
+/// The runtimes in `~/.volt/node`, newest first
+pub fn installed(config: &VoltConfig) -> Result<Vec<Installed>> {
+    let mut installed = fs::read_dir(config.node_dir()?)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let dir = entry.path();
+            let version = entry.file_name().to_str()?.parse::<Version>().ok()?;
+
+            let lts = fs::read_to_string(dir.join(RELEASE_FILE))
+                .ok()
+                .and_then(|data| serde_json::from_str::<Release>(&data).ok())
+                .and_then(|release| release.lts().map(String::from));
+
+            Some(Installed { version, lts, dir })
+        })
+        .collect::<Vec<_>>();
+
+    installed.sort_by(|a, b| b.version.cmp(&a.version));
+
+    Ok(installed)
+}
+
+/// The newest installed runtime matching `spec`
+pub fn find_installed(config: &VoltConfig, spec: &str) -> Result<Option<Installed>> {
+    let parsed = spec.parse::<Spec>()?;
+
+    Ok(installed(config)?
+        .into_iter()
+        .find(|runtime| parsed.matches(&runtime.version, runtime.lts.as_deref())))
+}
+
+/// Download a release, check it against the mirror's checksums and install it in
+/// `~/.volt/node/<version>`
+pub async fn install(config: &VoltConfig, client: &Client, release: &Release) -> Result<Installed> {
+    let version = release
+        .semver()
+        .ok_or_else(|| VoltError::NodeVersionError {
+            spec: release.version.clone(),
+        })?;
+
+    let node_dir = config.node_dir()?;
+    let dir = node_dir.join(version.to_string());
+
+    if !dir.exists() {
+        let (os, arch) = platform()?;
+        let name = download_name(&version, os, arch);
+
+        let base = format!("{}/{}", MIRROR, release.version);
+
+        let get = |url: String| async move {
+            client
+                .get(url)
+                .send()
+                .await
+                .into_diagnostic()?
+                .error_for_status()
+                .into_diagnostic()?
+                .bytes()
+                .await
+                .into_diagnostic()
+        };
+
+        let checksums = get(format!("{}/SHASUMS256.txt", base)).await?;
+        let data = get(format!("{}/{}", base, name)).await?;
+
+        // `<sha256>  <file>` on every line
+        let expected = String::from_utf8_lossy(&checksums)
+            .lines()
+            .filter_map(|line| line.split_once("  "))
+            .find(|(_, file)| *file == name)
+            .map(|(checksum, _)| checksum.to_string());
+
+        if expected.as_deref() != Some(hex::encode(Sha256::digest(&data)).as_str()) {
+            return Err(VoltError::NodeChecksumError { file: name }.into());
+        }
+
+        let staging = node_dir.join(format!(".{}.partial", version));
+
+        if staging.exists() {
+            fs::remove_dir_all(&staging).map_err(|e| VoltError::WriteFileError {
+                source: e,
+                name: staging.display().to_string(),
+            })?;
+        }
+
+        fs::create_dir_all(&staging).map_err(VoltError::CreateDirError)?;
+
+        // the tarball holds a `node-v18.12.1-linux-x64` directory
+        let extracted = if os == "win" {
+            fs::write(staging.join("node.exe"), &data).map_err(|e| VoltError::WriteFileError {
+                source: e,
+                name: staging.display().to_string(),
+            })?;
+
+            staging.clone()
+        } else {
+            tar::Archive::new(decompress_gzip(&data)?.as_slice())
+                .unpack(&staging)
+                .into_diagnostic()?;
+
+            staging.join(name.trim_end_matches(".tar.gz"))
+        };
+
+        fs::write(
+            extracted.join(RELEASE_FILE),
+            serde_json::to_vec(release).into_diagnostic()?,
+        )
+        .map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: extracted.display().to_string(),
+        })?;
+
+        fs::rename(&extracted, &dir).map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: dir.display().to_string(),
+        })?;
+
+        let _ = fs::remove_dir_all(&staging);
+    }
+
+    Ok(Installed {
+        version,
+        lts: release.lts().map(String::from),
+        dir,
+    })
+}
+
+/// The version pinned by the project in `dir`, from the closest `.node-version` or package.json
+/// with a `node` engine
+pub fn pinned(dir: &Path) -> Option<Pin> {
+    for parent in dir.ancestors() {
+        let version_file = parent.join(VERSION_FILE);
+
+        if let Ok(data) = fs::read_to_string(&version_file) {
+            if let Some(spec) = data.lines().map(str::trim).find(|line| !line.is_empty()) {
+                return Some(Pin {
+                    spec: spec.to_string(),
+                    source: version_file,
+                });
+            }
+        }
+
+        let package_file = parent.join("package.json");
+
+        if let Ok(package) = PackageJson::read(&package_file) {
+            if let Some(spec) = package
+                .engines
+                .and_then(|mut engines| engines.remove("node"))
+            {
+                return Some(Pin {
+                    spec,
+                    source: package_file,
+                });
+            }
+        }
+    }
+
+    None
+}
+
+/// The version used outside of pinned projects
+pub fn default_version(config: &VoltConfig) -> Result<Option<String>> {
+    Ok(fs::read_to_string(config.node_dir()?.join(DEFAULT_FILE))
+        .ok()
+        .map(|data| data.trim().to_string())
+        .filter(|spec| !spec.is_empty()))
+}
+
+pub fn set_default_version(config: &VoltConfig, spec: Option<&str>) -> Result<()> {
+    let path = config.node_dir()?.join(DEFAULT_FILE);
+
+    let written = match spec {
+        Some(spec) => fs::create_dir_all(config.node_dir()?)
+            .and_then(|_| fs::write(&path, format!("{}\n", spec))),
+        None if path.exists() => fs::remove_file(&path),
+        None => Ok(()),
+    };
+
+    written.map_err(|e| VoltError::WriteFileError {
+        source: e,
+        name: path.display().to_string(),
+    })?;
+
+    Ok(())
+}
+
+/// The runtime the project in `dir` runs with, `None` to use the `node` on the `PATH`
+pub fn selected(config: &VoltConfig, dir: &Path) -> Result<Option<Installed>> {
+    let spec = match pinned(dir) {
+        // a pin volt can't read is ignored, so a bad engines field doesn't break every command
+        Some(pin) if pin.spec.parse::<Spec>().is_err() => {
+            UNREADABLE_PIN_WARNING.call_once(|| {
+                eprintln!(
+                    "{}: {} asks for node {}, which isn't a version or range, ignoring it",
+                    "warning".bright_yellow().bold(),
+                    pin.source.display(),
+                    pin.spec
+                )
+            });
+
+            default_version(config)?
+        }
+        Some(pin) => {
+            let runtime = find_installed(config, &pin.spec)?;
+
+            // only the runtimes volt manages are used, a pin they don't satisfy is only a warning
+            if runtime.is_none() && !installed(config)?.is_empty() {
+                MISSING_PIN_WARNING.call_once(|| {
+                    eprintln!(
+                        "{}: {} asks for node {}, which isn't installed, run {}",
+                        "warning".bright_yellow().bold(),
+                        pin.source.display(),
+                        pin.spec,
+                        "volt node install".bright_cyan()
+                    )
+                });
+            }
+
+            return Ok(runtime);
+        }
+        None => default_version(config)?,
+    };
+
+    match spec {
+        Some(spec) => find_installed(config, &spec),
+        None => Ok(None),
+    }
+}
+
+/// The version of the node that runs in `dir`, the project's runtime or the one on the `PATH`
+pub fn node_version(config: &VoltConfig, dir: &Path) -> Result<Option<Version>> {
+    if let Some(runtime) = selected(config, dir)? {
+        return Ok(Some(runtime.version));
+    }
+
+    let output = match Command::new("node").arg("--version").output() {
+        Ok(output) if output.status.success() => output,
+        _ => return Ok(None),
+    };
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .trim_start_matches('v')
+        .parse()
+        .ok())
+}
+
+/// The `PATH` for a command run by volt in `dir`: `first`, then the project's runtime, then the
+/// `PATH` volt was run with
+pub fn search_path(config: &VoltConfig, dir: &Path, first: &[PathBuf]) -> Result<OsString> {
+    let mut paths = first.to_vec();
+
+    if let Some(runtime) = selected(config, dir)? {
+        paths.push(runtime.bin_dir());
+    }
+
+    if let Some(path) = std::env::var_os("PATH") {
+        paths.extend(std::env::split_paths(&path));
+    }
+
+    std::env::join_paths(paths).into_diagnostic()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_specs() {
+        let version = |v: &str| v.parse::<Version>().unwrap();
+
+        let exact = "v18.12.1".parse::<Spec>().unwrap();
+        assert!(exact.matches(&version("18.12.1"), None));
+        assert!(!exact.matches(&version("18.12.0"), None));
+
+        let major = "18".parse::<Spec>().unwrap();
+        assert!(major.matches(&version("18.0.0"), None));
+        assert!(!major.matches(&version("19.0.0"), None));
+
+        let engines = ">=16 <19".parse::<Spec>().unwrap();
+        assert!(engines.matches(&version("16.20.0"), None));
+
+        let lts = "lts/hydrogen".parse::<Spec>().unwrap();
+        assert!(lts.matches(&version("18.12.1"), Some("Hydrogen")));
+        assert!(!lts.matches(&version("16.20.0"), Some("Gallium")));
+        assert!("lts/*"
+            .parse::<Spec>()
+            .unwrap()
+            .matches(&version("16.20.0"), Some("Gallium")));
+
+        assert!("banana".parse::<Spec>().is_err());
+    }
+}
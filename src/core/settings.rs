@@ -0,0 +1,161 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Project settings read from `volt.toml`, or `.voltrc` for projects that prefer a dotfile.
+
+use miette::Result;
+use serde::{Deserialize, Serialize};
+
+use std::{
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+use crate::core::{
+    hooks::Hooks,
+    model::{audit::AuditException, policy::Policy, signature::SignatureMode},
+    utils::errors::VoltError,
+};
+
+/// Settings for a project, read from the `volt.toml` next to its package.json
+///
+/// The file is meant to be committed, and can also pin any option from `volt config list`,
+/// which then applies to everyone working on the project.
+///
+/// ```toml
+/// registry = "https://registry.example.com"
+/// registry-signatures = "warn"
+///
+/// [[audit-exceptions]]
+/// id = 1067342
+/// package = "minimist"
+/// expires = 2022-12-31
+/// reason = "only reachable from build tooling"
+///
+/// [policy]
+/// deny-deprecated = true
+///
+/// [hooks]
+/// post-resolve = "node scripts/check-licenses.js"
+///
+/// [unused]
+/// ignore = ["prettier", "eslint-*"]
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct Settings {
+    /// Advisories that have been accepted and shouldn't fail `volt audit`
+    pub audit_exceptions: Vec<AuditException>,
+
+    /// Rules restricting which packages can be installed
+    pub policy: Policy,
+
+    /// Only request versions published at least this many days ago
+    pub minimum_release_age: Option<u32>,
+
+    /// Packages exempt from `minimum-release-age`, either names or prefixes ending in `*`
+    pub minimum_release_age_exclude: Vec<String>,
+
+    /// Whether a missing or invalid registry signature fails the install (`fail`), only
+    /// prints a warning (`warn`) or isn't checked (`off`, the default)
+    pub registry_signatures: SignatureMode,
+
+    /// Commands run around the phases of an install, see [`Hooks`]
+    pub hooks: Hooks,
+
+    /// Files `volt compress` removes from packages besides the built-in ones
+    pub compress: CompressSettings,
+
+    /// Dependencies `volt unused` doesn't report, see [`UnusedSettings`]
+    pub unused: UnusedSettings,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct CompressSettings {
+    /// Extra patterns to remove, like `*.map`
+    pub remove: Vec<String>,
+
+    /// Patterns to keep even though they match a removable one, like `README.md`
+    pub keep: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct UnusedSettings {
+    /// Packages used without being imported, like CLI tools run from config files, either
+    /// names or prefixes ending in `*`
+    pub ignore: Vec<String>,
+}
+
+impl UnusedSettings {
+    /// Whether `volt unused` skips a package
+    pub fn ignores(&self, name: &str) -> bool {
+        matches_any(&self.ignore, name)
+    }
+}
+
+impl Settings {
+    pub const FILE_NAME: &'static str = "volt.toml";
+    pub const RC_FILE_NAME: &'static str = ".voltrc";
+
+    /// The settings file of the project in `dir`, `volt.toml` unless there's only a `.voltrc`
+    pub fn path(dir: &Path) -> PathBuf {
+        let rc = dir.join(Self::RC_FILE_NAME);
+
+        if rc.exists() && !dir.join(Self::FILE_NAME).exists() {
+            rc
+        } else {
+            dir.join(Self::FILE_NAME)
+        }
+    }
+
+    /// Whether `minimum-release-age` applies to a package
+    pub fn release_age_applies(&self, name: &str) -> bool {
+        !matches_any(&self.minimum_release_age_exclude, name)
+    }
+
+    /// Load the settings in `dir`, or the defaults if it has no settings file
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = Self::path(dir);
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let data = read_to_string(&path).map_err(|e| VoltError::ReadFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
+
+        Ok(
+            toml::from_str(&data).map_err(|e| VoltError::SettingsParseError {
+                source: e,
+                name: path.display().to_string(),
+            })?,
+        )
+    }
+}
+
+/// Whether a package name matches one of `patterns`, which are names or prefixes ending in `*`
+fn matches_any(patterns: &[String], name: &str) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == pattern,
+        })
+}
@@ -0,0 +1,250 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Anonymous usage statistics, which are only collected after `volt telemetry enable`.
+//!
+//! Each run records the command name, how long it took, the share of packages copied from the
+//! store, the OS and architecture and the code of the error it failed with. Arguments, package
+//! names and paths are never recorded. Records are queued in `~/.volt/telemetry` and uploaded
+//! in batches, so most runs don't make a request at all.
+
+use crate::{cli::VoltConfig, core::utils::errors::VoltError};
+
+use colored::Colorize;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+/// Where batches of records are uploaded
+pub const ENDPOINT: &str = "https://telemetry.voltpkg.com/v1/batch";
+
+/// How many records are queued before they're uploaded
+pub const BATCH_SIZE: usize = 20;
+
+/// How many records are kept while uploads fail, the oldest are dropped past it
+const MAX_QUEUED: usize = 5 * BATCH_SIZE;
+
+/// How long to wait after a failed upload before trying again
+const RETRY_AFTER: Duration = Duration::from_secs(60 * 60);
+
+const QUEUE_FILE: &str = "queue.ndjson";
+const NOTICE_FILE: &str = "notice-shown";
+/// Written when an upload fails, so later runs wait [`RETRY_AFTER`] before trying again
+const FAILED_FILE: &str = "upload-failed";
+
+lazy_static! {
+    /// Packages copied from the store and downloaded by the install of this run, if any
+    static ref CACHE: Mutex<Option<(usize, usize)>> = Mutex::new(None);
+}
+
+/// What's recorded about a run
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Record {
+    pub volt_version: String,
+    pub command: String,
+    pub duration_ms: u128,
+    /// Between 0 and 1, only for commands that install packages
+    pub cache_hit_rate: Option<f64>,
+    pub os: String,
+    pub arch: String,
+    /// The `VOLT_E_*` code of the error the command failed with
+    pub error_code: Option<String>,
+}
+
+impl Record {
+    pub fn new(command: &str, duration: Duration, error_code: Option<String>) -> Self {
+        let cache_hit_rate = CACHE
+            .lock()
+            .ok()
+            .and_then(|cache| *cache)
+            .filter(|(hits, misses)| hits + misses > 0)
+            .map(|(hits, misses)| {
+                // two decimals are plenty, and don't give away the size of the project
+                (hits as f64 / (hits + misses) as f64 * 100.0).round() / 100.0
+            });
+
+        Self {
+            volt_version: env!("CARGO_PKG_VERSION").to_string(),
+            command: command.to_string(),
+            duration_ms: duration.as_millis(),
+            cache_hit_rate,
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            error_code,
+        }
+    }
+}
+
+/// Remember how many packages an install copied from the store and downloaded
+pub fn cache_stats(hits: usize, misses: usize) {
+    if let Ok(mut cache) = CACHE.lock() {
+        *cache = Some((hits, misses));
+    }
+}
+
+/// Tell the user about telemetry once, as long as they haven't enabled it already
+pub fn notice(config: &VoltConfig) {
+    if config.telemetry() || !config.interactive() || config.quiet() {
+        return;
+    }
+
+    let dir = match config.telemetry_dir() {
+        Ok(dir) => dir,
+        Err(_) => return,
+    };
+
+    let marker = dir.join(NOTICE_FILE);
+
+    if marker.exists() || fs::create_dir_all(&dir).is_err() || fs::write(&marker, "").is_err() {
+        return;
+    }
+
+    eprintln!(
+        "{} volt can send anonymous usage statistics to help improve it. Nothing is sent unless \
+         you run {}, see {} for what's collected.",
+        "note:".bright_cyan().bold(),
+        "volt telemetry enable".bright_cyan(),
+        "volt telemetry status".bright_cyan()
+    );
+}
+
+/// Queue a record of this run, uploading the queue once it holds a full batch
+///
+/// Telemetry never gets in the way of the command, so failures are ignored. After a failed
+/// upload the records stay queued and aren't uploaded again for an hour.
+pub async fn record(config: &VoltConfig, record: Record) {
+    if !config.telemetry() {
+        return;
+    }
+
+    let queue = match queue_file(config) {
+        Ok(queue) => queue,
+        Err(_) => return,
+    };
+
+    if append(&queue, &record).is_err() {
+        return;
+    }
+
+    let mut records = queued(&queue);
+
+    if records.len() > MAX_QUEUED {
+        records.drain(..records.len() - MAX_QUEUED);
+
+        if rewrite(&queue, &records).is_err() {
+            return;
+        }
+    }
+
+    let failed = queue.with_file_name(FAILED_FILE);
+
+    if records.len() < BATCH_SIZE || recently_failed(&failed) {
+        return;
+    }
+
+    if upload(&records).await {
+        let _ = fs::remove_file(&queue);
+        let _ = fs::remove_file(&failed);
+    } else {
+        let _ = fs::write(&failed, "");
+    }
+}
+
+/// Whether an upload failed less than [`RETRY_AFTER`] ago
+fn recently_failed(failed: &Path) -> bool {
+    fs::metadata(failed)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|time| time.elapsed().ok())
+        .map_or(false, |elapsed| elapsed < RETRY_AFTER)
+}
+
+/// Path to the records waiting to be uploaded (`~/.volt/telemetry/queue.ndjson`)
+pub fn queue_file(config: &VoltConfig) -> miette::Result<PathBuf> {
+    Ok(config.telemetry_dir()?.join(QUEUE_FILE))
+}
+
+/// The records waiting to be uploaded
+pub fn queued(queue: &Path) -> Vec<Record> {
+    fs::read_to_string(queue)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Forget the records that weren't uploaded yet
+pub fn clear(config: &VoltConfig) -> miette::Result<()> {
+    let queue = queue_file(config)?;
+
+    if queue.exists() {
+        fs::remove_file(&queue).map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: queue.display().to_string(),
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Replace the queue with `records`
+fn rewrite(queue: &Path, records: &[Record]) -> std::io::Result<()> {
+    let mut data = String::new();
+
+    for record in records {
+        data.push_str(&serde_json::to_string(record)?);
+        data.push('\n');
+    }
+
+    fs::write(queue, data)
+}
+
+fn append(queue: &Path, record: &Record) -> std::io::Result<()> {
+    if let Some(parent) = queue.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let line = serde_json::to_string(record)?;
+
+    let mut file = OpenOptions::new().create(true).append(true).open(queue)?;
+
+    writeln!(file, "{}", line)
+}
+
+async fn upload(records: &[Record]) -> bool {
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+    {
+        Ok(client) => client,
+        Err(_) => return false,
+    };
+
+    client
+        .post(ENDPOINT)
+        .json(records)
+        .send()
+        .await
+        .map_or(false, |response| response.status().is_success())
+}
@@ -14,5 +14,4 @@
     limitations under the License.
 */
 
-// pub static PROGRESS_CHARS: &str = "██ ";
 pub static MAX_RETRIES: u8 = 4;
@@ -14,28 +14,33 @@
     limitations under the License.
 */
 
-use miette::Diagnostic;
+use miette::{Diagnostic, NamedSource, SourceSpan};
 use thiserror::Error;
 
+/// Every error volt reports.
+///
+/// The diagnostic codes (`VOLT_E_*`) name the kind of failure rather than the variant, and are
+/// part of volt's interface: scripts and CI match on them, so a code is never renamed or reused
+/// for a different kind of failure.
 #[derive(Debug, Error, Diagnostic)]
 pub enum VoltError {
     // #[error("failed to enable ansi support")]
     // #[diagnostic(code(volt::environment::enable_ansi_support))]
     // EnableAnsiSupport(),
     #[error("failed to detect `{env}`")]
-    #[diagnostic(code(volt::environment::get))]
+    #[diagnostic(code("VOLT_E_ENV"))]
     EnvironmentError { source: std::io::Error, env: String },
 
     #[error("failed to parse package specification: `{spec}`")]
-    #[diagnostic(code(volt::package_spec::parse))]
+    #[diagnostic(code("VOLT_E_SPEC"))]
     PackageSpecificationError { spec: String },
 
     #[error("failed to detect your home directory")]
-    #[diagnostic(code(volt::environment::home_dir))]
+    #[diagnostic(code("VOLT_E_ENV"))]
     GetHomeDirError,
 
     #[error("failed to get the name of the current directory")]
-    #[diagnostic(code(volt::environment::current_dir_name))]
+    #[diagnostic(code("VOLT_E_ENV"))]
     GetCurrentDirNameError,
 
     // #[error("failed to initialize lz4 decoder")]
@@ -46,30 +51,36 @@ pub enum VoltError {
     // #[diagnostic(code(volt::decode::lz4::decode))]
     // DecodeError(#[source] std::io::Error),
     #[error("failed to recieve response from the registry")]
-    #[diagnostic(code(volt::network))]
+    #[diagnostic(
+        code("VOLT_E_NETWORK"),
+        help("check your internet connection, and HTTPS_PROXY if you're behind a proxy")
+    )]
     NetworkError(isahc::Error),
 
     // #[error("failed to recieve byte response")]
     // #[diagnostic(code(volt::network::rec))]
     // NetworkRecError(#[source] std::io::Error),
     #[error("failed to create directory")]
-    #[diagnostic(code(volt::io::create_dir))]
+    #[diagnostic(code("VOLT_E_IO"))]
     CreateDirError(#[source] std::io::Error),
 
     #[error("GET {url} - 404 - {package_name} was not found in the volt registry, or you don't have the permission to request it.")]
-    #[diagnostic(code(volt::registry::volt::package_not_found))]
+    #[diagnostic(
+        code("VOLT_E_RESOLVE"),
+        help("check the spelling of the package name, private packages need a login")
+    )]
     PackageNotFound { url: String, package_name: String },
 
     #[error("GET {url} - 429 - Too many requests has been sent to {url} on the volt registry. Please try again later.")]
-    #[diagnostic(code(volt::registry::volt::too_many_requests))]
+    #[diagnostic(code("VOLT_E_NETWORK"), help("wait a minute before installing again"))]
     TooManyRequests { url: String },
 
     #[error("GET {url} - 400 - Bad request. Please try again later.")]
-    #[diagnostic(code(volt::registry::volt::bad_request))]
+    #[diagnostic(code("VOLT_E_NETWORK"))]
     BadRequest { url: String },
 
     #[error("GET {url} - {} - An unknown error occured. Please try again later.")]
-    #[diagnostic(code(volt::registry::volt::unknown_error))]
+    #[diagnostic(code("VOLT_E_NETWORK"))]
     NetworkUnknownError {
         url: String,
         package_name: String,
@@ -77,62 +88,344 @@ pub enum VoltError {
     },
 
     #[error("failed to parse {hash} integrity hash.")]
-    #[diagnostic(code(volt::integrity::parse))]
+    #[diagnostic(code("VOLT_E_INTEGRITY"))]
     HashParseError { hash: String },
 
     #[error("failed to copy bytes to hasher.")]
-    #[diagnostic(code(volt::hasher::copy))]
+    #[diagnostic(code("VOLT_E_INTEGRITY"))]
     HasherCopyError(#[source] std::io::Error),
 
     #[error("failed to verify tarball checksum")]
-    #[diagnostic(code(volt::integrity::verify))]
+    #[diagnostic(code("VOLT_E_INTEGRITY"))]
     ChecksumVerificationError,
 
+    #[error("the tarball of {package} does not match its integrity {expected}")]
+    #[diagnostic(
+        code("VOLT_E_INTEGRITY"),
+        help("the download was corrupted or the registry served another tarball than the lock file records, retry the install and report the package if it keeps failing")
+    )]
+    TarballIntegrityError {
+        package: String,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("the tarball of {package} contains an invalid path `{path}`")]
+    #[diagnostic(code("VOLT_E_TARBALL"))]
+    TarballPathError { package: String, path: String },
+
     #[error("failed to convert integrity into hex")]
-    #[diagnostic(code(volt::integrity::convert))]
+    #[diagnostic(code("VOLT_E_INTEGRITY"))]
     IntegrityConversionError,
 
     #[error("failed to deserialize slice to `SpeedyVoltResponse`")]
-    #[diagnostic(code(volt::integrity::convert))]
+    #[diagnostic(code("VOLT_E_RESOLVE"))]
     DeserializeError,
 
     #[error("failed to build request client")]
-    #[diagnostic(code(volt::network::builder))]
+    #[diagnostic(code("VOLT_E_NETWORK"))]
     RequestBuilderError(#[source] isahc::http::Error),
 
     #[error("failed to build recieve response text")]
-    #[diagnostic(code(volt::io::rec::text))]
+    #[diagnostic(code("VOLT_E_NETWORK"))]
     IoTextRecError(#[source] std::io::Error),
 
     #[error("failed to find a hash that matches the specified version requirement: {version}")]
-    #[diagnostic(code(volt::io::rec::text))]
+    #[diagnostic(code("VOLT_E_RESOLVE"))]
     HashLookupError { version: String },
 
     #[error("failed to find a version that matches the specified version requirement for {name}")]
-    #[diagnostic(code(volt::io::rec::text))]
+    #[diagnostic(
+        code("VOLT_E_RESOLVE"),
+        help("run `volt info {name}` to see the published versions")
+    )]
     VersionLookupError { name: String },
 
     #[error("failed to read `{name}`")]
-    #[diagnostic(code(volt::io::file::read))]
+    #[diagnostic(code("VOLT_E_IO"))]
     ReadFileError {
         source: std::io::Error,
         name: String,
     },
 
     #[error("failed to write to `{name}`")]
-    #[diagnostic(code(volt::io::file::write))]
+    #[diagnostic(code("VOLT_E_IO"))]
     WriteFileError {
         source: std::io::Error,
         name: String,
     },
 
+    #[error("failed to connect to `{target}` to emit events")]
+    #[diagnostic(
+        code("VOLT_E_IO"),
+        help("pass `-` for stdout, `tcp://host:port`, or the path of a listening Unix socket")
+    )]
+    EventsConnectError {
+        source: std::io::Error,
+        target: String,
+    },
+
     // Convert error to `String` instead of having a `source` because `git_config::parser::Error`
     // has a lifetime parameter
     #[error("failed to parse git configuration file: `{error_text}`")]
-    #[diagnostic(code(volt::git::parse))]
+    #[diagnostic(code("VOLT_E_CONFIG"))]
     GitConfigParseError { error_text: String },
 
+    #[error("no package.json found in {directory} or any of its parents")]
+    #[diagnostic(code("VOLT_E_MANIFEST"), help("run `volt init` to create one"))]
+    PackageJsonNotFound { directory: String },
+
+    #[error("failed to parse package.json")]
+    #[diagnostic(
+        code("VOLT_E_MANIFEST"),
+        help("package.json must be valid JSON, without comments or trailing commas")
+    )]
+    PackageJsonParseError {
+        #[source_code]
+        source_code: NamedSource,
+        #[label("{message}")]
+        span: SourceSpan,
+        message: String,
+    },
+
+    #[error("failed to parse `{name}`")]
+    #[diagnostic(code("VOLT_E_CONFIG"))]
+    SettingsParseError {
+        source: toml::de::Error,
+        name: String,
+    },
+
+    #[error("`{key}` is not a configuration option")]
+    #[diagnostic(
+        code("VOLT_E_CONFIG"),
+        help("run `volt config list` to see every option")
+    )]
+    UnknownConfigKeyError { key: String },
+
+    #[error("`{value}` is not a valid value for `{key}`, expected {expected}")]
+    #[diagnostic(code("VOLT_E_CONFIG"))]
+    ConfigValueError {
+        key: String,
+        value: String,
+        expected: String,
+    },
+
+    #[error("{count} packages violate the policy in volt.toml")]
+    #[diagnostic(code("VOLT_E_POLICY"))]
+    PolicyViolationError { count: usize },
+
+    #[error("the daemon is already running as process {pid}")]
+    #[diagnostic(code("VOLT_E_DAEMON"), help("stop it first with `volt daemon stop`"))]
+    DaemonRunningError { pid: u32 },
+
+    #[error("{count} dependencies are never imported")]
+    #[diagnostic(
+        code("VOLT_E_UNUSED"),
+        help("remove them with `volt unused --fix`, or ignore them in the [unused] table of volt.toml")
+    )]
+    UnusedDependenciesError { count: usize },
+
+    #[error("{count} imported packages are not declared in package.json")]
+    #[diagnostic(
+        code("VOLT_E_MISSING"),
+        help("declare them with the installed versions using `volt missing --fix`")
+    )]
+    MissingDependenciesError { count: usize },
+
+    #[error("{count} duplicate packages could be removed")]
+    #[diagnostic(code("VOLT_E_DUPLICATES"), help("remove them with `volt dedupe`"))]
+    DuplicatePackagesError { count: usize },
+
+    #[error("{count} vulnerabilities are {level} or more severe")]
+    #[diagnostic(
+        code("VOLT_E_AUDIT"),
+        help("update the affected packages with `volt audit fix`, or raise `--audit-level`")
+    )]
+    VulnerabilitiesError { count: usize, level: String },
+
+    #[error("{count} packages have missing or invalid signatures or attestations")]
+    #[diagnostic(
+        code("VOLT_E_AUDIT_SIGNATURES"),
+        help("reinstall them from the registry, they may have been tampered with")
+    )]
+    SignatureAuditError { count: usize },
+
+    #[error("{count} doctor checks failed")]
+    #[diagnostic(
+        code("VOLT_E_DOCTOR"),
+        help("follow the fix printed under each failed check")
+    )]
+    DoctorChecksError { count: usize },
+
+    #[error("`{field}` is not a valid packageManager, like volt@0.0.3")]
+    #[diagnostic(code("VOLT_E_PACKAGE_MANAGER"))]
+    PackageManagerFieldError { field: String },
+
+    #[error("{path} says the project uses {name}, not volt")]
+    #[diagnostic(
+        code("VOLT_E_PACKAGE_MANAGER"),
+        help("use {name}, or remove the packageManager field to switch the project to volt")
+    )]
+    PackageManagerMismatchError { name: String, path: String },
+
+    #[error("{path} wants volt {wanted}, which volt {current} isn't compatible with")]
+    #[diagnostic(
+        code("VOLT_E_PACKAGE_MANAGER"),
+        help("install volt {wanted} into ~/.volt/versions/{wanted} to have volt switch to it")
+    )]
+    VoltVersionMismatchError {
+        wanted: String,
+        current: String,
+        path: String,
+    },
+
+    #[error("{count} engines ranges aren't satisfied by the node and volt versions in use")]
+    #[diagnostic(
+        code("VOLT_E_ENGINE"),
+        help("switch to a matching node with `volt node use`, or drop --engine-strict")
+    )]
+    EngineMismatchError { count: usize },
+
+    #[error("{count} packages failed registry signature verification")]
+    #[diagnostic(code("VOLT_E_SIGNATURE"))]
+    SignatureVerificationError { count: usize },
+
+    #[error("{count} packages have attestations that failed verification")]
+    #[diagnostic(code("VOLT_E_PROVENANCE"))]
+    ProvenanceVerificationError { count: usize },
+
+    #[error("the `{name}` script exited with code {code}")]
+    #[diagnostic(code("VOLT_E_SCRIPT"))]
+    ScriptFailedError { name: String, code: i32 },
+
+    #[error("you must be logged in to {registry}")]
+    #[diagnostic(
+        code("VOLT_E_AUTH"),
+        help("add an `_authToken` for the registry to your .npmrc")
+    )]
+    AuthenticationRequired { registry: String },
+
+    #[error("{method} {url} - a one-time password is required")]
+    #[diagnostic(
+        code("VOLT_E_AUTH"),
+        help("pass the code from your authenticator app with --otp")
+    )]
+    OtpRequired { method: String, url: String },
+
+    #[error("{method} {url} - {code} - {message}")]
+    #[diagnostic(code("VOLT_E_REGISTRY"))]
+    RegistryRequestError {
+        method: String,
+        url: String,
+        code: String,
+        message: String,
+    },
+
+    #[error("`{package}` does not provide a `{command}` executable (available: {available})")]
+    #[diagnostic(code("VOLT_E_BIN"))]
+    BinNotFoundError {
+        package: String,
+        command: String,
+        available: String,
+    },
+
+    #[error("the patch {patch} no longer applies to {package}")]
+    #[diagnostic(
+        code("VOLT_E_PATCH"),
+        help("run `volt patch {package}` to recreate it against the installed version")
+    )]
+    PatchApplyError { package: String, patch: String },
+
+    #[error("{package} in {lockfile} has a different integrity than the registry")]
+    #[diagnostic(
+        code("VOLT_E_INTEGRITY"),
+        help("the tarball was republished or the lockfile was edited, reinstall it with the old package manager first")
+    )]
+    LockfileIntegrityError { package: String, lockfile: String },
+
+    #[error("node_modules differs from the lock file in {count} places")]
+    #[diagnostic(
+        code("VOLT_E_DRIFT"),
+        help("run `volt install` to restore the locked packages")
+    )]
+    NodeModulesDriftError { count: usize },
+
+    #[error("the {hook} hook exited with code {code}")]
+    #[diagnostic(
+        code("VOLT_E_HOOK"),
+        help("hooks are set in the [hooks] table of volt.toml or ~/.volt/config.toml")
+    )]
+    HookFailedError { hook: String, code: i32 },
+
+    #[error("`{name}` is not a volt command or an installed plugin")]
+    #[diagnostic(
+        code("VOLT_E_PLUGIN"),
+        help("run `volt --help` for the commands, or put a `volt-{name}` executable on your PATH")
+    )]
+    PluginNotFoundError { name: String },
+
+    #[error("`{path}` is not a valid node_modules.pack: {reason}")]
+    #[diagnostic(
+        code("VOLT_E_PACK"),
+        help("the archive is truncated or was modified, create it again with `volt compress`")
+    )]
+    PackCorruptError { path: String, reason: String },
+
+    #[error("`{name}` is not in `{pack}`")]
+    #[diagnostic(code("VOLT_E_PACK"))]
+    PackPackageNotFoundError { name: String, pack: String },
+
+    #[error("`{path}` already exists")]
+    #[diagnostic(
+        code("VOLT_E_EXISTS"),
+        help("pass --clean to replace it with the contents of the archive")
+    )]
+    NodeModulesExistsError { path: String },
+
+    #[error("`{spec}` is not a node version or range")]
+    #[diagnostic(
+        code("VOLT_E_NODE"),
+        help("use a version like 18.12.1, a range like ^18 or lts")
+    )]
+    NodeVersionError { spec: String },
+
+    #[error("no node release matches `{spec}` for {platform}")]
+    #[diagnostic(
+        code("VOLT_E_NODE"),
+        help("run `volt node list --remote` for the available versions")
+    )]
+    NodeReleaseNotFoundError { spec: String, platform: String },
+
+    #[error("node isn't published for {platform}")]
+    #[diagnostic(code("VOLT_E_NODE"))]
+    NodePlatformError { platform: String },
+
+    #[error("`{file}` does not match its checksum in SHASUMS256.txt")]
+    #[diagnostic(
+        code("VOLT_E_INTEGRITY"),
+        help("the download was corrupted or tampered with, try installing it again")
+    )]
+    NodeChecksumError { file: String },
+
+    #[error("node {spec} is not installed")]
+    #[diagnostic(
+        code("VOLT_E_NODE"),
+        help("run `volt node list` for the installed versions")
+    )]
+    NodeNotInstalledError { spec: String },
+
+    #[error("{directory} doesn't pin a node version")]
+    #[diagnostic(
+        code("VOLT_E_NODE"),
+        help("pass the version to install, or pin one with `volt node use <version>`")
+    )]
+    NodeNotPinnedError { directory: String },
+
+    #[error("there is no `{name}` script or executable in {directory}")]
+    #[diagnostic(code("VOLT_E_SCRIPT"))]
+    ScriptNotFoundError { name: String, directory: String },
+
     #[error("an unknown error occured.")]
-    #[diagnostic(code(volt::unknown))]
+    #[diagnostic(code("VOLT_E_UNKNOWN"))]
     UnknownError,
 }
@@ -20,16 +20,23 @@ pub mod constants;
 pub mod errors;
 pub mod extensions;
 pub mod package;
+pub mod packlist;
+pub mod patch;
 pub mod scripts;
 pub mod voltapi;
 
 use crate::{
     cli::VoltConfig,
-    core::{io::extract_tarball, net::fetch_tarball, utils::voltapi::VoltPackage},
+    core::{
+        io::{extract_tarball, write_files_manifest},
+        net::fetch_tarball,
+        progress::InstallProgress,
+        utils::voltapi::VoltPackage,
+    },
 };
 
 use errors::VoltError;
-use futures::TryFutureExt;
+use futures::{stream, StreamExt, TryStreamExt};
 use git_config::file::GitConfig;
 use git_config::parser::parse_from_str;
 use miette::{IntoDiagnostic, Result};
@@ -38,7 +45,13 @@ use reqwest::Client;
 use ssri::{Algorithm, Integrity};
 
 use self::voltapi::Bin;
-use std::{collections::HashMap, ffi::OsStr, fs::read_to_string, io::Write, path::PathBuf};
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs::read_to_string,
+    io::Write,
+    path::{Path, PathBuf},
+};
 
 pub struct State {
     pub http_client: Client,
@@ -65,6 +78,19 @@ pub fn decompress_gzip(gz_data: &[u8]) -> Result<Vec<u8>> {
     Ok(outbuf)
 }
 
+pub fn compress_gzip(data: &[u8]) -> Result<Vec<u8>> {
+    let mut compressor = libdeflater::Compressor::new(libdeflater::CompressionLvl::default());
+
+    let mut outbuf = vec![0; compressor.gzip_compress_bound(data.len())];
+    let size = compressor
+        .gzip_compress(data, &mut outbuf)
+        .into_diagnostic()?;
+
+    outbuf.truncate(size);
+
+    Ok(outbuf)
+}
+
 fn get_git_config_value_if_exists(
     config: &VoltConfig,
     section: &str,
@@ -398,24 +424,7 @@ pub fn link_dependencies(package: &VoltPackage, config: &VoltConfig) -> miette::
             // node_modules/.volt/accepts@1.2.3/node_modules/ms
             target_link_path.push(&name);
 
-            #[cfg(windows)]
-            junction::create(&dependency_link_path, &target_link_path).unwrap_or_else(|e| {
-                eprintln!(
-                    "target: {} destination: {}, {}",
-                    target_link_path.display(),
-                    dependency_link_path.display(),
-                    e
-                );
-                std::process::exit(1);
-            });
-
-            #[cfg(unix)]
-            std::os::unix::fs::symlink(dependency_link_path, target_link_path).unwrap_or_else(
-                |e| {
-                    eprintln!("{}", e);
-                    std::process::exit(1);
-                },
-            );
+            create_link(&dependency_link_path, &target_link_path)?;
         }
     }
 
@@ -423,13 +432,22 @@ pub fn link_dependencies(package: &VoltPackage, config: &VoltConfig) -> miette::
 }
 
 /// Install a JavaScript package.
-pub async fn install_package(config: VoltConfig, package: VoltPackage, state: State) -> Result<()> {
+pub async fn install_package(
+    config: VoltConfig,
+    package: VoltPackage,
+    state: State,
+    progress: InstallProgress,
+) -> Result<()> {
     // Check if the package is already installed
     match verify_existing_installation(&package, &config) {
         Ok(value) => {
+            tracing::debug!("installing {} from the store", package.key());
+
+            progress.restored(&package);
+
             let cas_file_map: Vec<(PathBuf, Integrity)> =
                 serde_json::from_slice::<HashMap<PathBuf, Integrity>>(&value)
-                    .unwrap()
+                    .into_diagnostic()?
                     .into_par_iter()
                     .map(|(k, v)| (k, v))
                     .collect();
@@ -475,7 +493,12 @@ pub async fn install_package(config: VoltConfig, package: VoltPackage, state: St
                         }
 
                         // Write the contents to node_modules
-                        let mut file = std::fs::File::create(&file_path).unwrap();
+                        let mut file = std::fs::File::create(&file_path).map_err(|e| {
+                            VoltError::WriteFileError {
+                                source: e,
+                                name: file_path.display().to_string(),
+                            }
+                        })?;
 
                         file.write_all(&contents).into_diagnostic()?;
                     }
@@ -485,47 +508,57 @@ pub async fn install_package(config: VoltConfig, package: VoltPackage, state: St
             }
 
             for handle in handles {
-                handle
-                    .unwrap_or_else(|e| {
-                        eprintln!("{}", e);
-                        std::process::exit(1);
-                    })
-                    .await
-                    .unwrap_or_else(|e| {
-                        eprintln!("{}", e);
-                        std::process::exit(1);
-                    });
+                handle.await.into_diagnostic()??;
             }
 
-            link_dependencies(&package, &config);
+            write_files_manifest(&package, &config, &value)?;
+
+            progress.extracted(&package);
+
+            link_dependencies(&package, &config)?;
+
+            progress.linked(&package);
         }
         Err(_) => {
+            tracing::debug!("{} is not in the store", package.key());
+
             // fetch the tarball from the registry
             let response = fetch_tarball(&package, state).await?;
 
+            progress.downloaded(&package, response.len() as u64);
+
             tokio::task::spawn_blocking({
                 let config = config.clone();
                 let package = package.clone();
                 move || -> Result<()> {
                     // verify the checksum
                     // (checksum is valid, calculated checksum)
-                    let (verified, _checksum) = verify_checksum(&response, &package.integrity)?;
+                    let (verified, checksum) = verify_checksum(&response, &package.integrity)?;
+
+                    if !verified {
+                        return Err(VoltError::TarballIntegrityError {
+                            package: package.key(),
+                            expected: package.integrity.clone(),
+                            actual: checksum.unwrap_or_default(),
+                        }
+                        .into());
+                    }
 
-                    if verified {
-                        // decompress gzipped response
-                        let decompressed_response = decompress_gzip(&response)?;
+                    // decompress gzipped response
+                    let decompressed_response = decompress_gzip(&response)?;
 
-                        // extract the tarball
-                        extract_tarball(decompressed_response, &package, &config)?;
+                    // extract the tarball
+                    extract_tarball(decompressed_response, &package, &config)?;
 
-                        // generate .bin files
-                        generate_script(&config, &package);
+                    progress.extracted(&package);
 
-                        // generate symlinks
-                        link_dependencies(&package, &config)?;
-                    } else {
-                        // TODO: handle checksum failure
-                    }
+                    // generate .bin files
+                    generate_script(&config, &package);
+
+                    // generate symlinks
+                    link_dependencies(&package, &config)?;
+
+                    progress.linked(&package);
 
                     Ok(())
                 }
@@ -537,3 +570,238 @@ pub async fn install_package(config: VoltConfig, package: VoltPackage, state: St
 
     Ok(())
 }
+
+/// Install a flattened dependency tree into `node_modules/.volt` using the pnpm linking algorithm.
+///
+/// Packages that are incompatible with the current platform are skipped, the rest of the tree is
+/// returned once every package has been installed.
+pub async fn install_tree(
+    config: &VoltConfig,
+    mut tree: HashMap<String, VoltPackage>,
+    progress: &InstallProgress,
+) -> Result<HashMap<String, VoltPackage>> {
+    let nm_dir = config.node_modules()?;
+
+    let client = Client::builder()
+        .use_rustls_tls()
+        .build()
+        .into_diagnostic()?;
+
+    let mut incompatible_packages = vec![];
+
+    // pnpm linking algorithm
+    for (key, value) in tree.iter() {
+        // None means it's not platform-specific
+        // We get a list of platforms, and if our current OS isn't on this list - it means that we can skip this package
+        // this is only if the package is optional
+
+        if let Some(os) = &value.os {
+            if !os.contains(&"win32".to_string()) && !os.contains(&format!("!{}", "win32")) {
+                incompatible_packages.push(key.clone());
+                continue;
+            }
+        }
+
+        if let Some(architecture) = &value.cpu {
+            if !architecture.contains(&"x64".to_string()) {
+                incompatible_packages.push(key.clone());
+                continue;
+            }
+        }
+
+        // node_modules/.volt/accepts@1.2.3/node_modules/accepts
+        std::fs::create_dir_all(value.package_directory(&nm_dir))
+            .map_err(VoltError::CreateDirError)?;
+    }
+
+    for item in incompatible_packages {
+        tree.remove(&item);
+    }
+
+    progress.installing(tree.len());
+
+    stream::iter(tree.values().map(|data| {
+        install_package(
+            config.clone(),
+            data.clone(),
+            State {
+                http_client: client.clone(),
+            },
+            progress.clone(),
+        )
+    }))
+    .buffer_unordered(config.concurrency())
+    .try_collect::<Vec<_>>()
+    .await?;
+
+    Ok(tree)
+}
+
+/// Whether a directory is listed in the `PATH` environment variable
+pub fn is_on_path(dir: &Path) -> bool {
+    std::env::var_os("PATH").map_or(false, |paths| {
+        std::env::split_paths(&paths).any(|p| p == dir)
+    })
+}
+
+/// Remove a symlink (or junction on windows) without touching what it points to
+pub fn remove_link(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    std::fs::remove_file(path).into_diagnostic()?;
+
+    #[cfg(windows)]
+    std::fs::remove_dir(path).into_diagnostic()?;
+
+    Ok(())
+}
+
+/// Link a package into the top level of `node_modules` so that it can be required by name
+pub fn link_package(config: &VoltConfig, package: &VoltPackage) -> Result<()> {
+    let node_modules = config.node_modules()?;
+
+    let target = package.package_directory(&node_modules);
+
+    // node_modules/accepts or node_modules/@types/node
+    let link = node_modules.join(&package.name);
+
+    create_link(&target, &link)
+}
+
+/// Point `link` at the directory `target`, replacing the link if it already exists
+pub fn create_link(target: &Path, link: &Path) -> Result<()> {
+    if let Some(parent) = link.parent() {
+        std::fs::create_dir_all(parent).map_err(VoltError::CreateDirError)?;
+    }
+
+    if link.symlink_metadata().is_ok() {
+        remove_link(link)?;
+    }
+
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(target, link).into_diagnostic()?;
+
+    #[cfg(windows)]
+    junction::create(target, link).into_diagnostic()?;
+
+    Ok(())
+}
+
+/// Package URL for an npm package (`pkg:npm/%40babel/core@7.17.0`)
+pub fn purl(name: &str, version: &str) -> String {
+    format!("pkg:npm/{}@{}", name.replacen('@', "%40", 1), version)
+}
+
+/// Total size of the files in a directory, without following links into other packages
+pub fn directory_size(path: &Path) -> u64 {
+    jwalk::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.path().symlink_metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Link the executables of a package into `bin_dir`, returning the names of the linked commands
+pub fn link_bins(
+    bin_dir: &Path,
+    bins: HashMap<String, String>,
+    package_dir: &Path,
+) -> Result<Vec<String>> {
+    std::fs::create_dir_all(bin_dir).map_err(VoltError::CreateDirError)?;
+
+    let mut commands = vec![];
+
+    for (command, script) in bins {
+        let script = package_dir.join(script);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let link = bin_dir.join(&command);
+
+            if link.symlink_metadata().is_ok() {
+                remove_link(&link)?;
+            }
+
+            std::os::unix::fs::symlink(&script, &link).into_diagnostic()?;
+
+            std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))
+                .into_diagnostic()?;
+        }
+
+        #[cfg(windows)]
+        {
+            let shim = bin_dir.join(format!("{}.cmd", command));
+
+            std::fs::write(&shim, format!("@node \"{}\" %*\r\n", script.display())).map_err(
+                |e| VoltError::WriteFileError {
+                    source: e,
+                    name: shim.display().to_string(),
+                },
+            )?;
+        }
+
+        commands.push(command);
+    }
+
+    Ok(commands)
+}
+
+/// Read the executables declared in the package.json of a package directory
+pub fn read_bins(package_dir: &Path, name: &str) -> Result<HashMap<String, String>> {
+    let pkg_path = package_dir.join("package.json");
+
+    if !pkg_path.exists() {
+        return Ok(HashMap::new());
+    }
+
+    let data: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(pkg_path).into_diagnostic()?)
+            .into_diagnostic()?;
+
+    Ok(data
+        .get("bin")
+        .cloned()
+        .and_then(|bin| serde_json::from_value::<Bin>(bin).ok())
+        .map(|bin| bin.commands(name))
+        .unwrap_or_default())
+}
+
+/// Remove executables previously created by [`link_bins`] for the package in `package_dir`
+///
+/// A command another package has since linked under the same name is left alone.
+pub fn unlink_bins(
+    bin_dir: &Path,
+    bins: HashMap<String, String>,
+    package_dir: &Path,
+) -> Result<()> {
+    for (command, script) in bins {
+        let script = std::fs::canonicalize(package_dir.join(script));
+
+        #[cfg(unix)]
+        let (link, target) = {
+            let link = bin_dir.join(&command);
+            let target = std::fs::canonicalize(&link);
+
+            (link, target)
+        };
+
+        // the shim runs `@node "<script>" %*`
+        #[cfg(windows)]
+        let (link, target) = {
+            let link = bin_dir.join(format!("{}.cmd", command));
+            let shim = std::fs::read_to_string(&link).unwrap_or_default();
+            let target = std::fs::canonicalize(shim.split('"').nth(1).unwrap_or_default());
+
+            (link, target)
+        };
+
+        if matches!((target, script), (Ok(target), Ok(script)) if target == script) {
+            std::fs::remove_file(&link).into_diagnostic()?;
+        }
+    }
+
+    Ok(())
+}
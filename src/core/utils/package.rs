@@ -16,7 +16,7 @@
 
 use super::errors::VoltError;
 
-use miette::{IntoDiagnostic, Result};
+use miette::{IntoDiagnostic, NamedSource, Result};
 use package_spec::PackageSpec;
 use serde::{Deserialize, Serialize};
 
@@ -196,59 +196,148 @@ pub struct PackageJson {
     #[serde(rename = "devDependencies")]
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
-    pub dev_dependencies: Option<HashMap<String, String>>,
+    pub dev_dependencies: Option<BTreeMap<String, String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
     pub scripts: Option<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub workspaces: Option<Vec<String>>,
+    /// Patches re-applied after installing a dependency (`name@version` -> patch file)
+    #[serde(rename = "patchedDependencies")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub patched_dependencies: Option<BTreeMap<String, String>>,
+    /// Versions the project settled on for packages, which volt uses to accept unmet peers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub overrides: Option<BTreeMap<String, String>>,
+    /// Versions of node and other runtimes the project works with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub engines: Option<BTreeMap<String, String>>,
+    /// The package manager the project is meant to be used with (`volt@0.0.3`)
+    #[serde(rename = "packageManager")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub package_manager: Option<String>,
 }
 
 impl PackageJson {
     pub fn get() -> Result<(Self, PathBuf)> {
-        for parent in std::env::current_dir()
-            .map_err(|e| VoltError::EnvironmentError {
-                env: String::from("CURRENT_DIR"),
-                source: e,
-            })?
-            .ancestors()
-        {
+        let current_dir = std::env::current_dir().map_err(|e| VoltError::EnvironmentError {
+            env: String::from("CURRENT_DIR"),
+            source: e,
+        })?;
+
+        Self::get_from_dir(&current_dir)
+    }
+
+    pub fn get_from_dir(from: &Path) -> Result<(Self, PathBuf)> {
+        for parent in from.ancestors() {
             let pkg_path = parent.join("package.json");
 
             if pkg_path.exists() {
-                let data = read_to_string(&pkg_path).map_err(|e| VoltError::ReadFileError {
-                    source: e,
-                    name: pkg_path.to_str().unwrap().to_string(),
-                })?;
-
-                return Ok((
-                    serde_json::from_str(data.as_str()).into_diagnostic()?,
-                    pkg_path,
-                ));
+                return Ok((Self::read(&pkg_path)?, pkg_path));
             }
         }
 
-        miette::bail!("No package.json found!");
+        Err(VoltError::PackageJsonNotFound {
+            directory: from.display().to_string(),
+        }
+        .into())
     }
 
-    pub fn get_from_dir(from: &Path) -> Result<(Self, PathBuf)> {
-        for parent in from.ancestors() {
-            let pkg_path = from.join("package.json");
+    /// Read and parse a package.json, pointing at the problem when it isn't valid
+    pub fn read(path: &Path) -> Result<Self> {
+        let data = read_to_string(path).map_err(|e| VoltError::ReadFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
 
-            if pkg_path.exists() {
-                let data = read_to_string(&pkg_path).map_err(|e| VoltError::ReadFileError {
-                    source: e,
-                    name: pkg_path.to_str().unwrap().to_string(),
-                })?;
-                return Ok((
-                    serde_json::from_str(data.as_str()).into_diagnostic()?,
-                    pkg_path,
-                ));
+        serde_json::from_str(&data).map_err(|e| {
+            let offset = json_offset(&data, e.line(), e.column());
+
+            // the position is shown by the label instead
+            let message = e.to_string();
+            let message = match message.rsplit_once(" at line ") {
+                Some((message, _)) => message.to_string(),
+                None => message,
+            };
+
+            VoltError::PackageJsonParseError {
+                span: (offset, usize::from(offset < data.len())).into(),
+                source_code: NamedSource::new(path.display().to_string(), data),
+                message,
+            }
+            .into()
+        })
+    }
+
+    /// Load the package.json in `dir` (without searching its ancestors), or create an empty one
+    pub fn load_or_new(dir: &Path, name: &str) -> Result<Self> {
+        let pkg_path = dir.join("package.json");
+
+        if !pkg_path.exists() {
+            return Ok(Self {
+                name: name.to_string(),
+                version: String::from("0.0.0"),
+                main: None,
+                repository: None,
+                author: None,
+                license: None,
+                dependencies: None,
+                dev_dependencies: None,
+                scripts: None,
+                workspaces: None,
+                patched_dependencies: None,
+                overrides: None,
+                engines: None,
+                package_manager: None,
+            });
+        }
+
+        Self::read(&pkg_path)
+    }
+
+    /// Load the package.json of every workspace declared by a project at `root`.
+    /// Supports plain directories (`tools/cli`) and direct children (`packages/*`).
+    pub fn workspace_packages(&self, root: &Path) -> Result<Vec<(PathBuf, Self)>> {
+        let mut packages = vec![];
+
+        for pattern in self.workspaces.iter().flatten() {
+            let directories = match pattern.strip_suffix("/*") {
+                Some(parent) => match fs::read_dir(root.join(parent)) {
+                    Ok(entries) => entries
+                        .filter_map(|entry| entry.ok())
+                        .map(|entry| entry.path())
+                        .filter(|path| path.is_dir())
+                        .collect(),
+                    Err(_) => vec![],
+                },
+                None => vec![root.join(pattern)],
+            };
+
+            for directory in directories {
+                let pkg_path = directory.join("package.json");
+
+                if pkg_path.exists() {
+                    packages.push((directory, Self::read(&pkg_path)?));
+                }
             }
         }
-        miette::bail!("No package.json found!");
+
+        packages.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Ok(packages)
     }
 
     pub fn save(&self) -> Result<()> {
-        let mut file = fs::File::create("package.json").into_diagnostic()?;
+        self.save_to(Path::new("package.json"))
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        let mut file = fs::File::create(path).into_diagnostic()?;
 
         file.write(
             serde_json::to_string_pretty(self)
@@ -270,18 +359,54 @@ impl PackageJson {
             requested,
         } = package
         {
-            #[allow(clippy::redundant_closure)]
-            /*
-            self.dependencies
-                .clone()
-                .unwrap_or_else(|| HashMap::new())
-                .insert(name, requested.as_ref().unwrap().to_string());
-            */
+            let requested = requested.map_or_else(|| String::from("latest"), |r| r.to_string());
+
             self.dependencies
-                .clone()
-                .unwrap_or_else(|| BTreeMap::new())
-                .insert(name, requested.as_ref().unwrap().to_string());
+                .get_or_insert_with(BTreeMap::new)
+                .insert(name, requested);
+        }
+    }
+
+    /// Remove a package from `dependencies` and `devDependencies`, returning whether it was found
+    pub fn remove_dependency(&mut self, name: &str) -> bool {
+        let dependency = self
+            .dependencies
+            .as_mut()
+            .and_then(|d| d.remove(name))
+            .is_some();
+
+        let dev_dependency = self
+            .dev_dependencies
+            .as_mut()
+            .and_then(|d| d.remove(name))
+            .is_some();
+
+        dependency || dev_dependency
+    }
+
+    /// Point the range of an existing dependency at `version`, keeping its `^`/`~` prefix (or
+    /// pinning it when the range was an exact version). Returns whether the dependency was found
+    pub fn bump_dependency(&mut self, name: &str, version: &str) -> bool {
+        let mut found = false;
+
+        for dependencies in [self.dependencies.as_mut(), self.dev_dependencies.as_mut()]
+            .into_iter()
+            .flatten()
+        {
+            if let Some(range) = dependencies.get_mut(name) {
+                *range = if range.starts_with('~') {
+                    format!("~{}", version)
+                } else if range.parse::<node_semver::Version>().is_ok() {
+                    version.to_string()
+                } else {
+                    format!("^{}", version)
+                };
+
+                found = true;
+            }
         }
+
+        found
     }
 
     // pub fn add_dev_dependency(&mut self, package: Package) {
@@ -310,3 +435,28 @@ impl PackageJson {
     //     }
     // }
 }
+
+/// Byte offset of a 1-based line and column reported by serde_json
+fn json_offset(data: &str, line: usize, column: usize) -> usize {
+    let line_start = data
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum::<usize>();
+
+    (line_start + column.saturating_sub(1)).min(data.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_lines_and_columns_to_offsets() {
+        let data = "{\n  \"name\": 1\n}";
+
+        assert_eq!(json_offset(data, 1, 1), 0);
+        assert_eq!(&data[json_offset(data, 2, 3)..][..6], "\"name\"");
+        assert_eq!(json_offset(data, 3, 2), data.len());
+    }
+}
@@ -0,0 +1,392 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Select the files of a project that go into its published tarball.
+//!
+//! Follows npm's rules: the `files` field is a whitelist, otherwise `.npmignore` (or
+//! `.gitignore` when a directory has no `.npmignore`) excludes files. package.json, the readme,
+//! the license, `main` and `bin` files are always included.
+
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match,
+};
+use miette::{IntoDiagnostic, Result};
+use serde_json::Value;
+
+use std::{
+    fs::{self, read_to_string},
+    path::{Path, PathBuf},
+};
+
+use super::{compress_gzip, errors::VoltError};
+
+/// Files that are never published
+const ALWAYS_IGNORED: &[&str] = &[
+    ".git",
+    ".svn",
+    ".hg",
+    "CVS",
+    ".DS_Store",
+    "._*",
+    ".*.swp",
+    ".lock-wscript",
+    ".wafpickle-*",
+    ".npmrc",
+    ".npmignore",
+    ".gitignore",
+    "npm-debug.log",
+    "config.gypi",
+    "*.orig",
+    "node_modules",
+    "/package-lock.json",
+    "/yarn.lock",
+    "/pnpm-lock.yaml",
+    "/volt.lock",
+];
+
+/// Files in the project root that are always published, matched case-insensitively by prefix
+const ALWAYS_INCLUDED: &[&str] = &["readme", "license", "licence", "copying", "changelog"];
+
+/// Modification time npm gives every tarball entry (1985-10-26T08:15:00Z) so packs are
+/// reproducible
+const TARBALL_MTIME: u64 = 499_162_500;
+
+/// A file selected for the tarball
+#[derive(Debug, Clone)]
+pub struct PackedFile {
+    /// Path relative to the project root, separated by `/`
+    pub path: String,
+    pub size: u64,
+    pub executable: bool,
+}
+
+/// List the files of the project in `dir` that would be published, sorted by path
+pub fn packlist(dir: &Path, manifest: &Value) -> Result<Vec<PackedFile>> {
+    let always_ignored = matcher(dir, ALWAYS_IGNORED.iter().copied())?;
+
+    let whitelist = match manifest.get("files").and_then(Value::as_array) {
+        Some(files) => Some(matcher(
+            dir,
+            files.iter().filter_map(Value::as_str).map(|pattern| {
+                format!(
+                    "/{}",
+                    pattern.trim_start_matches("./").trim_start_matches('/')
+                )
+            }),
+        )?),
+        None => None,
+    };
+
+    let bins = bin_files(manifest);
+
+    let mut required = vec![String::from("package.json")];
+
+    if let Some(main) = manifest.get("main").and_then(Value::as_str) {
+        required.push(normalize(main));
+    }
+
+    required.extend(bins.iter().cloned());
+
+    let walker = Walker {
+        root: dir,
+        always_ignored,
+        whitelist,
+        required: &required,
+        bins: &bins,
+    };
+
+    let mut files = vec![];
+    let mut ignores = vec![];
+
+    walker.walk(dir, &mut ignores, &mut files)?;
+
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(files)
+}
+
+/// Build the gzipped tarball npm would publish for the listed files
+pub fn create_tarball(dir: &Path, files: &[PackedFile]) -> Result<Vec<u8>> {
+    let mut builder = tar::Builder::new(vec![]);
+
+    for file in files {
+        let path = dir.join(&file.path);
+
+        let data = fs::read(&path).map_err(|e| VoltError::ReadFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
+
+        let mut header = tar::Header::new_gnu();
+
+        header.set_size(data.len() as u64);
+        header.set_mode(if file.executable { 0o755 } else { 0o644 });
+        header.set_mtime(TARBALL_MTIME);
+        header.set_uid(0);
+        header.set_gid(0);
+
+        builder
+            .append_data(
+                &mut header,
+                format!("package/{}", file.path),
+                data.as_slice(),
+            )
+            .into_diagnostic()?;
+    }
+
+    compress_gzip(&builder.into_inner().into_diagnostic()?)
+}
+
+/// File name npm gives a tarball (`@scope/name` becomes `scope-name-1.0.0.tgz`)
+pub fn tarball_name(name: &str, version: &str) -> String {
+    format!(
+        "{}-{}.tgz",
+        name.trim_start_matches('@').replace('/', "-"),
+        version
+    )
+}
+
+struct Walker<'a> {
+    root: &'a Path,
+    always_ignored: Gitignore,
+    whitelist: Option<Gitignore>,
+    /// Files that are published even when ignored
+    required: &'a [String],
+    /// Executables, which are published as such even without the executable bit
+    bins: &'a [String],
+}
+
+impl Walker<'_> {
+    fn walk(
+        &self,
+        dir: &Path,
+        ignores: &mut Vec<Gitignore>,
+        files: &mut Vec<PackedFile>,
+    ) -> Result<()> {
+        // the `files` whitelist replaces the root ignore file, but not nested ones
+        let ignore = if self.whitelist.is_some() && dir == self.root {
+            None
+        } else {
+            ignore_file(dir)?
+        };
+
+        let pushed = ignore.is_some();
+
+        ignores.extend(ignore);
+
+        let mut entries = fs::read_dir(dir)
+            .into_diagnostic()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .collect::<Vec<PathBuf>>();
+
+        entries.sort();
+
+        for path in entries {
+            let metadata = match fs::symlink_metadata(&path) {
+                Ok(metadata) if !metadata.file_type().is_symlink() => metadata,
+                _ => continue,
+            };
+
+            let is_dir = metadata.is_dir();
+
+            if self.always_ignored.matched(&path, is_dir).is_ignore() {
+                continue;
+            }
+
+            let relative = relative_path(self.root, &path);
+
+            if is_dir {
+                if !ignored(ignores, &path, true) {
+                    self.walk(&path, ignores, files)?;
+                }
+
+                continue;
+            }
+
+            let included = self.required.contains(&relative)
+                || (dir == self.root && always_included(&relative))
+                || match &self.whitelist {
+                    Some(whitelist) => {
+                        whitelist
+                            .matched_path_or_any_parents(&path, false)
+                            .is_ignore()
+                            && !ignored(ignores, &path, false)
+                    }
+                    None => !ignored(ignores, &path, false),
+                };
+
+            if included {
+                files.push(PackedFile {
+                    executable: is_executable(&metadata) || self.bins.contains(&relative),
+                    path: relative,
+                    size: metadata.len(),
+                });
+            }
+        }
+
+        if pushed {
+            ignores.pop();
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether the innermost ignore file with an opinion on `path` excludes it
+fn ignored(ignores: &[Gitignore], path: &Path, is_dir: bool) -> bool {
+    for ignore in ignores.iter().rev() {
+        match ignore.matched(path, is_dir) {
+            Match::Ignore(_) => return true,
+            Match::Whitelist(_) => return false,
+            Match::None => {}
+        }
+    }
+
+    false
+}
+
+/// The `.npmignore` in a directory, or its `.gitignore` when there is none
+fn ignore_file(dir: &Path) -> Result<Option<Gitignore>> {
+    for name in [".npmignore", ".gitignore"] {
+        let path = dir.join(name);
+
+        if path.is_file() {
+            let data = read_to_string(&path).map_err(|e| VoltError::ReadFileError {
+                source: e,
+                name: path.display().to_string(),
+            })?;
+
+            return Ok(Some(matcher(dir, data.lines().map(String::from))?));
+        }
+    }
+
+    Ok(None)
+}
+
+fn matcher<I, S>(dir: &Path, lines: I) -> Result<Gitignore>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut builder = GitignoreBuilder::new(dir);
+
+    for line in lines {
+        builder.add_line(None, line.as_ref()).into_diagnostic()?;
+    }
+
+    builder.build().into_diagnostic()
+}
+
+/// The files listed in `bin`, relative to the project root
+fn bin_files(manifest: &Value) -> Vec<String> {
+    match manifest.get("bin") {
+        Some(Value::String(path)) => vec![normalize(path)],
+        Some(Value::Object(bins)) => bins
+            .values()
+            .filter_map(Value::as_str)
+            .map(normalize)
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn always_included(name: &str) -> bool {
+    let name = name.to_lowercase();
+
+    ALWAYS_INCLUDED
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+}
+
+fn normalize(path: &str) -> String {
+    path.trim_start_matches("./").replace('\\', "/")
+}
+
+fn relative_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, path: &str) {
+        let path = dir.join(path);
+
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+
+    fn paths(dir: &Path, manifest: Value) -> Vec<String> {
+        packlist(dir, &manifest)
+            .unwrap()
+            .into_iter()
+            .map(|file| file.path)
+            .collect()
+    }
+
+    #[test]
+    fn respects_files_and_npmignore() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+
+        for path in [
+            "package.json",
+            "README.md",
+            "index.js",
+            "dist/index.js",
+            "dist/index.js.map",
+            "dist/.npmignore",
+            "test/index.test.js",
+            "node_modules/left-pad/index.js",
+        ] {
+            write(dir, path);
+        }
+
+        fs::write(dir.join("dist/.npmignore"), "*.map\n").unwrap();
+
+        assert_eq!(
+            paths(dir, serde_json::json!({ "files": ["dist"] })),
+            ["README.md", "dist/index.js", "package.json"]
+        );
+
+        fs::write(dir.join(".npmignore"), "test/\n").unwrap();
+
+        assert_eq!(
+            paths(dir, serde_json::json!({})),
+            ["README.md", "dist/index.js", "index.js", "package.json"]
+        );
+    }
+}
@@ -0,0 +1,214 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Create and apply the patches in `patchedDependencies`.
+//!
+//! Patches are git-style unified diffs relative to the package directory, created and applied
+//! with the git CLI.
+
+use crate::{
+    cli::VoltConfig,
+    core::utils::{errors::VoltError, voltapi::VoltPackage},
+};
+
+use miette::{IntoDiagnostic, Result};
+use ssri::Integrity;
+
+use std::{collections::HashMap, fs, path::Path, process::Command};
+
+/// Where the patch for a version of a package is stored, relative to the project
+pub fn patch_path(name: &str, version: &str) -> String {
+    format!("patches/{}@{}.patch", name.replace('/', "+"), version)
+}
+
+/// Write the files of a package as they were published, from the store
+pub fn extract_cached(config: &VoltConfig, package: &VoltPackage, dest: &Path) -> Result<()> {
+    let volt_home = config.volt_home()?;
+
+    let map = cacache::read_sync(&volt_home, package.cacache_key()).into_diagnostic()?;
+    let files: HashMap<String, Integrity> = serde_json::from_slice(&map).into_diagnostic()?;
+
+    for (name, integrity) in files {
+        let path = dest.join(name);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(VoltError::CreateDirError)?;
+        }
+
+        let contents = cacache::read_hash_sync(&volt_home, &integrity).into_diagnostic()?;
+
+        fs::write(&path, contents).map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Diff two copies of a package, returning an empty patch if they are the same
+pub fn create_patch(original: &Path, edited: &Path) -> Result<String> {
+    let output = Command::new("git")
+        .args([
+            "diff",
+            "--no-index",
+            "--no-color",
+            "--no-renames",
+            "--binary",
+            "--full-index",
+        ])
+        .arg(original)
+        .arg(edited)
+        .output()
+        .into_diagnostic()?;
+
+    // `git diff` exits with 1 when the files differ
+    if !matches!(output.status.code(), Some(0 | 1)) {
+        miette::bail!(
+            "git diff failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let diff = String::from_utf8_lossy(&output.stdout);
+
+    Ok(relative_paths(
+        &diff,
+        &git_path(original),
+        &git_path(edited),
+    ))
+}
+
+/// Apply a patch to an installed package, doing nothing if it was already applied
+pub fn apply_patch(package_dir: &Path, patch: &Path, package: &str) -> Result<()> {
+    // an already patched package reverses cleanly
+    if git_apply(package_dir, patch, &["--check", "--reverse"])? {
+        return Ok(());
+    }
+
+    if !git_apply(package_dir, patch, &[])? {
+        return Err(VoltError::PatchApplyError {
+            package: package.to_string(),
+            patch: patch.display().to_string(),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+fn git_apply(package_dir: &Path, patch: &Path, args: &[&str]) -> Result<bool> {
+    // node_modules is usually inside the project's repository, which would make git apply
+    // the patch relative to the repository root, so stop it from looking for one
+    let ceiling = package_dir.parent().unwrap_or(package_dir);
+
+    let status = Command::new("git")
+        .arg("apply")
+        .args(args)
+        .arg(patch)
+        .current_dir(package_dir)
+        .env("GIT_CEILING_DIRECTORIES", ceiling)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .into_diagnostic()?;
+
+    Ok(status.success())
+}
+
+/// How git prints a path in diff headers, without the leading `/`
+fn git_path(path: &Path) -> String {
+    path.to_string_lossy()
+        .replace('\\', "/")
+        .trim_start_matches('/')
+        .to_string()
+}
+
+/// Rewrite `a/<original>/file` and `b/<edited>/file` in diff headers to `a/file` and `b/file`
+fn relative_paths(diff: &str, original: &str, edited: &str) -> String {
+    let mut patch = String::with_capacity(diff.len());
+
+    // added and deleted files use the same directory on both sides
+    let prefixes = [
+        (format!("a/{}/", original), "a/"),
+        (format!("a/{}/", edited), "a/"),
+        (format!("b/{}/", original), "b/"),
+        (format!("b/{}/", edited), "b/"),
+    ];
+
+    let mut in_header = false;
+
+    for line in diff.split_inclusive('\n') {
+        if line.starts_with("diff --git ") {
+            in_header = true;
+        } else if line.starts_with("@@") || line.starts_with("GIT binary patch") {
+            in_header = false;
+        }
+
+        if in_header {
+            let line = prefixes
+                .iter()
+                .fold(line.to_string(), |line, (prefix, relative)| {
+                    line.replace(prefix.as_str(), relative)
+                });
+
+            patch.push_str(&line);
+        } else {
+            patch.push_str(line);
+        }
+    }
+
+    patch
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers_are_relative_to_the_package() {
+        let diff = "diff --git a/tmp/original/lib/index.js b/tmp/package/lib/index.js\n\
+                    index 1111111..2222222 100644\n\
+                    --- a/tmp/original/lib/index.js\n\
+                    +++ b/tmp/package/lib/index.js\n\
+                    @@ -1 +1 @@\n\
+                    -a/tmp/original/\n\
+                    +fixed\n\
+                    diff --git a/tmp/package/new.js b/tmp/package/new.js\n\
+                    new file mode 100644\n\
+                    --- /dev/null\n\
+                    +++ b/tmp/package/new.js\n\
+                    @@ -0,0 +1 @@\n\
+                    +new\n";
+
+        assert_eq!(
+            relative_paths(diff, "tmp/original", "tmp/package"),
+            "diff --git a/lib/index.js b/lib/index.js\n\
+             index 1111111..2222222 100644\n\
+             --- a/lib/index.js\n\
+             +++ b/lib/index.js\n\
+             @@ -1 +1 @@\n\
+             -a/tmp/original/\n\
+             +fixed\n\
+             diff --git a/new.js b/new.js\n\
+             new file mode 100644\n\
+             --- /dev/null\n\
+             +++ b/new.js\n\
+             @@ -0,0 +1 @@\n\
+             +new\n"
+        );
+    }
+}
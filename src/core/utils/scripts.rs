@@ -16,10 +16,26 @@
 // use crate::core::utils::errors;
 // use crate::core::utils::package::PackageJson;
 use async_trait::async_trait;
-// use colored::Colorize;
-use miette::Result;
+use colored::Colorize;
+use miette::{IntoDiagnostic, Result};
 
-use crate::cli::{VoltCommand, VoltConfig};
+use std::{
+    io::{BufRead, BufReader, Read},
+    path::Path,
+    process::{Command, ExitStatus, Stdio},
+    thread,
+};
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::{
+        ci::{annotate, Annotation},
+        events::Event,
+        output::status,
+        runtime::search_path,
+        utils::errors::VoltError,
+    },
+};
 
 pub struct Script {}
 
@@ -74,3 +90,107 @@ impl VoltCommand for Script {
         Ok(())
     }
 }
+
+/// Run a package.json script in `dir` with `node_modules/.bin` and the project's node runtime on
+/// the `PATH`
+pub fn run_script(
+    config: &VoltConfig,
+    dir: &Path,
+    name: &str,
+    script: &str,
+    env: &[(&str, &str)],
+) -> Result<()> {
+    status(config, format!("$ {}", script).truecolor(156, 156, 156));
+
+    let path = search_path(config, dir, &[dir.join("node_modules").join(".bin")])?;
+
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+
+    let mut command = Command::new(shell);
+
+    command
+        .args([flag, script])
+        .current_dir(dir)
+        .env("PATH", path)
+        .env("npm_lifecycle_event", name)
+        .envs(env.iter().copied());
+
+    let exit = if config.events().enabled() {
+        run_piped(command, config, name)?
+    } else {
+        command.status().into_diagnostic()?
+    };
+
+    if exit.success() {
+        return Ok(());
+    }
+
+    let code = exit.code().unwrap_or(1);
+
+    annotate(
+        Annotation::Error,
+        &format!("Script `{}` failed", name),
+        Some(&dir.join("package.json")),
+        &format!("`{}` exited with code {}", script, code),
+    );
+
+    Err(VoltError::ScriptFailedError {
+        name: name.to_string(),
+        code,
+    }
+    .into())
+}
+
+/// Run a script with its output piped, so every line it prints is also emitted as an event
+fn run_piped(mut command: Command, config: &VoltConfig, name: &str) -> Result<ExitStatus> {
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .into_diagnostic()?;
+
+    let forward = |reader: Box<dyn Read + Send>, stream: &'static str| {
+        let events = config.events().clone();
+        let to_stderr = stream == "stderr" || config.stdout_reserved();
+        let name = name.to_string();
+
+        thread::spawn(move || {
+            for line in BufReader::new(reader).lines().flatten() {
+                if to_stderr {
+                    eprintln!("{}", line);
+                } else {
+                    println!("{}", line);
+                }
+
+                events.emit(Event::ScriptOutput {
+                    script: &name,
+                    stream,
+                    line: &line,
+                });
+            }
+        })
+    };
+
+    let forwarders = [
+        child
+            .stdout
+            .take()
+            .map(|stdout| forward(Box::new(stdout), "stdout")),
+        child
+            .stderr
+            .take()
+            .map(|stderr| forward(Box::new(stderr), "stderr")),
+    ];
+
+    let exit = child.wait().into_diagnostic()?;
+
+    for forwarder in forwarders.into_iter().flatten() {
+        let _ = forwarder.join();
+    }
+
+    Ok(exit)
+}
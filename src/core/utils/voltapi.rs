@@ -13,7 +13,10 @@
 
 use serde::{Deserialize, Serialize};
 use speedy::{Readable, Writable};
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 #[derive(Debug, Clone, Writable, Readable)]
 pub struct VoltResponse {
@@ -24,7 +27,7 @@ pub struct VoltResponse {
     pub tree: HashMap<String, VoltPackage>, // the flattened dependency tree for the latest version of the package <name@version, data>
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Writable, Readable)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Writable, Readable)]
 pub struct VoltPackage {
     pub name: String,                                       // the name of the package
     pub version: String,                                    // the version of the package
@@ -44,6 +47,11 @@ pub struct VoltPackage {
 }
 
 impl VoltPackage {
+    /// The `name@version` key of the package in a dependency tree
+    pub fn key(&self) -> String {
+        format!("{}@{}", self.name, self.version)
+    }
+
     pub fn directory_name(&self) -> String {
         format!("{}@{}", self.name.replace('/', "+"), self.version)
     }
@@ -51,6 +59,33 @@ impl VoltPackage {
     pub fn cacache_key(&self) -> String {
         format!("pkg::{}::{}::{}", self.name, self.version, self.integrity)
     }
+
+    /// Path to the integrity of every file the package was installed with
+    /// (`node_modules/.volt/accepts@1.2.3/files.json`), checked by `volt check --integrity`
+    pub fn files_manifest(&self, node_modules: &Path) -> PathBuf {
+        node_modules
+            .join(".volt")
+            .join(self.directory_name())
+            .join("files.json")
+    }
+
+    /// Path to the extracted package (`node_modules/.volt/accepts@1.2.3/node_modules/accepts`)
+    pub fn package_directory(&self, node_modules: &Path) -> PathBuf {
+        node_modules
+            .join(".volt")
+            .join(self.directory_name())
+            .join("node_modules")
+            .join(&self.name)
+    }
+
+    /// Executables provided by the package, mapped from command name to the script's path
+    /// relative to the package directory
+    pub fn bins(&self) -> HashMap<String, String> {
+        self.bin
+            .as_ref()
+            .map(|bin| bin.commands(&self.name))
+            .unwrap_or_default()
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Readable, Writable)]
@@ -74,6 +109,22 @@ pub enum Bin {
     Map(HashMap<String, String>),
 }
 
+impl Bin {
+    /// Map of command name to script path for a package's `bin` field
+    pub fn commands(&self, package_name: &str) -> HashMap<String, String> {
+        match self {
+            Self::String(path) if !path.is_empty() => {
+                // a single executable is named after the package (without its scope)
+                let command = package_name.rsplit('/').next().unwrap_or(package_name);
+
+                HashMap::from([(command.to_string(), path.clone())])
+            }
+            Self::Map(map) => map.clone(),
+            _ => HashMap::new(),
+        }
+    }
+}
+
 impl Default for Bin {
     fn default() -> Self {
         Self::String(String::new())
@@ -25,37 +25,99 @@ mod cli;
 mod commands;
 mod core;
 
-use std::{io::stdin, str::FromStr, time::Instant};
+use std::{io::stdin, time::Instant};
 
-use tracing::Level;
-use tracing_subscriber::EnvFilter;
-
-use crate::cli::{VoltCli, VoltCommand};
+use crate::cli::{VoltCli, VoltCommand, VoltSubCmd};
 
 //#[tokio::main(worker_threads = 6)]
 //#[tokio::main(flavor = "current_thread")]
 fn main() -> miette::Result<()> {
     let body = async {
-        tracing_subscriber::fmt()
-            .with_max_level(Level::TRACE)
-            .with_env_filter(
-                EnvFilter::try_from_default_env()
-                    .unwrap_or_else(|_| EnvFilter::from_str("volt=info").unwrap()),
-            )
-            .without_time()
-            .init();
+        let mut app = VoltCli::new();
+
+        app.config.load_options()?;
+        app.config.connect_events()?;
+        core::net::set_registry(app.config.registry());
+        core::daemon::set_address(&app.config);
+        core::prompt::prompts::set_ci(&app.config);
+
+        let color = app.config.color();
+
+        // completion scripts are sourced, so they can't end with the timing, and run on every
+        // tab press, so they aren't worth a debug log
+        let completing = matches!(app.cmd, VoltSubCmd::Completions(_));
+
+        let debug_log = if completing {
+            None
+        } else {
+            core::logging::init(&app.config, color)
+        };
+
+        if cfg!(windows) && color {
+            // without ansi support colors are printed as escape codes, which is still usable
+            let _ = core::utils::enable_ansi_support();
+        }
+
+        colored::control::set_override(color);
+
+        // errors are rendered after `body` returns, with the same choice
+        let _ = miette::set_hook(Box::new(move |_| {
+            Box::new(miette::MietteHandlerOpts::new().color(color).build())
+        }));
+
+        if !completing {
+            core::telemetry::notice(&app.config);
+        }
 
-        if cfg!(windows) {
-            core::utils::enable_ansi_support().unwrap();
+        if app.cmd.uses_project() {
+            core::manager::enforce(&app.config)?;
         }
 
         let start = Instant::now();
 
-        let app = VoltCli::new();
+        let timed = !completing && !app.config.quiet();
+        let stdout_reserved = app.config.stdout_reserved();
 
-        app.cmd.exec(app.config).await?;
+        let command = app.cmd.name();
+        let config = app.config.clone();
 
-        println!("Finished in {:.2}s", start.elapsed().as_secs_f32());
+        let result = app.cmd.exec(app.config).await;
+
+        if !completing {
+            let error_code = result
+                .as_ref()
+                .err()
+                .and_then(|error| error.code())
+                .map(|code| code.to_string());
+
+            core::telemetry::record(
+                &config,
+                core::telemetry::Record::new(command, start.elapsed(), error_code),
+            )
+            .await;
+        }
+
+        if let Err(error) = result {
+            tracing::debug!("{:?}", error);
+
+            if let Some(path) = debug_log {
+                eprintln!("{:?}", error);
+                eprintln!(
+                    "A complete log of this run can be found in {}",
+                    path.display()
+                );
+
+                std::process::exit(1);
+            }
+
+            return Err(error);
+        }
+
+        if timed && stdout_reserved {
+            eprintln!("Finished in {:.2}s", start.elapsed().as_secs_f32());
+        } else if timed {
+            println!("Finished in {:.2}s", start.elapsed().as_secs_f32());
+        }
 
         Ok(())
     };